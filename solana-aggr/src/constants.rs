@@ -6,6 +6,60 @@ pub const AUTHORITY_SEED: &[u8] = b"authority";
 #[constant]
 pub const WSOL_SEED: &[u8] = b"wsol";
 
+/// Seeds the program authority and wSOL account were derived from before the current ones.
+/// Kept equal to the current seeds until those change, which makes `migrate_wsol_authority` a no-op.
+#[constant]
+pub const PREVIOUS_AUTHORITY_SEED: &[u8] = b"authority";
+
+#[constant]
+pub const PREVIOUS_WSOL_SEED: &[u8] = b"wsol";
+
+#[constant]
+pub const CONFIG_SEED: &[u8] = b"config";
+
+#[constant]
+pub const ROUTE_BUFFER_SEED: &[u8] = b"route_buffer";
+
+/// Seeds the `PriceFeed` a mint's fees are charged in SOL at, apart from any other price.
+#[constant]
+pub const SOL_FEE_PRICE_SEED: &[u8] = b"sol_fee_price";
+
+#[constant]
+pub const FEE_COUNTER_SEED: &[u8] = b"fee_counter";
+
+#[constant]
+pub const ALLOWANCE_SEED: &[u8] = b"allowance";
+
+#[constant]
+pub const ALLOWANCE_DELEGATE_SEED: &[u8] = b"allowance_delegate";
+
+#[constant]
+pub const VOLUME_CAP_SEED: &[u8] = b"volume_cap";
+
+#[constant]
+pub const IDEMPOTENCY_SEED: &[u8] = b"idem";
+
+#[constant]
+pub const FEE_RECEIPT_SEED: &[u8] = b"fee_receipt";
+
+#[constant]
+pub const CLAWBACK_SEED: &[u8] = b"clawback";
+
+#[constant]
+pub const WSOL_DEPOSIT_SEED: &[u8] = b"wsol_deposit";
+
+#[constant]
+pub const ESCROW_SEED: &[u8] = b"escrow";
+
+#[constant]
+pub const ESCROW_AUTHORITY_SEED: &[u8] = b"escrow_authority";
+
+#[constant]
+pub const QUOTE_NONCE_SEED: &[u8] = b"quote_nonce";
+
+#[constant]
+pub const SECONDS_PER_DAY: i64 = 86_400;
+
 #[constant]
 pub const NATIVE_MINT: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
 
@@ -14,3 +68,76 @@ pub const UNIZEN: Pubkey = pubkey!("6sp6GWkpHzzS8Mow5ZtyqG9DUVNXy5rXXZy1mNuRS1VJ
 
 #[constant]
 pub const FEE_DENOM: u64 = 10000;
+
+/// Current `Config` layout version; `migrate_config` brings older accounts up to it.
+#[constant]
+pub const CONFIG_VERSION: u8 = 1;
+
+#[constant]
+pub const MAX_PRIVILEGED: usize = 16;
+
+#[constant]
+pub const MAX_WSOL_CLOSERS: usize = 8;
+
+#[constant]
+pub const MAX_FEE_EXEMPT_USERS: usize = 16;
+
+#[constant]
+pub const MAX_ALLOWED_ROUTERS: usize = 8;
+
+#[constant]
+pub const MAX_FEE_OVERRIDES: usize = 16;
+
+#[constant]
+pub const MAX_FEE_TIERS: usize = 8;
+
+#[constant]
+pub const MAX_DISCOUNT_TIERS: usize = 4;
+
+/// Seconds a price stays usable after its publish time, for oracle and `PriceFeed` prices.
+#[constant]
+pub const MAX_PRICE_AGE: i64 = 60;
+
+/// Pyth receiver program owning the `PriceUpdateV2` accounts read as oracle prices.
+#[constant]
+pub const PYTH_RECEIVER: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+
+#[constant]
+pub const IDEMPOTENCY_TTL: i64 = 600;
+
+#[constant]
+pub const MAX_ROUTE_ACCOUNTS: usize = 64;
+
+/// Runtime limit on instruction stack height, counting the top-level instruction.
+#[constant]
+pub const MAX_STACK_HEIGHT: usize = 5;
+
+/// Stack levels a route needs below us: the router, its AMM and the token program.
+#[constant]
+pub const ROUTE_STACK_DEPTH: usize = 3;
+
+/// Upper bound on a program token account with Token-2022 extensions, well above any
+/// real extension set.
+#[constant]
+pub const MAX_TOKEN_ACCOUNT_SPACE: usize = 1_024;
+
+#[constant]
+pub const ROUTE_ACCOUNT_COMPUTE_UNITS: u64 = 2_000;
+
+#[constant]
+pub const SWAP_CLEANUP_COMPUTE_UNITS: u64 = 30_000;
+
+/// Lamports `swap_sol_for_tokens_percent` leaves on top of the rent floor for the rest of
+/// the transaction, e.g. ATA rent.
+#[constant]
+pub const SOL_PERCENT_RESERVE: u64 = 10_000_000;
+
+/// Compute kept back for `close_program_wsol` once the route has run.
+#[constant]
+pub const CLOSE_WSOL_COMPUTE_UNITS: u64 = 15_000;
+
+#[constant]
+pub const MAX_BATCH_SWAPS: usize = 3;
+
+#[constant]
+pub const MAX_FEE_ATA_BATCH: usize = 8;
@@ -4,8 +4,136 @@ use anchor_lang::prelude::*;
 pub enum ErrorCode {
     #[msg("The authority account provided is not a valid owner of WSOL account.")]
     IncorrectOwner,
+    /// Deprecated: no longer returned, see `OutputBelowMinimum`. Kept so `Underflow` keeps
+    /// its error code.
     #[msg("Out amount lower than expected after swap.")]
     InvalidSwapAmount,
     #[msg("Subtraction resulted in underflow")]
     Underflow,
+    #[msg("Swap amount must be greater than zero.")]
+    ZeroAmount,
+    #[msg("The program wSOL account is not a valid token account.")]
+    InvalidWsolAccount,
+    #[msg("The signer is not authorized to perform this action.")]
+    Unauthorized,
+    #[msg("Too many entries provided for config list.")]
+    ListTooLong,
+    #[msg("Number of destination accounts does not match outputs.")]
+    AccountCountMismatch,
+    #[msg("The program wSOL account is empty.")]
+    WsolAccountEmpty,
+    #[msg("Unizen and referrer shares exceed the fee denominator.")]
+    InvalidFeeShare,
+    #[msg("Referrer token account is required when referrer percent is set.")]
+    MissingReferrer,
+    #[msg("Fee denominator must be greater than zero.")]
+    InvalidFeeDenom,
+    #[msg("Fee token account mint does not match the source mint.")]
+    MintMismatch,
+    #[msg("Write exceeds the route buffer length.")]
+    RouteBufferOverflow,
+    #[msg("Price feed is older than the allowed maximum age.")]
+    StalePrice,
+    #[msg("Arithmetic overflow")]
+    Overflow,
+    #[msg("Fee source account is not owned by the fee payer.")]
+    InvalidFeePayer,
+    #[msg("Route has more accounts than allowed.")]
+    TooManyRouteAccounts,
+    #[msg("Quoted output net of fees is below the minimum out amount.")]
+    FeeExceedsGain,
+    #[msg("Config account is required for this instruction.")]
+    ConfigMissing,
+    #[msg("Fee account must be distinct from the fee source and owned by its recipient.")]
+    InvalidFeeAccount,
+    #[msg("Receiver account is not the destination owner's associated token account.")]
+    ReceiverMismatch,
+    #[msg("Not enough compute budget left to complete the swap.")]
+    InsufficientComputeBudget,
+    #[msg("Flat fee exceeds the input amount.")]
+    FlatFeeExceedsAmount,
+    #[msg("Fee tier thresholds must be strictly ascending.")]
+    UnsortedFeeTiers,
+    #[msg("Account does not match the PDA derived from its seeds and bump.")]
+    InvalidPda,
+    #[msg("User balance is insufficient for the requested amount.")]
+    InsufficientBalance,
+    #[msg("Receiver would not be rent exempt after the swap.")]
+    ReceiverNotRentExempt,
+    #[msg("Swap exceeds the remaining allowance budget.")]
+    AllowanceExhausted,
+    #[msg("Swap exceeds the allowance per-swap cap.")]
+    SwapCapExceeded,
+    #[msg("Source account is already delegated to another allowance.")]
+    DelegateInUse,
+    #[msg("Route references a program authority account other than the swap escrows.")]
+    UnexpectedAuthorityAccount,
+    #[msg("Reentrant swap detected.")]
+    Reentrancy,
+    #[msg("Router program account is not the executable Jupiter program.")]
+    InvalidRouterProgram,
+    #[msg("Minimum fee exceeds the input amount.")]
+    MinFeeExceedsAmount,
+    #[msg("Leg weights must be non-zero and sum to the fee denominator.")]
+    InvalidWeights,
+    #[msg("Integrator daily volume cap exceeded.")]
+    VolumeCapExceeded,
+    #[msg("wSOL swaps must use the SOL entrypoints unless allow_wsol is set.")]
+    UseSolEntrypoint,
+    #[msg("Out amount lower than the minimum after swap.")]
+    OutputBelowMinimum,
+    #[msg("Route consumed more source tokens than allowed.")]
+    InputOverspent,
+    #[msg("Route produced no output.")]
+    RouteProducedNothing,
+    #[msg("A request with this idempotency key was already executed.")]
+    DuplicateRequest,
+    #[msg("Idempotency record has not expired yet.")]
+    IdempotencyRecordActive,
+    #[msg("Route output was left stranded in the shared account.")]
+    OutputStranded,
+    #[msg("Unizen fee account does not belong to the configured recipient.")]
+    WrongFeeRecipient,
+    #[msg("Receiver account mint does not match the expected output mint.")]
+    WrongDestinationMint,
+    #[msg("Callback program is not executable or not allowed.")]
+    InvalidCallbackProgram,
+    #[msg("Router program is not on the allowed list.")]
+    RouterNotAllowed,
+    #[msg("Rent payer does not match the account that funded program wSOL.")]
+    WrongRentPayer,
+    #[msg("Called too deep via CPI to invoke the router.")]
+    CpiDepthExceeded,
+    #[msg("Computed token account size is out of bounds.")]
+    InvalidAccountSize,
+    #[msg("The fee claw-back window has closed.")]
+    ClawbackWindowClosed,
+    #[msg("Claw-back exceeds the fee left on the receipt.")]
+    ClawbackExceedsFee,
+    #[msg("Fee receipt account is required when the fee has a nonce.")]
+    FeeReceiptMissing,
+    #[msg("Fee receipt can still be clawed back.")]
+    ClawbackWindowOpen,
+    #[msg("Fee counter account is required while fee clawback is enabled.")]
+    FeeCounterMissing,
+    #[msg("Signed quote is missing, expired or not signed by the quote signer.")]
+    InvalidQuote,
+    #[msg("amount_out_min must be greater than zero.")]
+    SlippageProtectionRequired,
+    #[msg("Integrator received less than its fee share.")]
+    FeeNotReceived,
+    #[msg("Discount tiers must ascend by balance and not exceed the fee denominator.")]
+    InvalidDiscountTiers,
+    #[msg("Discount account is not the user's account for the discount mint.")]
+    InvalidDiscountAccount,
+    #[msg("The program cannot route through itself.")]
+    SelfCallForbidden,
+    #[msg("Percentage exceeds the fee denominator.")]
+    InvalidPercent,
+    #[msg("Program authority holds lamports beyond its rent after cleanup.")]
+    DirtyAuthority,
+    #[msg("Price account is not a fully verified Pyth price update.")]
+    InvalidOracle,
+    #[msg("Config was written by an earlier layout and has to be migrated first.")]
+    ConfigNeedsMigration,
 }
@@ -1,12 +1,34 @@
 use anchor_lang::{
     prelude::*,
-    solana_program::{entrypoint::ProgramResult, instruction::Instruction, program::invoke_signed},
-    system_program,
+    solana_program::{
+        compute_units::sol_remaining_compute_units,
+        ed25519_program,
+        instruction::{get_stack_height, Instruction},
+        log::sol_log_compute_units,
+        program::{invoke, invoke_signed},
+        sysvar::instructions::{load_current_index_checked, load_instruction_at_checked},
+    },
+    system_program, Discriminator,
+};
+use anchor_spl::{
+    associated_token::get_associated_token_address_with_program_id,
+    token,
+    token_2022::{
+        self,
+        spl_token_2022::{
+            self,
+            extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+        },
+    },
+    token_interface::{self, Mint, TokenAccount, TokenInterface},
 };
-use anchor_spl::token::{self, Mint, Token, TokenAccount};
 
 use crate::constants;
 use crate::errors;
+use crate::state::{
+    Config, FeeCounter, FeeMode, FeeReceipt, IdempotencyRecord, PriceFeed, PriceFeedMessage,
+    PriceUpdateV2, QuoteNonce, RouteBuffer, ShareMode, SignedQuote, VerificationLevel, VolumeCap,
+};
 
 mod jupiter {
     use anchor_lang::declare_id;
@@ -22,38 +44,353 @@ impl anchor_lang::Id for Jupiter {
     }
 }
 
+mod orca {
+    use anchor_lang::declare_id;
+    declare_id!("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc");
+}
+
+#[derive(Clone)]
+pub struct Whirlpool;
+
+impl anchor_lang::Id for Whirlpool {
+    fn id() -> Pubkey {
+        orca::id()
+    }
+}
+
+pub fn resolve_route_data(route_buffer: &Option<Account<RouteBuffer>>, data: Vec<u8>) -> Vec<u8> {
+    match route_buffer {
+        Some(route_buffer) => route_buffer.data.clone(),
+        None => data,
+    }
+}
+
+/// Rough lower bound on the compute a swap still needs: a fixed allowance per route account
+/// for the Jupiter CPI plus `reserve` for whatever must run after it, e.g. closing program wSOL.
+/// Bailing here is cheaper than running out of compute between the swap and the cleanup.
+pub fn assert_compute_budget(route_accounts: usize, reserve: u64) -> Result<()> {
+    let required = (route_accounts as u64)
+        .saturating_mul(constants::ROUTE_ACCOUNT_COMPUTE_UNITS)
+        .saturating_add(reserve);
+    let remaining = sol_remaining_compute_units();
+    if remaining < required {
+        msg!(
+            "Error: {} compute units remain but the route needs about {}.",
+            remaining,
+            required
+        );
+        return err!(errors::ErrorCode::InsufficientComputeBudget);
+    }
+
+    Ok(())
+}
+
 pub fn swap_on_jupiter(
+    config: &AccountInfo,
+    remaining_accounts: &[AccountInfo],
+    jupiter_program: Program<Jupiter>,
+    data: Vec<u8>,
+) -> Result<()> {
+    assert_router_program(&jupiter_program, &Jupiter::id())?;
+    let emit_events = events_enabled(&load_config(config)?);
+    lock_reentrancy(config)?;
+    let result = invoke_router(
+        remaining_accounts,
+        jupiter_program.key,
+        "Jupiter",
+        data,
+        emit_events,
+        None,
+        &[],
+    );
+    unlock_reentrancy(config)?;
+    result
+}
+
+/// Same as `swap_on_jupiter`, but `signer` is a program PDA signing via `signer_seeds`.
+pub fn swap_on_jupiter_signed(
+    config: &AccountInfo,
     remaining_accounts: &[AccountInfo],
     jupiter_program: Program<Jupiter>,
     data: Vec<u8>,
-) -> ProgramResult {
-    msg!("Swap on Jupiter");
+    signer: &Pubkey,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    assert_router_program(&jupiter_program, &Jupiter::id())?;
+    let emit_events = events_enabled(&load_config(config)?);
+    lock_reentrancy(config)?;
+    let result = invoke_router(
+        remaining_accounts,
+        jupiter_program.key,
+        "Jupiter",
+        data,
+        emit_events,
+        Some(signer),
+        signer_seeds,
+    );
+    unlock_reentrancy(config)?;
+    result
+}
+
+pub fn swap_on_orca(
+    config: &AccountInfo,
+    remaining_accounts: &[AccountInfo],
+    whirlpool_program: Program<Whirlpool>,
+    data: Vec<u8>,
+) -> Result<()> {
+    assert_router_program(&whirlpool_program, &Whirlpool::id())?;
+    let emit_events = events_enabled(&load_config(config)?);
+    lock_reentrancy(config)?;
+    let result = invoke_router(
+        remaining_accounts,
+        whirlpool_program.key,
+        "Orca",
+        data,
+        emit_events,
+        None,
+        &[],
+    );
+    unlock_reentrancy(config)?;
+    result
+}
+
+/// Routes through a program known only at runtime, so it must be on the config's
+/// `allowed_routers` list; without a config nothing is allowed.
+pub fn swap_on_router(
+    config: &AccountInfo,
+    remaining_accounts: &[AccountInfo],
+    router_program: &AccountInfo,
+    data: Vec<u8>,
+) -> Result<()> {
+    require!(
+        router_program.executable,
+        errors::ErrorCode::InvalidRouterProgram
+    );
+    let state = load_config(config)?;
+    if !state
+        .as_ref()
+        .is_some_and(|config| config.allowed_routers.contains(router_program.key))
+    {
+        msg!("Error: Router {} is not allowed.", router_program.key);
+        return err!(errors::ErrorCode::RouterNotAllowed);
+    }
+
+    let emit_events = events_enabled(&state);
+    lock_reentrancy(config)?;
+    let result = invoke_router(
+        remaining_accounts,
+        router_program.key,
+        "router",
+        data,
+        emit_events,
+        None,
+        &[],
+    );
+    unlock_reentrancy(config)?;
+    result
+}
+
+// The flag is written straight to account data so a nested call sees it mid-CPI.
+fn lock_reentrancy(config: &AccountInfo) -> Result<()> {
+    if let Some(mut state) = load_config(config)? {
+        if state.reentrancy_locked {
+            return err!(errors::ErrorCode::Reentrancy);
+        }
+        state.reentrancy_locked = true;
+        store_config(config, &state)?;
+    }
+    Ok(())
+}
+
+fn unlock_reentrancy(config: &AccountInfo) -> Result<()> {
+    if let Some(mut state) = load_config(config)? {
+        state.reentrancy_locked = false;
+        store_config(config, &state)?;
+    }
+    Ok(())
+}
+
+/// Fails early when invoked so deep via CPI that the route would hit the runtime's
+/// stack limit somewhere inside the router.
+fn assert_cpi_depth() -> Result<()> {
+    let stack_height = get_stack_height();
+    if stack_height + constants::ROUTE_STACK_DEPTH > constants::MAX_STACK_HEIGHT {
+        msg!(
+            "Error: Stack height is {} which leaves no room for a {} level route.",
+            stack_height,
+            constants::ROUTE_STACK_DEPTH
+        );
+        return err!(errors::ErrorCode::CpiDepthExceeded);
+    }
+
+    Ok(())
+}
+
+/// A failing router aborts the whole transaction with the router's own error: the runtime
+/// never hands a failed CPI back to the caller, so nothing after `invoke_signed` runs.
+fn invoke_router(
+    remaining_accounts: &[AccountInfo],
+    router_program: &Pubkey,
+    router_name: &str,
+    data: Vec<u8>,
+    emit_events: bool,
+    signer: Option<&Pubkey>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    // The reentrancy lock only covers swaps; a self-CPI could reach any other instruction.
+    if *router_program == crate::ID {
+        return err!(errors::ErrorCode::SelfCallForbidden);
+    }
+    assert_cpi_depth()?;
+
+    if remaining_accounts.len() > constants::MAX_ROUTE_ACCOUNTS {
+        msg!(
+            "Error: Route has {} accounts which exceeds {}.",
+            remaining_accounts.len(),
+            constants::MAX_ROUTE_ACCOUNTS
+        );
+        return err!(errors::ErrorCode::TooManyRouteAccounts);
+    }
+
+    msg!("Swap on {}", router_name);
 
     let accounts: Vec<AccountMeta> = remaining_accounts
         .iter()
         .map(|acc| AccountMeta {
             pubkey: *acc.key,
-            is_signer: acc.is_signer,
+            is_signer: acc.is_signer || signer == Some(acc.key),
             is_writable: acc.is_writable,
         })
         .collect();
+    let account_count = u16::try_from(accounts.len()).unwrap_or(u16::MAX);
+    let data_len = u16::try_from(data.len()).unwrap_or(u16::MAX);
 
     invoke_signed(
         &Instruction {
-            program_id: *jupiter_program.key,
+            program_id: *router_program,
             accounts,
             data,
         },
         remaining_accounts,
-        &[],
-    )
+        signer_seeds,
+    )?;
+
+    if emit_events {
+        emit!(RouteExecuted {
+            program_id: *router_program,
+            account_count,
+            data_len,
+        });
+    }
+
+    Ok(())
+}
+
+/// Reads the config PDA, which instructions take as a required account so it can't be
+/// left out. Only an empty or not yet initialized PDA counts as having no config. A config
+/// written by an earlier layout is rejected until `migrate_config` brings it up to date,
+/// since its zero padding would otherwise read as cleared fields.
+pub fn load_config(config: &AccountInfo) -> Result<Option<Config>> {
+    if config.data_is_empty() || config.owner != &crate::ID {
+        return Ok(None);
+    }
+    let data = config.try_borrow_data()?;
+    if data.get(..8) != Some(Config::DISCRIMINATOR.as_slice()) {
+        return err!(anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch);
+    }
+    let state = Config::deserialize_lenient(&data[8..])?;
+    if state.version < constants::CONFIG_VERSION {
+        msg!(
+            "Error: Config is at version {} but {} is required.",
+            state.version,
+            constants::CONFIG_VERSION
+        );
+        return err!(errors::ErrorCode::ConfigNeedsMigration);
+    }
+    Ok(Some(state))
+}
+
+pub fn store_config(config: &AccountInfo, state: &Config) -> Result<()> {
+    let mut data = config.try_borrow_mut_data()?;
+    state.try_serialize(&mut &mut data[..])
+}
+
+fn assert_router_program(router_program: &AccountInfo, expected: &Pubkey) -> Result<()> {
+    require!(
+        router_program.executable,
+        errors::ErrorCode::InvalidRouterProgram
+    );
+    require_keys_eq!(
+        *router_program.key,
+        *expected,
+        errors::ErrorCode::InvalidRouterProgram
+    );
+    Ok(())
+}
+
+/// Like the route, a failing callback aborts the whole swap with the callback's error.
+pub fn invoke_swap_callback<'info>(
+    callback_program: &UncheckedAccount<'info>,
+    user: &AccountInfo<'info>,
+    receiver_dst_ata: &InterfaceAccount<'info, TokenAccount>,
+    amount_out: u64,
+    callback_data: Vec<u8>,
+) -> Result<()> {
+    if !callback_program.executable || callback_program.key() == crate::ID {
+        return err!(errors::ErrorCode::InvalidCallbackProgram);
+    }
+
+    // The user is passed read-only and unsigned so the callback cannot act on its behalf.
+    let mut data = callback_data;
+    data.extend_from_slice(receiver_dst_ata.mint.as_ref());
+    data.extend_from_slice(&amount_out.to_le_bytes());
+
+    invoke(
+        &Instruction {
+            program_id: callback_program.key(),
+            accounts: vec![
+                AccountMeta::new_readonly(user.key(), false),
+                AccountMeta::new_readonly(receiver_dst_ata.key(), false),
+            ],
+            data,
+        },
+        &[
+            user.clone(),
+            receiver_dst_ata.to_account_info(),
+            callback_program.to_account_info(),
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Ensures `user` keeps at least `floor` lamports (rent exempt by default) after spending `amount`.
+pub fn assert_lamports_floor(user: &Signer, amount: u64, floor: Option<u64>) -> Result<()> {
+    let floor = match floor {
+        Some(floor) => floor,
+        None => Rent::get()?.minimum_balance(0),
+    };
+    let remaining = user
+        .lamports()
+        .checked_sub(amount)
+        .ok_or_else(|| error!(errors::ErrorCode::InsufficientBalance))?;
+    if remaining < floor {
+        msg!(
+            "Error: User would keep {} lamports which is below {}.",
+            remaining,
+            floor
+        );
+        return err!(errors::ErrorCode::InsufficientBalance);
+    }
+
+    Ok(())
 }
 
 pub fn wrap_user_sol<'info>(
     system_program: Program<'info, System>,
-    token_program: Program<'info, Token>,
+    token_program: Interface<'info, TokenInterface>,
     user: Signer<'info>,
-    wsol_receive_account: Account<'info, TokenAccount>,
+    wsol_receive_account: InterfaceAccount<'info, TokenAccount>,
     amount: u64,
 ) -> Result<()> {
     msg!("Wrap user's SOL");
@@ -68,9 +405,9 @@ pub fn wrap_user_sol<'info>(
         amount,
     )?;
 
-    token::sync_native(CpiContext::new(
+    token_interface::sync_native(CpiContext::new(
         token_program.to_account_info(),
-        token::SyncNative {
+        token_interface::SyncNative {
             account: wsol_receive_account.to_account_info(),
         },
     ))?;
@@ -78,188 +415,2729 @@ pub fn wrap_user_sol<'info>(
     Ok(())
 }
 
-pub fn take_integrator_fee<'info>(
-    accounts: AccountsForFee,
-    in_amount: u64,
-    fee_percent: u64,
-    share_percent: u64,
-) -> Result<()> {
-    emit!(TakeFee {
-        user: accounts.user_token_account.owner.to_string(),
-        token: accounts.user_token_account.mint.to_string(),
-        amount: in_amount,
-        fee_percent,
-        share_percent
-    });
+/// Wraps `amount` lamports and returns the synced token balance. The ATA's
+/// rent-exempt reserve is never counted by `sync_native`, so the result is the
+/// prior token balance plus `amount` (plus any stray lamports above rent).
+pub fn wrap_user_sol_synced<'info>(
+    system_program: Program<'info, System>,
+    token_program: Interface<'info, TokenInterface>,
+    user: Signer<'info>,
+    mut wsol_receive_account: InterfaceAccount<'info, TokenAccount>,
+    amount: u64,
+) -> Result<u64> {
+    wrap_user_sol(
+        system_program,
+        token_program,
+        user,
+        wsol_receive_account.clone(),
+        amount,
+    )?;
+
+    wsol_receive_account.reload()?;
+    Ok(wsol_receive_account.amount)
+}
 
-    if fee_percent == 0 {
+pub fn close_user_token_account_if_empty<'info>(
+    token_program: Interface<'info, TokenInterface>,
+    user: Signer<'info>,
+    mut token_account: InterfaceAccount<'info, TokenAccount>,
+    emit_events: bool,
+) -> Result<()> {
+    token_account.reload()?;
+    if token_account.amount > 0 {
+        msg!("Skip closing user token account with remaining balance");
+        if emit_events {
+            emit!(TokenAccountNotClosed {
+                account: token_account.key(),
+                remaining: token_account.amount,
+            });
+        }
         return Ok(());
     }
 
-    let total_fee = in_amount * fee_percent / constants::FEE_DENOM;
-    let mut unizen_fee: u64 = 0;
+    msg!("Close user token account");
+    token_interface::close_account(CpiContext::new(
+        token_program.to_account_info(),
+        token_interface::CloseAccount {
+            account: token_account.to_account_info(),
+            destination: user.to_account_info(),
+            authority: user.to_account_info(),
+        },
+    ))
+}
 
-    if share_percent > 0 {
-        unizen_fee = total_fee * share_percent / constants::FEE_DENOM;
-        msg!("Transfer fee to Unizen");
-        token::transfer(
-            CpiContext::new(
-                accounts.token_program.to_account_info(),
-                token::Transfer {
-                    from: accounts.user_token_account.to_account_info(),
-                    to: accounts.unizen_token_account.to_account_info(),
-                    authority: accounts.user.to_account_info(),
-                },
-            ),
-            unizen_fee,
-        )?;
+/// Fee-split rounding can leave a few lamports of wSOL behind after the route. Dust up to
+/// the config threshold goes to Unizen, or back to the user as SOL when the account is
+/// being closed anyway. Returns whether the user account was closed.
+pub fn sweep_wsol_dust<'info>(
+    config: &Option<Config>,
+    accounts: WsolDustAccounts<'info>,
+    prev_wsol_bal: u64,
+    close_user_wsol: bool,
+) -> Result<bool> {
+    let Some(config) = config else {
+        return Ok(false);
+    };
+    let WsolDustAccounts {
+        token_program,
+        sol_mint,
+        user,
+        mut user_wsol_ata,
+        unizen_wsol_ata,
+    } = accounts;
+    user_wsol_ata.reload()?;
+    // Only what this swap left behind is dust; the rest was the user's before it.
+    let dust = user_wsol_ata.amount.saturating_sub(prev_wsol_bal);
+    if dust == 0 || dust > config.wsol_dust_threshold {
+        return Ok(false);
     }
 
-    msg!("Transfer fee to integrator");
-    token::transfer(
-        CpiContext::new(
-            accounts.token_program.to_account_info(),
-            token::Transfer {
-                from: accounts.user_token_account.to_account_info(),
-                to: accounts.integrator_token_account.to_account_info(),
-                authority: accounts.user.to_account_info(),
+    let closed = if config.wsol_dust_to_unizen {
+        msg!("Sweep wSOL dust to Unizen");
+        transfer_interface(
+            &token_program,
+            &sol_mint,
+            user_wsol_ata.to_account_info(),
+            unizen_wsol_ata.to_account_info(),
+            user.to_account_info(),
+            &[],
+            dust,
+        )?;
+        false
+    } else if close_user_wsol && prev_wsol_bal == 0 {
+        // Closing a native account releases its token balance along with the rent.
+        msg!("Sweep wSOL dust to user");
+        token_interface::close_account(CpiContext::new(
+            token_program.to_account_info(),
+            token_interface::CloseAccount {
+                account: user_wsol_ata.to_account_info(),
+                destination: user.to_account_info(),
+                authority: user.to_account_info(),
             },
-        ),
-        total_fee - unizen_fee,
-    )?;
-
-    Ok(())
-}
+        ))?;
+        true
+    } else {
+        return Ok(false);
+    };
 
-pub fn assert_amount_out(prev_bal: u64, post_bal: u64, threshold: u64) -> Result<()> {
-    if post_bal
-        .checked_sub(prev_bal)
-        .ok_or_else(|| error!(errors::ErrorCode::Underflow))?
-        < threshold
-    {
-        msg!(
-            "Error: Out amount after swap is {} which is lower than expected {}.",
-            post_bal - prev_bal,
-            threshold
-        );
-        return err!(errors::ErrorCode::InvalidSwapAmount);
+    if config.emit_events {
+        emit!(WsolDustSwept {
+            user: user.key(),
+            amount: dust,
+            to_unizen: config.wsol_dust_to_unizen,
+        });
     }
 
-    Ok(())
+    Ok(closed)
 }
 
-pub fn create_program_wsol_idempotent<'info>(
-    program_authority: SystemAccount<'info>,
-    program_wsol: UncheckedAccount<'info>,
-    sol_mint: Account<'info, Mint>,
-    token_program: Program<'info, Token>,
+/// Unwraps route output delivered to the user's own wSOL account: the account is closed
+/// to the user and the wrapped amount forwarded to `receiver` as SOL. Balance the account
+/// already held before the swap is the user's, so such an account is left alone.
+pub fn unwrap_user_wsol<'info>(
+    token_program: Interface<'info, TokenInterface>,
     system_program: Program<'info, System>,
-    authority_bump: &[u8],
-    wsol_bump: &[u8],
-) -> Result<TokenAccount> {
-    if program_wsol.data_is_empty() {
-        let signer_seeds: &[&[&[u8]]] = &[
-            &[constants::AUTHORITY_SEED, authority_bump],
-            &[constants::WSOL_SEED, wsol_bump],
-        ];
+    user: Signer<'info>,
+    mut user_wsol_ata: InterfaceAccount<'info, TokenAccount>,
+    receiver: SystemAccount<'info>,
+    prev_wsol_bal: u64,
+    emit_events: bool,
+) -> Result<u64> {
+    user_wsol_ata.reload()?;
+    if prev_wsol_bal > 0 {
+        msg!("Skip closing user wSOL account with prior balance");
+        if emit_events {
+            emit!(TokenAccountNotClosed {
+                account: user_wsol_ata.key(),
+                remaining: user_wsol_ata.amount,
+            });
+        }
+        return Ok(0);
+    }
+    let out_amount = user_wsol_ata.amount;
 
-        msg!("Initialize program wSOL account");
-        let rent = Rent::get()?;
-        let space = TokenAccount::LEN;
-        let lamports = rent.minimum_balance(space);
-        system_program::create_account(
-            CpiContext::new_with_signer(
+    msg!("Close user wSOL account");
+    token_interface::close_account(CpiContext::new(
+        token_program.to_account_info(),
+        token_interface::CloseAccount {
+            account: user_wsol_ata.to_account_info(),
+            destination: user.to_account_info(),
+            authority: user.to_account_info(),
+        },
+    ))?;
+
+    if out_amount > 0 && receiver.key() != user.key() {
+        msg!("Transfer unwrapped SOL to receiver");
+        system_program::transfer(
+            CpiContext::new(
                 system_program.to_account_info(),
-                system_program::CreateAccount {
-                    from: program_authority.to_account_info(),
-                    to: program_wsol.to_account_info(),
+                system_program::Transfer {
+                    from: user.to_account_info(),
+                    to: receiver.to_account_info(),
                 },
-                signer_seeds,
             ),
-            lamports,
-            space as u64,
-            token_program.key,
+            out_amount,
         )?;
+    }
 
-        msg!("Initialize program wSOL token account");
-        token::initialize_account3(CpiContext::new(
-            token_program.to_account_info(),
-            token::InitializeAccount3 {
-                account: program_wsol.to_account_info(),
-                mint: sol_mint.to_account_info(),
-                authority: program_authority.to_account_info(),
-            },
-        ))?;
-
-        let data = program_wsol.try_borrow_data()?;
-        let wsol_token_account = TokenAccount::try_deserialize(&mut data.as_ref())?;
-
-        Ok(wsol_token_account)
-    } else {
-        let data = program_wsol.try_borrow_data()?;
-        let wsol_token_account = TokenAccount::try_deserialize(&mut data.as_ref())?;
-        if &wsol_token_account.owner != program_authority.key {
-            return err!(errors::ErrorCode::IncorrectOwner);
-        }
+    Ok(out_amount)
+}
 
-        Ok(wsol_token_account)
+/// Fails unless `account` already holds its rent-exempt minimum.
+pub fn assert_rent_exempt(account: &SystemAccount) -> Result<()> {
+    let min_balance = Rent::get()?.minimum_balance(account.data_len());
+    if account.lamports() < min_balance {
+        msg!(
+            "Error: Receiver holds {} lamports which is below rent-exempt minimum {}.",
+            account.lamports(),
+            min_balance
+        );
+        return err!(errors::ErrorCode::ReceiverNotRentExempt);
     }
+
+    Ok(())
 }
 
-pub fn close_program_wsol<'info>(
-    program_authority: SystemAccount<'info>,
-    program_wsol: UncheckedAccount<'info>,
-    receiver: SystemAccount<'info>,
-    token_program: Program<'info, Token>,
+/// Tops `account` up to its rent-exempt minimum from `payer`, so a fresh receiver
+/// can accept proceeds smaller than the rent-exempt reserve.
+pub fn top_up_rent_exempt<'info>(
     system_program: Program<'info, System>,
-    authority_bump: &[u8],
+    payer: Signer<'info>,
+    account: SystemAccount<'info>,
 ) -> Result<()> {
-    let signer_seeds: &[&[&[u8]]] = &[&[constants::AUTHORITY_SEED, authority_bump]];
-
-    let wsol_balance = program_wsol.lamports();
     let rent = Rent::get()?;
-    let rent_lamports = rent.minimum_balance(TokenAccount::LEN);
-    let out_amount = wsol_balance - rent_lamports;
-
-    msg!("Close program wSOL token account");
-    token::close_account(CpiContext::new_with_signer(
-        token_program.to_account_info(),
-        token::CloseAccount {
-            account: program_wsol.to_account_info(),
-            destination: program_authority.to_account_info(),
-            authority: program_authority.to_account_info(),
-        },
-        signer_seeds,
-    ))?;
+    let min_balance = rent.minimum_balance(account.data_len());
+    let shortfall = min_balance.saturating_sub(account.lamports());
+    if shortfall == 0 {
+        return Ok(());
+    }
 
-    msg!("Transfer SOL to receiver");
+    msg!("Top up receiver to rent-exempt minimum");
     system_program::transfer(
-        CpiContext::new_with_signer(
+        CpiContext::new(
             system_program.to_account_info(),
             system_program::Transfer {
-                from: program_authority.to_account_info(),
-                to: receiver.to_account_info(),
+                from: payer.to_account_info(),
+                to: account.to_account_info(),
             },
-            signer_seeds,
         ),
-        out_amount,
+        shortfall,
     )
 }
 
-#[derive(Accounts)]
-pub struct AccountsForFee<'info> {
-    pub user: Signer<'info>,
-    pub token_program: Program<'info, Token>,
-    pub user_token_account: Account<'info, TokenAccount>,
-    pub unizen_token_account: Account<'info, TokenAccount>,
-    pub integrator_token_account: Account<'info, TokenAccount>,
+pub(crate) fn apply_bps(amount: u64, bps: u64, denom: u64) -> Result<u64> {
+    let value = (amount as u128)
+        .checked_mul(bps as u128)
+        .and_then(|value| value.checked_div(denom as u128))
+        .ok_or_else(|| error!(errors::ErrorCode::Overflow))?;
+
+    u64::try_from(value).map_err(|_| error!(errors::ErrorCode::Overflow))
 }
 
-#[event]
-pub struct TakeFee {
-    pub user: String,
-    pub token: String,
-    pub amount: u64,
-    pub fee_percent: u64,
-    pub share_percent: u64,
+pub fn compute_fee_split(
+    accounts: &AccountsForFee,
+    in_amount: u64,
+    fee_mode: FeeMode,
+    referrer_percent: u64,
+) -> Result<FeeSplit> {
+    let fee_denom = accounts
+        .config
+        .as_ref()
+        .map_or(constants::FEE_DENOM, |config| config.fee_denom);
+    if fee_denom == 0 {
+        return err!(errors::ErrorCode::InvalidFeeDenom);
+    }
+
+    let share_percent = match fee_mode {
+        FeeMode::Percentage { share_percent, .. } | FeeMode::Flat { share_percent, .. } => {
+            share_percent
+        }
+    };
+    let total_share = share_percent
+        .checked_add(referrer_percent)
+        .ok_or_else(|| error!(errors::ErrorCode::InvalidFeeShare))?;
+    if total_share > fee_denom {
+        return err!(errors::ErrorCode::InvalidFeeShare);
+    }
+
+    let mut fee_tier = None;
+    let (fee_percent, total_fee) = match fee_mode {
+        FeeMode::Percentage { fee_percent, .. } => {
+            let fee_override = accounts.config.as_ref().and_then(|config| {
+                config
+                    .fee_overrides
+                    .iter()
+                    .find(|fee_override| fee_override.mint == accounts.user_token_account.mint)
+            });
+            let fee_percent = match fee_override {
+                Some(fee_override) => fee_override.fee_percent,
+                None => {
+                    fee_tier = accounts.config.as_ref().and_then(|config| {
+                        config
+                            .fee_tiers
+                            .iter()
+                            .rposition(|tier| tier.threshold_amount <= in_amount)
+                            .map(|index| (index as u8, config.fee_tiers[index].fee_percent))
+                    });
+                    fee_tier.map_or(fee_percent, |(_, tier_fee_percent)| tier_fee_percent)
+                }
+            };
+            let fee_percent = apply_holding_discount(accounts, fee_percent, fee_denom)?;
+            let mut total_fee = apply_bps(in_amount, fee_percent, fee_denom)?;
+            if fee_percent > 0 {
+                if let Some(config) = accounts.config.as_ref() {
+                    total_fee = total_fee.max(min_fee_amount(config, accounts.mint.decimals)?);
+                }
+            }
+            if total_fee > in_amount {
+                msg!(
+                    "Error: Minimum fee {} exceeds input amount {}.",
+                    total_fee,
+                    in_amount
+                );
+                return err!(errors::ErrorCode::MinFeeExceedsAmount);
+            }
+            (fee_percent, total_fee)
+        }
+        FeeMode::Flat { amount, .. } => {
+            if amount > in_amount {
+                msg!(
+                    "Error: Flat fee {} exceeds input amount {}.",
+                    amount,
+                    in_amount
+                );
+                return err!(errors::ErrorCode::FlatFeeExceedsAmount);
+            }
+            (0, amount)
+        }
+    };
+    let charges_fee = fee_percent > 0 || total_fee > 0;
+
+    if charges_fee && referrer_percent > 0 && accounts.referrer_token_account.is_none() {
+        return err!(errors::ErrorCode::MissingReferrer);
+    }
+
+    assert_fee_accounts(accounts)?;
+
+    let referrer_fee = apply_bps(total_fee, referrer_percent, fee_denom)?;
+    let remaining_fee = total_fee - referrer_fee;
+    let unizen_fee = apply_bps(remaining_fee, share_percent, fee_denom)?;
+
+    Ok(FeeSplit {
+        fee_percent,
+        fee_tier: fee_tier.map(|(index, _)| index),
+        share_percent,
+        charges_fee,
+        total_fee,
+        referrer_fee,
+        unizen_fee,
+        integrator_fee: remaining_fee - unizen_fee,
+    })
+}
+
+/// Cuts `fee_percent` by the discount tier the user's discount-mint balance reaches. No
+/// discount account, no configured mint or a balance below every tier means the full fee.
+fn apply_holding_discount(
+    accounts: &AccountsForFee,
+    fee_percent: u64,
+    fee_denom: u64,
+) -> Result<u64> {
+    let (Some(config), Some(discount_token_account)) = (
+        accounts.config.as_ref(),
+        accounts.discount_token_account.as_ref(),
+    ) else {
+        return Ok(fee_percent);
+    };
+    if config.discount_mint == Pubkey::default() {
+        return Ok(fee_percent);
+    }
+    if discount_token_account.mint != config.discount_mint
+        || discount_token_account.owner != accounts.user.key()
+    {
+        return err!(errors::ErrorCode::InvalidDiscountAccount);
+    }
+
+    let discount_percent = config
+        .discount_tiers
+        .iter()
+        .rev()
+        .find(|tier| tier.min_balance <= discount_token_account.amount)
+        .map_or(0, |tier| tier.discount_percent);
+    if discount_percent > 0 {
+        msg!("Apply holding discount of {}", discount_percent);
+    }
+
+    fee_percent
+        .checked_sub(apply_bps(fee_percent, discount_percent, fee_denom)?)
+        .ok_or_else(|| error!(errors::ErrorCode::Underflow))
+}
+
+fn apply_share_mode(
+    fee_split: &mut FeeSplit,
+    share_mode: ShareMode,
+    referrer_percent: u64,
+    fee_denom: u64,
+) -> Result<()> {
+    let remaining_fee = fee_split.total_fee - fee_split.referrer_fee;
+    let unizen_fee = match share_mode {
+        ShareMode::Percent(share_percent) => {
+            let total_share = share_percent
+                .checked_add(referrer_percent)
+                .ok_or_else(|| error!(errors::ErrorCode::InvalidFeeShare))?;
+            if total_share > fee_denom {
+                return err!(errors::ErrorCode::InvalidFeeShare);
+            }
+            fee_split.share_percent = share_percent;
+            apply_bps(remaining_fee, share_percent, fee_denom)?
+        }
+        ShareMode::Absolute(share_amount) => {
+            fee_split.share_percent = 0;
+            share_amount.min(remaining_fee)
+        }
+    };
+
+    fee_split.unizen_fee = unizen_fee;
+    fee_split.integrator_fee = remaining_fee - unizen_fee;
+
+    Ok(())
+}
+
+fn min_fee_amount(config: &Config, decimals: u8) -> Result<u64> {
+    if !config.min_fee_in_whole_tokens {
+        return Ok(config.min_fee);
+    }
+
+    10u64
+        .checked_pow(decimals as u32)
+        .and_then(|scale| config.min_fee.checked_mul(scale))
+        .ok_or_else(|| error!(errors::ErrorCode::Overflow))
+}
+
+fn assert_fee_accounts(accounts: &AccountsForFee) -> Result<()> {
+    let source = accounts.user_token_account.key();
+    // Checked against the swapping user, not the source owner: a sponsoring integrator
+    // pays from its own account and may well collect into another of its own.
+    let user = accounts.user.key();
+
+    if accounts.unizen_token_account.key() == source {
+        msg!(
+            "Error: Unizen fee account {} is the fee source.",
+            accounts.unizen_token_account.key()
+        );
+        return err!(errors::ErrorCode::InvalidFeeAccount);
+    }
+    require_keys_eq!(
+        accounts.unizen_token_account.owner,
+        unizen_fee_recipient(&accounts.config),
+        errors::ErrorCode::WrongFeeRecipient
+    );
+    if accounts.integrator_token_account.key() == source
+        || accounts.integrator_token_account.owner == user
+    {
+        msg!(
+            "Error: Integrator fee account {} is the fee source or owned by the user.",
+            accounts.integrator_token_account.key()
+        );
+        return err!(errors::ErrorCode::InvalidFeeAccount);
+    }
+    if let Some(referrer_token_account) = accounts.referrer_token_account.as_ref() {
+        if referrer_token_account.key() == source || referrer_token_account.owner == user {
+            msg!(
+                "Error: Referrer fee account {} is the fee source or owned by the user.",
+                referrer_token_account.key()
+            );
+            return err!(errors::ErrorCode::InvalidFeeAccount);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn strict_cleanup_enabled(config: &Option<Config>) -> bool {
+    config.as_ref().is_some_and(|config| config.strict_cleanup)
+}
+
+/// Owner of the accounts Unizen's fee share goes to, `UNIZEN` until `set_unizen_fee_recipient`
+/// changes it.
+pub fn unizen_fee_recipient(config: &Option<Config>) -> Pubkey {
+    config
+        .as_ref()
+        .map_or(constants::UNIZEN, |config| config.unizen_fee_recipient)
+}
+
+/// Checks a wallet standing in for Unizen, e.g. to receive swept dust, is the fee recipient.
+pub fn assert_unizen_fee_recipient(unizen: &Pubkey, config: &Option<Config>) -> Result<()> {
+    require_keys_eq!(
+        *unizen,
+        unizen_fee_recipient(config),
+        errors::ErrorCode::WrongFeeRecipient
+    );
+    Ok(())
+}
+
+pub fn events_enabled(config: &Option<Config>) -> bool {
+    match config {
+        Some(config) => config.emit_events,
+        None => true,
+    }
+}
+
+/// Logs remaining compute units tagged with `phase` when `Config.profiling` is set.
+pub fn log_phase_compute(config: &Option<Config>, phase: &str) {
+    if matches!(config, Some(config) if config.profiling) {
+        msg!("Profile: {}", phase);
+        sol_log_compute_units();
+    }
+}
+
+/// Checks the fee accounts and split a swap would use, without moving tokens or
+/// spending a nonce. Only swaps that take their fee from the user up front accept
+/// `dry_run`; the escrow and allowance swaps fund their fee inside the same call.
+pub fn validate_dry_run(
+    accounts: &AccountsForFee,
+    in_amount: u64,
+    fee_mode: FeeMode,
+    referrer_percent: u64,
+) -> Result<()> {
+    let fee_split = compute_fee_split(accounts, in_amount, fee_mode, referrer_percent)?;
+
+    if events_enabled(&accounts.config) {
+        emit!(DryRunValidated {
+            user: accounts.user.key(),
+            amount_in: in_amount,
+            total_fee: fee_split.total_fee,
+        });
+    }
+
+    Ok(())
+}
+
+/// Without a nonce no `FeeReceipt` is recorded, so the counter is required while
+/// `clawback_fee` is enabled; otherwise a caller could leave it out to dodge the clawback.
+pub fn next_fee_nonce(
+    fee_counter: &mut Option<Account<FeeCounter>>,
+    config: &Option<Config>,
+) -> Result<Option<u64>> {
+    match fee_counter {
+        Some(fee_counter) => {
+            fee_counter.nonce = fee_counter
+                .nonce
+                .checked_add(1)
+                .ok_or_else(|| error!(errors::ErrorCode::Overflow))?;
+            Ok(Some(fee_counter.nonce))
+        }
+        None => {
+            if config
+                .as_ref()
+                .is_some_and(|config| config.clawback_window > 0)
+            {
+                return err!(errors::ErrorCode::FeeCounterMissing);
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// The integrator's cap PDA is a required account so the breaker can't be skipped; until
+/// `init_volume_cap` creates it the integrator is uncapped.
+pub fn track_daily_volume(volume_cap: &AccountInfo, in_amount: u64) -> Result<()> {
+    if volume_cap.data_is_empty() || volume_cap.owner != &crate::ID {
+        return Ok(());
+    }
+    let mut state = {
+        let data = volume_cap.try_borrow_data()?;
+        VolumeCap::try_deserialize(&mut data.as_ref())?
+    };
+
+    let day = Clock::get()?.unix_timestamp / constants::SECONDS_PER_DAY;
+    if state.day != day {
+        state.day = day;
+        state.volume = 0;
+    }
+
+    let volume = state
+        .volume
+        .checked_add(in_amount)
+        .ok_or_else(|| error!(errors::ErrorCode::Overflow))?;
+    if volume > state.daily_cap {
+        msg!(
+            "Error: Daily volume {} would exceed cap {}.",
+            volume,
+            state.daily_cap
+        );
+        return err!(errors::ErrorCode::VolumeCapExceeded);
+    }
+    state.volume = volume;
+
+    let mut data = volume_cap.try_borrow_mut_data()?;
+    state.try_serialize(&mut &mut data[..])
+}
+
+pub fn take_integrator_fee<'info>(
+    accounts: AccountsForFee<'info>,
+    in_amount: u64,
+    fee_mode: FeeMode,
+    referrer_percent: u64,
+    nonce: Option<u64>,
+    share_mode: Option<ShareMode>,
+) -> Result<()> {
+    take_integrator_fee_into(
+        accounts,
+        in_amount,
+        fee_mode,
+        referrer_percent,
+        nonce,
+        share_mode,
+        None,
+    )
+}
+
+/// Same as `take_integrator_fee`, but with `aggregate` set the fee is added to it instead of
+/// emitting `TakeFee`, so a swap taking fees at several hops emits one `TakeFeeAggregate`.
+pub fn take_integrator_fee_into<'info>(
+    accounts: AccountsForFee<'info>,
+    in_amount: u64,
+    fee_mode: FeeMode,
+    referrer_percent: u64,
+    nonce: Option<u64>,
+    share_mode: Option<ShareMode>,
+    aggregate: Option<&mut FeeAggregate>,
+) -> Result<()> {
+    let fee_exempt = accounts
+        .config
+        .as_ref()
+        .is_some_and(|config| config.fee_exempt_users.contains(accounts.user.key));
+    if fee_exempt {
+        msg!("Waive fee for exempt user");
+        if events_enabled(&accounts.config) {
+            emit!(FeeWaived {
+                user: accounts.user.key(),
+                token: accounts.user_token_account.mint,
+                amount: in_amount,
+            });
+        }
+        return Ok(());
+    }
+
+    // The fee and the route draw on the same balance, so it has to cover `in_amount` up front
+    // rather than fail inside the route once the fee is gone.
+    if accounts.fee_payer.is_none() && accounts.user_token_account.amount < in_amount {
+        msg!(
+            "Error: User balance is {} but the swap needs {}.",
+            accounts.user_token_account.amount,
+            in_amount
+        );
+        return err!(errors::ErrorCode::InsufficientBalance);
+    }
+
+    let mut fee_split = compute_fee_split(&accounts, in_amount, fee_mode, referrer_percent)?;
+    if let Some(share_mode) = share_mode {
+        let fee_denom = accounts
+            .config
+            .as_ref()
+            .map_or(constants::FEE_DENOM, |config| config.fee_denom);
+        apply_share_mode(&mut fee_split, share_mode, referrer_percent, fee_denom)?;
+    }
+
+    let fee_authority = match accounts.fee_payer.as_ref() {
+        Some(fee_payer) => fee_payer.authority.clone(),
+        None => accounts.user.clone(),
+    };
+    if accounts.user_token_account.owner != fee_authority.key() {
+        msg!(
+            "Error: Fee source account is owned by {} but fee authority is {}.",
+            accounts.user_token_account.owner,
+            fee_authority.key()
+        );
+        return err!(errors::ErrorCode::InvalidFeePayer);
+    }
+    let sol_fee = accounts.sol_fee.as_ref();
+    if let Some(sol_fee) = sol_fee {
+        assert_sol_fee_accounts(&accounts, sol_fee)?;
+    }
+
+    let mut referrer_received = 0;
+    let mut unizen_received = 0;
+    let mut integrator_received = 0;
+    if fee_split.charges_fee {
+        if let Some(referrer_token_account) = accounts.referrer_token_account.as_ref() {
+            if referrer_percent > 0 {
+                msg!("Transfer fee to referrer");
+                referrer_received = pay_fee(
+                    &accounts,
+                    sol_fee,
+                    fee_authority.clone(),
+                    referrer_token_account,
+                    sol_fee.and_then(|sol_fee| sol_fee.referrer.as_ref()),
+                    fee_split.referrer_fee,
+                )?;
+            }
+        }
+
+        if fee_split.share_percent > 0 || fee_split.unizen_fee > 0 {
+            msg!("Transfer fee to Unizen");
+            unizen_received = pay_fee(
+                &accounts,
+                sol_fee,
+                fee_authority.clone(),
+                &accounts.unizen_token_account,
+                sol_fee.map(|sol_fee| &sol_fee.unizen),
+                fee_split.unizen_fee,
+            )?;
+        }
+
+        msg!("Transfer fee to integrator");
+        integrator_received = pay_fee(
+            &accounts,
+            sol_fee,
+            fee_authority.clone(),
+            &accounts.integrator_token_account,
+            sol_fee.map(|sol_fee| &sol_fee.integrator),
+            fee_split.integrator_fee,
+        )?;
+        if sol_fee.is_none() {
+            assert_integrator_fee_received(
+                &accounts.config,
+                fee_split.integrator_fee,
+                integrator_received,
+            )?;
+        }
+    }
+
+    // Aggregated hops share one nonce, so their caller records a single receipt instead.
+    if let (Some(nonce), None, None) = (nonce, sol_fee, aggregate.as_ref()) {
+        if integrator_received > 0 {
+            record_fee_receipt(&accounts, nonce, integrator_received)?;
+        }
+    }
+
+    if aggregate.is_some() || events_enabled(&accounts.config) {
+        let (unizen_account, integrator_account) = match sol_fee {
+            Some(sol_fee) => (sol_fee.unizen.key(), sol_fee.integrator.key()),
+            None => (
+                accounts.unizen_token_account.key(),
+                accounts.integrator_token_account.key(),
+            ),
+        };
+        let event = TakeFee {
+            user: accounts.user.key(),
+            token: accounts.user_token_account.mint,
+            amount: in_amount,
+            decimals: accounts.mint.decimals,
+            fee_mode,
+            fee_percent: fee_split.fee_percent,
+            fee_tier: fee_split.fee_tier,
+            share_percent: fee_split.share_percent,
+            referrer: accounts
+                .referrer_token_account
+                .as_ref()
+                .map(|acc| acc.owner),
+            referrer_percent,
+            nonce,
+            paid_in_sol: sol_fee.is_some(),
+            share_mode,
+            unizen_received,
+            integrator_received,
+            unizen_account,
+            integrator_account,
+        };
+        match aggregate {
+            Some(aggregate) => aggregate.add(&event, referrer_received)?,
+            None => emit!(event),
+        }
+    }
+
+    Ok(())
+}
+
+/// Fee totals across the hops of one swap. All hops must charge the same token to the same
+/// recipients, otherwise the totals would be meaningless.
+#[derive(Default)]
+pub struct FeeAggregate {
+    pub hops: u8,
+    pub token: Pubkey,
+    pub amount: u64,
+    pub referrer: Option<Pubkey>,
+    pub referrer_received: u64,
+    pub unizen_account: Pubkey,
+    pub unizen_received: u64,
+    pub integrator_account: Pubkey,
+    pub integrator_received: u64,
+}
+
+impl FeeAggregate {
+    fn add(&mut self, fee: &TakeFee, referrer_received: u64) -> Result<()> {
+        if self.hops == 0 {
+            self.token = fee.token;
+            self.referrer = fee.referrer;
+            self.unizen_account = fee.unizen_account;
+            self.integrator_account = fee.integrator_account;
+        } else {
+            require_keys_eq!(fee.token, self.token, errors::ErrorCode::MintMismatch);
+            if fee.referrer != self.referrer
+                || fee.unizen_account != self.unizen_account
+                || fee.integrator_account != self.integrator_account
+            {
+                return err!(errors::ErrorCode::InvalidFeeAccount);
+            }
+        }
+
+        self.hops = self
+            .hops
+            .checked_add(1)
+            .ok_or_else(|| error!(errors::ErrorCode::Overflow))?;
+        self.amount = checked_sum(self.amount, fee.amount)?;
+        self.referrer_received = checked_sum(self.referrer_received, referrer_received)?;
+        self.unizen_received = checked_sum(self.unizen_received, fee.unizen_received)?;
+        self.integrator_received = checked_sum(self.integrator_received, fee.integrator_received)?;
+
+        Ok(())
+    }
+
+    /// Emits the totals once the last hop is done; nothing when no hop charged a fee.
+    pub fn emit(self, config: &Option<Config>, user: Pubkey) {
+        if self.hops == 0 || !events_enabled(config) {
+            return;
+        }
+        emit!(TakeFeeAggregate {
+            user,
+            token: self.token,
+            hops: self.hops,
+            amount: self.amount,
+            referrer: self.referrer,
+            referrer_received: self.referrer_received,
+            unizen_account: self.unizen_account,
+            unizen_received: self.unizen_received,
+            integrator_account: self.integrator_account,
+            integrator_received: self.integrator_received,
+        });
+    }
+}
+
+fn checked_sum(total: u64, amount: u64) -> Result<u64> {
+    total
+        .checked_add(amount)
+        .ok_or_else(|| error!(errors::ErrorCode::Overflow))
+}
+
+pub fn sol_fee_accounts<'info>(
+    price_feed: Option<Account<'info, PriceFeed>>,
+    unizen: Option<SystemAccount<'info>>,
+    integrator: Option<SystemAccount<'info>>,
+    referrer: Option<SystemAccount<'info>>,
+    system_program: Program<'info, System>,
+) -> Result<Option<SolFeeAccounts<'info>>> {
+    let Some(price_feed) = price_feed else {
+        return Ok(None);
+    };
+    match (unizen, integrator) {
+        (Some(unizen), Some(integrator)) => Ok(Some(SolFeeAccounts {
+            price_feed,
+            unizen,
+            integrator,
+            referrer,
+            system_program,
+        })),
+        _ => err!(errors::ErrorCode::InvalidFeeAccount),
+    }
+}
+
+fn assert_sol_fee_accounts(accounts: &AccountsForFee, sol_fee: &SolFeeAccounts) -> Result<()> {
+    assert_price_fresh(sol_fee.price_feed.publish_time)?;
+    if sol_fee.price_feed.mint != accounts.mint.key() {
+        return err!(errors::ErrorCode::MintMismatch);
+    }
+    let (expected_feed, _) = Pubkey::find_program_address(
+        &[constants::SOL_FEE_PRICE_SEED, accounts.mint.key().as_ref()],
+        &crate::ID,
+    );
+    if sol_fee.price_feed.key() != expected_feed {
+        msg!(
+            "Error: Price feed {} is not the SOL fee price {}.",
+            sol_fee.price_feed.key(),
+            expected_feed
+        );
+        return err!(errors::ErrorCode::InvalidPda);
+    }
+    if sol_fee.unizen.key() != unizen_fee_recipient(&accounts.config)
+        || sol_fee.integrator.key() != accounts.integrator_token_account.owner
+    {
+        return err!(errors::ErrorCode::InvalidFeeAccount);
+    }
+    match (
+        sol_fee.referrer.as_ref(),
+        accounts.referrer_token_account.as_ref(),
+    ) {
+        (Some(referrer), Some(referrer_token_account))
+            if referrer.key() != referrer_token_account.owner =>
+        {
+            err!(errors::ErrorCode::InvalidFeeAccount)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fails when the integrator got less than `expected` beyond the configured tolerance.
+/// Catches mints that silently withhold part of a transfer, e.g. via a transfer fee.
+fn assert_integrator_fee_received(
+    config: &Option<Config>,
+    expected: u64,
+    received: u64,
+) -> Result<()> {
+    let Some(tolerance_bps) = config
+        .as_ref()
+        .and_then(|config| config.integrator_fee_tolerance_bps)
+    else {
+        return Ok(());
+    };
+    let allowed = apply_bps(expected, tolerance_bps, constants::FEE_DENOM)?;
+    if expected.saturating_sub(received) > allowed {
+        msg!(
+            "Error: Integrator received {} of its {} fee share.",
+            received,
+            expected
+        );
+        return err!(errors::ErrorCode::FeeNotReceived);
+    }
+
+    Ok(())
+}
+
+/// Pays a fee leg in `mint`, or in lamports at the price feed rate when `sol_fee` is set.
+fn pay_fee<'info>(
+    accounts: &AccountsForFee<'info>,
+    sol_fee: Option<&SolFeeAccounts<'info>>,
+    authority: AccountInfo<'info>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    sol_to: Option<&SystemAccount<'info>>,
+    amount: u64,
+) -> Result<u64> {
+    let Some(sol_fee) = sol_fee else {
+        return transfer_fee(accounts, authority, to, amount);
+    };
+    let sol_to = sol_to.ok_or_else(|| error!(errors::ErrorCode::InvalidFeeAccount))?;
+
+    let lamports = (amount as u128)
+        .checked_mul(sol_fee.price_feed.price as u128)
+        .and_then(|value| {
+            value.checked_div(10u128.checked_pow(sol_fee.price_feed.decimals as u32)?)
+        })
+        .and_then(|value| u64::try_from(value).ok())
+        .ok_or_else(|| error!(errors::ErrorCode::Overflow))?;
+    system_program::transfer(
+        CpiContext::new(
+            sol_fee.system_program.to_account_info(),
+            system_program::Transfer {
+                from: authority,
+                to: sol_to.to_account_info(),
+            },
+        ),
+        lamports,
+    )?;
+
+    Ok(lamports)
+}
+
+/// Transfers `amount` from the fee source to `to` and returns what `to` actually received,
+/// which is less than `amount` when the mint withholds a transfer fee.
+fn transfer_fee<'info>(
+    accounts: &AccountsForFee<'info>,
+    authority: AccountInfo<'info>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    amount: u64,
+) -> Result<u64> {
+    let seeds: Vec<&[u8]> = accounts
+        .fee_payer
+        .iter()
+        .flat_map(|fee_payer| fee_payer.signer_seeds.iter().map(Vec::as_slice))
+        .collect();
+    let signer_seeds: &[&[&[u8]]] = if seeds.is_empty() { &[] } else { &[&seeds] };
+
+    let prev_bal = read_token_amount(&to.to_account_info())?;
+    transfer_interface(
+        &accounts.token_program,
+        &accounts.mint,
+        accounts.user_token_account.to_account_info(),
+        to.to_account_info(),
+        authority,
+        signer_seeds,
+        amount,
+    )?;
+    let post_bal = read_token_amount(&to.to_account_info())?;
+
+    post_bal
+        .checked_sub(prev_bal)
+        .ok_or_else(|| error!(errors::ErrorCode::Underflow))
+}
+
+pub fn read_token_amount(account: &AccountInfo) -> Result<u64> {
+    if account.owner != &token::ID && account.owner != &token_2022::ID {
+        return err!(errors::ErrorCode::IncorrectOwner);
+    }
+    let data = account.try_borrow_data()?;
+    let token_account = TokenAccount::try_deserialize(&mut data.as_ref())?;
+
+    Ok(token_account.amount)
+}
+
+/// Reads `account` as a token account of either token program, or `None` if it isn't one.
+pub fn load_token_account(account: &AccountInfo) -> Result<Option<TokenAccount>> {
+    if account.owner != &token::ID && account.owner != &token_2022::ID {
+        return Ok(None);
+    }
+    let data = account.try_borrow_data()?;
+    Ok(TokenAccount::try_deserialize(&mut data.as_ref()).ok())
+}
+
+pub fn assert_amount_in(amount_in: u64) -> Result<()> {
+    if amount_in == 0 {
+        return err!(errors::ErrorCode::ZeroAmount);
+    }
+
+    Ok(())
+}
+
+/// Fails below `threshold`; between `threshold` and `soft_min` only emits a warning.
+pub fn assert_amount_out(
+    prev_bal: u64,
+    post_bal: u64,
+    threshold: u64,
+    soft_min: Option<u64>,
+    emit_events: bool,
+) -> Result<()> {
+    let amount_out = post_bal
+        .checked_sub(prev_bal)
+        .ok_or_else(|| error!(errors::ErrorCode::Underflow))?;
+    if amount_out < threshold {
+        msg!(
+            "Error: Out amount after swap is {} which is lower than expected {}.",
+            amount_out,
+            threshold
+        );
+        if emit_events {
+            emit!(SlippageExceeded {
+                expected: threshold,
+                actual: amount_out,
+                shortfall: threshold
+                    .checked_sub(amount_out)
+                    .ok_or_else(|| error!(errors::ErrorCode::Underflow))?,
+            });
+        }
+        if amount_out == 0 {
+            return err!(errors::ErrorCode::RouteProducedNothing);
+        }
+        return err!(errors::ErrorCode::OutputBelowMinimum);
+    }
+
+    let soft_min = soft_min.unwrap_or(threshold);
+    if amount_out < soft_min {
+        msg!(
+            "Warning: Out amount after swap is {} which is lower than preferred {}.",
+            amount_out,
+            soft_min
+        );
+        if emit_events {
+            emit!(SlippageWarning {
+                expected: soft_min,
+                actual: amount_out,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves the minimum output of a swap into `dst_mint`: the `min_out` of `signed_quote`
+/// when one is given, otherwise `amount_out_min` unless `Config.require_signed_quote` is set.
+/// A quote is accepted once, for its user only, and its nonce is consumed unless `dry_run`.
+pub fn resolve_quoted_min_out(
+    accounts: &QuoteAccounts,
+    config: &Option<Config>,
+    signed_quote: Option<&SignedQuote>,
+    signature_index: usize,
+    dst_mint: &Pubkey,
+    amount_out_min: u64,
+    dry_run: bool,
+) -> Result<u64> {
+    let Some(quote) = signed_quote else {
+        if config
+            .as_ref()
+            .is_some_and(|config| config.require_signed_quote)
+        {
+            msg!("Error: A signed quote is required.");
+            return err!(errors::ErrorCode::InvalidQuote);
+        }
+        return Ok(amount_out_min);
+    };
+    let config = config
+        .as_ref()
+        .ok_or_else(|| error!(errors::ErrorCode::ConfigMissing))?;
+    if quote.user != accounts.user.key() {
+        msg!("Error: Quote was signed for {}.", quote.user);
+        return err!(errors::ErrorCode::InvalidQuote);
+    }
+    let instructions_sysvar = accounts
+        .instructions_sysvar
+        .as_ref()
+        .ok_or_else(|| error!(errors::ErrorCode::InvalidQuote))?;
+    let min_out = verify_signed_quote(
+        instructions_sysvar,
+        &config.quote_signer,
+        quote,
+        dst_mint,
+        signature_index,
+    )?;
+    consume_quote_nonce(accounts, quote.nonce, dry_run)?;
+
+    Ok(min_out)
+}
+
+/// Records `nonce` as the user's last used quote nonce, creating the PDA on first use.
+/// With `dry_run` the nonce is only checked, so simulating a swap doesn't use up its quote.
+fn consume_quote_nonce(accounts: &QuoteAccounts, nonce: u64, dry_run: bool) -> Result<()> {
+    let (Some(quote_nonce), Some(bump)) = (&accounts.quote_nonce, accounts.quote_nonce_bump) else {
+        return err!(errors::ErrorCode::InvalidQuote);
+    };
+
+    let last_nonce = if quote_nonce.data_is_empty() {
+        0
+    } else {
+        if quote_nonce.owner != &crate::ID {
+            return err!(errors::ErrorCode::IncorrectOwner);
+        }
+        let data = quote_nonce.try_borrow_data()?;
+        QuoteNonce::try_deserialize(&mut data.as_ref())?.last_nonce
+    };
+    if nonce <= last_nonce {
+        msg!("Error: Quote nonce {} is not above {}.", nonce, last_nonce);
+        return err!(errors::ErrorCode::InvalidQuote);
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    if quote_nonce.data_is_empty() {
+        msg!("Initialize quote nonce");
+        create_pda_account(
+            &accounts.user,
+            quote_nonce,
+            &accounts.system_program,
+            8 + QuoteNonce::INIT_SPACE,
+            &crate::ID,
+            &[&[
+                constants::QUOTE_NONCE_SEED,
+                accounts.user.key.as_ref(),
+                &bump_seed(bump),
+            ]],
+        )?;
+    }
+    let record = QuoteNonce { last_nonce: nonce };
+
+    let mut data = quote_nonce.try_borrow_mut_data()?;
+    record.try_serialize(&mut &mut data[..])
+}
+
+/// Checks `quote` against signature `signature_index` of the Ed25519 program instruction
+/// right before this one and returns its `min_out`. The signature itself is verified by
+/// the runtime; here we only make sure it covers this exact quote and comes from the
+/// configured quote signer.
+pub fn verify_signed_quote(
+    instructions_sysvar: &AccountInfo,
+    quote_signer: &Pubkey,
+    quote: &SignedQuote,
+    dst_mint: &Pubkey,
+    signature_index: usize,
+) -> Result<u64> {
+    if *quote_signer == Pubkey::default() || quote.dst_mint != *dst_mint {
+        return err!(errors::ErrorCode::InvalidQuote);
+    }
+    if quote.expiry < Clock::get()?.unix_timestamp {
+        msg!("Error: Quote expired at {}.", quote.expiry);
+        return err!(errors::ErrorCode::InvalidQuote);
+    }
+
+    let current_index = load_current_index_checked(instructions_sysvar)?;
+    let ed25519_index = current_index
+        .checked_sub(1)
+        .ok_or_else(|| error!(errors::ErrorCode::InvalidQuote))?;
+    let ed25519_ix = load_instruction_at_checked(ed25519_index as usize, instructions_sysvar)?;
+    if ed25519_ix.program_id != ed25519_program::ID {
+        return err!(errors::ErrorCode::InvalidQuote);
+    }
+
+    // Layout: signature count, padding, then 14 bytes of u16 offsets per signature. Offsets
+    // must point into the Ed25519 instruction itself (index u16::MAX).
+    let data = &ed25519_ix.data;
+    let read_u16 = |at: usize| -> Result<usize> {
+        data.get(at..at + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+            .ok_or_else(|| error!(errors::ErrorCode::InvalidQuote))
+    };
+    let signature_count = data.first().copied().unwrap_or(0) as usize;
+    if signature_index >= signature_count {
+        return err!(errors::ErrorCode::InvalidQuote);
+    }
+    let offsets = 2 + signature_index * 14;
+    let public_key_offset = read_u16(offsets + 4)?;
+    let message_offset = read_u16(offsets + 8)?;
+    let message_size = read_u16(offsets + 10)?;
+    for instruction_index_at in [2, 6, 12] {
+        if read_u16(offsets + instruction_index_at)? != u16::MAX as usize {
+            return err!(errors::ErrorCode::InvalidQuote);
+        }
+    }
+
+    let public_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or_else(|| error!(errors::ErrorCode::InvalidQuote))?;
+    let message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or_else(|| error!(errors::ErrorCode::InvalidQuote))?;
+    if public_key != quote_signer.as_ref() || message != quote.try_to_vec()?.as_slice() {
+        return err!(errors::ErrorCode::InvalidQuote);
+    }
+
+    Ok(quote.min_out)
+}
+
+/// Non-failing: only flags realized output that strays from the client's
+/// approximate quote, which usually means stale or misrouted route data.
+pub fn check_output_deviation(
+    amount_out: u64,
+    expected_out: u64,
+    tolerance_bps: u64,
+    emit_events: bool,
+) {
+    let deviation = amount_out.abs_diff(expected_out);
+    let allowed = apply_bps(expected_out, tolerance_bps, constants::FEE_DENOM).unwrap_or(u64::MAX);
+    if deviation > allowed {
+        msg!(
+            "Warning: Out amount after swap is {} which deviates from approximate {}.",
+            amount_out,
+            expected_out
+        );
+        if emit_events {
+            emit!(OutputDeviated {
+                expected: expected_out,
+                actual: amount_out,
+                tolerance_bps,
+            });
+        }
+    }
+}
+
+pub fn assert_slippage_protection(config: &Option<Config>, amount_out_min: u64) -> Result<()> {
+    let required = config
+        .as_ref()
+        .is_some_and(|config| config.require_slippage_protection);
+    if required && amount_out_min == 0 {
+        return err!(errors::ErrorCode::SlippageProtectionRequired);
+    }
+
+    Ok(())
+}
+
+/// Checks the quote still clears `amount_out_min` once the fee, converted to
+/// output units pro rata to `amount_in`, is deducted from it.
+pub fn assert_fee_within_gain(
+    quoted_out: u64,
+    amount_in: u64,
+    total_fee: u64,
+    amount_out_min: u64,
+) -> Result<()> {
+    let fee_out = (quoted_out as u128)
+        .checked_mul(total_fee as u128)
+        .and_then(|value| value.checked_div(amount_in as u128))
+        .and_then(|value| u64::try_from(value).ok())
+        .ok_or_else(|| error!(errors::ErrorCode::Overflow))?;
+    let net_out = quoted_out.saturating_sub(fee_out);
+    if net_out < amount_out_min {
+        msg!(
+            "Error: Quoted out amount net of fees is {} which is lower than expected {}.",
+            net_out,
+            amount_out_min
+        );
+        return err!(errors::ErrorCode::FeeExceedsGain);
+    }
+
+    Ok(())
+}
+
+/// Checks `receiver_ata` is `dst_owner`'s ATA for its mint under `token_program`.
+pub fn assert_receiver_ata_with_program(
+    receiver_ata: &InterfaceAccount<token_interface::TokenAccount>,
+    dst_owner: Pubkey,
+    token_program: &Pubkey,
+) -> Result<()> {
+    let expected =
+        get_associated_token_address_with_program_id(&dst_owner, &receiver_ata.mint, token_program);
+    if receiver_ata.key() != expected {
+        msg!(
+            "Error: Receiver account is {} but expected {} for owner {}.",
+            receiver_ata.key(),
+            expected,
+            dst_owner
+        );
+        return err!(errors::ErrorCode::ReceiverMismatch);
+    }
+
+    Ok(())
+}
+
+/// Moves `amount` with `transfer_checked` under whichever token program owns the accounts.
+pub fn transfer_interface<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    mint: &InterfaceAccount<'info, token_interface::Mint>,
+    from: AccountInfo<'info>,
+    to: AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+) -> Result<()> {
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            token_interface::TransferChecked {
+                from,
+                mint: mint.to_account_info(),
+                to,
+                authority,
+            },
+            signer_seeds,
+        ),
+        amount,
+        mint.decimals,
+    )
+}
+
+pub fn assert_destination_mint(
+    receiver_ata: &InterfaceAccount<TokenAccount>,
+    dst_mint: Pubkey,
+) -> Result<()> {
+    if receiver_ata.mint != dst_mint {
+        msg!(
+            "Error: Receiver account mint is {} but the route outputs {}.",
+            receiver_ata.mint,
+            dst_mint
+        );
+        return err!(errors::ErrorCode::WrongDestinationMint);
+    }
+
+    Ok(())
+}
+
+pub fn assert_amount_spent(prev_bal: u64, post_bal: u64, max_amount_in: u64) -> Result<()> {
+    let spent = prev_bal.saturating_sub(post_bal);
+    if spent > max_amount_in {
+        msg!(
+            "Error: In amount spent by swap is {} which is higher than allowed {}.",
+            spent,
+            max_amount_in
+        );
+        return err!(errors::ErrorCode::InputOverspent);
+    }
+
+    Ok(())
+}
+
+/// Like `assert_amount_out`, with `threshold` in the price units of the Pyth `oracle`
+/// (`10^exponent` of its quote currency) rather than in destination tokens. `decimals` are
+/// the destination mint's.
+pub fn assert_amount_out_in_reference(
+    prev_bal: u64,
+    post_bal: u64,
+    threshold: u64,
+    soft_min: Option<u64>,
+    oracle: &AccountInfo,
+    decimals: u8,
+    emit_events: bool,
+) -> Result<()> {
+    let price = load_oracle_price(oracle)?;
+
+    let amount_out = post_bal
+        .checked_sub(prev_bal)
+        .ok_or_else(|| error!(errors::ErrorCode::Underflow))?;
+    let reference_out = (amount_out as u128)
+        .checked_mul(price.price as u128)
+        .and_then(|value| value.checked_div(10u128.checked_pow(decimals as u32)?))
+        .and_then(|value| u64::try_from(value).ok())
+        .ok_or_else(|| error!(errors::ErrorCode::Overflow))?;
+
+    assert_amount_out(0, reference_out, threshold, soft_min, emit_events)
+}
+
+/// Reads a Pyth receiver price update, rejecting one that is not fully verified, has no
+/// positive price or was published more than `MAX_PRICE_AGE` seconds ago.
+pub fn load_oracle_price(oracle: &AccountInfo) -> Result<PriceFeedMessage> {
+    if oracle.owner != &constants::PYTH_RECEIVER {
+        return err!(errors::ErrorCode::InvalidOracle);
+    }
+    let data = oracle.try_borrow_data()?;
+    if data.get(..8) != Some(PriceUpdateV2::DISCRIMINATOR.as_slice()) {
+        return err!(errors::ErrorCode::InvalidOracle);
+    }
+    let update = PriceUpdateV2::deserialize(&mut &data[8..])?;
+    if update.verification_level != VerificationLevel::Full || update.price_message.price <= 0 {
+        return err!(errors::ErrorCode::InvalidOracle);
+    }
+    assert_price_fresh(update.price_message.publish_time)?;
+
+    Ok(update.price_message)
+}
+
+fn assert_price_fresh(publish_time: i64) -> Result<()> {
+    let age = Clock::get()?.unix_timestamp.saturating_sub(publish_time);
+    if age > constants::MAX_PRICE_AGE {
+        msg!(
+            "Error: Price feed is {} seconds old which exceeds {}.",
+            age,
+            constants::MAX_PRICE_AGE
+        );
+        return err!(errors::ErrorCode::StalePrice);
+    }
+
+    Ok(())
+}
+
+/// Single-byte seed for a PDA bump, independent of how the bump value is typed.
+pub fn bump_seed(bump: u8) -> [u8; 1] {
+    [bump]
+}
+
+fn assert_pda(key: &Pubkey, seeds: &[&[u8]]) -> Result<()> {
+    let expected = Pubkey::create_program_address(seeds, &crate::ID)
+        .map_err(|_| error!(errors::ErrorCode::InvalidPda))?;
+    if key != &expected {
+        msg!("Error: Account {} is not the PDA {}.", key, expected);
+        return err!(errors::ErrorCode::InvalidPda);
+    }
+
+    Ok(())
+}
+
+pub fn forward_shared_output<'info>(
+    accounts: SharedOutputAccounts<'info>,
+    authority_seeds: &[&[u8]],
+    prev_shared_bal: u64,
+    stranded_epsilon: u64,
+) -> Result<u64> {
+    let SharedOutputAccounts {
+        program_authority,
+        shared_dst_ata,
+        receiver_dst_ata,
+        dst_mint,
+        token_program,
+    } = accounts;
+    assert_pda(program_authority.key, authority_seeds)?;
+    let post_shared_bal = {
+        if shared_dst_ata.owner != token_program.key {
+            return err!(errors::ErrorCode::IncorrectOwner);
+        }
+        let data = shared_dst_ata.try_borrow_data()?;
+        let shared = TokenAccount::try_deserialize(&mut data.as_ref())?;
+        if shared.owner != program_authority.key() {
+            return err!(errors::ErrorCode::IncorrectOwner);
+        }
+        shared.amount
+    };
+    let out_amount = post_shared_bal
+        .checked_sub(prev_shared_bal)
+        .ok_or_else(|| error!(errors::ErrorCode::Underflow))?;
+
+    let prev_receiver_bal = read_token_amount(&receiver_dst_ata.to_account_info())?;
+    msg!("Transfer shared route output to receiver");
+    transfer_interface(
+        &token_program,
+        &dst_mint,
+        shared_dst_ata,
+        receiver_dst_ata.to_account_info(),
+        program_authority.to_account_info(),
+        &[authority_seeds],
+        out_amount,
+    )?;
+
+    // The whole delta was sent, so anything missing was withheld on the way to the
+    // receiver, e.g. by a transfer fee or rounding in the mint.
+    let received = read_token_amount(&receiver_dst_ata.to_account_info())?
+        .checked_sub(prev_receiver_bal)
+        .ok_or_else(|| error!(errors::ErrorCode::Underflow))?;
+    let stranded = out_amount.saturating_sub(received);
+    if stranded > stranded_epsilon {
+        msg!(
+            "Error: {} of {} route output did not reach the receiver, exceeding {}.",
+            stranded,
+            out_amount,
+            stranded_epsilon
+        );
+        return err!(errors::ErrorCode::OutputStranded);
+    }
+
+    Ok(received)
+}
+
+/// Token account size for `mint`, including the account extensions a Token-2022 mint requires.
+pub fn token_account_space(token_program: &Pubkey, mint: &AccountInfo) -> Result<usize> {
+    if *token_program != token_2022::ID {
+        return Ok(token::TokenAccount::LEN);
+    }
+
+    let data = mint.try_borrow_data()?;
+    let space = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)
+        .and_then(|mint_state| {
+            let account_extensions = ExtensionType::get_required_init_account_extensions(
+                &mint_state.get_extension_types()?,
+            );
+            ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(
+                &account_extensions,
+            )
+        })
+        .map_err(|err| {
+            msg!(
+                "Error: Mint {} extensions give no account size: {}.",
+                mint.key,
+                err
+            );
+            error!(errors::ErrorCode::InvalidAccountSize)
+        })?;
+    assert_token_account_space(space)?;
+
+    Ok(space)
+}
+
+fn assert_token_account_space(space: usize) -> Result<()> {
+    if space > constants::MAX_TOKEN_ACCOUNT_SPACE {
+        msg!(
+            "Error: Token account needs {} bytes which exceeds {}.",
+            space,
+            constants::MAX_TOKEN_ACCOUNT_SPACE
+        );
+        return err!(errors::ErrorCode::InvalidAccountSize);
+    }
+
+    Ok(())
+}
+
+/// Creates a PDA of `space` bytes owned by `owner`. Unlike `create_account` this can't be
+/// blocked by someone sending lamports to the address first: any existing balance is
+/// topped up to the rent-exempt minimum, then the account is allocated and assigned.
+pub fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    space: usize,
+    owner: &Pubkey,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let lamports = Rent::get()?.minimum_balance(space);
+    let space = u64::try_from(space).map_err(|_| error!(errors::ErrorCode::InvalidAccountSize))?;
+    if account.lamports() == 0 {
+        return system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                system_program::CreateAccount {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+                signer_seeds,
+            ),
+            lamports,
+            space,
+            owner,
+        );
+    }
+
+    let shortfall = lamports.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+    system_program::allocate(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            system_program::Allocate {
+                account_to_allocate: account.clone(),
+            },
+            signer_seeds,
+        ),
+        space,
+    )?;
+    system_program::assign(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            system_program::Assign {
+                account_to_assign: account.clone(),
+            },
+            signer_seeds,
+        ),
+        owner,
+    )
+}
+
+/// Records `key` in the user's PDA for it, failing with `DuplicateRequest` if it was used
+/// within the TTL.
+pub fn record_idempotency_key<'info>(
+    record: UncheckedAccount<'info>,
+    payer: Signer<'info>,
+    system_program: Program<'info, System>,
+    key: [u8; 16],
+) -> Result<()> {
+    let (expected, bump) = Pubkey::find_program_address(
+        &[constants::IDEMPOTENCY_SEED, payer.key.as_ref(), &key],
+        &crate::ID,
+    );
+    if record.key() != expected {
+        msg!(
+            "Error: Account {} is not the PDA {}.",
+            record.key(),
+            expected
+        );
+        return err!(errors::ErrorCode::InvalidPda);
+    }
+    let now = Clock::get()?.unix_timestamp;
+
+    let idempotency_record = if record.data_is_empty() {
+        msg!("Initialize idempotency record");
+        create_pda_account(
+            &payer,
+            &record,
+            &system_program,
+            8 + IdempotencyRecord::INIT_SPACE,
+            &crate::ID,
+            &[&[
+                constants::IDEMPOTENCY_SEED,
+                payer.key.as_ref(),
+                &key,
+                &bump_seed(bump),
+            ]],
+        )?;
+        IdempotencyRecord {
+            payer: payer.key(),
+            created_at: now,
+        }
+    } else {
+        if record.owner != &crate::ID {
+            return err!(errors::ErrorCode::IncorrectOwner);
+        }
+        let data = record.try_borrow_data()?;
+        let mut idempotency_record = IdempotencyRecord::try_deserialize(&mut data.as_ref())?;
+        if now.saturating_sub(idempotency_record.created_at) < constants::IDEMPOTENCY_TTL {
+            return err!(errors::ErrorCode::DuplicateRequest);
+        }
+        idempotency_record.created_at = now;
+        idempotency_record
+    };
+
+    let mut data = record.try_borrow_mut_data()?;
+    idempotency_record.try_serialize(&mut data.as_mut())
+}
+
+/// Creates the receipt `clawback_fee` checks against, keyed by the integrator fee account
+/// and the fee nonce. Rent comes from the fee authority and returns to it through
+/// `close_fee_receipt`.
+pub fn record_fee_receipt(accounts: &AccountsForFee, nonce: u64, amount: u64) -> Result<()> {
+    let receipt = accounts
+        .fee_receipt
+        .as_ref()
+        .ok_or_else(|| error!(errors::ErrorCode::FeeReceiptMissing))?;
+    let payer = accounts
+        .fee_payer
+        .as_ref()
+        .map_or(&accounts.user, |fee_payer| &fee_payer.rent_payer);
+    let integrator_token_account = accounts.integrator_token_account.key();
+    let nonce_bytes = nonce.to_le_bytes();
+    let (expected, bump) = Pubkey::find_program_address(
+        &[
+            constants::FEE_RECEIPT_SEED,
+            integrator_token_account.as_ref(),
+            &nonce_bytes,
+        ],
+        &crate::ID,
+    );
+    if receipt.key() != expected {
+        msg!(
+            "Error: Account {} is not the PDA {}.",
+            receipt.key(),
+            expected
+        );
+        return err!(errors::ErrorCode::InvalidPda);
+    }
+
+    msg!("Initialize fee receipt");
+    create_pda_account(
+        payer,
+        receipt,
+        &accounts.system_program,
+        8 + FeeReceipt::INIT_SPACE,
+        &crate::ID,
+        &[&[
+            constants::FEE_RECEIPT_SEED,
+            integrator_token_account.as_ref(),
+            &nonce_bytes,
+            &bump_seed(bump),
+        ]],
+    )?;
+
+    let fee_receipt = FeeReceipt {
+        integrator_token_account,
+        amount,
+        taken_at: Clock::get()?.unix_timestamp,
+        clawed_back: 0,
+        payer: payer.key(),
+    };
+    let mut data = receipt.try_borrow_mut_data()?;
+    fee_receipt.try_serialize(&mut data.as_mut())
+}
+
+pub fn ensure_program_wsol<'info>(
+    accounts: ProgramWsolAccounts<'info>,
+    authority_bump: &[u8],
+    wsol_bump: &[u8],
+) -> Result<()> {
+    let ProgramWsolAccounts {
+        program_authority,
+        program_wsol,
+        sol_mint,
+        token_program,
+        system_program,
+        rent_payer,
+    } = accounts;
+    assert_pda(
+        program_authority.key,
+        &[constants::AUTHORITY_SEED, authority_bump],
+    )?;
+    assert_pda(program_wsol.key, &[constants::WSOL_SEED, wsol_bump])?;
+
+    let space = token_account_space(token_program.key, &sol_mint.to_account_info())?;
+    if program_wsol.data_is_empty() {
+        let signer_seeds: &[&[&[u8]]] = &[
+            &[constants::AUTHORITY_SEED, authority_bump],
+            &[constants::WSOL_SEED, wsol_bump],
+        ];
+
+        msg!("Initialize program wSOL account");
+        let rent = Rent::get()?;
+        let lamports = rent.minimum_balance(space);
+        let funder = match rent_payer {
+            Some(rent_payer) => rent_payer.to_account_info(),
+            None => program_authority.to_account_info(),
+        };
+        system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                system_program::CreateAccount {
+                    from: funder,
+                    to: program_wsol.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            lamports,
+            u64::try_from(space).map_err(|_| error!(errors::ErrorCode::InvalidAccountSize))?,
+            token_program.key,
+        )?;
+
+        msg!("Initialize program wSOL token account");
+        return token_interface::initialize_account3(CpiContext::new(
+            token_program.to_account_info(),
+            token_interface::InitializeAccount3 {
+                account: program_wsol.to_account_info(),
+                mint: sol_mint.to_account_info(),
+                authority: program_authority.to_account_info(),
+            },
+        ));
+    }
+
+    if program_wsol.owner != token_program.key {
+        msg!(
+            "Error: Program wSOL account owner is {} but expected {}.",
+            program_wsol.owner,
+            token_program.key
+        );
+        return err!(errors::ErrorCode::InvalidWsolAccount);
+    }
+    if program_wsol.data_len() != space {
+        msg!(
+            "Error: Program wSOL account size is {} but expected {}.",
+            program_wsol.data_len(),
+            space
+        );
+        return err!(errors::ErrorCode::InvalidWsolAccount);
+    }
+
+    // Byte 108 of the base layout is the account state, zero until initialized. A create
+    // that never reached `initialize_account3` is finished here instead of failing later.
+    if program_wsol.try_borrow_data()?[108] == 0 {
+        msg!("Complete program wSOL token account initialization");
+        return token_interface::initialize_account3(CpiContext::new(
+            token_program.to_account_info(),
+            token_interface::InitializeAccount3 {
+                account: program_wsol.to_account_info(),
+                mint: sol_mint.to_account_info(),
+                authority: program_authority.to_account_info(),
+            },
+        ));
+    }
+
+    // The token account owner is bytes 32..64 of the base layout; reading it directly
+    // skips a full deserialize on the swap path.
+    let data = program_wsol.try_borrow_data()?;
+    if &data[32..64] != program_authority.key.as_ref() {
+        return err!(errors::ErrorCode::IncorrectOwner);
+    }
+
+    Ok(())
+}
+
+/// Same as `ensure_program_wsol`, but also returns the deserialized account.
+pub fn ensure_program_wsol_loaded<'info>(
+    accounts: ProgramWsolAccounts<'info>,
+    authority_bump: &[u8],
+    wsol_bump: &[u8],
+) -> Result<TokenAccount> {
+    let program_wsol = accounts.program_wsol.clone();
+    ensure_program_wsol(accounts, authority_bump, wsol_bump)?;
+
+    let data = program_wsol.try_borrow_data()?;
+    // Token-2022 extensions trail the base account layout.
+    let wsol_token_account = TokenAccount::try_deserialize(&mut &data[..token::TokenAccount::LEN])?;
+
+    Ok(wsol_token_account)
+}
+
+/// Checks `rent_payer` against the payer recorded for program wSOL. A recorded payer has
+/// to be passed so the rent it fronted goes back to it on close.
+pub fn recorded_wsol_rent_payer<'info>(
+    recorded: Pubkey,
+    rent_payer: Option<SystemAccount<'info>>,
+) -> Result<Option<SystemAccount<'info>>> {
+    match rent_payer {
+        Some(rent_payer) => {
+            require_keys_eq!(
+                rent_payer.key(),
+                recorded,
+                errors::ErrorCode::WrongRentPayer
+            );
+            Ok(Some(rent_payer))
+        }
+        None if recorded != Pubkey::default() => err!(errors::ErrorCode::WrongRentPayer),
+        None => Ok(None),
+    }
+}
+
+/// Creates `user`'s wSOL deposit account on first use. The deposit is its own token
+/// authority, so only instructions that derive it from the user can move or close it and
+/// nothing signed by the program authority can reach it.
+pub fn ensure_wsol_deposit<'info>(
+    wsol_deposit: &UncheckedAccount<'info>,
+    sol_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    system_program: &Program<'info, System>,
+    user: &Signer<'info>,
+    deposit_bump: u8,
+) -> Result<TokenAccount> {
+    if wsol_deposit.data_is_empty() {
+        msg!("Initialize wSOL deposit account");
+        create_pda_account(
+            user,
+            wsol_deposit,
+            system_program,
+            token::TokenAccount::LEN,
+            token_program.key,
+            &[&[
+                constants::WSOL_DEPOSIT_SEED,
+                user.key.as_ref(),
+                &bump_seed(deposit_bump),
+            ]],
+        )?;
+        token_interface::initialize_account3(CpiContext::new(
+            token_program.to_account_info(),
+            token_interface::InitializeAccount3 {
+                account: wsol_deposit.to_account_info(),
+                mint: sol_mint.to_account_info(),
+                authority: wsol_deposit.to_account_info(),
+            },
+        ))?;
+    }
+
+    if wsol_deposit.owner != token_program.key {
+        return err!(errors::ErrorCode::InvalidWsolAccount);
+    }
+    let data = wsol_deposit.try_borrow_data()?;
+    let deposit = TokenAccount::try_deserialize(&mut data.as_ref())?;
+    if deposit.owner != wsol_deposit.key() || deposit.mint != sol_mint.key() {
+        return err!(errors::ErrorCode::InvalidWsolAccount);
+    }
+
+    Ok(deposit)
+}
+
+/// Creates the escrow a swap routes through, at `[ESCROW_SEED, user, mint]` and held by
+/// `escrow_authority`. It must be closed with `close_swap_escrow` before the swap returns,
+/// so no escrow outlives the swap that opened it.
+pub fn open_swap_escrow<'info>(
+    escrow_authority: &SystemAccount<'info>,
+    escrow: &UncheckedAccount<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    system_program: &Program<'info, System>,
+    user: &Signer<'info>,
+    escrow_bump: u8,
+) -> Result<()> {
+    msg!("Open swap escrow for {}", mint.key());
+    create_pda_account(
+        user,
+        escrow,
+        system_program,
+        token_account_space(token_program.key, &mint.to_account_info())?,
+        token_program.key,
+        &[&[
+            constants::ESCROW_SEED,
+            user.key.as_ref(),
+            mint.key().as_ref(),
+            &bump_seed(escrow_bump),
+        ]],
+    )?;
+    token_interface::initialize_account3(CpiContext::new(
+        token_program.to_account_info(),
+        token_interface::InitializeAccount3 {
+            account: escrow.to_account_info(),
+            mint: mint.to_account_info(),
+            authority: escrow_authority.to_account_info(),
+        },
+    ))
+}
+
+/// Closes an escrow opened by `open_swap_escrow`, returning its rent to the user. Fails
+/// if the swap left any tokens in it.
+pub fn close_swap_escrow<'info>(
+    escrow_authority: &SystemAccount<'info>,
+    escrow: &UncheckedAccount<'info>,
+    user: &Signer<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    token_interface::close_account(CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        token_interface::CloseAccount {
+            account: escrow.to_account_info(),
+            destination: user.to_account_info(),
+            authority: escrow_authority.to_account_info(),
+        },
+        signer_seeds,
+    ))
+}
+
+/// A route signed by the program authority can move any token account the authority
+/// owns or is delegated on, so such accounts may only appear if they are the escrows of
+/// the swap being routed.
+pub fn assert_route_escrow_only(
+    remaining_accounts: &[AccountInfo],
+    authority: &Pubkey,
+    escrows: &[Pubkey],
+) -> Result<()> {
+    for account in remaining_accounts {
+        if escrows.contains(account.key) {
+            continue;
+        }
+        let Some(token_account) = load_token_account(account)? else {
+            continue;
+        };
+        let delegate: Option<Pubkey> = token_account.delegate.into();
+        if token_account.owner == *authority || delegate == Some(*authority) {
+            msg!(
+                "Error: Route references authority account {} outside the swap escrows.",
+                account.key
+            );
+            return err!(errors::ErrorCode::UnexpectedAuthorityAccount);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn close_program_wsol<'info>(
+    accounts: CloseProgramWsolAccounts<'info>,
+    authority_bump: &[u8],
+    emit_events: bool,
+    strict_cleanup: bool,
+) -> Result<u64> {
+    let CloseProgramWsolAccounts {
+        program_authority,
+        program_wsol,
+        receiver,
+        unizen,
+        rent_payer,
+        token_program,
+        system_program,
+    } = accounts;
+    assert_pda(
+        program_authority.key,
+        &[constants::AUTHORITY_SEED, authority_bump],
+    )?;
+    let signer_seeds: &[&[&[u8]]] = &[&[constants::AUTHORITY_SEED, authority_bump]];
+
+    let wsol_balance = program_wsol.lamports();
+    let rent = Rent::get()?;
+    let rent_lamports = rent.minimum_balance(program_wsol.data_len());
+    let out_amount = wsol_balance
+        .checked_sub(rent_lamports)
+        .ok_or_else(|| error!(errors::ErrorCode::Underflow))?;
+
+    msg!("Close program wSOL token account");
+    token_interface::close_account(CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        token_interface::CloseAccount {
+            account: program_wsol.to_account_info(),
+            destination: program_authority.to_account_info(),
+            authority: program_authority.to_account_info(),
+        },
+        signer_seeds,
+    ))?;
+
+    msg!("Transfer SOL to receiver");
+    system_program::transfer(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: program_authority.to_account_info(),
+                to: receiver.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        out_amount,
+    )?;
+
+    let retained_rent = if rent_payer.is_some() {
+        0
+    } else {
+        rent_lamports
+    };
+    if let Some(rent_payer) = rent_payer.as_ref() {
+        msg!("Reimburse program wSOL rent to payer");
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: program_authority.to_account_info(),
+                    to: rent_payer.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            rent_lamports,
+        )?;
+    }
+
+    if emit_events {
+        emit!(WsolClosed {
+            receiver: receiver.key(),
+            out_amount,
+            rent_reclaimed: rent_lamports,
+        });
+    }
+
+    if let Some(unizen) = unizen {
+        // Keep enough to recreate program wSOL while staying rent exempt itself.
+        let reserve = rent_lamports
+            .checked_add(rent.minimum_balance(0))
+            .ok_or_else(|| error!(errors::ErrorCode::Overflow))?;
+        let dust = program_authority.lamports().saturating_sub(reserve);
+        if dust > 0 {
+            msg!("Sweep program authority dust to Unizen");
+            system_program::transfer(
+                CpiContext::new_with_signer(
+                    system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: program_authority.to_account_info(),
+                        to: unizen.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                dust,
+            )?;
+
+            if emit_events {
+                emit!(DustSwept {
+                    receiver: unizen.key(),
+                    amount: dust,
+                });
+            }
+        }
+    }
+
+    if strict_cleanup {
+        // The authority may keep its own rent and the closed account's rent, nothing else.
+        let allowed = rent
+            .minimum_balance(0)
+            .checked_add(retained_rent)
+            .ok_or_else(|| error!(errors::ErrorCode::Overflow))?;
+        let held = program_authority.lamports();
+        if held > allowed {
+            msg!(
+                "Error: Program authority holds {} lamports after close, expected at most {}.",
+                held,
+                allowed
+            );
+            return err!(errors::ErrorCode::DirtyAuthority);
+        }
+    }
+
+    Ok(out_amount)
+}
+
+pub struct AccountsForFee<'info> {
+    /// The swapping user. Only read for its key unless it is also the fee authority, in which
+    /// case it has to sign.
+    pub user: AccountInfo<'info>,
+    pub fee_payer: Option<FeePayer<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// Source of the fee transfers, owned by `fee_payer` when set or `user` otherwise.
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub unizen_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub integrator_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub referrer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// The user's balance of `Config.discount_mint`, for the holding discount.
+    pub discount_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub config: Option<Config>,
+    /// Receipt PDA for `clawback_fee`, required whenever a fee counter assigns a nonce.
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+    /// Set to pay the fee in lamports instead of `mint`.
+    pub sol_fee: Option<SolFeeAccounts<'info>>,
+}
+
+/// Authority over the fee source when it isn't the user's own account.
+pub struct FeePayer<'info> {
+    pub authority: AccountInfo<'info>,
+    /// Seeds to sign with when `authority` is a program PDA, empty for a signer.
+    pub signer_seeds: Vec<Vec<u8>>,
+    /// Pays the fee receipt rent and gets it back when the receipt closes.
+    pub rent_payer: AccountInfo<'info>,
+}
+
+impl<'info> FeePayer<'info> {
+    /// A signer that both owns the fee source and pays the receipt rent.
+    pub fn signer(signer: &Signer<'info>) -> Self {
+        Self {
+            authority: signer.to_account_info(),
+            signer_seeds: Vec::new(),
+            rent_payer: signer.to_account_info(),
+        }
+    }
+}
+
+/// Accounts for `forward_shared_output`.
+pub struct SharedOutputAccounts<'info> {
+    /// Program PDA that owns `shared_dst_ata`.
+    pub program_authority: SystemAccount<'info>,
+    /// Authority-owned account the route delivered into.
+    pub shared_dst_ata: AccountInfo<'info>,
+    pub receiver_dst_ata: InterfaceAccount<'info, TokenAccount>,
+    pub dst_mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Accounts for `ensure_program_wsol`.
+pub struct ProgramWsolAccounts<'info> {
+    pub program_authority: SystemAccount<'info>,
+    pub program_wsol: UncheckedAccount<'info>,
+    pub sol_mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    /// Funds a new account instead of the program authority.
+    pub rent_payer: Option<Signer<'info>>,
+}
+
+/// Accounts for `close_program_wsol`.
+pub struct CloseProgramWsolAccounts<'info> {
+    pub program_authority: SystemAccount<'info>,
+    pub program_wsol: UncheckedAccount<'info>,
+    pub receiver: SystemAccount<'info>,
+    /// Receives the authority's lamports above its reserve when set.
+    pub unizen: Option<SystemAccount<'info>>,
+    /// Reimbursed the account rent when set, see `recorded_wsol_rent_payer`.
+    pub rent_payer: Option<SystemAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for checking signed quotes with `resolve_quoted_min_out`.
+pub struct QuoteAccounts<'info> {
+    pub user: Signer<'info>,
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+    /// The user's `[QUOTE_NONCE_SEED, user]` PDA, created on the first quote.
+    pub quote_nonce: Option<UncheckedAccount<'info>>,
+    pub quote_nonce_bump: Option<u8>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for paying fees in lamports; `price_feed` is the fee mint's `SOL_FEE_PRICE_SEED`
+/// PDA and quotes lamports per whole fee token.
+pub struct SolFeeAccounts<'info> {
+    pub price_feed: Account<'info, PriceFeed>,
+    pub unizen: SystemAccount<'info>,
+    pub integrator: SystemAccount<'info>,
+    pub referrer: Option<SystemAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts for `sweep_wsol_dust`.
+pub struct WsolDustAccounts<'info> {
+    pub token_program: Interface<'info, TokenInterface>,
+    pub sol_mint: InterfaceAccount<'info, Mint>,
+    pub user: Signer<'info>,
+    pub user_wsol_ata: InterfaceAccount<'info, TokenAccount>,
+    pub unizen_wsol_ata: InterfaceAccount<'info, TokenAccount>,
+}
+
+pub struct FeeSplit {
+    pub fee_percent: u64,
+    pub fee_tier: Option<u8>,
+    pub share_percent: u64,
+    pub charges_fee: bool,
+    pub total_fee: u64,
+    pub referrer_fee: u64,
+    pub unizen_fee: u64,
+    pub integrator_fee: u64,
+}
+
+#[event]
+pub struct TakeFee {
+    pub user: Pubkey,
+    pub token: Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+    pub fee_mode: FeeMode,
+    pub fee_percent: u64,
+    pub fee_tier: Option<u8>,
+    pub share_percent: u64,
+    pub referrer: Option<Pubkey>,
+    pub referrer_percent: u64,
+    pub nonce: Option<u64>,
+    pub paid_in_sol: bool,
+    pub share_mode: Option<ShareMode>,
+    pub unizen_received: u64,
+    pub integrator_received: u64,
+    /// Where the Unizen and integrator shares went: token accounts, or system accounts
+    /// when `paid_in_sol`.
+    pub unizen_account: Pubkey,
+    pub integrator_account: Pubkey,
+}
+
+#[event]
+pub struct TakeFeeAggregate {
+    pub user: Pubkey,
+    pub token: Pubkey,
+    pub hops: u8,
+    pub amount: u64,
+    pub referrer: Option<Pubkey>,
+    pub referrer_received: u64,
+    pub unizen_account: Pubkey,
+    pub unizen_received: u64,
+    pub integrator_account: Pubkey,
+    pub integrator_received: u64,
+}
+
+#[event]
+pub struct RouteExecuted {
+    pub program_id: Pubkey,
+    pub account_count: u16,
+    pub data_len: u16,
+}
+
+#[event]
+pub struct FeeWaived {
+    pub user: Pubkey,
+    pub token: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct SlippageExceeded {
+    pub expected: u64,
+    pub actual: u64,
+    pub shortfall: u64,
+}
+
+#[event]
+pub struct SlippageWarning {
+    pub expected: u64,
+    pub actual: u64,
+}
+
+#[event]
+pub struct DryRunValidated {
+    pub user: Pubkey,
+    pub amount_in: u64,
+    pub total_fee: u64,
+}
+
+#[event]
+pub struct TokenAccountNotClosed {
+    pub account: Pubkey,
+    pub remaining: u64,
+}
+
+#[event]
+pub struct WsolClosed {
+    pub receiver: Pubkey,
+    pub out_amount: u64,
+    pub rent_reclaimed: u64,
+}
+
+#[event]
+pub struct DustSwept {
+    pub receiver: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct WsolMigrated {
+    pub old_wsol: Pubkey,
+    pub new_wsol: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct ProgramAtasClosed {
+    pub receiver: Pubkey,
+    pub closed: u32,
+    pub skipped: u32,
+    pub rent_reclaimed: u64,
+}
+
+#[event]
+pub struct WeightedLegSwapped {
+    pub leg: u8,
+    pub dst: Pubkey,
+    pub weight_bps: u64,
+    pub amount_in: u64,
+    pub amount_out: u64,
+}
+
+#[event]
+pub struct OutputDeviated {
+    pub expected: u64,
+    pub actual: u64,
+    pub tolerance_bps: u64,
+}
+
+#[event]
+pub struct WsolDustSwept {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub to_unizen: bool,
+}
+
+#[event]
+pub struct FeeAtasCreated {
+    pub integrator: Pubkey,
+    pub created: u32,
+    pub existing: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{constants::FEE_DENOM, test_harness::TestAccount};
+
+    fn take_fee(token: Pubkey, amount: u64, unizen: u64, integrator: u64) -> TakeFee {
+        TakeFee {
+            user: Pubkey::new_unique(),
+            token,
+            amount,
+            decimals: 6,
+            fee_mode: FeeMode::Percentage {
+                fee_percent: 30,
+                share_percent: 1_000,
+            },
+            fee_percent: 30,
+            fee_tier: None,
+            share_percent: 1_000,
+            referrer: None,
+            referrer_percent: 0,
+            nonce: None,
+            paid_in_sol: false,
+            share_mode: None,
+            unizen_received: unizen,
+            integrator_received: integrator,
+            unizen_account: Pubkey::default(),
+            integrator_account: Pubkey::default(),
+        }
+    }
+
+    #[test]
+    fn fee_within_gain_accepts_net_output_at_the_minimum() {
+        // A 1% fee takes 1% of the quoted output too.
+        assert!(assert_fee_within_gain(500_000, 1_000_000, 10_000, 495_000).is_ok());
+        assert!(assert_fee_within_gain(500_000, 1_000_000, 10_000, 400_000).is_ok());
+        assert!(assert_fee_within_gain(500_000, 1_000_000, 0, 500_000).is_ok());
+        assert!(assert_fee_within_gain(u64::MAX, u64::MAX, 1, u64::MAX - 1).is_ok());
+    }
+
+    #[test]
+    fn fee_within_gain_rejects_net_output_below_the_minimum() {
+        for (quoted_out, total_fee, amount_out_min) in [
+            (500_000, 10_000, 495_001),
+            (500_000, 0, 500_001),
+            (500_000, 1_000_000, 1),
+        ] {
+            assert_eq!(
+                assert_fee_within_gain(quoted_out, 1_000_000, total_fee, amount_out_min)
+                    .unwrap_err(),
+                errors::ErrorCode::FeeExceedsGain.into()
+            );
+        }
+    }
+
+    #[test]
+    fn bump_seed_is_the_single_bump_byte() {
+        for bump in [0, 1, 254, 255] {
+            assert_eq!(bump_seed(bump), [bump]);
+        }
+        let (authority, bump) = crate::pda::find_program_authority();
+        let seed = bump_seed(bump);
+        assert_eq!(
+            Pubkey::create_program_address(&[constants::AUTHORITY_SEED, &seed], &crate::ID)
+                .unwrap(),
+            authority
+        );
+    }
+
+    #[test]
+    fn other_bump_does_not_sign_for_the_authority() {
+        let (authority, bump) = crate::pda::find_program_authority();
+        let seed = bump_seed(bump.wrapping_sub(1));
+        assert_ne!(
+            Pubkey::create_program_address(&[constants::AUTHORITY_SEED, &seed], &crate::ID).ok(),
+            Some(authority)
+        );
+    }
+
+    #[test]
+    fn assert_pda_accepts_the_canonical_bump() {
+        let (program_wsol, bump) = crate::pda::find_program_wsol();
+        assert!(assert_pda(&program_wsol, &[constants::WSOL_SEED, &bump_seed(bump)]).is_ok());
+    }
+
+    #[test]
+    fn assert_pda_rejects_a_wrong_bump() {
+        let (program_wsol, bump) = crate::pda::find_program_wsol();
+        for bump in [bump.wrapping_sub(1), bump.wrapping_add(1)] {
+            assert_eq!(
+                assert_pda(&program_wsol, &[constants::WSOL_SEED, &bump_seed(bump)]).unwrap_err(),
+                errors::ErrorCode::InvalidPda.into()
+            );
+        }
+    }
+
+    fn check_router(key: Pubkey, executable: bool) -> Result<()> {
+        let (mut lamports, mut data) = (1, Vec::new());
+        let owner = Pubkey::new_unique();
+        let router_program = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &owner,
+            executable,
+            0,
+        );
+        assert_router_program(&router_program, &Jupiter::id())
+    }
+
+    #[test]
+    fn router_program_must_be_the_executable_expected_program() {
+        assert!(check_router(Jupiter::id(), true).is_ok());
+        assert_eq!(
+            check_router(Jupiter::id(), false).unwrap_err(),
+            errors::ErrorCode::InvalidRouterProgram.into()
+        );
+        assert_eq!(
+            check_router(Whirlpool::id(), true).unwrap_err(),
+            errors::ErrorCode::InvalidRouterProgram.into()
+        );
+    }
+
+    fn load_test_config(mut config: TestAccount) -> Result<Option<Config>> {
+        let key = config.key;
+        let info = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut config.lamports,
+            &mut config.data,
+            &crate::ID,
+            false,
+            0,
+        );
+        load_config(&info)
+    }
+
+    #[test]
+    fn load_config_reads_a_current_config() {
+        let config = crate::test_harness::default_config(Pubkey::new_unique());
+        let loaded = load_test_config(TestAccount::config(&config)).unwrap();
+        assert_eq!(loaded.unwrap().authority, config.authority);
+    }
+
+    #[test]
+    fn load_config_rejects_a_config_that_needs_migrating() {
+        // A version 0 account zero-padded to its full size decodes strictly, with every
+        // nonzero default cleared.
+        let config = Config {
+            emit_events: false,
+            unizen_fee_recipient: Pubkey::default(),
+            version: 0,
+            ..crate::test_harness::default_config(Pubkey::new_unique())
+        };
+        let Err(error) = load_test_config(TestAccount::config(&config)) else {
+            panic!("loaded a config that needs migrating");
+        };
+        assert_eq!(error, errors::ErrorCode::ConfigNeedsMigration.into());
+    }
+
+    fn mint_account_space(mut mint: TestAccount) -> Result<usize> {
+        let key = mint.key;
+        let info = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut mint.lamports,
+            &mut mint.data,
+            &token_2022::ID,
+            false,
+            0,
+        );
+        token_account_space(&token_2022::ID, &info)
+    }
+
+    #[test]
+    fn token_account_space_covers_required_extensions() {
+        let mint = TestAccount::mint_2022_with_transfer_fee(Pubkey::new_unique(), 6, 100);
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(&[
+            ExtensionType::TransferFeeAmount,
+        ])
+        .unwrap();
+        assert_eq!(mint_account_space(mint).unwrap(), space);
+    }
+
+    #[test]
+    fn token_account_space_rejects_inconsistent_mint_extensions() {
+        let mut mint = TestAccount::mint_2022_with_transfer_fee(Pubkey::new_unique(), 6, 100);
+        // The last extension now claims more bytes than the mint holds.
+        mint.data.truncate(mint.data.len() - 1);
+        assert_eq!(
+            mint_account_space(mint).unwrap_err(),
+            errors::ErrorCode::InvalidAccountSize.into()
+        );
+    }
+
+    #[test]
+    fn token_account_space_is_bounded() {
+        assert!(assert_token_account_space(constants::MAX_TOKEN_ACCOUNT_SPACE).is_ok());
+        assert_eq!(
+            assert_token_account_space(constants::MAX_TOKEN_ACCOUNT_SPACE + 1).unwrap_err(),
+            errors::ErrorCode::InvalidAccountSize.into()
+        );
+    }
+
+    /// A 10_000 fee of which 2_000 went to the referrer.
+    fn fee_split_with_referrer() -> FeeSplit {
+        FeeSplit {
+            fee_percent: 100,
+            fee_tier: None,
+            share_percent: 2_000,
+            charges_fee: true,
+            total_fee: 10_000,
+            referrer_fee: 2_000,
+            unizen_fee: 0,
+            integrator_fee: 0,
+        }
+    }
+
+    #[test]
+    fn absolute_share_mode_gives_unizen_a_fixed_amount() {
+        let mut fee_split = fee_split_with_referrer();
+        apply_share_mode(&mut fee_split, ShareMode::Absolute(3_000), 0, FEE_DENOM).unwrap();
+
+        assert_eq!(fee_split.share_percent, 0);
+        assert_eq!(fee_split.unizen_fee, 3_000);
+        assert_eq!(fee_split.integrator_fee, 5_000);
+    }
+
+    #[test]
+    fn absolute_share_mode_is_clamped_to_the_fee_after_the_referrer() {
+        let mut fee_split = fee_split_with_referrer();
+        apply_share_mode(&mut fee_split, ShareMode::Absolute(50_000), 0, FEE_DENOM).unwrap();
+
+        assert_eq!(fee_split.unizen_fee, 8_000);
+        assert_eq!(fee_split.integrator_fee, 0);
+    }
+
+    #[test]
+    fn percent_share_mode_splits_the_fee_after_the_referrer() {
+        let mut fee_split = fee_split_with_referrer();
+        apply_share_mode(&mut fee_split, ShareMode::Percent(2_500), 2_000, FEE_DENOM).unwrap();
+
+        assert_eq!(fee_split.share_percent, 2_500);
+        assert_eq!(fee_split.unizen_fee, 2_000);
+        assert_eq!(fee_split.integrator_fee, 6_000);
+        assert_eq!(
+            apply_share_mode(&mut fee_split, ShareMode::Percent(8_001), 2_000, FEE_DENOM)
+                .unwrap_err(),
+            errors::ErrorCode::InvalidFeeShare.into()
+        );
+    }
+
+    #[test]
+    fn min_fee_in_whole_tokens_scales_with_decimals() {
+        let mut config = crate::test_harness::default_config(Pubkey::new_unique());
+        config.min_fee = 3;
+        assert_eq!(min_fee_amount(&config, 6).unwrap(), 3);
+
+        config.min_fee_in_whole_tokens = true;
+        assert_eq!(min_fee_amount(&config, 6).unwrap(), 3_000_000);
+        assert_eq!(min_fee_amount(&config, 9).unwrap(), 3_000_000_000);
+    }
+
+    #[test]
+    fn assert_amount_out_only_warns_below_soft_min() {
+        assert!(assert_amount_out(100, 250, 100, Some(200), true).is_ok());
+        assert!(assert_amount_out(100, 150, 50, Some(80), true).is_ok());
+    }
+
+    #[test]
+    fn soft_min_does_not_relax_the_minimum() {
+        assert_eq!(
+            assert_amount_out(100, 140, 50, Some(30), true).unwrap_err(),
+            errors::ErrorCode::OutputBelowMinimum.into()
+        );
+    }
+
+    #[test]
+    fn swap_checks_accept_the_limits() {
+        assert!(assert_amount_out(100, 200, 100, None, true).is_ok());
+        assert!(assert_amount_spent(1_000, 900, 100).is_ok());
+    }
+
+    #[test]
+    fn swap_checks_return_specific_errors() {
+        assert_eq!(
+            assert_amount_out(100, 199, 100, None, true).unwrap_err(),
+            errors::ErrorCode::OutputBelowMinimum.into()
+        );
+        assert_eq!(
+            assert_amount_out(100, 100, 1, Some(10), true).unwrap_err(),
+            errors::ErrorCode::RouteProducedNothing.into()
+        );
+        assert_eq!(
+            assert_amount_spent(1_000, 899, 100).unwrap_err(),
+            errors::ErrorCode::InputOverspent.into()
+        );
+        assert_eq!(
+            assert_amount_out(100, 99, 0, None, true).unwrap_err(),
+            errors::ErrorCode::Underflow.into()
+        );
+    }
+
+    #[test]
+    fn fee_aggregate_sums_hops() {
+        let token = Pubkey::new_unique();
+        let mut aggregate = FeeAggregate::default();
+        aggregate.add(&take_fee(token, 1_000, 3, 27), 0).unwrap();
+        aggregate.add(&take_fee(token, 2_000, 6, 54), 2).unwrap();
+
+        assert_eq!(aggregate.hops, 2);
+        assert_eq!(aggregate.token, token);
+        assert_eq!(aggregate.amount, 3_000);
+        assert_eq!(aggregate.referrer_received, 2);
+        assert_eq!(aggregate.unizen_received, 9);
+        assert_eq!(aggregate.integrator_received, 81);
+    }
+
+    #[test]
+    fn fee_aggregate_rejects_mixed_hops() {
+        let token = Pubkey::new_unique();
+        let mut aggregate = FeeAggregate::default();
+        aggregate.add(&take_fee(token, 1_000, 3, 27), 0).unwrap();
+
+        assert_eq!(
+            aggregate
+                .add(&take_fee(Pubkey::new_unique(), 1_000, 3, 27), 0)
+                .unwrap_err(),
+            errors::ErrorCode::MintMismatch.into()
+        );
+        let mut other_integrator = take_fee(token, 1_000, 3, 27);
+        other_integrator.integrator_account = Pubkey::new_unique();
+        assert_eq!(
+            aggregate.add(&other_integrator, 0).unwrap_err(),
+            errors::ErrorCode::InvalidFeeAccount.into()
+        );
+        assert_eq!(aggregate.hops, 1);
+    }
+
+    #[test]
+    fn fee_aggregate_rejects_overflow() {
+        let token = Pubkey::new_unique();
+        let mut aggregate = FeeAggregate::default();
+        aggregate.add(&take_fee(token, u64::MAX, 0, 0), 0).unwrap();
+        assert_eq!(
+            aggregate.add(&take_fee(token, 1, 0, 0), 0).unwrap_err(),
+            errors::ErrorCode::Overflow.into()
+        );
+    }
+
+    #[test]
+    fn apply_bps_rounds_down() {
+        assert_eq!(apply_bps(10_000, 30, FEE_DENOM).unwrap(), 30);
+        assert_eq!(apply_bps(333, 1, FEE_DENOM).unwrap(), 0);
+        assert_eq!(apply_bps(9_999, 1, FEE_DENOM).unwrap(), 0);
+        assert_eq!(apply_bps(10_001, 1, FEE_DENOM).unwrap(), 1);
+    }
+
+    #[test]
+    fn apply_bps_never_exceeds_the_exact_share() {
+        for amount in (0..50_000u64).step_by(7).chain([u64::MAX - 1, u64::MAX]) {
+            for bps in [0, 1, 3, 25, 30, 333, 5_000, 9_999, FEE_DENOM] {
+                let fee = apply_bps(amount, bps, FEE_DENOM).unwrap();
+                let exact = amount as u128 * bps as u128;
+                // Floor division: within one unit of the exact share and never above it.
+                assert!(fee as u128 * FEE_DENOM as u128 <= exact);
+                assert!((fee as u128 + 1) * FEE_DENOM as u128 > exact);
+                assert!(fee <= amount);
+            }
+        }
+    }
+
+    #[test]
+    fn apply_bps_full_share_is_identity() {
+        for amount in [0, 1, 12_345, u64::MAX] {
+            assert_eq!(apply_bps(amount, FEE_DENOM, FEE_DENOM).unwrap(), amount);
+        }
+    }
+
+    #[test]
+    fn apply_bps_rejects_overflow_and_zero_denom() {
+        assert_eq!(
+            apply_bps(u64::MAX, FEE_DENOM + 1, FEE_DENOM).unwrap_err(),
+            errors::ErrorCode::Overflow.into()
+        );
+        assert_eq!(
+            apply_bps(u64::MAX, u64::MAX, 1).unwrap_err(),
+            errors::ErrorCode::Overflow.into()
+        );
+        assert_eq!(
+            apply_bps(1, 1, 0).unwrap_err(),
+            errors::ErrorCode::Overflow.into()
+        );
+    }
 }
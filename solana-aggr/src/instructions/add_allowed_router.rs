@@ -0,0 +1,34 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn add_allowed_router(ctx: Context<AddAllowedRouter>, router: Pubkey) -> Result<()> {
+    if router == crate::ID {
+        return err!(errors::ErrorCode::SelfCallForbidden);
+    }
+
+    let allowed_routers = &mut ctx.accounts.config.allowed_routers;
+
+    if allowed_routers.contains(&router) {
+        return Ok(());
+    }
+
+    if allowed_routers.len() >= MAX_ALLOWED_ROUTERS {
+        return err!(errors::ErrorCode::ListTooLong);
+    }
+    allowed_routers.push(router);
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AddAllowedRouter<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
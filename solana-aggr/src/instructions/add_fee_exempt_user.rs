@@ -0,0 +1,30 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn add_fee_exempt_user(ctx: Context<AddFeeExemptUser>, user: Pubkey) -> Result<()> {
+    let fee_exempt_users = &mut ctx.accounts.config.fee_exempt_users;
+
+    if fee_exempt_users.contains(&user) {
+        return Ok(());
+    }
+
+    if fee_exempt_users.len() >= MAX_FEE_EXEMPT_USERS {
+        return err!(errors::ErrorCode::ListTooLong);
+    }
+    fee_exempt_users.push(user);
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AddFeeExemptUser<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
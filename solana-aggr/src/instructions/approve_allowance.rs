@@ -0,0 +1,184 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{self, Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{constants::*, errors, state::SwapAllowance};
+
+/// Sets the budget of the (user, integrator) allowance to `remaining_budget`, replacing
+/// whatever was left of it, and approves the pair's delegate PDA for exactly that amount.
+/// A source account can only back one allowance at a time: approving another integrator
+/// while a delegation is still outstanding fails instead of replacing it.
+pub fn approve_allowance(
+    ctx: Context<ApproveAllowance>,
+    remaining_budget: u64,
+    per_swap_cap: u64,
+) -> Result<()> {
+    let delegate = ctx.accounts.allowance_delegate.key();
+    let current_delegate: Option<Pubkey> = ctx.accounts.user_src_ata.delegate.into();
+    if let Some(current_delegate) = current_delegate {
+        if current_delegate != delegate && ctx.accounts.user_src_ata.delegated_amount > 0 {
+            msg!(
+                "Error: Source account is delegated to {} for {}.",
+                current_delegate,
+                ctx.accounts.user_src_ata.delegated_amount
+            );
+            return err!(errors::ErrorCode::DelegateInUse);
+        }
+    }
+
+    let allowance = &mut ctx.accounts.allowance;
+    if allowance.mint != Pubkey::default() && allowance.mint != ctx.accounts.src_token.key() {
+        return err!(errors::ErrorCode::MintMismatch);
+    }
+    allowance.user = ctx.accounts.user.key();
+    allowance.integrator = ctx.accounts.integrator.key();
+    allowance.mint = ctx.accounts.src_token.key();
+    allowance.remaining_budget = remaining_budget;
+    allowance.per_swap_cap = per_swap_cap;
+
+    msg!("Approve allowance delegate");
+    token_interface::approve(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            token_interface::Approve {
+                to: ctx.accounts.user_src_ata.to_account_info(),
+                delegate: ctx.accounts.allowance_delegate.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+            },
+        ),
+        remaining_budget,
+    )
+}
+
+#[derive(Accounts)]
+pub struct ApproveAllowance<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: Only used as the allowance seed and executor key.
+    pub integrator: UncheckedAccount<'info>,
+    pub src_token: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = src_token,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        seeds = [ALLOWANCE_DELEGATE_SEED, user.key().as_ref(), integrator.key().as_ref()],
+        bump
+    )]
+    pub allowance_delegate: SystemAccount<'info>,
+    /// Escrow the allowance swaps route from, so the route never touches `user_src_ata`.
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = src_token,
+        associated_token::authority = allowance_delegate,
+        associated_token::token_program = token_program
+    )]
+    pub delegate_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + SwapAllowance::INIT_SPACE,
+        seeds = [ALLOWANCE_SEED, user.key().as_ref(), integrator.key().as_ref()],
+        bump
+    )]
+    pub allowance: Account<'info, SwapAllowance>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::{associated_token, token};
+
+    use super::*;
+    use crate::test_harness::{Outcome, TokenSwap};
+
+    const BUDGET: u64 = 3_000_000;
+    const CAP: u64 = 1_000_000;
+
+    pub(crate) fn find_delegate(user: &Pubkey, integrator: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[ALLOWANCE_DELEGATE_SEED, user.as_ref(), integrator.as_ref()],
+            &crate::ID,
+        )
+        .0
+    }
+
+    pub(crate) fn find_allowance(user: &Pubkey, integrator: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[ALLOWANCE_SEED, user.as_ref(), integrator.as_ref()],
+            &crate::ID,
+        )
+        .0
+    }
+
+    pub(crate) fn approve(
+        swap: &mut TokenSwap,
+        integrator: Pubkey,
+        remaining_budget: u64,
+        per_swap_cap: u64,
+    ) -> Outcome {
+        let allowance_delegate = find_delegate(&swap.user, &integrator);
+        swap.ledger.run(
+            crate::accounts::ApproveAllowance {
+                user: swap.user,
+                integrator,
+                src_token: swap.src_mint,
+                user_src_ata: swap.user_src_ata,
+                allowance_delegate,
+                delegate_src_ata: associated_token::get_associated_token_address(
+                    &allowance_delegate,
+                    &swap.src_mint,
+                ),
+                allowance: find_allowance(&swap.user, &integrator),
+                token_program: token::ID,
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            },
+            &[],
+            crate::instruction::ApproveAllowance {
+                remaining_budget,
+                per_swap_cap,
+            },
+        )
+    }
+
+    #[test]
+    fn reapproval_replaces_budget_of_pair_delegate() {
+        let mut swap = TokenSwap::new();
+        let integrator = swap.integrator;
+        approve(&mut swap, integrator, BUDGET, CAP).assert_ok();
+        approve(&mut swap, integrator, 2 * BUDGET, CAP).assert_ok();
+
+        let allowance: SwapAllowance = swap
+            .ledger
+            .get(&find_allowance(&swap.user, &integrator))
+            .read();
+        assert_eq!(allowance.remaining_budget, 2 * BUDGET);
+        assert_eq!(allowance.per_swap_cap, CAP);
+        let state = swap.ledger.get(&swap.user_src_ata).token_state();
+        assert_eq!(
+            Option::<Pubkey>::from(state.delegate),
+            Some(find_delegate(&swap.user, &integrator))
+        );
+        assert_eq!(state.delegated_amount, 2 * BUDGET);
+    }
+
+    #[test]
+    fn second_integrator_cannot_take_over_delegation() {
+        let mut swap = TokenSwap::new();
+        let integrator = swap.integrator;
+        approve(&mut swap, integrator, BUDGET, CAP).assert_ok();
+
+        approve(&mut swap, Pubkey::new_unique(), BUDGET, CAP)
+            .assert_error(errors::ErrorCode::DelegateInUse);
+    }
+}
@@ -0,0 +1,30 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{self, TokenAccount, TokenInterface};
+
+use crate::constants::*;
+
+/// Lets the claw-back authority pull up to `amount` from the integrator fee account.
+pub fn approve_fee_clawback(ctx: Context<ApproveFeeClawback>, amount: u64) -> Result<()> {
+    msg!("Approve claw-back authority as delegate");
+    token_interface::approve(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            token_interface::Approve {
+                to: ctx.accounts.integrator_ata.to_account_info(),
+                delegate: ctx.accounts.clawback_authority.to_account_info(),
+                authority: ctx.accounts.integrator.to_account_info(),
+            },
+        ),
+        amount,
+    )
+}
+
+#[derive(Accounts)]
+pub struct ApproveFeeClawback<'info> {
+    pub integrator: Signer<'info>,
+    #[account(mut, token::authority = integrator)]
+    pub integrator_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds = [CLAWBACK_SEED], bump)]
+    pub clawback_authority: SystemAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
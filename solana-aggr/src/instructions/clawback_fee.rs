@@ -0,0 +1,210 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors, helpers,
+    state::{Config, FeeReceipt},
+};
+
+pub fn clawback_fee(ctx: Context<ClawbackFee>, _nonce: u64, amount: u64) -> Result<()> {
+    helpers::assert_amount_in(amount)?;
+
+    let config = &ctx.accounts.config;
+    let fee_receipt = &mut ctx.accounts.fee_receipt;
+    let age = Clock::get()?
+        .unix_timestamp
+        .saturating_sub(fee_receipt.taken_at);
+    if config.clawback_window == 0 || age > config.clawback_window {
+        msg!(
+            "Error: Fee was taken {} seconds ago which is past the {} second window.",
+            age,
+            config.clawback_window
+        );
+        return err!(errors::ErrorCode::ClawbackWindowClosed);
+    }
+
+    let clawed_back = fee_receipt
+        .clawed_back
+        .checked_add(amount)
+        .ok_or_else(|| error!(errors::ErrorCode::Overflow))?;
+    if clawed_back > fee_receipt.amount {
+        return err!(errors::ErrorCode::ClawbackExceedsFee);
+    }
+    fee_receipt.clawed_back = clawed_back;
+
+    let clawback_bump = helpers::bump_seed(ctx.bumps.clawback_authority);
+    msg!("Claw back fee to Unizen");
+    helpers::transfer_interface(
+        &ctx.accounts.token_program,
+        &ctx.accounts.mint,
+        ctx.accounts.integrator_ata.to_account_info(),
+        ctx.accounts.unizen_ata.to_account_info(),
+        ctx.accounts.clawback_authority.to_account_info(),
+        &[&[CLAWBACK_SEED, &clawback_bump]],
+        amount,
+    )
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct ClawbackFee<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [FEE_RECEIPT_SEED, integrator_ata.key().as_ref(), &nonce.to_le_bytes()],
+        bump
+    )]
+    pub fee_receipt: Account<'info, FeeReceipt>,
+    #[account(mut)]
+    pub integrator_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = integrator_ata.mint @ errors::ErrorCode::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = unizen_ata.mint == integrator_ata.mint @ errors::ErrorCode::MintMismatch,
+        constraint = unizen_ata.owner == config.unizen_fee_recipient
+            @ errors::ErrorCode::WrongFeeRecipient
+    )]
+    pub unizen_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds = [CLAWBACK_SEED], bump)]
+    pub clawback_authority: SystemAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::{
+        instructions::swap_tokens_for_tokens::tests::{
+            accounts, args, INTEGRATOR_FEE, ROUTE_IN, ROUTE_OUT,
+        },
+        state::FeeCounter,
+        test_harness::{
+            default_config, route_data, set_clock, Outcome, TestAccount, TokenSwap, NOW,
+        },
+    };
+
+    const WINDOW: i64 = 3_600;
+
+    /// A swap paying the integrator fee under a config with a claw-back window and
+    /// `authority` as its authority. `fee_counter` decides whether the counter is passed.
+    fn swap(authority: Pubkey, fee_counter: bool) -> (TokenSwap, Outcome) {
+        let mut swap = TokenSwap::new();
+        swap.ledger.add(TestAccount::config(&Config {
+            clawback_window: WINDOW,
+            ..default_config(authority)
+        }));
+        let counter =
+            Pubkey::find_program_address(&[FEE_COUNTER_SEED, swap.integrator.as_ref()], &crate::ID)
+                .0;
+        swap.ledger.add(TestAccount::state(
+            counter,
+            &FeeCounter { nonce: 0 },
+            8 + FeeCounter::INIT_SPACE,
+        ));
+        let mut accounts = accounts(&swap);
+        if fee_counter {
+            accounts.fee_counter = Some(counter);
+            accounts.fee_receipt = Some(find_receipt(&swap));
+        }
+        let route = swap.route();
+        let args = args(&swap, ROUTE_OUT);
+        let outcome = swap.ledger.run(
+            accounts,
+            &route,
+            crate::instruction::SwapTokensForTokens {
+                args,
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        );
+        (swap, outcome)
+    }
+
+    fn find_receipt(swap: &TokenSwap) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                FEE_RECEIPT_SEED,
+                swap.integrator_src_ata.as_ref(),
+                &1u64.to_le_bytes(),
+            ],
+            &crate::ID,
+        )
+        .0
+    }
+
+    fn claw_back(swap: &mut TokenSwap, authority: Pubkey, amount: u64) -> Outcome {
+        let clawback_authority = Pubkey::find_program_address(&[CLAWBACK_SEED], &crate::ID).0;
+        swap.ledger
+            .add(TestAccount::wallet(authority, 1_000_000_000));
+        swap.ledger.add(TestAccount::wallet(clawback_authority, 0));
+        swap.ledger
+            .run(
+                crate::accounts::ApproveFeeClawback {
+                    integrator: swap.integrator,
+                    integrator_ata: swap.integrator_src_ata,
+                    clawback_authority,
+                    token_program: token::ID,
+                },
+                &[],
+                crate::instruction::ApproveFeeClawback { amount },
+            )
+            .assert_ok();
+        swap.ledger.run(
+            crate::accounts::ClawbackFee {
+                authority,
+                config: crate::pda::find_config().0,
+                fee_receipt: find_receipt(swap),
+                integrator_ata: swap.integrator_src_ata,
+                mint: swap.src_mint,
+                unizen_ata: swap.unizen_src_ata,
+                clawback_authority,
+                token_program: token::ID,
+            },
+            &[],
+            crate::instruction::ClawbackFee { nonce: 1, amount },
+        )
+    }
+
+    #[test]
+    fn claws_back_fee_within_window() {
+        let authority = Pubkey::new_unique();
+        let (mut swap, outcome) = swap(authority, true);
+        outcome.assert_ok();
+        let receipt: FeeReceipt = swap.ledger.get(&find_receipt(&swap)).read();
+        assert_eq!(receipt.amount, INTEGRATOR_FEE);
+
+        let unizen_fee = swap.ledger.token_amount(&swap.unizen_src_ata);
+        claw_back(&mut swap, authority, INTEGRATOR_FEE).assert_ok();
+        assert_eq!(swap.ledger.token_amount(&swap.integrator_src_ata), 0);
+        assert_eq!(
+            swap.ledger.token_amount(&swap.unizen_src_ata),
+            unizen_fee + INTEGRATOR_FEE
+        );
+    }
+
+    #[test]
+    fn rejects_clawback_past_window() {
+        let authority = Pubkey::new_unique();
+        let (mut swap, outcome) = swap(authority, true);
+        outcome.assert_ok();
+
+        set_clock(NOW + WINDOW + 1);
+        claw_back(&mut swap, authority, INTEGRATOR_FEE)
+            .assert_error(errors::ErrorCode::ClawbackWindowClosed);
+    }
+
+    #[test]
+    fn swap_cannot_skip_receipt_while_clawback_is_enabled() {
+        let (_, outcome) = swap(Pubkey::new_unique(), false);
+        outcome.assert_error(errors::ErrorCode::FeeCounterMissing);
+    }
+}
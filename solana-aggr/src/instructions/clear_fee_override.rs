@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn clear_fee_override(ctx: Context<ClearFeeOverride>, mint: Pubkey) -> Result<()> {
+    ctx.accounts
+        .config
+        .fee_overrides
+        .retain(|fee_override| fee_override.mint != mint);
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ClearFeeOverride<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
@@ -0,0 +1,89 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+/// Instructions read the config PDA through `load_config`, so once it is closed they see
+/// an empty account and fall back to the built-in defaults.
+pub fn close_config(_ctx: Context<CloseConfig>) -> Result<()> {
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CloseConfig<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        close = recipient,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub recipient: SystemAccount<'info>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        instructions::swap_tokens_for_tokens::tests::{accounts, args, ROUTE_IN, ROUTE_OUT},
+        test_harness::{default_config, route_data, Outcome, TestAccount, TokenSwap},
+    };
+
+    fn close(swap: &mut TokenSwap, authority: Pubkey, recipient: Pubkey) -> Outcome {
+        swap.ledger.run(
+            crate::accounts::CloseConfig {
+                authority,
+                config: crate::pda::find_config().0,
+                recipient,
+            },
+            &[],
+            crate::instruction::CloseConfig {},
+        )
+    }
+
+    fn run_swap(swap: &mut TokenSwap) -> Outcome {
+        let route = swap.route();
+        swap.ledger.run(
+            accounts(swap),
+            &route,
+            crate::instruction::SwapTokensForTokens {
+                args: args(swap, ROUTE_OUT),
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    /// A swap whose config has a fee denominator no fee can be computed with.
+    fn swap_with_config(authority: Pubkey) -> TokenSwap {
+        let mut swap = TokenSwap::new();
+        swap.ledger.add(TestAccount::config(&Config {
+            fee_denom: 0,
+            ..default_config(authority)
+        }));
+        swap
+    }
+
+    #[test]
+    fn swaps_fall_back_to_defaults_once_closed() {
+        let authority = Pubkey::new_unique();
+        let mut swap = swap_with_config(authority);
+        run_swap(&mut swap).assert_error(errors::ErrorCode::InvalidFeeDenom);
+
+        let recipient = Pubkey::new_unique();
+        let config_rent = swap.ledger.lamports(&crate::pda::find_config().0);
+        close(&mut swap, authority, recipient).assert_ok();
+        assert_eq!(swap.ledger.lamports(&recipient), config_rent);
+        assert_eq!(swap.ledger.lamports(&crate::pda::find_config().0), 0);
+
+        run_swap(&mut swap).assert_ok();
+    }
+
+    #[test]
+    fn rejects_other_signer() {
+        let mut swap = swap_with_config(Pubkey::new_unique());
+        close(&mut swap, Pubkey::new_unique(), Pubkey::new_unique())
+            .assert_error(errors::ErrorCode::Unauthorized);
+    }
+}
@@ -0,0 +1,32 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::*,
+    errors,
+    state::{Config, FeeReceipt},
+};
+
+pub fn close_fee_receipt(ctx: Context<CloseFeeReceipt>) -> Result<()> {
+    let age = Clock::get()?
+        .unix_timestamp
+        .saturating_sub(ctx.accounts.fee_receipt.taken_at);
+    if age <= ctx.accounts.config.clawback_window {
+        return err!(errors::ErrorCode::ClawbackWindowOpen);
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CloseFeeReceipt<'info> {
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+    #[account(seeds = [CONFIG_SEED], bump)]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        has_one = payer @ errors::ErrorCode::Unauthorized,
+        close = payer
+    )]
+    pub fee_receipt: Account<'info, FeeReceipt>,
+}
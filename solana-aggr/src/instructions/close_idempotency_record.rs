@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::IdempotencyRecord};
+
+pub fn close_idempotency_record(ctx: Context<CloseIdempotencyRecord>) -> Result<()> {
+    let age = Clock::get()?
+        .unix_timestamp
+        .saturating_sub(ctx.accounts.idempotency_record.created_at);
+    if age < IDEMPOTENCY_TTL {
+        return err!(errors::ErrorCode::IdempotencyRecordActive);
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CloseIdempotencyRecord<'info> {
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+    #[account(
+        mut,
+        has_one = payer @ errors::ErrorCode::Unauthorized,
+        close = payer
+    )]
+    pub idempotency_record: Account<'info, IdempotencyRecord>,
+}
@@ -0,0 +1,263 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{self, Mint, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors,
+    helpers::{self, bump_seed, load_token_account, ProgramAtasClosed},
+    state::Config,
+};
+
+pub fn close_program_atas<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CloseProgramAtas<'info>>,
+    force: bool,
+) -> Result<()> {
+    let authority_bump = bump_seed(ctx.bumps.program_authority);
+    let signer_seeds: &[&[&[u8]]] = &[&[AUTHORITY_SEED, &authority_bump]];
+
+    let mut closed: u32 = 0;
+    let mut skipped: u32 = 0;
+    let mut rent_reclaimed: u64 = 0;
+    // Receiver token accounts and their mints may be passed alongside so forced closes can
+    // move the balance.
+    for account in ctx.remaining_accounts.iter() {
+        let Some(token_account) = load_token_account(account)? else {
+            continue;
+        };
+        if token_account.owner == ctx.accounts.receiver.key() {
+            continue;
+        }
+        // wSOL deposits are their own authority, so they fail this check and a user's
+        // deposit can never be closed here.
+        if token_account.owner != ctx.accounts.program_authority.key() {
+            return err!(errors::ErrorCode::IncorrectOwner);
+        }
+
+        if token_account.amount > 0 && !token_account.is_native() {
+            if !force {
+                msg!(
+                    "Skip {} holding {} tokens",
+                    account.key(),
+                    token_account.amount
+                );
+                skipped += 1;
+                continue;
+            }
+
+            let mut destination = None;
+            for candidate in ctx.remaining_accounts.iter() {
+                if load_token_account(candidate)?.is_some_and(|candidate| {
+                    candidate.owner == ctx.accounts.receiver.key()
+                        && candidate.mint == token_account.mint
+                }) {
+                    destination = Some(candidate);
+                    break;
+                }
+            }
+            let destination = destination.ok_or_else(|| error!(errors::ErrorCode::MintMismatch))?;
+            let mint = ctx
+                .remaining_accounts
+                .iter()
+                .find(|candidate| candidate.key == &token_account.mint)
+                .ok_or_else(|| error!(errors::ErrorCode::MintMismatch))?;
+            let mint = InterfaceAccount::<Mint>::try_from(mint)?;
+            msg!(
+                "Transfer {} tokens from {} to {}",
+                token_account.amount,
+                account.key(),
+                destination.key()
+            );
+            helpers::transfer_interface(
+                &ctx.accounts.token_program,
+                &mint,
+                account.clone(),
+                destination.clone(),
+                ctx.accounts.program_authority.to_account_info(),
+                signer_seeds,
+                token_account.amount,
+            )?;
+        }
+
+        let wrapped = if token_account.is_native() {
+            token_account.amount
+        } else {
+            0
+        };
+        let rent = account
+            .lamports()
+            .checked_sub(wrapped)
+            .ok_or_else(|| error!(errors::ErrorCode::Underflow))?;
+
+        msg!("Close program token account {}", account.key());
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token_interface::CloseAccount {
+                account: account.clone(),
+                destination: ctx.accounts.receiver.to_account_info(),
+                authority: ctx.accounts.program_authority.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+
+        closed += 1;
+        rent_reclaimed = rent_reclaimed
+            .checked_add(rent)
+            .ok_or_else(|| error!(errors::ErrorCode::Overflow))?;
+    }
+
+    if ctx.accounts.config.emit_events {
+        emit!(ProgramAtasClosed {
+            receiver: ctx.accounts.receiver.key(),
+            closed,
+            skipped,
+            rent_reclaimed,
+        });
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CloseProgramAtas<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(seeds = [AUTHORITY_SEED], bump)]
+    pub program_authority: SystemAccount<'info>,
+    #[account(mut)]
+    pub receiver: SystemAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::test_harness::{default_config, rent, Ledger, Outcome, TestAccount};
+
+    /// A program-owned token account of `mint` holding `amount`.
+    fn program_account(ledger: &mut Ledger, mint: Pubkey, amount: u64) -> Pubkey {
+        ledger.add(TestAccount::token(
+            Pubkey::new_unique(),
+            mint,
+            crate::pda::find_program_authority().0,
+            amount,
+        ))
+    }
+
+    fn close(ledger: &mut Ledger, authority: Pubkey, receiver: Pubkey, account: Pubkey) -> Outcome {
+        close_all(ledger, authority, receiver, &[account], false)
+    }
+
+    fn close_all(
+        ledger: &mut Ledger,
+        authority: Pubkey,
+        receiver: Pubkey,
+        accounts: &[Pubkey],
+        force: bool,
+    ) -> Outcome {
+        let accounts: Vec<_> = accounts
+            .iter()
+            .map(|account| AccountMeta::new(*account, false))
+            .collect();
+        ledger.run(
+            crate::accounts::CloseProgramAtas {
+                authority,
+                config: crate::pda::find_config().0,
+                program_authority: crate::pda::find_program_authority().0,
+                receiver,
+                token_program: token::ID,
+            },
+            &accounts,
+            crate::instruction::CloseProgramAtas { force },
+        )
+    }
+
+    fn setup() -> (Ledger, Pubkey, Pubkey, Pubkey) {
+        let authority = Pubkey::new_unique();
+        let receiver = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let ledger = Ledger::new([
+            TestAccount::wallet(authority, 1_000_000_000),
+            TestAccount::wallet(receiver, 0),
+            TestAccount::mint(mint, 6),
+            TestAccount::mint(NATIVE_MINT, 9),
+            TestAccount::config(&default_config(authority)),
+        ]);
+        (ledger, authority, receiver, mint)
+    }
+
+    #[test]
+    fn closes_empty_program_account() {
+        let (mut ledger, authority, receiver, mint) = setup();
+        let account = ledger.add(TestAccount::ata(
+            mint,
+            crate::pda::find_program_authority().0,
+            0,
+        ));
+        close(&mut ledger, authority, receiver, account).assert_ok();
+
+        assert_eq!(ledger.lamports(&account), 0);
+        assert_eq!(ledger.lamports(&receiver), rent(token::TokenAccount::LEN));
+    }
+
+    #[test]
+    fn never_closes_wsol_deposit() {
+        let (mut ledger, authority, receiver, _) = setup();
+        let user = Pubkey::new_unique();
+        let deposit =
+            Pubkey::find_program_address(&[WSOL_DEPOSIT_SEED, user.as_ref()], &crate::ID).0;
+        ledger.add(TestAccount::native(deposit, deposit, 1_000_000));
+
+        close(&mut ledger, authority, receiver, deposit)
+            .assert_error(errors::ErrorCode::IncorrectOwner);
+    }
+
+    #[test]
+    fn closes_two_empty_accounts_in_one_call() {
+        let (mut ledger, authority, receiver, mint) = setup();
+        let accounts = [
+            program_account(&mut ledger, mint, 0),
+            program_account(&mut ledger, NATIVE_MINT, 0),
+        ];
+        let outcome = close_all(&mut ledger, authority, receiver, &accounts, false);
+        outcome.assert_ok();
+
+        let rent_reclaimed = 2 * rent(token::TokenAccount::LEN);
+        let events = outcome.events::<ProgramAtasClosed>();
+        assert_eq!(events.len(), 1);
+        let closed = &events[0];
+        assert_eq!(closed.receiver, receiver);
+        assert_eq!((closed.closed, closed.skipped), (2, 0));
+        assert_eq!(closed.rent_reclaimed, rent_reclaimed);
+        assert_eq!(ledger.lamports(&receiver), rent_reclaimed);
+    }
+
+    #[test]
+    fn skips_account_holding_tokens_without_force() {
+        let (mut ledger, authority, receiver, mint) = setup();
+        let accounts = [
+            program_account(&mut ledger, mint, 0),
+            program_account(&mut ledger, mint, 1),
+        ];
+        let outcome = close_all(&mut ledger, authority, receiver, &accounts, false);
+        outcome.assert_ok();
+
+        let closed = &outcome.events::<ProgramAtasClosed>()[0];
+        assert_eq!((closed.closed, closed.skipped), (1, 1));
+        assert_eq!(ledger.token_amount(&accounts[1]), 1);
+    }
+
+    #[test]
+    fn rejects_other_signer() {
+        let (mut ledger, _, receiver, mint) = setup();
+        let signer = ledger.add(TestAccount::wallet(Pubkey::new_unique(), 1_000_000_000));
+        let account = program_account(&mut ledger, mint, 0);
+        close(&mut ledger, signer, receiver, account).assert_error(errors::ErrorCode::Unauthorized);
+    }
+}
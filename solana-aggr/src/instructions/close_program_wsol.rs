@@ -1,23 +1,68 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::Token;
+use anchor_spl::token_interface::TokenInterface;
 
-use crate::{constants::*, helpers};
+use crate::{constants::*, errors, helpers};
 
 pub fn close_program_wsol(ctx: Context<CloseProgramWsol>) -> Result<()> {
-    let authority_bump = ctx.bumps.program_authority.to_le_bytes();
+    // Without a config there is no allowlist and no recorded rent payer.
+    let mut config = helpers::load_config(&ctx.accounts.config)?;
+    if let Some(config) = config.as_ref() {
+        let wsol_closers = &config.wsol_closers;
+        if !wsol_closers.is_empty() && !wsol_closers.contains(ctx.accounts.caller.key) {
+            return err!(errors::ErrorCode::Unauthorized);
+        }
+    }
+    if let Some(unizen) = ctx.accounts.unizen.as_ref() {
+        helpers::assert_unizen_fee_recipient(unizen.key, &config)?;
+    }
+
+    if ctx.accounts.program_wsol.data_is_empty()
+        || helpers::read_token_amount(&ctx.accounts.program_wsol)? == 0
+    {
+        return err!(errors::ErrorCode::WsolAccountEmpty);
+    }
+
+    let (recorded_rent_payer, emit_events, strict_cleanup) = match config.as_ref() {
+        Some(config) => (
+            config.wsol_rent_payer,
+            config.emit_events,
+            config.strict_cleanup,
+        ),
+        None => (Pubkey::default(), true, false),
+    };
+    let rent_payer =
+        helpers::recorded_wsol_rent_payer(recorded_rent_payer, ctx.accounts.rent_payer.clone())?;
+
+    let authority_bump = helpers::bump_seed(ctx.bumps.program_authority);
 
     helpers::close_program_wsol(
-        ctx.accounts.program_authority.clone(),
-        ctx.accounts.program_wsol.clone(),
-        ctx.accounts.receiver.clone(),
-        ctx.accounts.token_program.clone(),
-        ctx.accounts.system_program.clone(),
+        helpers::CloseProgramWsolAccounts {
+            program_authority: ctx.accounts.program_authority.clone(),
+            program_wsol: ctx.accounts.program_wsol.clone(),
+            receiver: ctx.accounts.receiver.clone(),
+            unizen: ctx.accounts.unizen.clone(),
+            rent_payer,
+            token_program: ctx.accounts.token_program.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+        },
         &authority_bump,
-    )
+        emit_events,
+        strict_cleanup,
+    )?;
+    if let Some(config) = config.as_mut() {
+        config.wsol_rent_payer = Pubkey::default();
+        helpers::store_config(&ctx.accounts.config, config)?;
+    }
+
+    Ok(())
 }
 
 #[derive(Accounts)]
 pub struct CloseProgramWsol<'info> {
+    pub caller: Signer<'info>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
     #[account(mut, seeds = [AUTHORITY_SEED], bump)]
     pub program_authority: SystemAccount<'info>,
     /// CHECK: This may not be initialized yet.
@@ -25,6 +70,211 @@ pub struct CloseProgramWsol<'info> {
     pub program_wsol: UncheckedAccount<'info>,
     #[account(mut)]
     pub receiver: SystemAccount<'info>,
-    pub token_program: Program<'info, Token>,
+    /// Swept the program authority's dust; must be the Unizen fee recipient.
+    #[account(mut)]
+    pub unizen: Option<SystemAccount<'info>>,
+    #[account(mut)]
+    pub rent_payer: Option<SystemAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::{solana_program::program_pack::Pack, system_program};
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::{
+        helpers::{DustSwept, WsolClosed},
+        state::Config,
+        test_harness::{default_config, rent, Ledger, Outcome, TestAccount},
+    };
+
+    const AMOUNT: u64 = 1_000_000;
+
+    /// Closes a program wSOL account holding `wsol_amount`, or no account for `None`.
+    fn run(closers: Vec<Pubkey>, caller: Pubkey, wsol_amount: Option<u64>) -> (Ledger, Outcome) {
+        let mut config = default_config(Pubkey::new_unique());
+        config.wsol_closers = closers;
+        run_with(&config, caller, wsol_amount)
+    }
+
+    fn run_with(config: &Config, caller: Pubkey, wsol_amount: Option<u64>) -> (Ledger, Outcome) {
+        close(config, caller, wsol_amount, 0, None)
+    }
+
+    /// Closes with the program authority holding `authority_lamports`, sweeping any
+    /// excess to `unizen` when passed.
+    fn close(
+        config: &Config,
+        caller: Pubkey,
+        wsol_amount: Option<u64>,
+        authority_lamports: u64,
+        unizen: Option<Pubkey>,
+    ) -> (Ledger, Outcome) {
+        let program_authority = crate::pda::find_program_authority().0;
+        let program_wsol = crate::pda::find_program_wsol().0;
+        let receiver = Pubkey::new_unique();
+        let mut ledger = Ledger::new([
+            TestAccount::config(config),
+            TestAccount::wallet(caller, 1_000_000_000),
+            TestAccount::wallet(program_authority, authority_lamports),
+            TestAccount::wallet(receiver, 0),
+        ]);
+        if let Some(unizen) = unizen {
+            ledger.add(TestAccount::wallet(unizen, 0));
+        }
+        if let Some(amount) = wsol_amount {
+            ledger.add(TestAccount::native(program_wsol, program_authority, amount));
+        }
+        let outcome = ledger.run(
+            crate::accounts::CloseProgramWsol {
+                caller,
+                config: crate::pda::find_config().0,
+                program_authority,
+                program_wsol,
+                receiver,
+                unizen,
+                rent_payer: None,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &[],
+            crate::instruction::CloseProgramWsol {},
+        );
+        (ledger, outcome)
+    }
+
+    #[test]
+    fn listed_closer_closes_program_wsol() {
+        let caller = Pubkey::new_unique();
+        let (ledger, outcome) = run(vec![caller], caller, Some(AMOUNT));
+        outcome.assert_ok();
+
+        assert_eq!(ledger.lamports(&crate::pda::find_program_wsol().0), 0);
+    }
+
+    #[test]
+    fn rejects_unlisted_caller() {
+        let (_, outcome) = run(
+            vec![Pubkey::new_unique()],
+            Pubkey::new_unique(),
+            Some(AMOUNT),
+        );
+        outcome.assert_error(errors::ErrorCode::Unauthorized);
+    }
+
+    #[test]
+    fn does_not_report_with_events_disabled() {
+        let mut config = default_config(Pubkey::new_unique());
+        config.emit_events = false;
+        let (_, outcome) = run_with(&config, Pubkey::new_unique(), Some(AMOUNT));
+        outcome.assert_ok();
+
+        assert!(outcome.events::<WsolClosed>().is_empty());
+    }
+
+    #[test]
+    fn anyone_closes_program_wsol_holding_tokens() {
+        let (ledger, outcome) = run(Vec::new(), Pubkey::new_unique(), Some(AMOUNT));
+        outcome.assert_ok();
+
+        assert_eq!(ledger.lamports(&crate::pda::find_program_wsol().0), 0);
+    }
+
+    #[test]
+    fn rejects_empty_program_wsol() {
+        let (_, outcome) = run(Vec::new(), Pubkey::new_unique(), Some(0));
+        outcome.assert_error(errors::ErrorCode::WsolAccountEmpty);
+    }
+
+    #[test]
+    fn rejects_missing_program_wsol() {
+        let (_, outcome) = run(Vec::new(), Pubkey::new_unique(), None);
+        outcome.assert_error(errors::ErrorCode::WsolAccountEmpty);
+    }
+
+    #[test]
+    fn reports_proceeds_forwarded_to_receiver() {
+        let (ledger, outcome) = run(Vec::new(), Pubkey::new_unique(), Some(AMOUNT));
+        outcome.assert_ok();
+
+        let events = outcome.events::<WsolClosed>();
+        assert_eq!(events.len(), 1);
+        let closed = &events[0];
+        assert_eq!(closed.out_amount, AMOUNT);
+        assert_eq!(ledger.lamports(&closed.receiver), closed.out_amount);
+        assert_eq!(
+            closed.rent_reclaimed,
+            rent(token::spl_token::state::Account::LEN)
+        );
+    }
+
+    const DUST: u64 = 1_234;
+
+    fn close_with_dust(authority_lamports: u64) -> (Ledger, Outcome) {
+        close(
+            &default_config(Pubkey::new_unique()),
+            Pubkey::new_unique(),
+            Some(AMOUNT),
+            authority_lamports,
+            Some(UNIZEN),
+        )
+    }
+
+    #[test]
+    fn sweeps_authority_dust_to_unizen() {
+        let (ledger, outcome) = close_with_dust(rent(0) + DUST);
+        outcome.assert_ok();
+
+        let swept = outcome.events::<DustSwept>();
+        assert_eq!(swept.len(), 1);
+        assert_eq!((swept[0].receiver, swept[0].amount), (UNIZEN, DUST));
+        assert_eq!(ledger.lamports(&UNIZEN), DUST);
+        // Enough is kept to recreate program wSOL.
+        assert_eq!(
+            ledger.lamports(&crate::pda::find_program_authority().0),
+            rent(0) + rent(token::spl_token::state::Account::LEN)
+        );
+    }
+
+    #[test]
+    fn keeps_authority_reserve_without_dust() {
+        let (ledger, outcome) = close_with_dust(rent(0));
+        outcome.assert_ok();
+
+        assert!(outcome.events::<DustSwept>().is_empty());
+        assert_eq!(ledger.lamports(&UNIZEN), 0);
+    }
+
+    fn close_strict(authority_lamports: u64, unizen: Option<Pubkey>) -> (Ledger, Outcome) {
+        let mut config = default_config(Pubkey::new_unique());
+        config.strict_cleanup = true;
+        close(
+            &config,
+            Pubkey::new_unique(),
+            Some(AMOUNT),
+            authority_lamports,
+            unizen,
+        )
+    }
+
+    #[test]
+    fn strict_cleanup_rejects_lamports_left_on_the_authority() {
+        let (_, outcome) = close_strict(rent(0) + DUST, None);
+        outcome.assert_error(errors::ErrorCode::DirtyAuthority);
+    }
+
+    #[test]
+    fn strict_cleanup_accepts_an_authority_swept_to_its_reserve() {
+        let (_, outcome) = close_strict(rent(0) + DUST, Some(UNIZEN));
+        outcome.assert_ok();
+    }
+
+    #[test]
+    fn strict_cleanup_accepts_an_authority_holding_only_rent() {
+        let (_, outcome) = close_strict(rent(0), None);
+        outcome.assert_ok();
+    }
+}
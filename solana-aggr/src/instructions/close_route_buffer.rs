@@ -0,0 +1,21 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::RouteBuffer};
+
+pub fn close_route_buffer(_ctx: Context<CloseRouteBuffer>) -> Result<()> {
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CloseRouteBuffer<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [ROUTE_BUFFER_SEED, authority.key().as_ref()],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub route_buffer: Account<'info, RouteBuffer>,
+}
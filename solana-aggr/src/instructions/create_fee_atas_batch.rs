@@ -0,0 +1,162 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{self, get_associated_token_address_with_program_id, AssociatedToken},
+    token_interface::TokenInterface,
+};
+
+use crate::{
+    constants::*,
+    errors,
+    helpers::{assert_unizen_fee_recipient, events_enabled, load_config, FeeAtasCreated},
+};
+
+/// `remaining_accounts` holds one `(mint, unizen_ata, integrator_ata)` triple per mint.
+pub fn create_fee_atas_batch<'info>(
+    ctx: Context<'_, '_, '_, 'info, CreateFeeAtasBatch<'info>>,
+) -> Result<()> {
+    let triples = ctx.remaining_accounts.chunks_exact(3);
+    if triples.len() == 0 || !triples.remainder().is_empty() {
+        return err!(errors::ErrorCode::AccountCountMismatch);
+    }
+    if triples.len() > MAX_FEE_ATA_BATCH {
+        return err!(errors::ErrorCode::ListTooLong);
+    }
+    let config = load_config(&ctx.accounts.config)?;
+    assert_unizen_fee_recipient(ctx.accounts.unizen.key, &config)?;
+
+    let mut created: u32 = 0;
+    let mut existing: u32 = 0;
+    for triple in triples {
+        let mint = &triple[0];
+        // The ATAs live under the mint's own program, which must be the one creating them.
+        if mint.owner != ctx.accounts.token_program.key {
+            return err!(errors::ErrorCode::IncorrectOwner);
+        }
+        let owners = [
+            (&triple[1], ctx.accounts.unizen.to_account_info()),
+            (&triple[2], ctx.accounts.integrator.to_account_info()),
+        ];
+        for (ata, authority) in owners {
+            let expected =
+                get_associated_token_address_with_program_id(authority.key, mint.key, mint.owner);
+            if ata.key() != expected {
+                return err!(errors::ErrorCode::InvalidPda);
+            }
+            if !ata.data_is_empty() {
+                existing += 1;
+                continue;
+            }
+
+            msg!("Initialize fee ATA {}", ata.key());
+            associated_token::create_idempotent(CpiContext::new(
+                ctx.accounts.associated_token_program.to_account_info(),
+                associated_token::Create {
+                    payer: ctx.accounts.payer.to_account_info(),
+                    associated_token: ata.clone(),
+                    authority,
+                    mint: mint.clone(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                },
+            ))?;
+            created += 1;
+        }
+    }
+
+    if events_enabled(&config) {
+        emit!(FeeAtasCreated {
+            integrator: ctx.accounts.integrator.key(),
+            created,
+            existing,
+        });
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CreateFeeAtasBatch<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: Only used as the ATA authority; must be the Unizen fee recipient.
+    pub unizen: UncheckedAccount<'info>,
+    /// CHECK: Only used as the ATA authority.
+    pub integrator: UncheckedAccount<'info>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::{token, token_2022};
+
+    use super::*;
+    use crate::test_harness::{Ledger, Outcome, TestAccount};
+
+    fn run(
+        ledger: &mut Ledger,
+        token_program: Pubkey,
+        integrator: Pubkey,
+        triple: [Pubkey; 3],
+    ) -> Outcome {
+        let payer = ledger.add(TestAccount::wallet(Pubkey::new_unique(), 1_000_000_000));
+        ledger.run(
+            crate::accounts::CreateFeeAtasBatch {
+                payer,
+                unizen: UNIZEN,
+                integrator,
+                config: crate::pda::find_config().0,
+                token_program,
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            },
+            &[
+                AccountMeta::new_readonly(triple[0], false),
+                AccountMeta::new(triple[1], false),
+                AccountMeta::new(triple[2], false),
+            ],
+            crate::instruction::CreateFeeAtasBatch {},
+        )
+    }
+
+    fn fee_atas(mint: Pubkey, integrator: Pubkey) -> [Pubkey; 3] {
+        [
+            mint,
+            get_associated_token_address_with_program_id(&UNIZEN, &mint, &token::ID),
+            get_associated_token_address_with_program_id(&integrator, &mint, &token::ID),
+        ]
+    }
+
+    #[test]
+    fn creates_fee_atas_under_mint_program() {
+        let mint = Pubkey::new_unique();
+        let integrator = Pubkey::new_unique();
+        let mut ledger = Ledger::new([TestAccount::mint(mint, 6)]);
+        let triple = fee_atas(mint, integrator);
+        run(&mut ledger, token::ID, integrator, triple).assert_ok();
+
+        for ata in &triple[1..] {
+            assert_eq!(ledger.get(ata).owner, token::ID);
+            assert_eq!(ledger.token_amount(ata), 0);
+        }
+    }
+
+    #[test]
+    fn rejects_token_program_other_than_mint_owner() {
+        let mint = Pubkey::new_unique();
+        let integrator = Pubkey::new_unique();
+        let mut ledger = Ledger::new([TestAccount::mint(mint, 6)]);
+        run(
+            &mut ledger,
+            token_2022::ID,
+            integrator,
+            fee_atas(mint, integrator),
+        )
+        .assert_error(errors::ErrorCode::IncorrectOwner);
+    }
+}
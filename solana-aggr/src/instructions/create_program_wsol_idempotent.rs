@@ -1,21 +1,48 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token};
+use anchor_spl::token_interface::{Mint, TokenInterface};
 
-use crate::{constants::*, helpers};
+use crate::{constants::*, errors, helpers};
 
 pub fn create_program_wsol_idempotent(ctx: Context<CreateWsolTokenIdempotent>) -> Result<()> {
-    let authority_bump = ctx.bumps.program_authority.to_le_bytes();
-    let wsol_bump = ctx.bumps.program_wsol.to_le_bytes();
-
-    helpers::create_program_wsol_idempotent(
-        ctx.accounts.program_authority.clone(),
-        ctx.accounts.program_wsol.clone(),
-        ctx.accounts.sol_mint.clone(),
-        ctx.accounts.token_program.clone(),
-        ctx.accounts.system_program.clone(),
+    let authority_bump = helpers::bump_seed(ctx.bumps.program_authority);
+    let wsol_bump = helpers::bump_seed(ctx.bumps.program_wsol);
+
+    // Only a fresh account is funded by the payer; an existing one keeps its recorded payer.
+    // The recorded payer is the only one `close_program_wsol` refunds, so it is only
+    // recorded for the config authority or an allowed closer, never for any signer.
+    let creating = ctx.accounts.program_wsol.data_is_empty();
+    if creating {
+        if let Some(rent_payer) = ctx.accounts.rent_payer.as_ref() {
+            let mut config = helpers::load_config(&ctx.accounts.config)?
+                .ok_or_else(|| error!(errors::ErrorCode::ConfigMissing))?;
+            let privileged = config.authority == rent_payer.key()
+                || config.wsol_closers.contains(rent_payer.key);
+            if privileged {
+                config.wsol_rent_payer = rent_payer.key();
+                helpers::store_config(&ctx.accounts.config, &config)?;
+            } else {
+                msg!("Rent payer {} is not recorded", rent_payer.key());
+            }
+        }
+    }
+
+    let program_wsol = helpers::ensure_program_wsol_loaded(
+        helpers::ProgramWsolAccounts {
+            program_authority: ctx.accounts.program_authority.clone(),
+            program_wsol: ctx.accounts.program_wsol.clone(),
+            sol_mint: ctx.accounts.sol_mint.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+            rent_payer: if creating {
+                ctx.accounts.rent_payer.clone()
+            } else {
+                None
+            },
+        },
         &authority_bump,
         &wsol_bump,
     )?;
+    msg!("Program wSOL balance is {}", program_wsol.amount);
 
     Ok(())
 }
@@ -28,7 +55,179 @@ pub struct CreateWsolTokenIdempotent<'info> {
     #[account(mut, seeds = [WSOL_SEED], bump)]
     pub program_wsol: UncheckedAccount<'info>,
     #[account(address = NATIVE_MINT)]
-    pub sol_mint: Account<'info, Mint>,
-    pub token_program: Program<'info, Token>,
+    pub sol_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub rent_payer: Option<Signer<'info>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::{solana_program::program_pack::Pack, system_program};
+    use anchor_spl::{
+        token,
+        token_2022::{
+            self,
+            spl_token_2022::{self, extension::ExtensionType},
+        },
+    };
+
+    use super::*;
+    use crate::{
+        state::Config,
+        test_harness::{default_config, rent, Ledger, Outcome, TestAccount},
+    };
+
+    fn run(ledger: &mut Ledger, rent_payer: Pubkey) -> Outcome {
+        run_under(ledger, rent_payer, token::ID)
+    }
+
+    fn run_under(ledger: &mut Ledger, rent_payer: Pubkey, token_program: Pubkey) -> Outcome {
+        ledger.add(TestAccount::wallet(rent_payer, 1_000_000_000));
+        ledger.run(
+            crate::accounts::CreateWsolTokenIdempotent {
+                program_authority: crate::pda::find_program_authority().0,
+                program_wsol: crate::pda::find_program_wsol().0,
+                sol_mint: NATIVE_MINT,
+                rent_payer: Some(rent_payer),
+                config: crate::pda::find_config().0,
+                token_program,
+                system_program: system_program::ID,
+            },
+            &[],
+            crate::instruction::CreateProgramWsolIdempotent {},
+        )
+    }
+
+    fn ledger(config: &Config) -> Ledger {
+        Ledger::new([
+            TestAccount::mint(NATIVE_MINT, 9),
+            TestAccount::config(config),
+        ])
+    }
+
+    fn recorded_rent_payer(ledger: &Ledger) -> Pubkey {
+        let config: Config = ledger.get(&crate::pda::find_config().0).read();
+        config.wsol_rent_payer
+    }
+
+    #[test]
+    fn records_authority_as_rent_payer() {
+        let authority = Pubkey::new_unique();
+        let mut ledger = ledger(&default_config(authority));
+        run(&mut ledger, authority).assert_ok();
+
+        assert_eq!(recorded_rent_payer(&ledger), authority);
+        assert_eq!(
+            ledger.lamports(&crate::pda::find_program_wsol().0),
+            rent(token::spl_token::state::Account::LEN)
+        );
+    }
+
+    #[test]
+    fn does_not_record_other_signers() {
+        let mut ledger = ledger(&default_config(Pubkey::new_unique()));
+        run(&mut ledger, Pubkey::new_unique()).assert_ok();
+
+        assert_eq!(recorded_rent_payer(&ledger), Pubkey::default());
+    }
+
+    fn existing_program_wsol() -> TestAccount {
+        TestAccount::native(
+            crate::pda::find_program_wsol().0,
+            crate::pda::find_program_authority().0,
+            1_000,
+        )
+    }
+
+    #[test]
+    fn accepts_existing_program_wsol() {
+        let mut ledger = ledger(&default_config(Pubkey::new_unique()));
+        ledger.add(existing_program_wsol());
+        run(&mut ledger, Pubkey::new_unique()).assert_ok();
+
+        assert_eq!(
+            ledger.token_amount(&crate::pda::find_program_wsol().0),
+            1_000
+        );
+    }
+
+    #[test]
+    fn rejects_program_wsol_with_wrong_owner() {
+        let mut ledger = ledger(&default_config(Pubkey::new_unique()));
+        ledger.add(TestAccount {
+            owner: Pubkey::new_unique(),
+            ..existing_program_wsol()
+        });
+        run(&mut ledger, Pubkey::new_unique()).assert_error(errors::ErrorCode::InvalidWsolAccount);
+    }
+
+    #[test]
+    fn rejects_program_wsol_with_wrong_size() {
+        let mut ledger = ledger(&default_config(Pubkey::new_unique()));
+        let mut program_wsol = existing_program_wsol();
+        program_wsol.data.push(0);
+        ledger.add(program_wsol);
+        run(&mut ledger, Pubkey::new_unique()).assert_error(errors::ErrorCode::InvalidWsolAccount);
+    }
+
+    /// wSOL under Token-2022, its mint requiring an extension on every token account.
+    fn ledger_2022(config: &Config) -> (Ledger, usize) {
+        let mut ledger = ledger(config);
+        ledger.add(TestAccount::mint_2022_with_transfer_fee(NATIVE_MINT, 9, 0));
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(&[
+            ExtensionType::TransferFeeAmount,
+        ])
+        .unwrap();
+        (ledger, space)
+    }
+
+    #[test]
+    fn creates_program_wsol_with_token_2022_extension_space() {
+        let authority = Pubkey::new_unique();
+        let (mut ledger, space) = ledger_2022(&default_config(authority));
+        run_under(&mut ledger, authority, token_2022::ID).assert_ok();
+
+        let program_wsol = ledger.get(&crate::pda::find_program_wsol().0);
+        assert!(space > token::spl_token::state::Account::LEN);
+        assert_eq!(program_wsol.owner, token_2022::ID);
+        assert_eq!(program_wsol.data.len(), space);
+        assert_eq!(program_wsol.lamports, rent(space));
+    }
+
+    #[test]
+    fn accepts_existing_token_2022_program_wsol_with_extension_space() {
+        let (mut ledger, space) = ledger_2022(&default_config(Pubkey::new_unique()));
+        let mut program_wsol = existing_program_wsol();
+        program_wsol.owner = token_2022::ID;
+        program_wsol.data.resize(space, 0);
+        ledger.add(program_wsol);
+        run_under(&mut ledger, Pubkey::new_unique(), token_2022::ID).assert_ok();
+    }
+
+    /// A create interrupted between `create_account` and `initialize_account3`.
+    #[test]
+    fn completes_a_partially_initialized_program_wsol() {
+        let mut ledger = ledger(&default_config(Pubkey::new_unique()));
+        let program_wsol = ledger.add(TestAccount {
+            key: crate::pda::find_program_wsol().0,
+            lamports: rent(token::spl_token::state::Account::LEN),
+            data: vec![0; token::spl_token::state::Account::LEN],
+            owner: token::ID,
+            executable: false,
+        });
+        run(&mut ledger, Pubkey::new_unique()).assert_ok();
+
+        let state = ledger.get(&program_wsol).token_state();
+        assert_eq!(
+            state.state,
+            spl_token_2022::state::AccountState::Initialized
+        );
+        assert_eq!(state.mint, NATIVE_MINT);
+        assert_eq!(state.owner, crate::pda::find_program_authority().0);
+    }
+}
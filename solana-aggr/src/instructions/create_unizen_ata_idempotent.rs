@@ -0,0 +1,113 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{self, AssociatedToken},
+    token_interface::{Mint, TokenInterface},
+};
+
+use crate::{constants::*, helpers};
+
+pub fn create_unizen_ata_idempotent(ctx: Context<CreateUnizenAtaIdempotent>) -> Result<()> {
+    let config = helpers::load_config(&ctx.accounts.config)?;
+    helpers::assert_unizen_fee_recipient(ctx.accounts.unizen.key, &config)?;
+    msg!("Initialize Unizen fee ATA");
+    associated_token::create_idempotent(CpiContext::new(
+        ctx.accounts.associated_token_program.to_account_info(),
+        associated_token::Create {
+            payer: ctx.accounts.payer.to_account_info(),
+            associated_token: ctx.accounts.unizen_ata.to_account_info(),
+            authority: ctx.accounts.unizen.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+        },
+    ))
+}
+
+#[derive(Accounts)]
+pub struct CreateUnizenAtaIdempotent<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: Only used as the ATA authority; must be the Unizen fee recipient.
+    pub unizen: UncheckedAccount<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Validated by the associated token program.
+    #[account(mut)]
+    pub unizen_ata: UncheckedAccount<'info>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::{associated_token::get_associated_token_address, token};
+
+    use super::*;
+    use crate::test_harness::{Ledger, Outcome, TestAccount};
+
+    const PAYER_LAMPORTS: u64 = 1_000_000_000;
+
+    fn ledger(mint: Pubkey, payer: Pubkey) -> Ledger {
+        Ledger::new([
+            TestAccount::wallet(payer, PAYER_LAMPORTS),
+            TestAccount::mint(mint, 6),
+        ])
+    }
+
+    fn run(ledger: &mut Ledger, mint: Pubkey, payer: Pubkey, unizen_ata: Pubkey) -> Outcome {
+        ledger.run(
+            crate::accounts::CreateUnizenAtaIdempotent {
+                payer,
+                unizen: UNIZEN,
+                mint,
+                unizen_ata,
+                config: crate::pda::find_config().0,
+                token_program: token::ID,
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            },
+            &[],
+            crate::instruction::CreateUnizenAtaIdempotent {},
+        )
+    }
+
+    #[test]
+    fn creates_missing_unizen_ata() {
+        let (mint, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let unizen_ata = get_associated_token_address(&UNIZEN, &mint);
+        let mut ledger = ledger(mint, payer);
+        run(&mut ledger, mint, payer, unizen_ata).assert_ok();
+
+        let state = ledger.get(&unizen_ata).token_state();
+        assert_eq!(state.owner, UNIZEN);
+        assert_eq!(state.mint, mint);
+        assert_eq!(
+            ledger.lamports(&payer),
+            PAYER_LAMPORTS - ledger.lamports(&unizen_ata)
+        );
+    }
+
+    #[test]
+    fn keeps_existing_unizen_ata() {
+        let (mint, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut ledger = ledger(mint, payer);
+        let unizen_ata = ledger.add(TestAccount::ata(mint, UNIZEN, 1_000));
+        run(&mut ledger, mint, payer, unizen_ata).assert_ok();
+
+        assert_eq!(ledger.token_amount(&unizen_ata), 1_000);
+        assert_eq!(ledger.lamports(&payer), PAYER_LAMPORTS);
+    }
+
+    #[test]
+    fn rejects_account_other_than_the_unizen_ata() {
+        let (mint, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut ledger = ledger(mint, payer);
+        let outcome = run(&mut ledger, mint, payer, Pubkey::new_unique());
+        // The CPI derives the ATA itself, which the caller then has not passed in.
+        assert_eq!(outcome.result, Err(ProgramError::NotEnoughAccountKeys));
+    }
+}
@@ -0,0 +1,139 @@
+use anchor_lang::{prelude::*, system_program};
+use anchor_spl::token_interface::{self, Mint, TokenInterface};
+
+use crate::{constants::*, errors, helpers};
+
+/// Wraps `amount` of the user's SOL into their wSOL deposit account. Funds stay the user's
+/// until `swap_wsol_deposit_for_tokens` spends them or `withdraw_wsol_deposit` returns
+/// them; the shared program wSOL is never touched.
+pub fn fund_program_wsol(ctx: Context<FundProgramWsol>, amount: u64) -> Result<()> {
+    helpers::assert_amount_in(amount)?;
+
+    let prev_bal = helpers::ensure_wsol_deposit(
+        &ctx.accounts.wsol_deposit,
+        &ctx.accounts.sol_mint,
+        &ctx.accounts.token_program,
+        &ctx.accounts.system_program,
+        &ctx.accounts.user,
+        ctx.bumps.wsol_deposit,
+    )?
+    .amount;
+
+    msg!("Wrap user's SOL into wSOL deposit");
+    system_program::transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.user.to_account_info(),
+                to: ctx.accounts.wsol_deposit.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+    token_interface::sync_native(CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        token_interface::SyncNative {
+            account: ctx.accounts.wsol_deposit.to_account_info(),
+        },
+    ))?;
+
+    // Lamports sent to the deposit address outside this instruction are wrapped along with
+    // `amount`; they can only ever be withdrawn by the user, so they are credited rather
+    // than rejected.
+    let post_bal = helpers::read_token_amount(&ctx.accounts.wsol_deposit)?;
+    if post_bal.saturating_sub(prev_bal) < amount {
+        msg!(
+            "Error: wSOL deposit grew by {} but {} was funded.",
+            post_bal.saturating_sub(prev_bal),
+            amount
+        );
+        return err!(errors::ErrorCode::InvalidWsolAccount);
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct FundProgramWsol<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: Created and validated by `ensure_wsol_deposit`.
+    #[account(mut, seeds = [WSOL_DEPOSIT_SEED, user.key().as_ref()], bump)]
+    pub wsol_deposit: UncheckedAccount<'info>,
+    #[account(address = NATIVE_MINT)]
+    pub sol_mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::solana_program::program_pack::Pack;
+    use anchor_spl::token::{self, spl_token::state::Account as TokenState};
+
+    use super::*;
+    use crate::test_harness::{rent, Ledger, Outcome, TestAccount, USER_LAMPORTS};
+
+    const AMOUNT: u64 = 1_000_000;
+
+    fn find_deposit(user: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[WSOL_DEPOSIT_SEED, user.as_ref()], &crate::ID).0
+    }
+
+    fn fund(ledger: &mut Ledger, user: Pubkey, amount: u64) -> Outcome {
+        ledger.run(
+            crate::accounts::FundProgramWsol {
+                user,
+                wsol_deposit: find_deposit(&user),
+                sol_mint: NATIVE_MINT,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &[],
+            crate::instruction::FundProgramWsol { amount },
+        )
+    }
+
+    fn setup() -> (Ledger, Pubkey) {
+        let user = Pubkey::new_unique();
+        let ledger = Ledger::new([
+            TestAccount::wallet(user, USER_LAMPORTS),
+            TestAccount::mint(NATIVE_MINT, 9),
+        ]);
+        (ledger, user)
+    }
+
+    #[test]
+    fn wraps_into_self_owned_deposit() {
+        let (mut ledger, user) = setup();
+        fund(&mut ledger, user, AMOUNT).assert_ok();
+
+        let deposit = find_deposit(&user);
+        let state = ledger.get(&deposit).token_state();
+        assert_eq!(state.owner, deposit);
+        assert_eq!(state.amount, AMOUNT);
+        assert_eq!(
+            ledger.lamports(&user),
+            USER_LAMPORTS - AMOUNT - rent(TokenState::LEN)
+        );
+    }
+
+    #[test]
+    fn credits_lamports_sent_to_the_deposit_beforehand() {
+        let (mut ledger, user) = setup();
+        fund(&mut ledger, user, AMOUNT).assert_ok();
+        let deposit = find_deposit(&user);
+        let mut stray = ledger.get(&deposit).clone();
+        stray.lamports += AMOUNT;
+        ledger.add(stray);
+
+        fund(&mut ledger, user, AMOUNT).assert_ok();
+        assert_eq!(ledger.token_amount(&deposit), 3 * AMOUNT);
+    }
+
+    #[test]
+    fn rejects_zero_amount() {
+        let (mut ledger, user) = setup();
+        fund(&mut ledger, user, 0).assert_error(errors::ErrorCode::ZeroAmount);
+    }
+}
@@ -0,0 +1,74 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{constants::*, helpers};
+
+pub fn get_accrued_fees(ctx: Context<GetAccruedFees>) -> Result<u64> {
+    let config = helpers::load_config(&ctx.accounts.config)?;
+    helpers::assert_unizen_fee_recipient(&ctx.accounts.unizen_ata.owner, &config)?;
+    Ok(ctx.accounts.unizen_ata.amount)
+}
+
+#[derive(Accounts)]
+pub struct GetAccruedFees<'info> {
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        token::mint = mint,
+        token::token_program = token_program
+    )]
+    pub unizen_ata: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_spl::{associated_token::get_associated_token_address, token, token_2022};
+
+    use super::*;
+    use crate::test_harness::{Ledger, Outcome, TestAccount};
+
+    const ACCRUED: u64 = 42_000;
+
+    fn run(token_program: Pubkey) -> Outcome {
+        let mint = Pubkey::new_unique();
+        let mut ledger = Ledger::new([
+            TestAccount::mint(mint, 6),
+            TestAccount::ata(mint, UNIZEN, ACCRUED),
+        ]);
+        ledger.run(
+            crate::accounts::GetAccruedFees {
+                mint,
+                unizen_ata: get_associated_token_address(&UNIZEN, &mint),
+                config: crate::pda::find_config().0,
+                token_program,
+            },
+            &[],
+            crate::instruction::GetAccruedFees {},
+        )
+    }
+
+    #[test]
+    fn returns_unizen_ata_balance() {
+        let outcome = run(token::ID);
+        outcome.assert_ok();
+
+        let (program, data) = outcome.return_data.unwrap();
+        assert_eq!(program, crate::ID);
+        assert_eq!(u64::try_from_slice(&data).unwrap(), ACCRUED);
+    }
+
+    #[test]
+    fn rejects_token_program_not_owning_the_ata() {
+        let outcome = run(token_2022::ID);
+        assert_eq!(
+            outcome.result,
+            Err(ProgramError::Custom(
+                anchor_lang::error::ErrorCode::ConstraintTokenTokenProgram.into()
+            ))
+        );
+        assert!(outcome.return_data.is_none());
+    }
+}
@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+
+use crate::version::{self, ProgramVersion};
+
+pub fn get_version(_ctx: Context<GetVersion>) -> Result<ProgramVersion> {
+    Ok(version::program_version())
+}
+
+#[derive(Accounts)]
+pub struct GetVersion {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_harness::Ledger;
+
+    #[test]
+    fn returns_the_program_version() {
+        let mut ledger = Ledger::new([]);
+        let outcome = ledger.run(
+            crate::accounts::GetVersion {},
+            &[],
+            crate::instruction::GetVersion {},
+        );
+        outcome.assert_ok();
+
+        let (program, data) = outcome.return_data.unwrap();
+        assert_eq!(program, crate::ID);
+        let version = ProgramVersion::try_from_slice(&data).unwrap();
+        let expected = version::program_version();
+        assert_eq!(
+            (version.major, version.minor, version.patch),
+            (expected.major, expected.minor, expected.patch)
+        );
+        assert_eq!(version.build_seeds_hash, version::build_seeds_hash());
+    }
+}
@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, state::FeeCounter};
+
+pub fn init_fee_counter(ctx: Context<InitFeeCounter>) -> Result<()> {
+    ctx.accounts.fee_counter.nonce = 0;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitFeeCounter<'info> {
+    #[account(mut)]
+    pub integrator: Signer<'info>,
+    #[account(
+        init,
+        payer = integrator,
+        space = 8 + FeeCounter::INIT_SPACE,
+        seeds = [FEE_COUNTER_SEED, integrator.key().as_ref()],
+        bump
+    )]
+    pub fee_counter: Account<'info, FeeCounter>,
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{
+    constants::*,
+    errors,
+    state::{Config, PriceFeed},
+};
+
+/// Creates the authority-maintained SOL fee price for `mint`; it is unusable until
+/// `update_price_feed` posts a price.
+pub fn init_price_feed(ctx: Context<InitPriceFeed>) -> Result<()> {
+    let price_feed = &mut ctx.accounts.price_feed;
+    price_feed.mint = ctx.accounts.mint.key();
+    price_feed.decimals = ctx.accounts.mint.decimals;
+    price_feed.price = 0;
+    price_feed.publish_time = 0;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitPriceFeed<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PriceFeed::INIT_SPACE,
+        seeds = [SOL_FEE_PRICE_SEED, mint.key().as_ref()],
+        bump
+    )]
+    pub price_feed: Account<'info, PriceFeed>,
+    pub system_program: Program<'info, System>,
+}
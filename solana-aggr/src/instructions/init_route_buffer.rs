@@ -0,0 +1,27 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, state::RouteBuffer};
+
+pub fn init_route_buffer(ctx: Context<InitRouteBuffer>, len: u32) -> Result<()> {
+    let route_buffer = &mut ctx.accounts.route_buffer;
+    route_buffer.authority = ctx.accounts.authority.key();
+    route_buffer.data = vec![0; len as usize];
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(len: u32)]
+pub struct InitRouteBuffer<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = RouteBuffer::space(len as usize),
+        seeds = [ROUTE_BUFFER_SEED, authority.key().as_ref()],
+        bump
+    )]
+    pub route_buffer: Account<'info, RouteBuffer>,
+    pub system_program: Program<'info, System>,
+}
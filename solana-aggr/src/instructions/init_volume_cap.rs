@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::*,
+    errors,
+    state::{Config, VolumeCap},
+};
+
+pub fn init_volume_cap(ctx: Context<InitVolumeCap>, daily_cap: u64) -> Result<()> {
+    let volume_cap = &mut ctx.accounts.volume_cap;
+    volume_cap.integrator = ctx.accounts.integrator.key();
+    volume_cap.daily_cap = daily_cap;
+    volume_cap.day = 0;
+    volume_cap.volume = 0;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitVolumeCap<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: Only used as the volume cap seed.
+    pub integrator: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + VolumeCap::INIT_SPACE,
+        seeds = [VOLUME_CAP_SEED, integrator.key().as_ref()],
+        bump
+    )]
+    pub volume_cap: Account<'info, VolumeCap>,
+    pub system_program: Program<'info, System>,
+}
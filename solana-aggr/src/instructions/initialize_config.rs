@@ -0,0 +1,51 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, state::Config};
+
+pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    config.authority = ctx.accounts.authority.key();
+    config.privileged = Vec::new();
+    config.fee_denom = FEE_DENOM;
+    config.fee_overrides = Vec::new();
+    config.fee_tiers = Vec::new();
+    config.wsol_closers = Vec::new();
+    config.emit_events = true;
+    config.reentrancy_locked = false;
+    config.min_fee = 0;
+    config.min_fee_in_whole_tokens = false;
+    config.stranded_output_epsilon = 0;
+    config.unizen_fee_recipient = UNIZEN;
+    config.profiling = false;
+    config.fee_exempt_users = Vec::new();
+    config.allowed_routers = Vec::new();
+    config.wsol_dust_threshold = 0;
+    config.wsol_dust_to_unizen = false;
+    config.wsol_rent_payer = Pubkey::default();
+    config.clawback_window = 0;
+    config.quote_signer = Pubkey::default();
+    config.require_slippage_protection = false;
+    config.integrator_fee_tolerance_bps = None;
+    config.discount_mint = Pubkey::default();
+    config.discount_tiers = Vec::new();
+    config.strict_cleanup = false;
+    config.require_signed_quote = false;
+    config.version = CONFIG_VERSION;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut, address = UNIZEN)]
+    pub authority: Signer<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Config::INIT_SPACE,
+        seeds = [CONFIG_SEED],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,144 @@
+use anchor_lang::{prelude::*, system_program, Discriminator};
+
+use crate::{constants::*, errors, helpers, state::Config};
+
+/// Brings a config written by an earlier layout up to `CONFIG_VERSION`: the account is
+/// reallocated to the current size and the fields it predates get the defaults
+/// `Config::deserialize_lenient` gives them. Old accounts were zero-padded to their full
+/// size, so a predated field may read as zero instead of running out of bytes. Zero is
+/// the default of every appended field but `fee_denom`, `unizen_fee_recipient` and
+/// `emit_events`, which are restored here; a version 0 `emit_events` of false can't be
+/// told from padding, so it is turned back on and has to be cleared again with
+/// `set_emit_events` after migrating.
+pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+    let config_info = ctx.accounts.config.to_account_info();
+    if config_info.owner != &crate::ID {
+        return err!(errors::ErrorCode::IncorrectOwner);
+    }
+    let mut config = {
+        let data = config_info.try_borrow_data()?;
+        if data.get(..8) != Some(Config::DISCRIMINATOR.as_slice()) {
+            return err!(errors::ErrorCode::ConfigMissing);
+        }
+        Config::deserialize_lenient(&data[8..])?
+    };
+    if config.authority != ctx.accounts.authority.key() {
+        return err!(errors::ErrorCode::Unauthorized);
+    }
+    if config.version >= CONFIG_VERSION {
+        msg!("Config is already at version {}", config.version);
+        return Ok(());
+    }
+
+    if config.version == 0 {
+        let defaults = Config::deserialize_lenient(&[])?;
+        if config.fee_denom == 0 {
+            config.fee_denom = defaults.fee_denom;
+        }
+        if config.unizen_fee_recipient == Pubkey::default() {
+            config.unizen_fee_recipient = defaults.unizen_fee_recipient;
+        }
+        config.emit_events = defaults.emit_events;
+    }
+    msg!(
+        "Migrate config from version {} to {}",
+        config.version,
+        CONFIG_VERSION
+    );
+    config.version = CONFIG_VERSION;
+
+    let space = 8 + Config::INIT_SPACE;
+    if config_info.data_len() < space {
+        let shortfall = Rent::get()?
+            .minimum_balance(space)
+            .saturating_sub(config_info.lamports());
+        if shortfall > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.authority.to_account_info(),
+                        to: config_info.clone(),
+                    },
+                ),
+                shortfall,
+            )?;
+        }
+        config_info.realloc(space, true)?;
+    }
+
+    helpers::store_config(&config_info, &config)
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: Decoded with `Config::deserialize_lenient`, since an older layout may not
+    /// deserialize as the current `Config`.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::{AccountSerialize, Space};
+
+    use super::*;
+    use crate::test_harness::{default_config, rent, Ledger, Outcome, TestAccount};
+
+    /// A version 0 config the way an older layout left it: the fields it predates were
+    /// never written, so they read as the zero padding.
+    fn zero_padded_config(authority: Pubkey) -> TestAccount {
+        let config = Config {
+            fee_denom: 0,
+            emit_events: false,
+            unizen_fee_recipient: Pubkey::default(),
+            version: 0,
+            ..default_config(authority)
+        };
+        let mut data = Vec::new();
+        config.try_serialize(&mut data).unwrap();
+        TestAccount {
+            lamports: rent(data.len()),
+            data,
+            owner: crate::ID,
+            ..TestAccount::wallet(crate::pda::find_config().0, 0)
+        }
+    }
+
+    fn run(ledger: &mut Ledger, authority: Pubkey) -> Outcome {
+        ledger.add(TestAccount::wallet(authority, 1_000_000_000));
+        ledger.run(
+            crate::accounts::MigrateConfig {
+                authority,
+                config: crate::pda::find_config().0,
+                system_program: system_program::ID,
+            },
+            &[],
+            crate::instruction::MigrateConfig {},
+        )
+    }
+
+    #[test]
+    fn restores_defaults_of_predated_fields() {
+        let authority = Pubkey::new_unique();
+        let mut ledger = Ledger::new([zero_padded_config(authority)]);
+        run(&mut ledger, authority).assert_ok();
+
+        let account = ledger.get(&crate::pda::find_config().0);
+        assert_eq!(account.data.len(), 8 + Config::INIT_SPACE);
+        let config: Config = account.read();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.fee_denom, FEE_DENOM);
+        assert_eq!(config.unizen_fee_recipient, UNIZEN);
+        assert!(config.emit_events);
+    }
+
+    #[test]
+    fn rejects_other_signers() {
+        let mut ledger = Ledger::new([zero_padded_config(Pubkey::new_unique())]);
+        run(&mut ledger, Pubkey::new_unique()).assert_error(errors::ErrorCode::Unauthorized);
+    }
+}
@@ -0,0 +1,249 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{self, Mint, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors,
+    helpers::{self, WsolMigrated},
+    state::Config,
+};
+
+// Tests derive the previous accounts from seeds of their own, so the migration runs
+// while the real previous seeds still equal the current ones.
+#[cfg(test)]
+const PREVIOUS_AUTHORITY_SEED: &[u8] = b"previous_authority";
+#[cfg(test)]
+const PREVIOUS_WSOL_SEED: &[u8] = b"previous_wsol";
+
+pub fn migrate_wsol_authority(ctx: Context<MigrateWsolAuthority>) -> Result<()> {
+    if PREVIOUS_AUTHORITY_SEED == AUTHORITY_SEED && PREVIOUS_WSOL_SEED == WSOL_SEED {
+        msg!("Program wSOL seeds unchanged, nothing to migrate");
+        return Ok(());
+    }
+    let old_wsol = ctx.accounts.old_program_wsol.key();
+    let new_wsol = ctx.accounts.program_wsol.key();
+    if old_wsol == new_wsol || ctx.accounts.old_program_wsol.data_is_empty() {
+        msg!("Program wSOL account already migrated");
+        return Ok(());
+    }
+
+    // Rent goes back to whoever funded the old account: the recorded payer, if any,
+    // otherwise the old program authority itself.
+    let rent_destination = match helpers::recorded_wsol_rent_payer(
+        ctx.accounts.config.wsol_rent_payer,
+        ctx.accounts.rent_payer.clone(),
+    )? {
+        Some(rent_payer) => rent_payer.to_account_info(),
+        None => ctx.accounts.old_program_authority.to_account_info(),
+    };
+
+    let authority_bump = helpers::bump_seed(ctx.bumps.program_authority);
+    let wsol_bump = helpers::bump_seed(ctx.bumps.program_wsol);
+    helpers::ensure_program_wsol(
+        helpers::ProgramWsolAccounts {
+            program_authority: ctx.accounts.program_authority.clone(),
+            program_wsol: ctx.accounts.program_wsol.clone(),
+            sol_mint: ctx.accounts.sol_mint.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+            rent_payer: None,
+        },
+        &authority_bump,
+        &wsol_bump,
+    )?;
+
+    let old_authority_bump = helpers::bump_seed(ctx.bumps.old_program_authority);
+    let signer_seeds: &[&[&[u8]]] = &[&[PREVIOUS_AUTHORITY_SEED, &old_authority_bump]];
+
+    let amount = helpers::read_token_amount(&ctx.accounts.old_program_wsol)?;
+    if amount > 0 {
+        msg!("Transfer wSOL to migrated account");
+        helpers::transfer_interface(
+            &ctx.accounts.token_program,
+            &ctx.accounts.sol_mint,
+            ctx.accounts.old_program_wsol.to_account_info(),
+            ctx.accounts.program_wsol.to_account_info(),
+            ctx.accounts.old_program_authority.to_account_info(),
+            signer_seeds,
+            amount,
+        )?;
+    }
+
+    msg!("Close previous program wSOL token account");
+    token_interface::close_account(CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        token_interface::CloseAccount {
+            account: ctx.accounts.old_program_wsol.to_account_info(),
+            destination: rent_destination,
+            authority: ctx.accounts.old_program_authority.to_account_info(),
+        },
+        signer_seeds,
+    ))?;
+    // The new account was funded by the program authority.
+    ctx.accounts.config.wsol_rent_payer = Pubkey::default();
+
+    if ctx.accounts.config.emit_events {
+        emit!(WsolMigrated {
+            old_wsol,
+            new_wsol,
+            amount,
+        });
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateWsolAuthority<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [PREVIOUS_AUTHORITY_SEED], bump)]
+    pub old_program_authority: SystemAccount<'info>,
+    /// CHECK: This may already be closed.
+    #[account(mut, seeds = [PREVIOUS_WSOL_SEED], bump)]
+    pub old_program_wsol: UncheckedAccount<'info>,
+    #[account(mut, seeds = [AUTHORITY_SEED], bump)]
+    pub program_authority: SystemAccount<'info>,
+    /// CHECK: This may not be initialized yet.
+    #[account(mut, seeds = [WSOL_SEED], bump)]
+    pub program_wsol: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub rent_payer: Option<SystemAccount<'info>>,
+    #[account(address = NATIVE_MINT)]
+    pub sol_mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::test_harness::{default_config, rent, Ledger, Outcome, TestAccount};
+
+    const AMOUNT: u64 = 1_000_000;
+
+    fn run(ledger: &mut Ledger, authority: Pubkey) -> Outcome {
+        ledger.add(TestAccount::wallet(authority, 1_000_000_000));
+        ledger.run(
+            crate::accounts::MigrateWsolAuthority {
+                authority,
+                config: crate::pda::find_config().0,
+                old_program_authority: Pubkey::find_program_address(
+                    &[PREVIOUS_AUTHORITY_SEED],
+                    &crate::ID,
+                )
+                .0,
+                old_program_wsol: Pubkey::find_program_address(&[PREVIOUS_WSOL_SEED], &crate::ID).0,
+                program_authority: crate::pda::find_program_authority().0,
+                program_wsol: crate::pda::find_program_wsol().0,
+                rent_payer: None,
+                sol_mint: NATIVE_MINT,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &[],
+            crate::instruction::MigrateWsolAuthority {},
+        )
+    }
+
+    /// A program wSOL account holding `AMOUNT` under a config owned by `authority`.
+    fn ledger(authority: Pubkey) -> Ledger {
+        let program_authority = crate::pda::find_program_authority().0;
+        Ledger::new([
+            TestAccount::mint(NATIVE_MINT, 9),
+            TestAccount::config(&default_config(authority)),
+            TestAccount::wallet(program_authority, 0),
+            TestAccount::native(crate::pda::find_program_wsol().0, program_authority, AMOUNT),
+        ])
+    }
+
+    // Nothing is left under the previous seeds, so the program wSOL account is already
+    // where a migration would move it and its balance must carry over untouched.
+    #[test]
+    fn keeps_program_wsol_and_balance_once_migrated() {
+        let authority = Pubkey::new_unique();
+        let mut ledger = ledger(authority);
+        let outcome = run(&mut ledger, authority);
+        outcome.assert_ok();
+
+        assert!(outcome.events::<WsolMigrated>().is_empty());
+        assert_eq!(
+            ledger.token_amount(&crate::pda::find_program_wsol().0),
+            AMOUNT
+        );
+    }
+
+    fn old_program_authority() -> Pubkey {
+        Pubkey::find_program_address(&[PREVIOUS_AUTHORITY_SEED], &crate::ID).0
+    }
+
+    fn old_program_wsol() -> Pubkey {
+        Pubkey::find_program_address(&[PREVIOUS_WSOL_SEED], &crate::ID).0
+    }
+
+    /// A program wSOL account holding `AMOUNT` under the previous seeds, and nothing yet
+    /// under the current ones but a funded program authority.
+    fn unmigrated_ledger(authority: Pubkey) -> Ledger {
+        Ledger::new([
+            TestAccount::mint(NATIVE_MINT, 9),
+            TestAccount::config(&default_config(authority)),
+            TestAccount::wallet(old_program_authority(), 0),
+            TestAccount::native(old_program_wsol(), old_program_authority(), AMOUNT),
+            TestAccount::wallet(crate::pda::find_program_authority().0, 1_000_000_000),
+        ])
+    }
+
+    #[test]
+    fn moves_the_wsol_balance_to_the_current_seeds() {
+        let authority = Pubkey::new_unique();
+        let mut ledger = unmigrated_ledger(authority);
+        let outcome = run(&mut ledger, authority);
+        outcome.assert_ok();
+
+        let program_wsol = crate::pda::find_program_wsol().0;
+        let migrated = outcome.events::<WsolMigrated>();
+        assert_eq!(migrated.len(), 1);
+        assert_eq!(migrated[0].old_wsol, old_program_wsol());
+        assert_eq!(migrated[0].new_wsol, program_wsol);
+        assert_eq!(migrated[0].amount, AMOUNT);
+        assert_eq!(ledger.token_amount(&program_wsol), AMOUNT);
+        assert_eq!(
+            ledger.get(&program_wsol).token_state().owner,
+            crate::pda::find_program_authority().0
+        );
+        assert_eq!(ledger.lamports(&old_program_wsol()), 0);
+        // The old account's rent went back to the old authority, which funded it.
+        assert!(ledger.lamports(&old_program_authority()) >= rent(token::TokenAccount::LEN));
+    }
+
+    #[test]
+    fn migrating_twice_is_a_no_op() {
+        let authority = Pubkey::new_unique();
+        let mut ledger = unmigrated_ledger(authority);
+        run(&mut ledger, authority).assert_ok();
+        let outcome = run(&mut ledger, authority);
+        outcome.assert_ok();
+
+        assert!(outcome.events::<WsolMigrated>().is_empty());
+        assert_eq!(
+            ledger.token_amount(&crate::pda::find_program_wsol().0),
+            AMOUNT
+        );
+    }
+
+    #[test]
+    fn rejects_other_signer() {
+        let mut ledger = ledger(Pubkey::new_unique());
+        run(&mut ledger, Pubkey::new_unique()).assert_error(errors::ErrorCode::Unauthorized);
+    }
+}
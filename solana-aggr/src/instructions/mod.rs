@@ -1,13 +1,133 @@
+mod add_allowed_router;
+mod add_fee_exempt_user;
+mod approve_allowance;
+mod approve_fee_clawback;
+mod clawback_fee;
+mod clear_fee_override;
+mod close_config;
+mod close_fee_receipt;
+mod close_idempotency_record;
+mod close_program_atas;
 mod close_program_wsol;
+mod close_route_buffer;
+mod create_fee_atas_batch;
 mod create_program_wsol_idempotent;
+mod create_unizen_ata_idempotent;
+mod fund_program_wsol;
+mod get_accrued_fees;
+mod get_version;
+mod init_fee_counter;
+mod init_price_feed;
+mod init_route_buffer;
+mod init_volume_cap;
+mod initialize_config;
+mod migrate_config;
+mod migrate_wsol_authority;
+mod remove_allowed_router;
+mod remove_fee_exempt_user;
+mod set_clawback_window;
+mod set_discount_tiers;
+mod set_emit_events;
+mod set_fee_denom;
+mod set_fee_override;
+mod set_fee_tiers;
+mod set_integrator_fee_tolerance;
+mod set_min_fee;
+mod set_privileged;
+mod set_profiling;
+mod set_quote_signer;
+mod set_slippage_protection;
+mod set_stranded_output_epsilon;
+mod set_strict_cleanup;
+mod set_unizen_fee_recipient;
+mod set_volume_cap;
+mod set_wsol_closers;
+mod set_wsol_dust_sweep;
+mod swap_batch;
 mod swap_sol_for_tokens;
+mod swap_sol_for_tokens_percent;
+mod swap_tokens_for_multiple_tokens;
+mod swap_tokens_for_program_wsol;
 mod swap_tokens_for_sol;
 mod swap_tokens_for_tokens;
+mod swap_tokens_for_tokens_exact_out;
+mod swap_tokens_for_tokens_no_fee;
+mod swap_tokens_for_tokens_orca;
+mod swap_tokens_for_tokens_private;
+mod swap_tokens_for_tokens_weighted;
+mod swap_tokens_for_tokens_with_allowance;
+mod swap_tokens_for_wsol;
+mod swap_wsol_deposit_for_tokens;
+mod take_fee_and_route;
 mod take_integrator_fee;
+mod take_integrator_fee_sponsored;
+mod update_price_feed;
+mod withdraw_wsol_deposit;
+mod write_route_buffer;
 
+pub use add_allowed_router::*;
+pub use add_fee_exempt_user::*;
+pub use approve_allowance::*;
+pub use approve_fee_clawback::*;
+pub use clawback_fee::*;
+pub use clear_fee_override::*;
+pub use close_config::*;
+pub use close_fee_receipt::*;
+pub use close_idempotency_record::*;
+pub use close_program_atas::*;
 pub use close_program_wsol::*;
+pub use close_route_buffer::*;
+pub use create_fee_atas_batch::*;
 pub use create_program_wsol_idempotent::*;
+pub use create_unizen_ata_idempotent::*;
+pub use fund_program_wsol::*;
+pub use get_accrued_fees::*;
+pub use get_version::*;
+pub use init_fee_counter::*;
+pub use init_price_feed::*;
+pub use init_route_buffer::*;
+pub use init_volume_cap::*;
+pub use initialize_config::*;
+pub use migrate_config::*;
+pub use migrate_wsol_authority::*;
+pub use remove_allowed_router::*;
+pub use remove_fee_exempt_user::*;
+pub use set_clawback_window::*;
+pub use set_discount_tiers::*;
+pub use set_emit_events::*;
+pub use set_fee_denom::*;
+pub use set_fee_override::*;
+pub use set_fee_tiers::*;
+pub use set_integrator_fee_tolerance::*;
+pub use set_min_fee::*;
+pub use set_privileged::*;
+pub use set_profiling::*;
+pub use set_quote_signer::*;
+pub use set_slippage_protection::*;
+pub use set_stranded_output_epsilon::*;
+pub use set_strict_cleanup::*;
+pub use set_unizen_fee_recipient::*;
+pub use set_volume_cap::*;
+pub use set_wsol_closers::*;
+pub use set_wsol_dust_sweep::*;
+pub use swap_batch::*;
 pub use swap_sol_for_tokens::*;
+pub use swap_sol_for_tokens_percent::*;
+pub use swap_tokens_for_multiple_tokens::*;
+pub use swap_tokens_for_program_wsol::*;
 pub use swap_tokens_for_sol::*;
 pub use swap_tokens_for_tokens::*;
+pub use swap_tokens_for_tokens_exact_out::*;
+pub use swap_tokens_for_tokens_no_fee::*;
+pub use swap_tokens_for_tokens_orca::*;
+pub use swap_tokens_for_tokens_private::*;
+pub use swap_tokens_for_tokens_weighted::*;
+pub use swap_tokens_for_tokens_with_allowance::*;
+pub use swap_tokens_for_wsol::*;
+pub use swap_wsol_deposit_for_tokens::*;
+pub use take_fee_and_route::*;
 pub use take_integrator_fee::*;
+pub use take_integrator_fee_sponsored::*;
+pub use update_price_feed::*;
+pub use withdraw_wsol_deposit::*;
+pub use write_route_buffer::*;
@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn remove_allowed_router(ctx: Context<RemoveAllowedRouter>, router: Pubkey) -> Result<()> {
+    ctx.accounts
+        .config
+        .allowed_routers
+        .retain(|allowed_router| *allowed_router != router);
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RemoveAllowedRouter<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn remove_fee_exempt_user(ctx: Context<RemoveFeeExemptUser>, user: Pubkey) -> Result<()> {
+    ctx.accounts
+        .config
+        .fee_exempt_users
+        .retain(|fee_exempt_user| *fee_exempt_user != user);
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RemoveFeeExemptUser<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
@@ -0,0 +1,21 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn set_clawback_window(ctx: Context<SetClawbackWindow>, clawback_window: i64) -> Result<()> {
+    ctx.accounts.config.clawback_window = clawback_window;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetClawbackWindow<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
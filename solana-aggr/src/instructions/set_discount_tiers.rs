@@ -0,0 +1,45 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::*,
+    errors,
+    state::{Config, DiscountTier},
+};
+
+pub fn set_discount_tiers(
+    ctx: Context<SetDiscountTiers>,
+    discount_mint: Pubkey,
+    discount_tiers: Vec<DiscountTier>,
+) -> Result<()> {
+    if discount_tiers.len() > MAX_DISCOUNT_TIERS {
+        return err!(errors::ErrorCode::ListTooLong);
+    }
+    let fee_denom = ctx.accounts.config.fee_denom;
+    if discount_tiers
+        .windows(2)
+        .any(|pair| pair[0].min_balance >= pair[1].min_balance)
+        || discount_tiers
+            .iter()
+            .any(|tier| tier.discount_percent > fee_denom)
+    {
+        return err!(errors::ErrorCode::InvalidDiscountTiers);
+    }
+
+    let config = &mut ctx.accounts.config;
+    config.discount_mint = discount_mint;
+    config.discount_tiers = discount_tiers;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetDiscountTiers<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
@@ -0,0 +1,21 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn set_emit_events(ctx: Context<SetEmitEvents>, emit_events: bool) -> Result<()> {
+    ctx.accounts.config.emit_events = emit_events;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetEmitEvents<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn set_fee_denom(ctx: Context<SetFeeDenom>, fee_denom: u64) -> Result<()> {
+    if fee_denom == 0 {
+        return err!(errors::ErrorCode::InvalidFeeDenom);
+    }
+
+    ctx.accounts.config.fee_denom = fee_denom;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetFeeDenom<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
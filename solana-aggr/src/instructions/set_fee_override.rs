@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::*,
+    errors,
+    state::{Config, FeeOverride},
+};
+
+pub fn set_fee_override(
+    ctx: Context<SetFeeOverride>,
+    mint: Pubkey,
+    fee_percent: u64,
+) -> Result<()> {
+    let fee_overrides = &mut ctx.accounts.config.fee_overrides;
+
+    if let Some(fee_override) = fee_overrides
+        .iter_mut()
+        .find(|fee_override| fee_override.mint == mint)
+    {
+        fee_override.fee_percent = fee_percent;
+        return Ok(());
+    }
+
+    if fee_overrides.len() >= MAX_FEE_OVERRIDES {
+        return err!(errors::ErrorCode::ListTooLong);
+    }
+    fee_overrides.push(FeeOverride { mint, fee_percent });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetFeeOverride<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
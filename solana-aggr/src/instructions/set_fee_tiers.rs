@@ -0,0 +1,102 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::*,
+    errors,
+    state::{Config, FeeTier},
+};
+
+pub fn set_fee_tiers(ctx: Context<SetFeeTiers>, fee_tiers: Vec<FeeTier>) -> Result<()> {
+    if fee_tiers.len() > MAX_FEE_TIERS {
+        return err!(errors::ErrorCode::ListTooLong);
+    }
+    if fee_tiers
+        .windows(2)
+        .any(|pair| pair[0].threshold_amount >= pair[1].threshold_amount)
+    {
+        return err!(errors::ErrorCode::UnsortedFeeTiers);
+    }
+
+    ctx.accounts.config.fee_tiers = fee_tiers;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetFeeTiers<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_harness::{default_config, Ledger, Outcome, TestAccount};
+
+    fn tier(threshold_amount: u64, fee_percent: u64) -> FeeTier {
+        FeeTier {
+            threshold_amount,
+            fee_percent,
+        }
+    }
+
+    fn run(ledger: &mut Ledger, authority: Pubkey, fee_tiers: Vec<FeeTier>) -> Outcome {
+        ledger.run(
+            crate::accounts::SetFeeTiers {
+                authority,
+                config: crate::pda::find_config().0,
+            },
+            &[],
+            crate::instruction::SetFeeTiers { fee_tiers },
+        )
+    }
+
+    fn ledger(authority: Pubkey) -> Ledger {
+        Ledger::new([
+            TestAccount::wallet(authority, 1_000_000_000),
+            TestAccount::config(&default_config(authority)),
+        ])
+    }
+
+    #[test]
+    fn stores_ascending_tiers() {
+        let authority = Pubkey::new_unique();
+        let mut ledger = ledger(authority);
+        let fee_tiers: Vec<_> = (0..MAX_FEE_TIERS as u64)
+            .map(|index| tier(index * 1_000, 100 - index))
+            .collect();
+        run(&mut ledger, authority, fee_tiers).assert_ok();
+
+        let config: Config = ledger.get(&crate::pda::find_config().0).read();
+        assert_eq!(config.fee_tiers.len(), MAX_FEE_TIERS);
+        assert_eq!(config.fee_tiers[1].threshold_amount, 1_000);
+    }
+
+    #[test]
+    fn rejects_tiers_out_of_order() {
+        let authority = Pubkey::new_unique();
+        let mut ledger = ledger(authority);
+        run(
+            &mut ledger,
+            authority,
+            vec![tier(1_000, 50), tier(1_000, 25)],
+        )
+        .assert_error(errors::ErrorCode::UnsortedFeeTiers);
+    }
+
+    #[test]
+    fn rejects_more_than_max_fee_tiers() {
+        let authority = Pubkey::new_unique();
+        let mut ledger = ledger(authority);
+        let fee_tiers = (0..=MAX_FEE_TIERS as u64)
+            .map(|index| tier(index, 100))
+            .collect();
+        run(&mut ledger, authority, fee_tiers).assert_error(errors::ErrorCode::ListTooLong);
+    }
+}
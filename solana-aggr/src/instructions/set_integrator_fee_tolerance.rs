@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn set_integrator_fee_tolerance(
+    ctx: Context<SetIntegratorFeeTolerance>,
+    integrator_fee_tolerance_bps: Option<u64>,
+) -> Result<()> {
+    ctx.accounts.config.integrator_fee_tolerance_bps = integrator_fee_tolerance_bps;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetIntegratorFeeTolerance<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
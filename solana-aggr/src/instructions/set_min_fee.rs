@@ -0,0 +1,23 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn set_min_fee(ctx: Context<SetMinFee>, min_fee: u64, in_whole_tokens: bool) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    config.min_fee = min_fee;
+    config.min_fee_in_whole_tokens = in_whole_tokens;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetMinFee<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn set_privileged(ctx: Context<SetPrivileged>, privileged: Vec<Pubkey>) -> Result<()> {
+    if privileged.len() > MAX_PRIVILEGED {
+        return err!(errors::ErrorCode::ListTooLong);
+    }
+
+    ctx.accounts.config.privileged = privileged;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetPrivileged<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
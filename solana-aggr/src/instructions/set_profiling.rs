@@ -0,0 +1,21 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn set_profiling(ctx: Context<SetProfiling>, profiling: bool) -> Result<()> {
+    ctx.accounts.config.profiling = profiling;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetProfiling<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
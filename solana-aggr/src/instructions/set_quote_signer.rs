@@ -0,0 +1,29 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn set_quote_signer(
+    ctx: Context<SetQuoteSigner>,
+    quote_signer: Pubkey,
+    require_signed_quote: bool,
+) -> Result<()> {
+    if require_signed_quote && quote_signer == Pubkey::default() {
+        return err!(errors::ErrorCode::InvalidQuote);
+    }
+    ctx.accounts.config.quote_signer = quote_signer;
+    ctx.accounts.config.require_signed_quote = require_signed_quote;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetQuoteSigner<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn set_slippage_protection(
+    ctx: Context<SetSlippageProtection>,
+    require_slippage_protection: bool,
+) -> Result<()> {
+    ctx.accounts.config.require_slippage_protection = require_slippage_protection;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetSlippageProtection<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
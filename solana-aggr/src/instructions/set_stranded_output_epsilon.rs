@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn set_stranded_output_epsilon(
+    ctx: Context<SetStrandedOutputEpsilon>,
+    stranded_output_epsilon: u64,
+) -> Result<()> {
+    ctx.accounts.config.stranded_output_epsilon = stranded_output_epsilon;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetStrandedOutputEpsilon<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
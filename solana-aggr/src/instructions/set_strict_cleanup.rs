@@ -0,0 +1,21 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn set_strict_cleanup(ctx: Context<SetStrictCleanup>, strict_cleanup: bool) -> Result<()> {
+    ctx.accounts.config.strict_cleanup = strict_cleanup;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetStrictCleanup<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
@@ -0,0 +1,102 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+/// Points Unizen's fee share, and the program authority dust swept for Unizen, at
+/// `unizen_fee_recipient`. Fee accounts passed to swaps have to be owned by it from then on.
+pub fn set_unizen_fee_recipient(
+    ctx: Context<SetUnizenFeeRecipient>,
+    unizen_fee_recipient: Pubkey,
+) -> Result<()> {
+    if unizen_fee_recipient == Pubkey::default() {
+        return err!(errors::ErrorCode::InvalidFeeAccount);
+    }
+    ctx.accounts.config.unizen_fee_recipient = unizen_fee_recipient;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetUnizenFeeRecipient<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        instructions::swap_tokens_for_tokens::tests::{accounts, args, ROUTE_IN, ROUTE_OUT},
+        test_harness::{default_config, route_data, Outcome, TestAccount, TokenSwap},
+    };
+
+    fn run(swap: &mut TokenSwap, authority: Pubkey, unizen_fee_recipient: Pubkey) -> Outcome {
+        swap.ledger.run(
+            crate::accounts::SetUnizenFeeRecipient {
+                authority,
+                config: crate::pda::find_config().0,
+            },
+            &[],
+            crate::instruction::SetUnizenFeeRecipient {
+                unizen_fee_recipient,
+            },
+        )
+    }
+
+    fn run_swap(swap: &mut TokenSwap) -> Outcome {
+        let route = swap.route();
+        let (accounts, args) = (accounts(swap), args(swap, ROUTE_OUT));
+        swap.ledger.run(
+            accounts,
+            &route,
+            crate::instruction::SwapTokensForTokens {
+                args,
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    fn swap_with_config(authority: Pubkey) -> TokenSwap {
+        let mut swap = TokenSwap::new();
+        swap.ledger
+            .add(TestAccount::config(&default_config(authority)));
+        swap
+    }
+
+    #[test]
+    fn swaps_pay_the_new_recipient() {
+        let authority = Pubkey::new_unique();
+        let mut swap = swap_with_config(authority);
+        let recipient = Pubkey::new_unique();
+        run(&mut swap, authority, recipient).assert_ok();
+
+        run_swap(&mut swap).assert_error(errors::ErrorCode::WrongFeeRecipient);
+
+        swap.unizen_src_ata = swap
+            .ledger
+            .add(TestAccount::ata(swap.src_mint, recipient, 0));
+        run_swap(&mut swap).assert_ok();
+        assert!(swap.ledger.token_amount(&swap.unizen_src_ata) > 0);
+    }
+
+    #[test]
+    fn rejects_the_default_pubkey() {
+        let authority = Pubkey::new_unique();
+        let mut swap = swap_with_config(authority);
+        run(&mut swap, authority, Pubkey::default())
+            .assert_error(errors::ErrorCode::InvalidFeeAccount);
+    }
+
+    #[test]
+    fn rejects_other_signer() {
+        let mut swap = swap_with_config(Pubkey::new_unique());
+        run(&mut swap, Pubkey::new_unique(), Pubkey::new_unique())
+            .assert_error(errors::ErrorCode::Unauthorized);
+    }
+}
@@ -0,0 +1,30 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::*,
+    errors,
+    state::{Config, VolumeCap},
+};
+
+pub fn set_volume_cap(ctx: Context<SetVolumeCap>, daily_cap: u64) -> Result<()> {
+    ctx.accounts.volume_cap.daily_cap = daily_cap;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetVolumeCap<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, volume_cap.integrator.as_ref()],
+        bump
+    )]
+    pub volume_cap: Account<'info, VolumeCap>,
+}
@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn set_wsol_closers(ctx: Context<SetWsolClosers>, wsol_closers: Vec<Pubkey>) -> Result<()> {
+    if wsol_closers.len() > MAX_WSOL_CLOSERS {
+        return err!(errors::ErrorCode::ListTooLong);
+    }
+
+    ctx.accounts.config.wsol_closers = wsol_closers;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetWsolClosers<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
@@ -0,0 +1,27 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::Config};
+
+pub fn set_wsol_dust_sweep(
+    ctx: Context<SetWsolDustSweep>,
+    threshold: u64,
+    to_unizen: bool,
+) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    config.wsol_dust_threshold = threshold;
+    config.wsol_dust_to_unizen = to_unizen;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetWsolDustSweep<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
@@ -0,0 +1,489 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors,
+    helpers::*,
+    state::{Config, FeeArgs, FeeCounter, SignedQuote},
+};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapLeg {
+    pub amount_in: u64,
+    pub amount_out_min: u64,
+    pub dst_index: u8,
+    pub route_start: u8,
+    pub route_len: u8,
+    pub data: Vec<u8>,
+    /// Replaces `amount_out_min` with the quoted minimum; see `resolve_quoted_min_out`.
+    pub signed_quote: Option<SignedQuote>,
+}
+
+/// Arguments of `swap_batch`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapBatchArgs {
+    pub legs: Vec<SwapLeg>,
+    pub fee: FeeArgs,
+    pub fee_per_leg: bool,
+    pub dry_run: bool,
+}
+
+pub fn swap_batch(ctx: Context<SwapBatch>, args: SwapBatchArgs) -> Result<()> {
+    let SwapBatchArgs {
+        mut legs,
+        fee,
+        fee_per_leg,
+        dry_run,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    if legs.is_empty() || legs.len() > MAX_BATCH_SWAPS {
+        return err!(errors::ErrorCode::ListTooLong);
+    }
+    let quote_accounts = QuoteAccounts {
+        user: ctx.accounts.user.clone(),
+        instructions_sysvar: ctx.accounts.instructions_sysvar.clone(),
+        quote_nonce: ctx.accounts.quote_nonce.clone(),
+        quote_nonce_bump: ctx.bumps.quote_nonce,
+        system_program: ctx.accounts.system_program.clone(),
+    };
+    // Quoted legs take the Ed25519 signatures in order.
+    let mut signature_index = 0;
+    for leg in legs.iter_mut() {
+        let dst_account = ctx
+            .remaining_accounts
+            .get(leg.dst_index as usize)
+            .ok_or_else(|| error!(errors::ErrorCode::AccountCountMismatch))?;
+        let dst_mint = load_token_account(dst_account)?
+            .ok_or_else(|| error!(errors::ErrorCode::IncorrectOwner))?
+            .mint;
+        leg.amount_out_min = resolve_quoted_min_out(
+            &quote_accounts,
+            &config,
+            leg.signed_quote.as_ref(),
+            signature_index,
+            &dst_mint,
+            leg.amount_out_min,
+            dry_run,
+        )?;
+        if leg.signed_quote.is_some() {
+            signature_index += 1;
+        }
+    }
+    let amount_in = legs.iter().try_fold(0u64, |total, leg| {
+        assert_amount_in(leg.amount_in)?;
+        assert_slippage_protection(&config, leg.amount_out_min)?;
+        total
+            .checked_add(leg.amount_in)
+            .ok_or_else(|| error!(errors::ErrorCode::Overflow))
+    })?;
+
+    // Per-leg fees are checked on the batch total too.
+    if dry_run {
+        return validate_dry_run(
+            &fee_accounts(ctx.accounts, &config),
+            amount_in,
+            fee.mode(),
+            fee.referrer_percent,
+        );
+    }
+
+    track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
+    let nonce = next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+
+    // Per-leg fees are taken before each leg and reported as one `TakeFeeAggregate`
+    // with one receipt under the batch's nonce.
+    let mut fee_aggregate = FeeAggregate::default();
+    if !fee_per_leg {
+        log_phase_compute(&config, "fee start");
+        take_leg_fee(ctx.accounts, amount_in, fee, nonce, &config, None)?;
+        log_phase_compute(&config, "fee end");
+    }
+
+    for leg in legs {
+        if fee_per_leg {
+            log_phase_compute(&config, "fee start");
+            take_leg_fee(
+                ctx.accounts,
+                leg.amount_in,
+                fee,
+                nonce,
+                &config,
+                Some(&mut fee_aggregate),
+            )?;
+            log_phase_compute(&config, "fee end");
+        }
+
+        let route_start = leg.route_start as usize;
+        let route_end = route_start
+            .checked_add(leg.route_len as usize)
+            .ok_or_else(|| error!(errors::ErrorCode::AccountCountMismatch))?;
+        let route_accounts = ctx
+            .remaining_accounts
+            .get(route_start..route_end)
+            .ok_or_else(|| error!(errors::ErrorCode::AccountCountMismatch))?;
+        let dst_account = ctx
+            .remaining_accounts
+            .get(leg.dst_index as usize)
+            .ok_or_else(|| error!(errors::ErrorCode::AccountCountMismatch))?;
+
+        ctx.accounts.user_src_ata.reload()?;
+        let prev_src_bal = ctx.accounts.user_src_ata.amount;
+        let prev_bal = read_token_amount(dst_account)?;
+
+        log_phase_compute(&config, "swap start");
+        swap_on_jupiter(
+            &ctx.accounts.config,
+            route_accounts,
+            ctx.accounts.jupiter_program.clone(),
+            leg.data,
+        )?;
+        log_phase_compute(&config, "swap end");
+
+        ctx.accounts.user_src_ata.reload()?;
+        let post_src_bal = ctx.accounts.user_src_ata.amount;
+        assert_amount_spent(prev_src_bal, post_src_bal, leg.amount_in)?;
+
+        let post_bal = read_token_amount(dst_account)?;
+        assert_amount_out(
+            prev_bal,
+            post_bal,
+            leg.amount_out_min,
+            None,
+            events_enabled(&config),
+        )?;
+    }
+
+    if let Some(nonce) = nonce {
+        if fee_aggregate.integrator_received > 0 {
+            record_fee_receipt(
+                &fee_accounts(ctx.accounts, &config),
+                nonce,
+                fee_aggregate.integrator_received,
+            )?;
+        }
+    }
+    fee_aggregate.emit(&config, ctx.accounts.user.key());
+
+    Ok(())
+}
+
+fn take_leg_fee(
+    accounts: &SwapBatch,
+    amount_in: u64,
+    fee: FeeArgs,
+    nonce: Option<u64>,
+    config: &Option<Config>,
+    aggregate: Option<&mut FeeAggregate>,
+) -> Result<()> {
+    take_integrator_fee_into(
+        fee_accounts(accounts, config),
+        amount_in,
+        fee.mode(),
+        fee.referrer_percent,
+        nonce,
+        None,
+        aggregate,
+    )
+}
+
+fn fee_accounts<'info>(
+    accounts: &SwapBatch<'info>,
+    config: &Option<Config>,
+) -> AccountsForFee<'info> {
+    AccountsForFee {
+        user: accounts.user.to_account_info(),
+        fee_payer: None,
+        token_program: accounts.token_program.clone(),
+        mint: accounts.src_token.clone(),
+        user_token_account: accounts.user_src_ata.clone(),
+        unizen_token_account: accounts.unizen_src_ata.clone(),
+        integrator_token_account: accounts.integrator_src_ata.clone(),
+        referrer_token_account: accounts.referrer_src_ata.clone(),
+        discount_token_account: accounts.discount_ata.clone(),
+        config: config.clone(),
+        fee_receipt: accounts.fee_receipt.clone(),
+        system_program: accounts.system_program.clone(),
+        sol_fee: None,
+    }
+}
+
+#[derive(Accounts)]
+pub struct SwapBatch<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub src_token: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = src_token,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = src_token,
+        token::token_program = token_program
+    )]
+    pub unizen_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_src_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    /// CHECK: Address-checked instructions sysvar, read by `verify_signed_quote`.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+    /// CHECK: Created and validated by `resolve_quoted_min_out`.
+    #[account(mut, seeds = [QUOTE_NONCE_SEED, user.key().as_ref()], bump)]
+    pub quote_nonce: Option<UncheckedAccount<'info>>,
+    pub jupiter_program: Program<'info, Jupiter>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::test_harness::{
+        default_config, route_data, Outcome, TestAccount, TokenSwap, USER_BALANCE,
+    };
+
+    const LEG_IN: u64 = 1_000_000;
+    const LEG_OUT: u64 = 500_000;
+    const FEE: FeeArgs = FeeArgs {
+        fee_percent: 100,
+        share_percent: 2_000,
+        referrer_percent: 0,
+    };
+    // 1% of both legs' `LEG_IN`, a fifth of it to Unizen.
+    const UNIZEN_FEE: u64 = 4_000;
+    const INTEGRATOR_FEE: u64 = 16_000;
+    // What each leg routes once its half of the fee is taken.
+    const LEG_ROUTED: u64 = LEG_IN - (UNIZEN_FEE + INTEGRATOR_FEE) / 2;
+
+    /// Two legs into the receiver under one fee take, or a fee per leg with `fee_per_leg`.
+    fn run(swap: &mut TokenSwap, fee_per_leg: bool) -> Outcome {
+        run_legs(swap, 2, LEG_OUT, fee_per_leg, false)
+    }
+
+    /// Runs `count` legs, the last one requiring `last_out_min`.
+    fn run_legs(
+        swap: &mut TokenSwap,
+        count: usize,
+        last_out_min: u64,
+        fee_per_leg: bool,
+        dry_run: bool,
+    ) -> Outcome {
+        let leg = SwapLeg {
+            amount_in: LEG_IN,
+            amount_out_min: LEG_OUT,
+            dst_index: 0,
+            route_start: 1,
+            route_len: 5,
+            data: route_data(LEG_ROUTED, LEG_OUT),
+            signed_quote: None,
+        };
+        let mut legs = vec![leg.clone(); count - 1];
+        legs.push(SwapLeg {
+            amount_out_min: last_out_min,
+            ..leg
+        });
+        let mut remaining = vec![AccountMeta::new(swap.receiver_dst_ata, false)];
+        remaining.extend(swap.route());
+        swap.ledger.run(
+            crate::accounts::SwapBatch {
+                user: swap.user,
+                src_token: swap.src_mint,
+                user_src_ata: swap.user_src_ata,
+                unizen_src_ata: swap.unizen_src_ata,
+                integrator_src_ata: swap.integrator_src_ata,
+                referrer_src_ata: None,
+                discount_ata: None,
+                config: crate::pda::find_config().0,
+                fee_counter: None,
+                volume_cap: Pubkey::find_program_address(
+                    &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                    &crate::ID,
+                )
+                .0,
+                instructions_sysvar: None,
+                quote_nonce: None,
+                jupiter_program: Jupiter::id(),
+                fee_receipt: None,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &remaining,
+            crate::instruction::SwapBatch {
+                args: SwapBatchArgs {
+                    legs,
+                    fee: FEE,
+                    fee_per_leg,
+                    dry_run,
+                },
+            },
+        )
+    }
+
+    #[test]
+    fn runs_every_leg_under_one_fee_take() {
+        let mut swap = TokenSwap::new();
+        let outcome = run(&mut swap, false);
+        outcome.assert_ok();
+
+        assert_eq!(
+            swap.ledger.token_amount(&swap.receiver_dst_ata),
+            2 * LEG_OUT
+        );
+        assert_eq!(
+            swap.ledger.token_amount(&swap.user_src_ata),
+            USER_BALANCE - 2 * LEG_IN
+        );
+        let events = outcome.events::<TakeFee>();
+        assert_eq!(events.len(), 1);
+        let take_fee = &events[0];
+        assert_eq!(take_fee.amount, 2 * LEG_IN);
+        assert_eq!(take_fee.unizen_received, UNIZEN_FEE);
+        assert_eq!(take_fee.integrator_received, INTEGRATOR_FEE);
+    }
+
+    #[test]
+    fn reports_per_leg_fees_as_one_aggregate() {
+        let mut swap = TokenSwap::new();
+        let outcome = run(&mut swap, true);
+        outcome.assert_ok();
+
+        assert!(outcome.events::<TakeFee>().is_empty());
+        let events = outcome.events::<TakeFeeAggregate>();
+        assert_eq!(events.len(), 1);
+        let aggregate = &events[0];
+        assert_eq!(aggregate.hops, 2);
+        assert_eq!(aggregate.amount, 2 * LEG_IN);
+        assert_eq!(aggregate.unizen_received, UNIZEN_FEE);
+        assert_eq!(aggregate.integrator_received, INTEGRATOR_FEE);
+        assert_eq!(
+            swap.ledger.token_amount(&swap.integrator_src_ata),
+            INTEGRATOR_FEE
+        );
+    }
+
+    #[test]
+    fn aggregate_is_not_emitted_with_events_disabled() {
+        let mut swap = TokenSwap::new();
+        let mut config = default_config(Pubkey::new_unique());
+        config.emit_events = false;
+        swap.ledger.add(TestAccount::config(&config));
+        let outcome = run(&mut swap, true);
+        outcome.assert_ok();
+
+        assert!(outcome.events::<TakeFeeAggregate>().is_empty());
+    }
+
+    #[test]
+    fn profiling_logs_each_leg_phase() {
+        let mut swap = TokenSwap::new();
+        swap.ledger.add(TestAccount::config(&Config {
+            profiling: true,
+            ..default_config(Pubkey::new_unique())
+        }));
+        let outcome = run(&mut swap, true);
+        outcome.assert_ok();
+
+        let phases: Vec<_> = outcome
+            .logs
+            .iter()
+            .filter_map(|line| line.strip_prefix("Profile: "))
+            .collect();
+        assert_eq!(
+            phases,
+            ["fee start", "fee end", "swap start", "swap end"].repeat(2)
+        );
+    }
+
+    #[test]
+    fn dry_run_checks_the_batch_total_and_moves_nothing() {
+        let mut swap = TokenSwap::new();
+        let before = swap.ledger.clone();
+        let outcome = run_legs(&mut swap, 2, LEG_OUT, true, true);
+        outcome.assert_ok();
+
+        let events = outcome.events::<DryRunValidated>();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].amount_in, 2 * LEG_IN);
+        assert_eq!(events[0].total_fee, UNIZEN_FEE + INTEGRATOR_FEE);
+        for key in [
+            swap.user_src_ata,
+            swap.unizen_src_ata,
+            swap.integrator_src_ata,
+            swap.receiver_dst_ata,
+        ] {
+            assert_eq!(swap.ledger.get(&key), before.get(&key));
+        }
+    }
+
+    #[test]
+    fn failing_leg_rolls_back_the_whole_batch() {
+        let mut swap = TokenSwap::new();
+        let before = swap.ledger.clone();
+        let outcome = run_legs(&mut swap, 2, LEG_OUT + 1, false, false);
+        outcome.assert_error(errors::ErrorCode::OutputBelowMinimum);
+
+        // The fee and the first leg had gone through when the second leg failed.
+        assert_eq!(
+            outcome.left.token_amount(&swap.receiver_dst_ata),
+            2 * LEG_OUT
+        );
+        assert_eq!(
+            outcome.left.token_amount(&swap.integrator_src_ata),
+            INTEGRATOR_FEE
+        );
+        for account in [
+            swap.user_src_ata,
+            swap.receiver_dst_ata,
+            swap.unizen_src_ata,
+            swap.integrator_src_ata,
+        ] {
+            assert_eq!(
+                swap.ledger.token_amount(&account),
+                before.token_amount(&account)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_more_than_max_legs() {
+        let mut swap = TokenSwap::new();
+        run_legs(&mut swap, MAX_BATCH_SWAPS + 1, LEG_OUT, false, false)
+            .assert_error(errors::ErrorCode::ListTooLong);
+    }
+}
@@ -1,75 +1,604 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{ Mint, Token, TokenAccount};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
-use crate::{constants::*, helpers::* };
+use crate::{
+    constants::*,
+    errors,
+    helpers::*,
+    state::{FeeArgs, FeeCounter, RouteBuffer, SignedQuote},
+};
+
+/// Arguments of `swap_sol_for_tokens`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapSolForTokensArgs {
+    pub amount_in: u64,
+    pub max_amount_in: u64,
+    pub amount_out_min: u64,
+    pub soft_min: Option<u64>,
+    pub fee: FeeArgs,
+    pub dry_run: bool,
+    pub close_user_wsol: bool,
+    pub min_user_lamports: Option<u64>,
+    pub dst_owner: Pubkey,
+    pub dst_mint: Pubkey,
+    pub callback_data: Option<Vec<u8>>,
+    pub signed_quote: Option<SignedQuote>,
+}
 
 pub fn swap_sol_for_tokens(
     ctx: Context<SwapSolForTokens>,
-    amount_in: u64,
-    amount_out_min: u64,
-    fee_percent: u64,
-    share_percent: u64,
+    args: SwapSolForTokensArgs,
     data: Vec<u8>,
 ) -> Result<()> {
-    wrap_user_sol(
+    let SwapSolForTokensArgs {
+        amount_in,
+        max_amount_in,
+        amount_out_min,
+        soft_min,
+        fee,
+        dry_run,
+        close_user_wsol,
+        min_user_lamports,
+        dst_owner,
+        dst_mint,
+        callback_data,
+        signed_quote,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    assert_amount_in(amount_in)?;
+    assert_receiver_ata_with_program(
+        &ctx.accounts.receiver_dst_ata,
+        dst_owner,
+        ctx.accounts.dst_token_program.key,
+    )?;
+    assert_destination_mint(&ctx.accounts.receiver_dst_ata, dst_mint)?;
+    let amount_out_min = resolve_quoted_min_out(
+        &QuoteAccounts {
+            user: ctx.accounts.user.clone(),
+            instructions_sysvar: ctx.accounts.instructions_sysvar.clone(),
+            quote_nonce: ctx.accounts.quote_nonce.clone(),
+            quote_nonce_bump: ctx.bumps.quote_nonce,
+            system_program: ctx.accounts.system_program.clone(),
+        },
+        &config,
+        signed_quote.as_ref(),
+        0,
+        &ctx.accounts.receiver_dst_ata.mint,
+        amount_out_min,
+        dry_run,
+    )?;
+    assert_slippage_protection(&config, amount_out_min)?;
+
+    let mut fee_accounts = AccountsForFee {
+        user: ctx.accounts.user.to_account_info(),
+        fee_payer: None,
+        token_program: ctx.accounts.token_program.clone(),
+        mint: ctx.accounts.sol_mint.clone(),
+        user_token_account: ctx.accounts.user_wsol_ata.clone(),
+        unizen_token_account: ctx.accounts.unizen_wsol_ata.clone(),
+        integrator_token_account: ctx.accounts.integrator_wsol_ata.clone(),
+        referrer_token_account: ctx.accounts.referrer_wsol_ata.clone(),
+        discount_token_account: ctx.accounts.discount_ata.clone(),
+        config: config.clone(),
+        fee_receipt: ctx.accounts.fee_receipt.clone(),
+        system_program: ctx.accounts.system_program.clone(),
+        sol_fee: None,
+    };
+
+    if dry_run {
+        return validate_dry_run(&fee_accounts, amount_in, fee.mode(), fee.referrer_percent);
+    }
+
+    assert_lamports_floor(&ctx.accounts.user, amount_in, min_user_lamports)?;
+
+    let prev_wsol_bal = ctx.accounts.user_wsol_ata.amount;
+    log_phase_compute(&config, "wrap start");
+    let wrapped_amount = wrap_user_sol_synced(
         ctx.accounts.system_program.clone(),
         ctx.accounts.token_program.clone(),
         ctx.accounts.user.clone(),
         ctx.accounts.user_wsol_ata.clone(),
         amount_in,
     )?;
+    log_phase_compute(&config, "wrap end");
+    msg!("Wrapped wSOL balance is {}", wrapped_amount);
+    fee_accounts.user_token_account.reload()?;
+
+    let nonce = next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
 
+    log_phase_compute(&config, "fee start");
     take_integrator_fee(
-        AccountsForFee {
-            user: ctx.accounts.user.clone(),
-            token_program: ctx.accounts.token_program.clone(),
-            user_token_account: ctx.accounts.user_wsol_ata.clone(),
-            unizen_token_account: ctx.accounts.unizen_wsol_ata.clone(),
-            integrator_token_account: ctx.accounts.integrator_wsol_ata.clone(),
-        },
+        fee_accounts,
         amount_in,
-        fee_percent,
-        share_percent,
+        fee.mode(),
+        fee.referrer_percent,
+        nonce,
+        None,
     )?;
+    log_phase_compute(&config, "fee end");
 
+    ctx.accounts.user_wsol_ata.reload()?;
+    let prev_src_bal = ctx.accounts.user_wsol_ata.amount;
     let prev_bal = ctx.accounts.receiver_dst_ata.amount;
 
+    log_phase_compute(&config, "swap start");
     swap_on_jupiter(
+        &ctx.accounts.config,
         ctx.remaining_accounts,
         ctx.accounts.jupiter_program.clone(),
-        data,
+        resolve_route_data(&ctx.accounts.route_buffer, data),
     )?;
+    log_phase_compute(&config, "swap end");
 
+    ctx.accounts.user_wsol_ata.reload()?;
+    let post_src_bal = ctx.accounts.user_wsol_ata.amount;
+    assert_amount_spent(prev_src_bal, post_src_bal, max_amount_in)?;
+
+    // Must run before the user wSOL close; if it fails the wrap and fees revert with it.
     ctx.accounts.receiver_dst_ata.reload()?;
     let post_bal = ctx.accounts.receiver_dst_ata.amount;
-    assert_amount_out(prev_bal, post_bal, amount_out_min)
-}
+    match (&ctx.accounts.price_feed, &ctx.accounts.dst_mint) {
+        (Some(price_feed), Some(dst_mint)) => assert_amount_out_in_reference(
+            prev_bal,
+            post_bal,
+            amount_out_min,
+            soft_min,
+            price_feed,
+            dst_mint.decimals,
+            events_enabled(&config),
+        )?,
+        (Some(_), None) => return err!(errors::ErrorCode::MintMismatch),
+        (None, _) => assert_amount_out(
+            prev_bal,
+            post_bal,
+            amount_out_min,
+            soft_min,
+            events_enabled(&config),
+        )?,
+    }
 
+    if let Some(callback_program) = &ctx.accounts.callback_program {
+        invoke_swap_callback(
+            callback_program,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.receiver_dst_ata,
+            post_bal.saturating_sub(prev_bal),
+            callback_data.unwrap_or_default(),
+        )?;
+    }
 
+    let user_wsol_closed = sweep_wsol_dust(
+        &config,
+        WsolDustAccounts {
+            token_program: ctx.accounts.token_program.clone(),
+            sol_mint: ctx.accounts.sol_mint.clone(),
+            user: ctx.accounts.user.clone(),
+            user_wsol_ata: ctx.accounts.user_wsol_ata.clone(),
+            unizen_wsol_ata: ctx.accounts.unizen_wsol_ata.clone(),
+        },
+        prev_wsol_bal,
+        close_user_wsol,
+    )?;
+
+    if close_user_wsol && !user_wsol_closed {
+        close_user_token_account_if_empty(
+            ctx.accounts.token_program.clone(),
+            ctx.accounts.user.clone(),
+            ctx.accounts.user_wsol_ata.clone(),
+            events_enabled(&config),
+        )?;
+    }
+
+    Ok(())
+}
 
 #[derive(Accounts)]
 pub struct SwapSolForTokens<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
     #[account(address = NATIVE_MINT)]
-    pub sol_mint: Account<'info, Mint>,
+    pub sol_mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut,        
         associated_token::mint = sol_mint,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = token_program
     )]
-    pub user_wsol_ata: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub receiver_dst_ata: Account<'info, TokenAccount>,
+    pub user_wsol_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::token_program = dst_token_program)]
+    pub receiver_dst_ata: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut, 
-        associated_token::mint = sol_mint,
-        associated_token::authority = UNIZEN
+        token::mint = sol_mint,
+        token::token_program = token_program
     )]
-    pub unizen_wsol_ata: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub integrator_wsol_ata: Account<'info, TokenAccount>,
+    pub unizen_wsol_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_wsol_ata.mint == sol_mint.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_wsol_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_wsol_ata.mint == sol_mint.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_wsol_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_wsol_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_wsol_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    pub route_buffer: Option<Account<'info, RouteBuffer>>,
+    /// CHECK: Pyth price update the user picked for the destination token, validated by
+    /// `load_oracle_price`; when passed, `amount_out_min` is in its price units.
+    pub price_feed: Option<UncheckedAccount<'info>>,
+    /// Read for its decimals when `price_feed` is passed.
+    #[account(address = receiver_dst_ata.mint @ errors::ErrorCode::MintMismatch)]
+    pub dst_mint: Option<InterfaceAccount<'info, Mint>>,
+    /// CHECK: Validated as an executable program by `invoke_swap_callback`.
+    pub callback_program: Option<UncheckedAccount<'info>>,
+    /// CHECK: Address-checked instructions sysvar, read by `verify_signed_quote`.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+    /// CHECK: Created and validated by `resolve_quoted_min_out`.
+    #[account(mut, seeds = [QUOTE_NONCE_SEED, user.key().as_ref()], bump)]
+    pub quote_nonce: Option<UncheckedAccount<'info>>,
     pub jupiter_program: Program<'info, Jupiter>,
-    pub token_program: Program<'info, Token>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub dst_token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::{
+        state::Config,
+        test_harness::{
+            default_config, rent, route_data, Outcome, SolSwap, TestAccount, USER_LAMPORTS,
+        },
+    };
+
+    const AMOUNT_IN: u64 = 1_000_000;
+    const FEE: FeeArgs = FeeArgs {
+        fee_percent: 100,
+        share_percent: 2_000,
+        referrer_percent: 0,
+    };
+    // 1% of `AMOUNT_IN`, a fifth of it to Unizen.
+    const UNIZEN_FEE: u64 = 2_000;
+    const INTEGRATOR_FEE: u64 = 8_000;
+    const ROUTE_IN: u64 = AMOUNT_IN - UNIZEN_FEE - INTEGRATOR_FEE;
+    const ROUTE_OUT: u64 = 500_000;
+
+    pub(crate) fn accounts(swap: &SolSwap) -> crate::accounts::SwapSolForTokens {
+        crate::accounts::SwapSolForTokens {
+            user: swap.user,
+            sol_mint: NATIVE_MINT,
+            user_wsol_ata: swap.user_wsol_ata,
+            receiver_dst_ata: swap.receiver_dst_ata,
+            unizen_wsol_ata: swap.unizen_wsol_ata,
+            integrator_wsol_ata: swap.integrator_wsol_ata,
+            referrer_wsol_ata: None,
+            discount_ata: None,
+            config: crate::pda::find_config().0,
+            fee_counter: None,
+            volume_cap: Pubkey::find_program_address(
+                &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                &crate::ID,
+            )
+            .0,
+            route_buffer: None,
+            price_feed: None,
+            dst_mint: None,
+            callback_program: None,
+            instructions_sysvar: None,
+            quote_nonce: None,
+            jupiter_program: Jupiter::id(),
+            fee_receipt: None,
+            token_program: token::ID,
+            dst_token_program: token::ID,
+            system_program: system_program::ID,
+        }
+    }
+
+    /// Wraps and swaps `AMOUNT_IN` lamports for a route output of `ROUTE_OUT`.
+    fn args(swap: &SolSwap, amount_out_min: u64) -> SwapSolForTokensArgs {
+        SwapSolForTokensArgs {
+            amount_in: AMOUNT_IN,
+            max_amount_in: AMOUNT_IN,
+            amount_out_min,
+            soft_min: None,
+            fee: FEE,
+            dry_run: false,
+            close_user_wsol: false,
+            min_user_lamports: None,
+            dst_owner: swap.user,
+            dst_mint: swap.dst_mint,
+            callback_data: None,
+            signed_quote: None,
+        }
+    }
+
+    fn run(swap: &mut SolSwap, args: SwapSolForTokensArgs) -> Outcome {
+        run_route(swap, args, ROUTE_IN, ROUTE_OUT)
+    }
+
+    /// Runs a route that spends `route_in` and pays out `route_out`.
+    fn run_route(
+        swap: &mut SolSwap,
+        args: SwapSolForTokensArgs,
+        route_in: u64,
+        route_out: u64,
+    ) -> Outcome {
+        let route = swap.route();
+        swap.ledger.run(
+            accounts(swap),
+            &route,
+            crate::instruction::SwapSolForTokens {
+                args,
+                data: route_data(route_in, route_out),
+            },
+        )
+    }
+
+    #[test]
+    fn swap_wraps_sol_and_pays_fees_and_output() {
+        let mut swap = SolSwap::new();
+        let args = args(&swap, ROUTE_OUT);
+        run(&mut swap, args).assert_ok();
+
+        let ledger = &swap.ledger;
+        assert_eq!(ledger.lamports(&swap.user), USER_LAMPORTS - AMOUNT_IN);
+        assert_eq!(ledger.token_amount(&swap.unizen_wsol_ata), UNIZEN_FEE);
+        assert_eq!(
+            ledger.token_amount(&swap.integrator_wsol_ata),
+            INTEGRATOR_FEE
+        );
+        assert_eq!(ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+    }
+
+    /// The SOL is wrapped, the fees are transferred and the route has run by the time the
+    /// output check fails, and nothing in the instruction unwinds them: leaving no net
+    /// change relies on the check failing the instruction before the user's wSOL account
+    /// is touched again, so the runtime discards all of it.
+    #[test]
+    fn failed_minimum_fails_after_wrap_fees_and_output_moved() {
+        let mut swap = SolSwap::new();
+        let args = args(&swap, ROUTE_OUT + 1);
+        let outcome = run(&mut swap, args);
+
+        outcome.assert_error(errors::ErrorCode::OutputBelowMinimum);
+        let left = &outcome.left;
+        assert_eq!(left.lamports(&swap.user), USER_LAMPORTS - AMOUNT_IN);
+        assert_eq!(left.token_amount(&swap.unizen_wsol_ata), UNIZEN_FEE);
+        assert_eq!(left.token_amount(&swap.integrator_wsol_ata), INTEGRATOR_FEE);
+        assert_eq!(left.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+    }
+
+    #[test]
+    fn rejects_zero_amount_in() {
+        let mut swap = SolSwap::new();
+        let args = SwapSolForTokensArgs {
+            amount_in: 0,
+            ..args(&swap, ROUTE_OUT)
+        };
+        run(&mut swap, args).assert_error(errors::ErrorCode::ZeroAmount);
+    }
+
+    /// wSOL the user already holds before the swap wraps more.
+    const PREFUNDED: u64 = 300_000;
+
+    fn prefunded_swap() -> SolSwap {
+        let mut swap = SolSwap::new();
+        swap.ledger
+            .add(TestAccount::native_ata(swap.user, PREFUNDED));
+        swap
+    }
+
+    #[test]
+    fn wraps_on_top_of_a_pre_funded_wsol_account() {
+        let mut swap = prefunded_swap();
+        let args = args(&swap, ROUTE_OUT);
+        run(&mut swap, args).assert_ok();
+
+        let ledger = &swap.ledger;
+        assert_eq!(ledger.lamports(&swap.user), USER_LAMPORTS - AMOUNT_IN);
+        assert_eq!(ledger.token_amount(&swap.user_wsol_ata), PREFUNDED);
+        assert_eq!(ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+    }
+
+    #[test]
+    fn route_cannot_spend_the_pre_funded_wsol() {
+        let mut swap = prefunded_swap();
+        let args = args(&swap, ROUTE_OUT);
+        run_route(&mut swap, args, ROUTE_IN + PREFUNDED, ROUTE_OUT)
+            .assert_error(errors::ErrorCode::InputOverspent);
+    }
+
+    #[test]
+    fn closes_emptied_user_wsol_account() {
+        let mut swap = SolSwap::new();
+        let wsol_rent = swap.ledger.lamports(&swap.user_wsol_ata);
+        let args = SwapSolForTokensArgs {
+            close_user_wsol: true,
+            ..args(&swap, ROUTE_OUT)
+        };
+        run(&mut swap, args).assert_ok();
+
+        let ledger = &swap.ledger;
+        assert_eq!(ledger.lamports(&swap.user_wsol_ata), 0);
+        assert_eq!(
+            ledger.lamports(&swap.user),
+            USER_LAMPORTS - AMOUNT_IN + wsol_rent
+        );
+    }
+
+    #[test]
+    fn keeps_user_wsol_account_holding_a_balance() {
+        let mut swap = prefunded_swap();
+        let args = SwapSolForTokensArgs {
+            close_user_wsol: true,
+            ..args(&swap, ROUTE_OUT)
+        };
+        let outcome = run(&mut swap, args);
+        outcome.assert_ok();
+
+        let events = outcome.events::<TokenAccountNotClosed>();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].account, swap.user_wsol_ata);
+        assert_eq!(events[0].remaining, PREFUNDED);
+        assert_eq!(swap.ledger.token_amount(&swap.user_wsol_ata), PREFUNDED);
+    }
+
+    #[test]
+    fn keeps_user_wsol_account_holding_a_balance_quietly_with_events_off() {
+        let mut swap = prefunded_swap();
+        swap.ledger.add(TestAccount::config(&Config {
+            emit_events: false,
+            ..default_config(Pubkey::new_unique())
+        }));
+        let args = SwapSolForTokensArgs {
+            close_user_wsol: true,
+            ..args(&swap, ROUTE_OUT)
+        };
+        let outcome = run(&mut swap, args);
+        outcome.assert_ok();
+
+        assert!(outcome.events::<TokenAccountNotClosed>().is_empty());
+        assert_eq!(swap.ledger.token_amount(&swap.user_wsol_ata), PREFUNDED);
+    }
+
+    /// A swap whose user holds just `AMOUNT_IN` on top of `spare` lamports.
+    fn near_full_balance_swap(spare: u64) -> SolSwap {
+        let mut swap = SolSwap::new();
+        swap.ledger
+            .add(TestAccount::wallet(swap.user, AMOUNT_IN + spare));
+        swap
+    }
+
+    #[test]
+    fn wraps_down_to_the_rent_exempt_floor() {
+        let mut swap = near_full_balance_swap(rent(0));
+        let args = args(&swap, ROUTE_OUT);
+        run(&mut swap, args).assert_ok();
+
+        assert_eq!(swap.ledger.lamports(&swap.user), rent(0));
+    }
+
+    #[test]
+    fn rejects_wrapping_below_the_rent_exempt_floor() {
+        let mut swap = near_full_balance_swap(rent(0) - 1);
+        let args = args(&swap, ROUTE_OUT);
+        run(&mut swap, args).assert_error(errors::ErrorCode::InsufficientBalance);
+    }
+
+    #[test]
+    fn floor_override_allows_wrapping_the_full_balance() {
+        let mut swap = near_full_balance_swap(0);
+        let args = SwapSolForTokensArgs {
+            min_user_lamports: Some(0),
+            ..args(&swap, ROUTE_OUT)
+        };
+        run(&mut swap, args).assert_ok();
+
+        assert_eq!(swap.ledger.lamports(&swap.user), 0);
+    }
+
+    #[test]
+    fn rejects_a_receiver_for_another_mint() {
+        let mut swap = SolSwap::new();
+        let args = SwapSolForTokensArgs {
+            dst_mint: Pubkey::new_unique(),
+            ..args(&swap, ROUTE_OUT)
+        };
+        run(&mut swap, args).assert_error(errors::ErrorCode::WrongDestinationMint);
+    }
+
+    /// wSOL the route leaves behind in the user's account.
+    const DUST: u64 = 50;
+
+    /// Swaps with a route leaving `DUST` behind, under a config sweeping dust up to
+    /// `threshold`.
+    fn run_leaving_dust(swap: &mut SolSwap, threshold: u64, to_unizen: bool) -> Outcome {
+        swap.ledger.add(TestAccount::config(&Config {
+            wsol_dust_threshold: threshold,
+            wsol_dust_to_unizen: to_unizen,
+            ..default_config(Pubkey::new_unique())
+        }));
+        let args = SwapSolForTokensArgs {
+            close_user_wsol: true,
+            ..args(swap, ROUTE_OUT)
+        };
+        run_route(swap, args, ROUTE_IN - DUST, ROUTE_OUT)
+    }
+
+    #[test]
+    fn sweeps_dust_to_unizen() {
+        let mut swap = SolSwap::new();
+        let outcome = run_leaving_dust(&mut swap, DUST, true);
+        outcome.assert_ok();
+
+        let swept = outcome.events::<WsolDustSwept>();
+        assert_eq!(swept.len(), 1);
+        assert_eq!(swept[0].amount, DUST);
+        assert!(swept[0].to_unizen);
+        let ledger = &swap.ledger;
+        assert_eq!(
+            ledger.token_amount(&swap.unizen_wsol_ata),
+            UNIZEN_FEE + DUST
+        );
+        assert_eq!(ledger.lamports(&swap.user_wsol_ata), 0);
+    }
+
+    #[test]
+    fn sweeps_dust_to_the_user_closing_the_account() {
+        let mut swap = SolSwap::new();
+        let wsol_rent = swap.ledger.lamports(&swap.user_wsol_ata);
+        let outcome = run_leaving_dust(&mut swap, DUST, false);
+        outcome.assert_ok();
+
+        let swept = outcome.events::<WsolDustSwept>();
+        assert_eq!(swept.len(), 1);
+        assert!(!swept[0].to_unizen);
+        let ledger = &swap.ledger;
+        assert_eq!(ledger.token_amount(&swap.unizen_wsol_ata), UNIZEN_FEE);
+        assert_eq!(ledger.lamports(&swap.user_wsol_ata), 0);
+        assert_eq!(
+            ledger.lamports(&swap.user),
+            USER_LAMPORTS - AMOUNT_IN + DUST + wsol_rent
+        );
+    }
+
+    #[test]
+    fn leaves_dust_above_the_threshold() {
+        let mut swap = SolSwap::new();
+        let outcome = run_leaving_dust(&mut swap, DUST - 1, true);
+        outcome.assert_ok();
+
+        assert!(outcome.events::<WsolDustSwept>().is_empty());
+        let ledger = &swap.ledger;
+        assert_eq!(ledger.token_amount(&swap.unizen_wsol_ata), UNIZEN_FEE);
+        assert_eq!(ledger.token_amount(&swap.user_wsol_ata), DUST);
+    }
+}
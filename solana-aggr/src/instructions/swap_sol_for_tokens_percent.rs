@@ -0,0 +1,139 @@
+use anchor_lang::prelude::*;
+
+use super::swap_sol_for_tokens::{swap_sol_for_tokens, SwapSolForTokens, SwapSolForTokensArgs};
+use crate::{
+    constants::*,
+    errors,
+    helpers::apply_bps,
+    state::{FeeArgs, SignedQuote},
+};
+
+/// Arguments of `swap_sol_for_tokens_percent`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapSolForTokensPercentArgs {
+    pub percent_bps: u64,
+    pub amount_out_min: u64,
+    pub soft_min: Option<u64>,
+    pub fee: FeeArgs,
+    pub close_user_wsol: bool,
+    pub dst_owner: Pubkey,
+    pub dst_mint: Pubkey,
+    pub callback_data: Option<Vec<u8>>,
+    pub signed_quote: Option<SignedQuote>,
+}
+
+/// `swap_sol_for_tokens` for `percent_bps` of the user's lamports above the rent floor and
+/// `SOL_PERCENT_RESERVE`, read at execution time.
+pub fn swap_sol_for_tokens_percent(
+    ctx: Context<SwapSolForTokens>,
+    args: SwapSolForTokensPercentArgs,
+    data: Vec<u8>,
+) -> Result<()> {
+    if args.percent_bps == 0 || args.percent_bps > FEE_DENOM {
+        return err!(errors::ErrorCode::InvalidPercent);
+    }
+
+    let reserve = Rent::get()?
+        .minimum_balance(0)
+        .checked_add(SOL_PERCENT_RESERVE)
+        .ok_or_else(|| error!(errors::ErrorCode::Overflow))?;
+    let spendable = ctx.accounts.user.lamports().saturating_sub(reserve);
+    let amount_in = apply_bps(spendable, args.percent_bps, FEE_DENOM)?;
+    msg!("Swap {} of {} spendable lamports", amount_in, spendable);
+
+    swap_sol_for_tokens(
+        ctx,
+        SwapSolForTokensArgs {
+            amount_in,
+            max_amount_in: amount_in,
+            amount_out_min: args.amount_out_min,
+            soft_min: args.soft_min,
+            fee: args.fee,
+            dry_run: false,
+            close_user_wsol: args.close_user_wsol,
+            min_user_lamports: Some(reserve),
+            dst_owner: args.dst_owner,
+            dst_mint: args.dst_mint,
+            callback_data: args.callback_data,
+            signed_quote: args.signed_quote,
+        },
+        data,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        instructions::swap_sol_for_tokens::tests::accounts,
+        test_harness::{rent, route_data, Outcome, SolSwap, TestAccount, USER_LAMPORTS},
+    };
+
+    const ROUTE_OUT: u64 = 500_000;
+
+    /// Swaps `percent_bps` of the user's spendable SOL, routing all of it after the 1% fee.
+    fn run(swap: &mut SolSwap, percent_bps: u64, expected_in: u64) -> Outcome {
+        let route = swap.route();
+        let args = SwapSolForTokensPercentArgs {
+            percent_bps,
+            amount_out_min: ROUTE_OUT,
+            soft_min: None,
+            fee: FeeArgs {
+                fee_percent: 100,
+                share_percent: 2_000,
+                referrer_percent: 0,
+            },
+            close_user_wsol: false,
+            dst_owner: swap.user,
+            dst_mint: swap.dst_mint,
+            callback_data: None,
+            signed_quote: None,
+        };
+        swap.ledger.run(
+            accounts(swap),
+            &route,
+            crate::instruction::SwapSolForTokensPercent {
+                args,
+                data: route_data(expected_in - expected_in / 100, ROUTE_OUT),
+            },
+        )
+    }
+
+    fn reserve() -> u64 {
+        rent(0) + SOL_PERCENT_RESERVE
+    }
+
+    #[test]
+    fn swaps_a_share_of_the_balance_above_the_reserve() {
+        for percent_bps in [1_000, 5_000] {
+            let mut swap = SolSwap::new();
+            let amount_in = (USER_LAMPORTS - reserve()) * percent_bps / FEE_DENOM;
+            run(&mut swap, percent_bps, amount_in).assert_ok();
+
+            assert_eq!(swap.ledger.lamports(&swap.user), USER_LAMPORTS - amount_in);
+            assert_eq!(swap.ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+        }
+    }
+
+    #[test]
+    fn swaps_all_of_a_near_empty_wallet_down_to_the_reserve() {
+        let mut swap = SolSwap::new();
+        swap.ledger
+            .add(TestAccount::wallet(swap.user, reserve() + 1_000));
+        run(&mut swap, FEE_DENOM, 1_000).assert_ok();
+
+        assert_eq!(swap.ledger.lamports(&swap.user), reserve());
+    }
+
+    #[test]
+    fn rejects_a_zero_percent() {
+        let mut swap = SolSwap::new();
+        run(&mut swap, 0, 100).assert_error(errors::ErrorCode::InvalidPercent);
+    }
+
+    #[test]
+    fn rejects_more_than_the_whole_balance() {
+        let mut swap = SolSwap::new();
+        run(&mut swap, FEE_DENOM + 1, 100).assert_error(errors::ErrorCode::InvalidPercent);
+    }
+}
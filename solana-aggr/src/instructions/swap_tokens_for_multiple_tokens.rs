@@ -0,0 +1,292 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors,
+    helpers::*,
+    state::{FeeArgs, FeeCounter, RouteBuffer},
+};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapOutput {
+    pub dst_index: u8,
+    pub amount_out_min: u64,
+    pub soft_min: Option<u64>,
+}
+
+/// Arguments of `swap_tokens_for_multiple_tokens`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapTokensForMultipleTokensArgs {
+    pub amount_in: u64,
+    pub max_amount_in: u64,
+    pub outputs: Vec<SwapOutput>,
+    pub dst_count: u8,
+    pub fee: FeeArgs,
+    pub dry_run: bool,
+}
+
+pub fn swap_tokens_for_multiple_tokens(
+    ctx: Context<SwapTokensForMultipleTokens>,
+    args: SwapTokensForMultipleTokensArgs,
+    data: Vec<u8>,
+) -> Result<()> {
+    let SwapTokensForMultipleTokensArgs {
+        amount_in,
+        max_amount_in,
+        outputs,
+        dst_count,
+        fee,
+        dry_run,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    assert_amount_in(amount_in)?;
+
+    if outputs.len() != dst_count as usize {
+        return err!(errors::ErrorCode::AccountCountMismatch);
+    }
+    for output in &outputs {
+        assert_slippage_protection(&config, output.amount_out_min)?;
+    }
+    let route_len = ctx
+        .remaining_accounts
+        .len()
+        .checked_sub(dst_count as usize)
+        .ok_or_else(|| error!(errors::ErrorCode::AccountCountMismatch))?;
+    let (route_accounts, dst_accounts) = ctx.remaining_accounts.split_at(route_len);
+
+    let fee_accounts = AccountsForFee {
+        user: ctx.accounts.user.to_account_info(),
+        fee_payer: None,
+        token_program: ctx.accounts.token_program.clone(),
+        mint: ctx.accounts.src_token.clone(),
+        user_token_account: ctx.accounts.user_src_ata.clone(),
+        unizen_token_account: ctx.accounts.unizen_src_ata.clone(),
+        integrator_token_account: ctx.accounts.integrator_src_ata.clone(),
+        referrer_token_account: ctx.accounts.referrer_src_ata.clone(),
+        discount_token_account: ctx.accounts.discount_ata.clone(),
+        config: config.clone(),
+        fee_receipt: ctx.accounts.fee_receipt.clone(),
+        system_program: ctx.accounts.system_program.clone(),
+        sol_fee: None,
+    };
+
+    if dry_run {
+        return validate_dry_run(&fee_accounts, amount_in, fee.mode(), fee.referrer_percent);
+    }
+
+    let nonce = next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
+
+    log_phase_compute(&config, "fee start");
+    take_integrator_fee(
+        fee_accounts,
+        amount_in,
+        fee.mode(),
+        fee.referrer_percent,
+        nonce,
+        None,
+    )?;
+    log_phase_compute(&config, "fee end");
+
+    ctx.accounts.user_src_ata.reload()?;
+    let prev_src_bal = ctx.accounts.user_src_ata.amount;
+    let prev_bals = dst_accounts
+        .iter()
+        .map(read_token_amount)
+        .collect::<Result<Vec<u64>>>()?;
+
+    log_phase_compute(&config, "swap start");
+    swap_on_jupiter(
+        &ctx.accounts.config,
+        route_accounts,
+        ctx.accounts.jupiter_program.clone(),
+        resolve_route_data(&ctx.accounts.route_buffer, data),
+    )?;
+    log_phase_compute(&config, "swap end");
+
+    ctx.accounts.user_src_ata.reload()?;
+    let post_src_bal = ctx.accounts.user_src_ata.amount;
+    assert_amount_spent(prev_src_bal, post_src_bal, max_amount_in)?;
+
+    for output in outputs.iter() {
+        let index = output.dst_index as usize;
+        let dst_account = dst_accounts
+            .get(index)
+            .ok_or_else(|| error!(errors::ErrorCode::AccountCountMismatch))?;
+        let post_bal = read_token_amount(dst_account)?;
+        assert_amount_out(
+            prev_bals[index],
+            post_bal,
+            output.amount_out_min,
+            output.soft_min,
+            events_enabled(&config),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SwapTokensForMultipleTokens<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub src_token: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = src_token,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = src_token,
+        token::token_program = token_program
+    )]
+    pub unizen_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_src_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    pub route_buffer: Option<Account<'info, RouteBuffer>>,
+    pub jupiter_program: Program<'info, Jupiter>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::test_harness::{route_data, Outcome, TestAccount, TokenSwap, POOL_BALANCE};
+
+    const AMOUNT_IN: u64 = 1_000_000;
+    const FEE: FeeArgs = FeeArgs {
+        fee_percent: 100,
+        share_percent: 2_000,
+        referrer_percent: 0,
+    };
+    // 1% of `AMOUNT_IN`.
+    const ROUTE_IN: u64 = AMOUNT_IN - 10_000;
+    const FIRST_OUT: u64 = 300_000;
+    const SECOND_OUT: u64 = 200_000;
+
+    /// Splits `AMOUNT_IN` into the swap's receiver and a receiver of a second token,
+    /// declaring `dst_count` destination accounts.
+    fn run(swap: &mut TokenSwap, dst_count: u8) -> (Outcome, Pubkey) {
+        let second_mint = swap.ledger.add(TestAccount::mint(Pubkey::new_unique(), 6));
+        let pool_second = swap.ledger.add(TestAccount::ata(
+            second_mint,
+            Pubkey::new_unique(),
+            POOL_BALANCE,
+        ));
+        let receiver_second = swap.ledger.add(TestAccount::ata(second_mint, swap.user, 0));
+
+        let mut remaining = swap.route();
+        remaining.extend([
+            AccountMeta::new(pool_second, false),
+            AccountMeta::new(receiver_second, false),
+            AccountMeta::new(swap.receiver_dst_ata, false),
+            AccountMeta::new(receiver_second, false),
+        ]);
+        let outputs = vec![
+            SwapOutput {
+                dst_index: 0,
+                amount_out_min: FIRST_OUT,
+                soft_min: None,
+            },
+            SwapOutput {
+                dst_index: 1,
+                amount_out_min: SECOND_OUT,
+                soft_min: None,
+            },
+        ];
+        let outcome = swap.ledger.run(
+            crate::accounts::SwapTokensForMultipleTokens {
+                user: swap.user,
+                src_token: swap.src_mint,
+                user_src_ata: swap.user_src_ata,
+                unizen_src_ata: swap.unizen_src_ata,
+                integrator_src_ata: swap.integrator_src_ata,
+                referrer_src_ata: None,
+                discount_ata: None,
+                config: crate::pda::find_config().0,
+                fee_counter: None,
+                volume_cap: Pubkey::find_program_address(
+                    &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                    &crate::ID,
+                )
+                .0,
+                route_buffer: None,
+                jupiter_program: Jupiter::id(),
+                fee_receipt: None,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &remaining,
+            crate::instruction::SwapTokensForMultipleTokens {
+                args: SwapTokensForMultipleTokensArgs {
+                    amount_in: AMOUNT_IN,
+                    max_amount_in: AMOUNT_IN,
+                    outputs,
+                    dst_count,
+                    fee: FEE,
+                    dry_run: false,
+                },
+                data: [
+                    route_data(ROUTE_IN, FIRST_OUT),
+                    SECOND_OUT.to_le_bytes().to_vec(),
+                ]
+                .concat(),
+            },
+        );
+        (outcome, receiver_second)
+    }
+
+    #[test]
+    fn splits_into_two_outputs() {
+        let mut swap = TokenSwap::new();
+        let (outcome, receiver_second) = run(&mut swap, 2);
+        outcome.assert_ok();
+
+        assert_eq!(swap.ledger.token_amount(&swap.receiver_dst_ata), FIRST_OUT);
+        assert_eq!(swap.ledger.token_amount(&receiver_second), SECOND_OUT);
+    }
+
+    #[test]
+    fn rejects_dst_count_not_matching_outputs() {
+        let mut swap = TokenSwap::new();
+        let (outcome, _) = run(&mut swap, 3);
+        outcome.assert_error(errors::ErrorCode::AccountCountMismatch);
+    }
+}
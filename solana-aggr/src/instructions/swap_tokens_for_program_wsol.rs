@@ -0,0 +1,276 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors,
+    helpers::*,
+    state::{FeeArgs, FeeCounter, RouteBuffer},
+};
+
+/// Arguments of `swap_tokens_for_program_wsol`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapTokensForProgramWsolArgs {
+    pub amount_in: u64,
+    pub max_amount_in: u64,
+    pub amount_out_min: u64,
+    pub soft_min: Option<u64>,
+    pub fee: FeeArgs,
+    pub dry_run: bool,
+}
+
+/// Swaps into the user's wSOL deposit, where it stays until the user swaps it with
+/// `swap_wsol_deposit_for_tokens` or takes it out with `withdraw_wsol_deposit`.
+pub fn swap_tokens_for_program_wsol(
+    ctx: Context<SwapTokensForProgramWsol>,
+    args: SwapTokensForProgramWsolArgs,
+    data: Vec<u8>,
+) -> Result<()> {
+    let SwapTokensForProgramWsolArgs {
+        amount_in,
+        max_amount_in,
+        amount_out_min,
+        soft_min,
+        fee,
+        dry_run,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    assert_amount_in(amount_in)?;
+    assert_slippage_protection(&config, amount_out_min)?;
+
+    let fee_accounts = AccountsForFee {
+        user: ctx.accounts.user.to_account_info(),
+        fee_payer: None,
+        token_program: ctx.accounts.token_program.clone(),
+        mint: ctx.accounts.src_token.clone(),
+        user_token_account: ctx.accounts.user_src_ata.clone(),
+        unizen_token_account: ctx.accounts.unizen_src_ata.clone(),
+        integrator_token_account: ctx.accounts.integrator_src_ata.clone(),
+        referrer_token_account: ctx.accounts.referrer_src_ata.clone(),
+        discount_token_account: ctx.accounts.discount_ata.clone(),
+        config: config.clone(),
+        fee_receipt: ctx.accounts.fee_receipt.clone(),
+        system_program: ctx.accounts.system_program.clone(),
+        sol_fee: None,
+    };
+
+    if dry_run {
+        return validate_dry_run(&fee_accounts, amount_in, fee.mode(), fee.referrer_percent);
+    }
+
+    let nonce = next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
+
+    log_phase_compute(&config, "fee start");
+    take_integrator_fee(
+        fee_accounts,
+        amount_in,
+        fee.mode(),
+        fee.referrer_percent,
+        nonce,
+        None,
+    )?;
+    log_phase_compute(&config, "fee end");
+
+    let prev_bal = ensure_wsol_deposit(
+        &ctx.accounts.wsol_deposit,
+        &ctx.accounts.sol_mint,
+        &ctx.accounts.token_program,
+        &ctx.accounts.system_program,
+        &ctx.accounts.user,
+        ctx.bumps.wsol_deposit,
+    )?
+    .amount;
+    ctx.accounts.user_src_ata.reload()?;
+    let prev_src_bal = ctx.accounts.user_src_ata.amount;
+
+    log_phase_compute(&config, "swap start");
+    swap_on_jupiter(
+        &ctx.accounts.config,
+        ctx.remaining_accounts,
+        ctx.accounts.jupiter_program.clone(),
+        resolve_route_data(&ctx.accounts.route_buffer, data),
+    )?;
+    log_phase_compute(&config, "swap end");
+
+    ctx.accounts.user_src_ata.reload()?;
+    let post_src_bal = ctx.accounts.user_src_ata.amount;
+    assert_amount_spent(prev_src_bal, post_src_bal, max_amount_in)?;
+
+    let post_bal = read_token_amount(&ctx.accounts.wsol_deposit)?;
+    assert_amount_out(
+        prev_bal,
+        post_bal,
+        amount_out_min,
+        soft_min,
+        events_enabled(&config),
+    )
+}
+
+#[derive(Accounts)]
+pub struct SwapTokensForProgramWsol<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: Created and validated by `ensure_wsol_deposit`.
+    #[account(mut, seeds = [WSOL_DEPOSIT_SEED, user.key().as_ref()], bump)]
+    pub wsol_deposit: UncheckedAccount<'info>,
+    pub src_token: InterfaceAccount<'info, Mint>,
+    #[account(address = NATIVE_MINT)]
+    pub sol_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = src_token,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = src_token,
+        token::token_program = token_program
+    )]
+    pub unizen_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_src_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    pub route_buffer: Option<Account<'info, RouteBuffer>>,
+    pub jupiter_program: Program<'info, Jupiter>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::test_harness::{route, route_data, Outcome, TestAccount, TokenSwap};
+
+    const AMOUNT_IN: u64 = 1_000_000;
+    const FEE: FeeArgs = FeeArgs {
+        fee_percent: 100,
+        share_percent: 2_000,
+        referrer_percent: 0,
+    };
+    // 1% of `AMOUNT_IN`.
+    const ROUTE_IN: u64 = AMOUNT_IN - 10_000;
+    const ROUTE_OUT: u64 = 500_000;
+
+    fn deposit(swap: &TokenSwap) -> Pubkey {
+        Pubkey::find_program_address(&[WSOL_DEPOSIT_SEED, swap.user.as_ref()], &crate::ID).0
+    }
+
+    /// Swaps the `TokenSwap` source for `ROUTE_OUT` wSOL paid into the user's deposit.
+    fn run(swap: &mut TokenSwap) -> Outcome {
+        swap.ledger.add(TestAccount::mint(NATIVE_MINT, 9));
+        let pool_wsol = swap
+            .ledger
+            .add(TestAccount::native_ata(Pubkey::new_unique(), 1_000_000_000));
+        let deposit = deposit(swap);
+        let route = route(
+            swap.user_src_ata,
+            swap.pool_src,
+            pool_wsol,
+            deposit,
+            swap.user,
+        );
+        swap.ledger.run(
+            crate::accounts::SwapTokensForProgramWsol {
+                user: swap.user,
+                wsol_deposit: deposit,
+                src_token: swap.src_mint,
+                sol_mint: NATIVE_MINT,
+                user_src_ata: swap.user_src_ata,
+                unizen_src_ata: swap.unizen_src_ata,
+                integrator_src_ata: swap.integrator_src_ata,
+                referrer_src_ata: None,
+                discount_ata: None,
+                config: crate::pda::find_config().0,
+                fee_counter: None,
+                volume_cap: Pubkey::find_program_address(
+                    &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                    &crate::ID,
+                )
+                .0,
+                route_buffer: None,
+                jupiter_program: Jupiter::id(),
+                fee_receipt: None,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &route,
+            crate::instruction::SwapTokensForProgramWsol {
+                args: SwapTokensForProgramWsolArgs {
+                    amount_in: AMOUNT_IN,
+                    max_amount_in: AMOUNT_IN,
+                    amount_out_min: ROUTE_OUT,
+                    soft_min: None,
+                    fee: FEE,
+                    dry_run: false,
+                },
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn swaps_into_a_new_deposit() {
+        let mut swap = TokenSwap::new();
+        run(&mut swap).assert_ok();
+
+        let deposit = deposit(&swap);
+        let state = swap.ledger.get(&deposit).token_state();
+        assert_eq!(state.owner, deposit);
+        assert_eq!(state.mint, NATIVE_MINT);
+        assert_eq!(state.amount, ROUTE_OUT);
+    }
+
+    #[test]
+    fn adds_to_an_existing_deposit() {
+        let mut swap = TokenSwap::new();
+        let deposit = deposit(&swap);
+        swap.ledger
+            .add(TestAccount::native(deposit, deposit, 1_000));
+        run(&mut swap).assert_ok();
+
+        assert_eq!(swap.ledger.token_amount(&deposit), 1_000 + ROUTE_OUT);
+    }
+
+    #[test]
+    fn rejects_deposit_held_by_another_authority() {
+        let mut swap = TokenSwap::new();
+        let deposit = deposit(&swap);
+        swap.ledger
+            .add(TestAccount::native(deposit, Pubkey::new_unique(), 1_000));
+        run(&mut swap).assert_error(errors::ErrorCode::InvalidWsolAccount);
+    }
+}
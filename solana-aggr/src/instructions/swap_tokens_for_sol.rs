@@ -1,63 +1,186 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{ Mint, Token, TokenAccount};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
-use crate::{constants::*, helpers::* };
+use crate::{
+    constants::*,
+    errors,
+    helpers::*,
+    state::{FeeArgs, FeeCounter, RouteBuffer},
+};
+
+/// Arguments of `swap_tokens_for_sol`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapTokensForSolArgs {
+    pub amount_in: u64,
+    pub max_amount_in: u64,
+    pub amount_out_min: u64,
+    pub soft_min: Option<u64>,
+    pub fee: FeeArgs,
+    pub top_up_receiver: bool,
+    pub dry_run: bool,
+}
 
 pub fn swap_tokens_for_sol(
     ctx: Context<SwapTokensForSol>,
-    amount_in: u64,
-    amount_out_min: u64,
-    fee_percent: u64,
-    share_percent: u64,
+    args: SwapTokensForSolArgs,
     data: Vec<u8>,
 ) -> Result<()> {
-    take_integrator_fee(
-        AccountsForFee {
-            user: ctx.accounts.user.clone(),
-            token_program: ctx.accounts.token_program.clone(),
-            user_token_account: ctx.accounts.user_src_ata.clone(),
-            unizen_token_account: ctx.accounts.unizen_src_ata.clone(),
-            integrator_token_account: ctx.accounts.integrator_src_ata.clone(),
-        },
+    let SwapTokensForSolArgs {
         amount_in,
-        fee_percent,
-        share_percent,
-    )?;
+        max_amount_in,
+        amount_out_min,
+        soft_min,
+        fee,
+        top_up_receiver,
+        dry_run,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    if let Some(unizen) = ctx.accounts.unizen.as_ref() {
+        assert_unizen_fee_recipient(unizen.key, &config)?;
+    }
+    assert_amount_in(amount_in)?;
+    assert_slippage_protection(&config, amount_out_min)?;
+    assert_compute_budget(ctx.remaining_accounts.len(), SWAP_CLEANUP_COMPUTE_UNITS)?;
 
-    let authority_bump = ctx.bumps.program_authority.to_le_bytes();
-    let wsol_bump = ctx.bumps.program_wsol.to_le_bytes();
-    create_program_wsol_idempotent(
-        ctx.accounts.program_authority.clone(),
-        ctx.accounts.program_wsol.clone(),
-        ctx.accounts.sol_mint.clone(),
-        ctx.accounts.token_program.clone(),
-        ctx.accounts.system_program.clone(),
+    let authority_bump = bump_seed(ctx.bumps.program_authority);
+    let wsol_bump = bump_seed(ctx.bumps.program_wsol);
+    ensure_program_wsol(
+        ProgramWsolAccounts {
+            program_authority: ctx.accounts.program_authority.clone(),
+            program_wsol: ctx.accounts.program_wsol.clone(),
+            sol_mint: ctx.accounts.sol_mint.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+            rent_payer: None,
+        },
         &authority_bump,
         &wsol_bump,
     )?;
 
-    let prev_sol_bal = ctx.accounts.receiver.to_account_info().get_lamports();
+    let fee_accounts = AccountsForFee {
+        user: ctx.accounts.user.to_account_info(),
+        fee_payer: None,
+        token_program: ctx.accounts.token_program.clone(),
+        mint: ctx.accounts.src_token.clone(),
+        user_token_account: ctx.accounts.user_src_ata.clone(),
+        unizen_token_account: ctx.accounts.unizen_src_ata.clone(),
+        integrator_token_account: ctx.accounts.integrator_src_ata.clone(),
+        referrer_token_account: ctx.accounts.referrer_src_ata.clone(),
+        discount_token_account: ctx.accounts.discount_ata.clone(),
+        config: config.clone(),
+        fee_receipt: ctx.accounts.fee_receipt.clone(),
+        system_program: ctx.accounts.system_program.clone(),
+        sol_fee: None,
+    };
+
+    if dry_run {
+        return validate_dry_run(&fee_accounts, amount_in, fee.mode(), fee.referrer_percent);
+    }
 
+    let nonce = next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
+
+    log_phase_compute(&config, "fee start");
+    take_integrator_fee(
+        fee_accounts,
+        amount_in,
+        fee.mode(),
+        fee.referrer_percent,
+        nonce,
+        None,
+    )?;
+    log_phase_compute(&config, "fee end");
+
+    ctx.accounts.user_src_ata.reload()?;
+    let prev_src_bal = ctx.accounts.user_src_ata.amount;
+    let prev_user_wsol_bal = ctx
+        .accounts
+        .user_wsol_ata
+        .as_ref()
+        .map_or(0, |user_wsol_ata| user_wsol_ata.amount);
+
+    // Fail before the route rather than strand program_wsol for lack of compute to close it.
+    assert_compute_budget(ctx.remaining_accounts.len(), CLOSE_WSOL_COMPUTE_UNITS)?;
+    log_phase_compute(&config, "swap start");
+    // A failed route reverts the whole transaction, program_wsol included, so there is
+    // nothing to clean up here; an account left over from elsewhere is closed through
+    // the close_program_wsol instruction.
     swap_on_jupiter(
+        &ctx.accounts.config,
         ctx.remaining_accounts,
         ctx.accounts.jupiter_program.clone(),
-        data,
+        resolve_route_data(&ctx.accounts.route_buffer, data),
     )?;
+    log_phase_compute(&config, "swap end");
 
-    close_program_wsol(
-        ctx.accounts.program_authority.clone(),
-        ctx.accounts.program_wsol.clone(),
-        ctx.accounts.receiver.clone(),
-        ctx.accounts.token_program.clone(),
-        ctx.accounts.system_program.clone(),
+    ctx.accounts.user_src_ata.reload()?;
+    let post_src_bal = ctx.accounts.user_src_ata.amount;
+    assert_amount_spent(prev_src_bal, post_src_bal, max_amount_in)?;
+
+    log_phase_compute(&config, "close start");
+    let recorded_rent_payer = config
+        .as_ref()
+        .map_or(Pubkey::default(), |config| config.wsol_rent_payer);
+    let rent_payer =
+        recorded_wsol_rent_payer(recorded_rent_payer, ctx.accounts.wsol_rent_payer.clone())?;
+    let mut out_amount = close_program_wsol(
+        CloseProgramWsolAccounts {
+            program_authority: ctx.accounts.program_authority.clone(),
+            program_wsol: ctx.accounts.program_wsol.clone(),
+            receiver: ctx.accounts.receiver.clone(),
+            unizen: ctx.accounts.unizen.clone(),
+            rent_payer,
+            token_program: ctx.accounts.token_program.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+        },
         &authority_bump,
+        events_enabled(&config),
+        strict_cleanup_enabled(&config),
     )?;
+    // The payer has its rent back, so a later program wSOL is funded afresh.
+    if let Some(mut config) = config.clone() {
+        if config.wsol_rent_payer != Pubkey::default() {
+            config.wsol_rent_payer = Pubkey::default();
+            store_config(&ctx.accounts.config, &config)?;
+        }
+    }
+    log_phase_compute(&config, "close end");
 
-    let post_sol_bal = ctx.accounts.receiver.to_account_info().get_lamports();
-    assert_amount_out(prev_sol_bal, post_sol_bal, amount_out_min)
-}
+    if let Some(user_wsol_ata) = ctx.accounts.user_wsol_ata.clone() {
+        let unwrapped = unwrap_user_wsol(
+            ctx.accounts.token_program.clone(),
+            ctx.accounts.system_program.clone(),
+            ctx.accounts.user.clone(),
+            user_wsol_ata,
+            ctx.accounts.receiver.clone(),
+            prev_user_wsol_bal,
+            events_enabled(&config),
+        )?;
+        out_amount = out_amount
+            .checked_add(unwrapped)
+            .ok_or_else(|| error!(errors::ErrorCode::Overflow))?;
+    }
 
+    if top_up_receiver {
+        top_up_rent_exempt(
+            ctx.accounts.system_program.clone(),
+            ctx.accounts.user.clone(),
+            ctx.accounts.receiver.clone(),
+        )?;
+    } else {
+        assert_rent_exempt(&ctx.accounts.receiver)?;
+    }
 
+    // Measured on the swap proceeds alone; the receiver's lamports also pick up reclaimed
+    // rent and anything else sent to it in the same transaction.
+    assert_amount_out(
+        0,
+        out_amount,
+        amount_out_min,
+        soft_min,
+        events_enabled(&config),
+    )
+}
 
 #[derive(Accounts)]
 pub struct SwapTokensForSol<'info> {
@@ -66,28 +189,368 @@ pub struct SwapTokensForSol<'info> {
     /// CHECK: This may not be initialized yet.
     #[account(mut, seeds = [WSOL_SEED], bump)]
     pub program_wsol: UncheckedAccount<'info>,
+    #[account(mut)]
     pub user: Signer<'info>,
     #[account(mut)]
     pub receiver: SystemAccount<'info>,
-    pub src_token: Account<'info, Mint>,
+    /// Swept the program authority's dust; must be the Unizen fee recipient.
+    #[account(mut)]
+    pub unizen: Option<SystemAccount<'info>>,
+    /// Reimbursed for program wSOL rent; must match `Config.wsol_rent_payer` when one is set.
+    #[account(mut)]
+    pub wsol_rent_payer: Option<SystemAccount<'info>>,
+    pub src_token: InterfaceAccount<'info, Mint>,
     #[account(address = NATIVE_MINT)]
-    pub sol_mint: Account<'info, Mint>,
+    pub sol_mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut,        
         associated_token::mint = src_token,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = sol_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
     )]
-    pub user_src_ata: Account<'info, TokenAccount>,
+    pub user_wsol_ata: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,        
-        associated_token::mint = src_token,
-        associated_token::authority = UNIZEN
+        token::mint = src_token,
+        token::token_program = token_program
     )]
-    pub unizen_src_ata: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub integrator_src_ata: Account<'info, TokenAccount>,
+    pub unizen_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_src_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    pub route_buffer: Option<Account<'info, RouteBuffer>>,
     pub jupiter_program: Program<'info, Jupiter>,
-    pub token_program: Program<'info, Token>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::{
+        state::Config,
+        test_harness::{
+            default_config, rent, route, route_data, set_compute_units, Outcome, TestAccount,
+            TokenSwap,
+        },
+    };
+
+    const AMOUNT_IN: u64 = 1_000_000;
+    const FEE: FeeArgs = FeeArgs {
+        fee_percent: 100,
+        share_percent: 2_000,
+        referrer_percent: 0,
+    };
+    const ROUTE_IN: u64 = AMOUNT_IN - 10_000;
+    /// Proceeds too small to make a fresh receiver rent exempt on their own.
+    const ROUTE_OUT: u64 = 5_000;
+
+    /// Swaps the `TokenSwap` source for `ROUTE_OUT` lamports paid into `receiver`, which
+    /// starts with `receiver_lamports`.
+    fn run(receiver_lamports: u64, top_up_receiver: bool) -> (TokenSwap, Pubkey, Outcome) {
+        run_with(receiver_lamports, top_up_receiver, ROUTE_OUT, false)
+    }
+
+    /// With `receiver_paid_rent` the receiver is the recorded program wSOL rent payer, so
+    /// closing program wSOL refunds it the rent on top of the proceeds.
+    fn run_with(
+        receiver_lamports: u64,
+        top_up_receiver: bool,
+        amount_out_min: u64,
+        receiver_paid_rent: bool,
+    ) -> (TokenSwap, Pubkey, Outcome) {
+        let mut swap = TokenSwap::new();
+        let receiver = swap
+            .ledger
+            .add(TestAccount::wallet(Pubkey::new_unique(), receiver_lamports));
+        let wsol_rent_payer = receiver_paid_rent.then_some(receiver);
+        if let Some(wsol_rent_payer) = wsol_rent_payer {
+            swap.ledger.add(TestAccount::config(&Config {
+                wsol_rent_payer,
+                ..default_config(Pubkey::new_unique())
+            }));
+        }
+        let outcome = run_into(
+            &mut swap,
+            receiver,
+            args(top_up_receiver, amount_out_min),
+            wsol_rent_payer,
+            None,
+        );
+        (swap, receiver, outcome)
+    }
+
+    /// Swaps all of `AMOUNT_IN`.
+    fn args(top_up_receiver: bool, amount_out_min: u64) -> SwapTokensForSolArgs {
+        SwapTokensForSolArgs {
+            amount_in: AMOUNT_IN,
+            max_amount_in: AMOUNT_IN,
+            amount_out_min,
+            soft_min: None,
+            fee: FEE,
+            top_up_receiver,
+            dry_run: false,
+        }
+    }
+
+    /// With `user_wsol_ata` the route pays the proceeds there instead of program wSOL.
+    fn run_into(
+        swap: &mut TokenSwap,
+        receiver: Pubkey,
+        args: SwapTokensForSolArgs,
+        wsol_rent_payer: Option<Pubkey>,
+        user_wsol_ata: Option<Pubkey>,
+    ) -> Outcome {
+        let pool_wsol = swap
+            .ledger
+            .add(TestAccount::native_ata(Pubkey::new_unique(), 1_000_000_000));
+        swap.ledger.add(TestAccount::mint(NATIVE_MINT, 9));
+        // The program authority funds the program wSOL account it opens for the proceeds.
+        let program_authority = swap.ledger.add(TestAccount::wallet(
+            crate::pda::find_program_authority().0,
+            1_000_000_000,
+        ));
+        let program_wsol = crate::pda::find_program_wsol().0;
+        let route = route(
+            swap.user_src_ata,
+            swap.pool_src,
+            pool_wsol,
+            user_wsol_ata.unwrap_or(program_wsol),
+            swap.user,
+        );
+        swap.ledger.run(
+            crate::accounts::SwapTokensForSol {
+                program_authority,
+                program_wsol,
+                user: swap.user,
+                receiver,
+                unizen: None,
+                wsol_rent_payer,
+                src_token: swap.src_mint,
+                sol_mint: NATIVE_MINT,
+                user_src_ata: swap.user_src_ata,
+                user_wsol_ata,
+                unizen_src_ata: swap.unizen_src_ata,
+                integrator_src_ata: swap.integrator_src_ata,
+                referrer_src_ata: None,
+                discount_ata: None,
+                config: crate::pda::find_config().0,
+                fee_counter: None,
+                volume_cap: Pubkey::find_program_address(
+                    &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                    &crate::ID,
+                )
+                .0,
+                route_buffer: None,
+                jupiter_program: Jupiter::id(),
+                fee_receipt: None,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &route,
+            crate::instruction::SwapTokensForSol {
+                args,
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn tops_up_fresh_receiver_to_rent_exempt() {
+        let (swap, receiver, outcome) = run(0, true);
+        outcome.assert_ok();
+
+        assert_eq!(swap.ledger.lamports(&receiver), rent(0));
+    }
+
+    #[test]
+    fn pays_rent_exempt_receiver_without_top_up() {
+        let (swap, receiver, outcome) = run(rent(0), false);
+        outcome.assert_ok();
+
+        assert_eq!(swap.ledger.lamports(&receiver), rent(0) + ROUTE_OUT);
+    }
+
+    #[test]
+    fn rejects_zero_amount_in() {
+        let mut swap = TokenSwap::new();
+        let receiver = swap
+            .ledger
+            .add(TestAccount::wallet(Pubkey::new_unique(), rent(0)));
+        let args = SwapTokensForSolArgs {
+            amount_in: 0,
+            ..args(false, ROUTE_OUT)
+        };
+        run_into(&mut swap, receiver, args, None, None).assert_error(errors::ErrorCode::ZeroAmount);
+    }
+
+    #[test]
+    fn rejects_receiver_left_below_rent_exempt() {
+        let (_, _, outcome) = run(0, false);
+        outcome.assert_error(errors::ErrorCode::ReceiverNotRentExempt);
+    }
+
+    #[test]
+    fn minimum_counts_only_the_swap_proceeds() {
+        let (swap, receiver, outcome) = run_with(rent(0), false, ROUTE_OUT, true);
+        outcome.assert_ok();
+
+        // The rent refund lands in the receiver too, but is not part of the output.
+        assert_eq!(
+            swap.ledger.lamports(&receiver),
+            rent(0) + ROUTE_OUT + rent(token::TokenAccount::LEN)
+        );
+    }
+
+    #[test]
+    fn rent_refund_does_not_make_up_a_shortfall() {
+        let (_, _, outcome) = run_with(rent(0), false, ROUTE_OUT + 1, true);
+        outcome.assert_error(errors::ErrorCode::OutputBelowMinimum);
+    }
+
+    /// What the five-account test route needs up front, cleanup included.
+    const ROUTE_COMPUTE_UNITS: u64 = 5 * ROUTE_ACCOUNT_COMPUTE_UNITS + SWAP_CLEANUP_COMPUTE_UNITS;
+
+    #[test]
+    fn swaps_with_just_enough_compute() {
+        set_compute_units(ROUTE_COMPUTE_UNITS);
+        let (_, _, outcome) = run(rent(0), false);
+        outcome.assert_ok();
+    }
+
+    #[test]
+    fn bails_before_wrapping_under_a_tight_compute_cap() {
+        set_compute_units(ROUTE_COMPUTE_UNITS - 1);
+        let (swap, _, outcome) = run(rent(0), false);
+        outcome.assert_error(errors::ErrorCode::InsufficientComputeBudget);
+
+        assert!(outcome.events::<TakeFee>().is_empty());
+        assert_eq!(swap.ledger.lamports(&crate::pda::find_program_wsol().0), 0);
+    }
+
+    /// With the user as the receiver, the proceeds land in the signer's own account info
+    /// during the close, and are still measured in full after it.
+    #[test]
+    fn measures_proceeds_paid_to_the_signing_user() {
+        let mut swap = TokenSwap::new();
+        let user = swap.user;
+        let before = swap.ledger.lamports(&user);
+        run_into(&mut swap, user, args(false, ROUTE_OUT), None, None).assert_ok();
+
+        assert_eq!(swap.ledger.lamports(&user), before + ROUTE_OUT);
+    }
+
+    #[test]
+    fn rejects_shortfall_paid_to_the_signing_user() {
+        let mut swap = TokenSwap::new();
+        let user = swap.user;
+        run_into(&mut swap, user, args(false, ROUTE_OUT + 1), None, None)
+            .assert_error(errors::ErrorCode::OutputBelowMinimum);
+    }
+
+    /// Routes the proceeds into the user's own wSOL account, which starts with `prior`.
+    fn run_through_user_wsol(prior: u64, emit_events: bool) -> (TokenSwap, Pubkey, Outcome) {
+        let mut swap = TokenSwap::new();
+        swap.ledger.add(TestAccount::config(&Config {
+            emit_events,
+            ..default_config(Pubkey::new_unique())
+        }));
+        let user_wsol_ata = swap.ledger.add(TestAccount::native_ata(swap.user, prior));
+        let receiver = swap
+            .ledger
+            .add(TestAccount::wallet(Pubkey::new_unique(), rent(0)));
+        let outcome = run_into(
+            &mut swap,
+            receiver,
+            args(false, 0),
+            None,
+            Some(user_wsol_ata),
+        );
+        (swap, user_wsol_ata, outcome)
+    }
+
+    #[test]
+    fn unwraps_and_closes_the_user_wsol_account() {
+        let mut swap = TokenSwap::new();
+        let user = swap.user;
+        let user_wsol_ata = swap.ledger.add(TestAccount::native_ata(user, 0));
+        let wsol_rent = swap.ledger.lamports(&user_wsol_ata);
+        let user_lamports = swap.ledger.lamports(&user);
+        let receiver = swap
+            .ledger
+            .add(TestAccount::wallet(Pubkey::new_unique(), rent(0)));
+        run_into(
+            &mut swap,
+            receiver,
+            args(false, ROUTE_OUT),
+            None,
+            Some(user_wsol_ata),
+        )
+        .assert_ok();
+
+        let ledger = &swap.ledger;
+        assert_eq!(ledger.lamports(&user_wsol_ata), 0);
+        assert_eq!(ledger.lamports(&receiver), rent(0) + ROUTE_OUT);
+        // The rent goes back to the user, who opened the account.
+        assert_eq!(ledger.lamports(&user), user_lamports + wsol_rent);
+    }
+
+    #[test]
+    fn keeps_a_user_wsol_account_with_a_prior_balance() {
+        let (swap, user_wsol_ata, outcome) = run_through_user_wsol(1_000, true);
+        outcome.assert_ok();
+
+        let events = outcome.events::<TokenAccountNotClosed>();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].account, user_wsol_ata);
+        assert_eq!(events[0].remaining, 1_000 + ROUTE_OUT);
+        assert_eq!(swap.ledger.token_amount(&user_wsol_ata), 1_000 + ROUTE_OUT);
+    }
+
+    #[test]
+    fn keeps_a_user_wsol_account_quietly_with_events_off() {
+        let (swap, user_wsol_ata, outcome) = run_through_user_wsol(1_000, false);
+        outcome.assert_ok();
+
+        assert!(outcome.events::<TokenAccountNotClosed>().is_empty());
+        assert_eq!(swap.ledger.token_amount(&user_wsol_ata), 1_000 + ROUTE_OUT);
+    }
+}
@@ -1,64 +1,1415 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{ Mint, Token, TokenAccount};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
-use crate::{constants::*, helpers::* };
+use crate::{
+    constants::*,
+    errors,
+    helpers::*,
+    state::{FeeArgs, FeeCounter, PriceFeed, RouteBuffer, SignedQuote},
+};
+
+/// Arguments of `swap_tokens_for_tokens`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapTokensForTokensArgs {
+    pub amount_in: u64,
+    pub max_amount_in: u64,
+    pub amount_out_min: u64,
+    pub soft_min: Option<u64>,
+    pub fee: FeeArgs,
+    pub dry_run: bool,
+    pub quoted_out: Option<u64>,
+    pub dst_owner: Pubkey,
+    pub dst_mint: Pubkey,
+    pub allow_wsol: bool,
+    pub idempotency_key: Option<[u8; 16]>,
+    pub callback_data: Option<Vec<u8>>,
+    pub expected_out_approx: Option<u64>,
+    pub deviation_tolerance_bps: u64,
+    pub signed_quote: Option<SignedQuote>,
+}
 
 pub fn swap_tokens_for_tokens(
     ctx: Context<SwapTokensForTokens>,
-    amount_in: u64,
-    amount_out_min: u64,
-    fee_percent: u64,
-    share_percent: u64,
+    args: SwapTokensForTokensArgs,
     data: Vec<u8>,
 ) -> Result<()> {
-    take_integrator_fee(
-        AccountsForFee {
+    let SwapTokensForTokensArgs {
+        amount_in,
+        max_amount_in,
+        amount_out_min,
+        soft_min,
+        fee,
+        dry_run,
+        quoted_out,
+        dst_owner,
+        dst_mint,
+        allow_wsol,
+        idempotency_key,
+        callback_data,
+        expected_out_approx,
+        deviation_tolerance_bps,
+        signed_quote,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    assert_amount_in(amount_in)?;
+    assert_receiver_ata_with_program(
+        &ctx.accounts.receiver_dst_ata,
+        dst_owner,
+        ctx.accounts.dst_token_program.key,
+    )?;
+    assert_destination_mint(&ctx.accounts.receiver_dst_ata, dst_mint)?;
+    if !allow_wsol
+        && (ctx.accounts.src_token.key() == NATIVE_MINT
+            || ctx.accounts.receiver_dst_ata.mint == NATIVE_MINT)
+    {
+        return err!(errors::ErrorCode::UseSolEntrypoint);
+    }
+    let amount_out_min = resolve_quoted_min_out(
+        &QuoteAccounts {
             user: ctx.accounts.user.clone(),
-            token_program: ctx.accounts.token_program.clone(),
-            user_token_account: ctx.accounts.user_src_ata.clone(),
-            unizen_token_account: ctx.accounts.unizen_src_ata.clone(),
-            integrator_token_account: ctx.accounts.integrator_src_ata.clone(),
+            instructions_sysvar: ctx.accounts.instructions_sysvar.clone(),
+            quote_nonce: ctx.accounts.quote_nonce.clone(),
+            quote_nonce_bump: ctx.bumps.quote_nonce,
+            system_program: ctx.accounts.system_program.clone(),
         },
+        &config,
+        signed_quote.as_ref(),
+        0,
+        &ctx.accounts.receiver_dst_ata.mint,
+        amount_out_min,
+        dry_run,
+    )?;
+    assert_slippage_protection(&config, amount_out_min)?;
+
+    let fee_accounts = AccountsForFee {
+        user: ctx.accounts.user.to_account_info(),
+        fee_payer: None,
+        token_program: ctx.accounts.token_program.clone(),
+        mint: ctx.accounts.src_token.clone(),
+        user_token_account: ctx.accounts.user_src_ata.clone(),
+        unizen_token_account: ctx.accounts.unizen_src_ata.clone(),
+        integrator_token_account: ctx.accounts.integrator_src_ata.clone(),
+        referrer_token_account: ctx.accounts.referrer_src_ata.clone(),
+        discount_token_account: ctx.accounts.discount_ata.clone(),
+        config: config.clone(),
+        fee_receipt: ctx.accounts.fee_receipt.clone(),
+        system_program: ctx.accounts.system_program.clone(),
+        sol_fee: sol_fee_accounts(
+            ctx.accounts.fee_price_feed.clone(),
+            ctx.accounts.unizen.clone(),
+            ctx.accounts.integrator.clone(),
+            ctx.accounts.referrer.clone(),
+            ctx.accounts.system_program.clone(),
+        )?,
+    };
+
+    if let Some(quoted_out) = quoted_out {
+        let fee_split =
+            compute_fee_split(&fee_accounts, amount_in, fee.mode(), fee.referrer_percent)?;
+        assert_fee_within_gain(quoted_out, amount_in, fee_split.total_fee, amount_out_min)?;
+    }
+
+    if dry_run {
+        return validate_dry_run(&fee_accounts, amount_in, fee.mode(), fee.referrer_percent);
+    }
+
+    if let Some(idempotency_key) = idempotency_key {
+        record_idempotency_key(
+            ctx.accounts
+                .idempotency_record
+                .clone()
+                .ok_or_else(|| error!(errors::ErrorCode::InvalidPda))?,
+            ctx.accounts.user.clone(),
+            ctx.accounts.system_program.clone(),
+            idempotency_key,
+        )?;
+    }
+
+    let nonce = next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
+
+    log_phase_compute(&config, "fee start");
+    take_integrator_fee(
+        fee_accounts,
         amount_in,
-        fee_percent,
-        share_percent,
+        fee.mode(),
+        fee.referrer_percent,
+        nonce,
+        None,
     )?;
+    log_phase_compute(&config, "fee end");
 
+    ctx.accounts.user_src_ata.reload()?;
+    let prev_src_bal = ctx.accounts.user_src_ata.amount;
     let prev_bal = ctx.accounts.receiver_dst_ata.amount;
+    let prev_shared_bal = ctx
+        .accounts
+        .shared_dst_ata
+        .as_ref()
+        .map_or(0, |shared_dst_ata| shared_dst_ata.amount);
 
+    log_phase_compute(&config, "swap start");
     swap_on_jupiter(
+        &ctx.accounts.config,
         ctx.remaining_accounts,
         ctx.accounts.jupiter_program.clone(),
-        data,
+        resolve_route_data(&ctx.accounts.route_buffer, data),
     )?;
+    log_phase_compute(&config, "swap end");
+
+    ctx.accounts.user_src_ata.reload()?;
+    let post_src_bal = ctx.accounts.user_src_ata.amount;
+    assert_amount_spent(prev_src_bal, post_src_bal, max_amount_in)?;
 
+    if let Some(shared_dst_ata) = ctx.accounts.shared_dst_ata.clone() {
+        let (program_authority, authority_bump) = match (
+            ctx.accounts.program_authority.clone(),
+            ctx.bumps.program_authority,
+        ) {
+            (Some(program_authority), Some(bump)) => (program_authority, bump_seed(bump)),
+            _ => return err!(errors::ErrorCode::IncorrectOwner),
+        };
+        let shared_dst_mint = ctx
+            .accounts
+            .shared_dst_mint
+            .as_ref()
+            .ok_or_else(|| error!(errors::ErrorCode::MintMismatch))?;
+        forward_shared_output(
+            SharedOutputAccounts {
+                program_authority,
+                shared_dst_ata: shared_dst_ata.to_account_info(),
+                receiver_dst_ata: ctx.accounts.receiver_dst_ata.clone(),
+                dst_mint: shared_dst_mint.clone(),
+                token_program: ctx.accounts.dst_token_program.clone(),
+            },
+            &[AUTHORITY_SEED, &authority_bump],
+            prev_shared_bal,
+            config
+                .as_ref()
+                .map_or(0, |config| config.stranded_output_epsilon),
+        )?;
+    }
+
+    // Fees and the forward above are not undone here: a failed minimum aborts the
+    // transaction, which reverts them along with the swap.
     ctx.accounts.receiver_dst_ata.reload()?;
     let post_bal = ctx.accounts.receiver_dst_ata.amount;
-    assert_amount_out(prev_bal, post_bal, amount_out_min)
-}
+    match (&ctx.accounts.price_feed, &ctx.accounts.dst_mint) {
+        (Some(price_feed), Some(dst_mint)) => assert_amount_out_in_reference(
+            prev_bal,
+            post_bal,
+            amount_out_min,
+            soft_min,
+            price_feed,
+            dst_mint.decimals,
+            events_enabled(&config),
+        )?,
+        (Some(_), None) => return err!(errors::ErrorCode::MintMismatch),
+        (None, _) => assert_amount_out(
+            prev_bal,
+            post_bal,
+            amount_out_min,
+            soft_min,
+            events_enabled(&config),
+        )?,
+    }
+    if let Some(expected_out_approx) = expected_out_approx {
+        check_output_deviation(
+            post_bal.saturating_sub(prev_bal),
+            expected_out_approx,
+            deviation_tolerance_bps,
+            events_enabled(&config),
+        );
+    }
 
+    if let Some(callback_program) = &ctx.accounts.callback_program {
+        invoke_swap_callback(
+            callback_program,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.receiver_dst_ata,
+            post_bal.saturating_sub(prev_bal),
+            callback_data.unwrap_or_default(),
+        )?;
+    }
+
+    Ok(())
+}
 
 #[derive(Accounts)]
 pub struct SwapTokensForTokens<'info> {
+    #[account(mut)]
     pub user: Signer<'info>,
-    pub src_token: Account<'info, Mint>,
+    pub src_token: InterfaceAccount<'info, Mint>,
     #[account(
         mut,        
         associated_token::mint = src_token,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = token_program
     )]
-    pub user_src_ata: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub receiver_dst_ata: Account<'info, TokenAccount>,
+    pub user_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::token_program = dst_token_program)]
+    pub receiver_dst_ata: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,        
-        associated_token::mint = src_token,
-        associated_token::authority = UNIZEN
+        token::mint = src_token,
+        token::token_program = token_program
+    )]
+    pub unizen_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_src_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_src_ata.owner.as_ref()],
+        bump
     )]
-    pub unizen_src_ata: Account<'info, TokenAccount>,
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    pub route_buffer: Option<Account<'info, RouteBuffer>>,
+    #[account(seeds = [AUTHORITY_SEED], bump)]
+    pub program_authority: Option<SystemAccount<'info>>,
+    #[account(
+        mut,
+        constraint = shared_dst_ata.mint == receiver_dst_ata.mint @ errors::ErrorCode::MintMismatch
+    )]
+    pub shared_dst_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(address = receiver_dst_ata.mint @ errors::ErrorCode::MintMismatch)]
+    pub shared_dst_mint: Option<InterfaceAccount<'info, Mint>>,
+    /// CHECK: Pyth price update the user picked for the destination token, validated by
+    /// `load_oracle_price`; when passed, `amount_out_min` is in its price units.
+    pub price_feed: Option<UncheckedAccount<'info>>,
+    /// Read for its decimals when `price_feed` is passed.
+    #[account(address = receiver_dst_ata.mint @ errors::ErrorCode::MintMismatch)]
+    pub dst_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(seeds = [SOL_FEE_PRICE_SEED, src_token.key().as_ref()], bump)]
+    pub fee_price_feed: Option<Account<'info, PriceFeed>>,
     #[account(mut)]
-    pub integrator_src_ata: Account<'info, TokenAccount>,
+    pub unizen: Option<SystemAccount<'info>>,
+    #[account(mut)]
+    pub integrator: Option<SystemAccount<'info>>,
+    #[account(mut)]
+    pub referrer: Option<SystemAccount<'info>>,
+    /// CHECK: Created and validated by `record_idempotency_key`.
+    #[account(mut)]
+    pub idempotency_record: Option<UncheckedAccount<'info>>,
+    /// CHECK: Validated as an executable program by `invoke_swap_callback`.
+    pub callback_program: Option<UncheckedAccount<'info>>,
+    /// CHECK: Address-checked instructions sysvar, read by `verify_signed_quote`.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+    /// CHECK: Created and validated by `resolve_quoted_min_out`.
+    #[account(mut, seeds = [QUOTE_NONCE_SEED, user.key().as_ref()], bump)]
+    pub quote_nonce: Option<UncheckedAccount<'info>>,
     pub jupiter_program: Program<'info, Jupiter>,
-    pub token_program: Program<'info, Token>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub dst_token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use anchor_lang::{
+        solana_program::{instruction::Instruction, sysvar},
+        system_program, InstructionData,
+    };
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::{
+        state::{Config, PriceFeedMessage, PriceUpdateV2, QuoteNonce, VerificationLevel},
+        test_harness::{
+            default_config, instructions_sysvar, quote_instruction, rent, route, route_data,
+            set_clock, set_router_failure, set_router_reentry, set_stack_height, Outcome,
+            TestAccount, TokenSwap, NOW, STUB_CALLBACK, USER_BALANCE,
+        },
+    };
+
+    const AMOUNT_IN: u64 = 1_000_000;
+    const FEE: FeeArgs = FeeArgs {
+        fee_percent: 100,
+        share_percent: 2_000,
+        referrer_percent: 0,
+    };
+    // 1% of `AMOUNT_IN`, a fifth of it to Unizen.
+    const UNIZEN_FEE: u64 = 2_000;
+    pub(crate) const INTEGRATOR_FEE: u64 = 8_000;
+    pub(crate) const ROUTE_IN: u64 = AMOUNT_IN - UNIZEN_FEE - INTEGRATOR_FEE;
+    pub(crate) const ROUTE_OUT: u64 = 500_000;
+
+    pub(crate) fn accounts(swap: &TokenSwap) -> crate::accounts::SwapTokensForTokens {
+        crate::accounts::SwapTokensForTokens {
+            user: swap.user,
+            src_token: swap.src_mint,
+            user_src_ata: swap.user_src_ata,
+            receiver_dst_ata: swap.receiver_dst_ata,
+            unizen_src_ata: swap.unizen_src_ata,
+            integrator_src_ata: swap.integrator_src_ata,
+            referrer_src_ata: None,
+            discount_ata: None,
+            config: crate::pda::find_config().0,
+            fee_counter: None,
+            volume_cap: Pubkey::find_program_address(
+                &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                &crate::ID,
+            )
+            .0,
+            route_buffer: None,
+            program_authority: None,
+            shared_dst_ata: None,
+            shared_dst_mint: None,
+            price_feed: None,
+            dst_mint: None,
+            fee_price_feed: None,
+            unizen: None,
+            integrator: None,
+            referrer: None,
+            idempotency_record: None,
+            callback_program: None,
+            instructions_sysvar: None,
+            quote_nonce: None,
+            jupiter_program: Jupiter::id(),
+            fee_receipt: None,
+            token_program: token::ID,
+            dst_token_program: token::ID,
+            system_program: system_program::ID,
+        }
+    }
+
+    /// Swaps all of `AMOUNT_IN` for a route output of `ROUTE_OUT`.
+    pub(crate) fn args(swap: &TokenSwap, amount_out_min: u64) -> SwapTokensForTokensArgs {
+        SwapTokensForTokensArgs {
+            amount_in: AMOUNT_IN,
+            max_amount_in: AMOUNT_IN,
+            amount_out_min,
+            soft_min: None,
+            fee: FEE,
+            dry_run: false,
+            quoted_out: None,
+            dst_owner: swap.user,
+            dst_mint: swap.dst_mint,
+            allow_wsol: false,
+            idempotency_key: None,
+            callback_data: None,
+            expected_out_approx: None,
+            deviation_tolerance_bps: 0,
+            signed_quote: None,
+        }
+    }
+
+    fn run(swap: &mut TokenSwap, args: SwapTokensForTokensArgs) -> Outcome {
+        run_route(swap, args, ROUTE_IN, ROUTE_OUT)
+    }
+
+    /// Runs a route that spends `route_in` and pays out `route_out`.
+    fn run_route(
+        swap: &mut TokenSwap,
+        args: SwapTokensForTokensArgs,
+        route_in: u64,
+        route_out: u64,
+    ) -> Outcome {
+        let route = swap.route();
+        swap.ledger.run(
+            accounts(swap),
+            &route,
+            crate::instruction::SwapTokensForTokens {
+                args,
+                data: route_data(route_in, route_out),
+            },
+        )
+    }
+
+    #[test]
+    fn swap_pays_fees_and_output() {
+        let mut swap = TokenSwap::new();
+        let args = args(&swap, ROUTE_OUT);
+        run(&mut swap, args).assert_ok();
+
+        let ledger = &swap.ledger;
+        assert_eq!(
+            ledger.token_amount(&swap.user_src_ata),
+            USER_BALANCE - AMOUNT_IN
+        );
+        assert_eq!(ledger.token_amount(&swap.unizen_src_ata), UNIZEN_FEE);
+        assert_eq!(
+            ledger.token_amount(&swap.integrator_src_ata),
+            INTEGRATOR_FEE
+        );
+        assert_eq!(ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+    }
+
+    /// The fees are transferred and the route has run by the time the output check fails,
+    /// and nothing in the instruction hands them back: leaving no net change relies on
+    /// the check failing the instruction, so the runtime discards all of it.
+    #[test]
+    fn failed_minimum_fails_after_fees_and_output_moved() {
+        let mut swap = TokenSwap::new();
+        let args = args(&swap, ROUTE_OUT + 1);
+        let outcome = run(&mut swap, args);
+
+        outcome.assert_error(errors::ErrorCode::OutputBelowMinimum);
+        let left = &outcome.left;
+        assert_eq!(
+            left.token_amount(&swap.user_src_ata),
+            USER_BALANCE - AMOUNT_IN
+        );
+        assert_eq!(left.token_amount(&swap.unizen_src_ata), UNIZEN_FEE);
+        assert_eq!(left.token_amount(&swap.integrator_src_ata), INTEGRATOR_FEE);
+        assert_eq!(left.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+    }
+
+    /// Adds a config with a quote signer and a transaction that carries `quote` signed by
+    /// it right before the swap, and returns the user's quote nonce PDA.
+    fn sign_quote(swap: &mut TokenSwap, quote: &SignedQuote) -> Pubkey {
+        let signer = Pubkey::new_unique();
+        swap.ledger.add(TestAccount::config(&Config {
+            quote_signer: signer,
+            ..default_config(Pubkey::new_unique())
+        }));
+        swap.ledger.add(instructions_sysvar(
+            &[
+                quote_instruction(&signer, &[*quote]),
+                Instruction::new_with_bytes(crate::ID, &[], Vec::new()),
+            ],
+            1,
+        ));
+        Pubkey::find_program_address(&[QUOTE_NONCE_SEED, swap.user.as_ref()], &crate::ID).0
+    }
+
+    fn run_quoted(swap: &mut TokenSwap, quote: SignedQuote, dry_run: bool) -> Outcome {
+        let mut accounts = accounts(swap);
+        accounts.instructions_sysvar = Some(sysvar::instructions::ID);
+        accounts.quote_nonce = Some(
+            Pubkey::find_program_address(&[QUOTE_NONCE_SEED, swap.user.as_ref()], &crate::ID).0,
+        );
+        let route = swap.route();
+        swap.ledger.run(
+            accounts,
+            &route,
+            crate::instruction::SwapTokensForTokens {
+                args: SwapTokensForTokensArgs {
+                    dry_run,
+                    signed_quote: Some(quote),
+                    ..args(swap, 0)
+                },
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn dry_run_leaves_quote_nonce_unused() {
+        let mut swap = TokenSwap::new();
+        let quote = SignedQuote {
+            user: swap.user,
+            nonce: 1,
+            dst_mint: swap.dst_mint,
+            min_out: ROUTE_OUT,
+            expiry: NOW,
+        };
+        let quote_nonce = sign_quote(&mut swap, &quote);
+
+        run_quoted(&mut swap, quote, true).assert_ok();
+        assert_eq!(swap.ledger.lamports(&quote_nonce), 0);
+
+        run_quoted(&mut swap, quote, false).assert_ok();
+        let record: QuoteNonce = swap.ledger.get(&quote_nonce).read();
+        assert_eq!(record.last_nonce, 1);
+    }
+
+    #[test]
+    fn quote_is_rejected_once_consumed() {
+        let mut swap = TokenSwap::new();
+        let quote = SignedQuote {
+            user: swap.user,
+            nonce: 1,
+            dst_mint: swap.dst_mint,
+            min_out: ROUTE_OUT,
+            expiry: NOW,
+        };
+        sign_quote(&mut swap, &quote);
+        run_quoted(&mut swap, quote, false).assert_ok();
+
+        run_quoted(&mut swap, quote, true).assert_error(errors::ErrorCode::InvalidQuote);
+    }
+
+    #[test]
+    fn swaps_a_single_unit() {
+        let mut swap = TokenSwap::new();
+        let args = SwapTokensForTokensArgs {
+            amount_in: 1,
+            max_amount_in: 1,
+            ..args(&swap, 1)
+        };
+        run_route(&mut swap, args, 1, 1).assert_ok();
+
+        assert_eq!(
+            swap.ledger.token_amount(&swap.user_src_ata),
+            USER_BALANCE - 1
+        );
+        assert_eq!(swap.ledger.token_amount(&swap.receiver_dst_ata), 1);
+    }
+
+    #[test]
+    fn rejects_zero_amount_in() {
+        let mut swap = TokenSwap::new();
+        let args = SwapTokensForTokensArgs {
+            amount_in: 0,
+            ..args(&swap, ROUTE_OUT)
+        };
+        run(&mut swap, args).assert_error(errors::ErrorCode::ZeroAmount);
+    }
+
+    #[test]
+    fn route_may_spend_up_to_max_amount_in() {
+        let mut swap = TokenSwap::new();
+        let args = SwapTokensForTokensArgs {
+            max_amount_in: ROUTE_IN,
+            ..args(&swap, ROUTE_OUT)
+        };
+        run(&mut swap, args).assert_ok();
+    }
+
+    #[test]
+    fn rejects_route_spending_more_than_max_amount_in() {
+        let mut swap = TokenSwap::new();
+        let args = SwapTokensForTokensArgs {
+            max_amount_in: ROUTE_IN - 1,
+            ..args(&swap, ROUTE_OUT)
+        };
+        run(&mut swap, args).assert_error(errors::ErrorCode::InputOverspent);
+    }
+
+    #[test]
+    fn reports_slippage_shortfall_on_failure() {
+        let mut swap = TokenSwap::new();
+        let args = args(&swap, ROUTE_OUT + 100);
+        let outcome = run(&mut swap, args);
+        outcome.assert_error(errors::ErrorCode::OutputBelowMinimum);
+
+        let events = outcome.events::<SlippageExceeded>();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].expected, ROUTE_OUT + 100);
+        assert_eq!(events[0].actual, ROUTE_OUT);
+        assert_eq!(events[0].shortfall, 100);
+    }
+
+    #[test]
+    fn no_slippage_event_when_minimum_is_met() {
+        let mut swap = TokenSwap::new();
+        let args = args(&swap, ROUTE_OUT);
+        let outcome = run(&mut swap, args);
+        outcome.assert_ok();
+
+        assert!(outcome.events::<SlippageExceeded>().is_empty());
+    }
+
+    #[test]
+    fn dry_run_moves_nothing() {
+        let mut swap = TokenSwap::new();
+        let before = swap.ledger.clone();
+        let args = SwapTokensForTokensArgs {
+            dry_run: true,
+            ..args(&swap, ROUTE_OUT)
+        };
+        let outcome = run(&mut swap, args);
+        outcome.assert_ok();
+
+        let events = outcome.events::<DryRunValidated>();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].amount_in, AMOUNT_IN);
+        assert_eq!(events[0].total_fee, UNIZEN_FEE + INTEGRATOR_FEE);
+        for key in [
+            swap.user_src_ata,
+            swap.unizen_src_ata,
+            swap.integrator_src_ata,
+            swap.receiver_dst_ata,
+        ] {
+            assert_eq!(swap.ledger.get(&key), before.get(&key));
+        }
+    }
+
+    #[test]
+    fn dry_run_rejects_invalid_fee_split() {
+        let mut swap = TokenSwap::new();
+        let args = SwapTokensForTokensArgs {
+            dry_run: true,
+            fee: FeeArgs {
+                share_percent: FEE_DENOM + 1,
+                ..FEE
+            },
+            ..args(&swap, ROUTE_OUT)
+        };
+        run(&mut swap, args).assert_error(errors::ErrorCode::InvalidFeeShare);
+    }
+
+    #[test]
+    fn swap_reads_route_from_buffer() {
+        let mut swap = TokenSwap::new();
+        let route_buffer = swap.ledger.add(TestAccount::state(
+            Pubkey::find_program_address(&[ROUTE_BUFFER_SEED, swap.user.as_ref()], &crate::ID).0,
+            &RouteBuffer {
+                authority: swap.user,
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+            RouteBuffer::space(16),
+        ));
+        let accounts = crate::accounts::SwapTokensForTokens {
+            route_buffer: Some(route_buffer),
+            ..accounts(&swap)
+        };
+        let route = swap.route();
+        let args = args(&swap, ROUTE_OUT);
+        swap.ledger
+            .run(
+                accounts,
+                &route,
+                crate::instruction::SwapTokensForTokens {
+                    args,
+                    data: Vec::new(),
+                },
+            )
+            .assert_ok();
+
+        assert_eq!(swap.ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+    }
+
+    /// A Pyth price update quoting 2.000000 per destination token at `publish_time`, so
+    /// `ROUTE_OUT` is worth `2 * ROUTE_OUT` price units.
+    fn price_update(verification_level: VerificationLevel, publish_time: i64) -> TestAccount {
+        let mut data = PriceUpdateV2::DISCRIMINATOR.to_vec();
+        PriceUpdateV2 {
+            write_authority: Pubkey::new_unique(),
+            verification_level,
+            price_message: PriceFeedMessage {
+                feed_id: [7; 32],
+                price: 2_000_000,
+                conf: 1_000,
+                exponent: -6,
+                publish_time,
+                prev_publish_time: publish_time - 1,
+                ema_price: 2_000_000,
+                ema_conf: 1_000,
+            },
+            posted_slot: 1,
+        }
+        .serialize(&mut data)
+        .unwrap();
+        TestAccount {
+            lamports: rent(data.len()),
+            data,
+            owner: PYTH_RECEIVER,
+            ..TestAccount::wallet(Pubkey::new_unique(), 0)
+        }
+    }
+
+    fn run_priced(swap: &mut TokenSwap, publish_time: i64, amount_out_min: u64) -> Outcome {
+        run_with_oracle(
+            swap,
+            price_update(VerificationLevel::Full, publish_time),
+            amount_out_min,
+        )
+    }
+
+    fn run_with_oracle(swap: &mut TokenSwap, oracle: TestAccount, amount_out_min: u64) -> Outcome {
+        let price_feed = swap.ledger.add(oracle);
+        let accounts = crate::accounts::SwapTokensForTokens {
+            price_feed: Some(price_feed),
+            dst_mint: Some(swap.dst_mint),
+            ..accounts(swap)
+        };
+        let route = swap.route();
+        let args = args(swap, amount_out_min);
+        swap.ledger.run(
+            accounts,
+            &route,
+            crate::instruction::SwapTokensForTokens {
+                args,
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn minimum_is_checked_in_reference_units() {
+        let mut swap = TokenSwap::new();
+        run_priced(&mut swap, NOW, 2 * ROUTE_OUT).assert_ok();
+
+        let mut swap = TokenSwap::new();
+        run_priced(&mut swap, NOW, 2 * ROUTE_OUT + 1)
+            .assert_error(errors::ErrorCode::OutputBelowMinimum);
+    }
+
+    #[test]
+    fn rejects_reference_price_older_than_max_age() {
+        let mut swap = TokenSwap::new();
+        run_priced(&mut swap, NOW - MAX_PRICE_AGE - 1, ROUTE_OUT)
+            .assert_error(errors::ErrorCode::StalePrice);
+    }
+
+    #[test]
+    fn rejects_a_price_account_not_owned_by_pyth() {
+        let mut swap = TokenSwap::new();
+        let oracle = TestAccount {
+            owner: crate::ID,
+            ..price_update(VerificationLevel::Full, NOW)
+        };
+        run_with_oracle(&mut swap, oracle, ROUTE_OUT)
+            .assert_error(errors::ErrorCode::InvalidOracle);
+    }
+
+    #[test]
+    fn rejects_a_partially_verified_price_update() {
+        let mut swap = TokenSwap::new();
+        let oracle = price_update(VerificationLevel::Partial { num_signatures: 3 }, NOW);
+        run_with_oracle(&mut swap, oracle, ROUTE_OUT)
+            .assert_error(errors::ErrorCode::InvalidOracle);
+    }
+
+    #[test]
+    fn fails_with_the_router_error_when_the_route_fails() {
+        let mut swap = TokenSwap::new();
+        set_router_failure(ProgramError::Custom(6_001));
+        let args = args(&swap, ROUTE_OUT);
+        let outcome = run(&mut swap, args);
+
+        assert_eq!(outcome.result, Err(ProgramError::Custom(6_001)));
+        assert!(outcome.events::<RouteExecuted>().is_empty());
+        assert_eq!(swap.ledger.token_amount(&swap.user_src_ata), USER_BALANCE);
+    }
+
+    /// Runs the swap with its route padded out to `route_len` accounts.
+    fn run_padded(swap: &mut TokenSwap, route_len: usize) -> Outcome {
+        let mut route = swap.route();
+        route.resize_with(route_len, || {
+            AccountMeta::new_readonly(Pubkey::new_unique(), false)
+        });
+        swap.ledger.run(
+            accounts(swap),
+            &route,
+            crate::instruction::SwapTokensForTokens {
+                args: args(swap, ROUTE_OUT),
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn route_may_use_max_route_accounts() {
+        let mut swap = TokenSwap::new();
+        run_padded(&mut swap, MAX_ROUTE_ACCOUNTS).assert_ok();
+    }
+
+    #[test]
+    fn rejects_route_over_max_route_accounts() {
+        let mut swap = TokenSwap::new();
+        run_padded(&mut swap, MAX_ROUTE_ACCOUNTS + 1)
+            .assert_error(errors::ErrorCode::TooManyRouteAccounts);
+    }
+
+    /// Points the swap's output at a new ATA of `receiver` for the destination mint.
+    fn pay_to(swap: &mut TokenSwap, receiver: Pubkey) {
+        swap.receiver_dst_ata = swap
+            .ledger
+            .add(TestAccount::ata(swap.dst_mint, receiver, 0));
+    }
+
+    #[test]
+    fn pays_output_to_the_named_dst_owner() {
+        let mut swap = TokenSwap::new();
+        let receiver = Pubkey::new_unique();
+        pay_to(&mut swap, receiver);
+        let args = SwapTokensForTokensArgs {
+            dst_owner: receiver,
+            ..args(&swap, ROUTE_OUT)
+        };
+        run(&mut swap, args).assert_ok();
+
+        assert_eq!(swap.ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+    }
+
+    #[test]
+    fn rejects_destination_not_owned_by_dst_owner() {
+        let mut swap = TokenSwap::new();
+        pay_to(&mut swap, Pubkey::new_unique());
+        let args = args(&swap, ROUTE_OUT);
+        run(&mut swap, args).assert_error(errors::ErrorCode::ReceiverMismatch);
+    }
+
+    /// Routes the output into `shared_dst_ata`, owned by `shared_owner`, for the program
+    /// to forward to the receiver.
+    fn run_shared(swap: &mut TokenSwap, shared_owner: Pubkey) -> Outcome {
+        let program_authority = swap.ledger.add(TestAccount::wallet(
+            crate::pda::find_program_authority().0,
+            0,
+        ));
+        let shared_dst_ata = swap
+            .ledger
+            .add(TestAccount::ata(swap.dst_mint, shared_owner, 0));
+        let route = route(
+            swap.user_src_ata,
+            swap.pool_src,
+            swap.pool_dst,
+            shared_dst_ata,
+            swap.user,
+        );
+        let args = args(swap, ROUTE_OUT);
+        swap.ledger.run(
+            crate::accounts::SwapTokensForTokens {
+                program_authority: Some(program_authority),
+                shared_dst_ata: Some(shared_dst_ata),
+                shared_dst_mint: Some(swap.dst_mint),
+                ..accounts(swap)
+            },
+            &route,
+            crate::instruction::SwapTokensForTokens {
+                args,
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn forwards_shared_route_output_to_receiver() {
+        let mut swap = TokenSwap::new();
+        run_shared(&mut swap, crate::pda::find_program_authority().0).assert_ok();
+
+        assert_eq!(swap.ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+    }
+
+    #[test]
+    fn rejects_shared_destination_not_held_by_the_program() {
+        let mut swap = TokenSwap::new();
+        run_shared(&mut swap, Pubkey::new_unique()).assert_error(errors::ErrorCode::IncorrectOwner);
+    }
+
+    fn run_with_callback(swap: &mut TokenSwap, callback_program: Pubkey) -> Outcome {
+        let route = swap.route();
+        let args = SwapTokensForTokensArgs {
+            callback_data: Some(vec![7, 7]),
+            ..args(swap, ROUTE_OUT)
+        };
+        swap.ledger.run(
+            crate::accounts::SwapTokensForTokens {
+                callback_program: Some(callback_program),
+                ..accounts(swap)
+            },
+            &route,
+            crate::instruction::SwapTokensForTokens {
+                args,
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn calls_back_with_the_realized_output() {
+        let mut swap = TokenSwap::new();
+        let outcome = run_with_callback(&mut swap, STUB_CALLBACK);
+        outcome.assert_ok();
+
+        assert_eq!(outcome.callbacks.len(), 1);
+        let callback = &outcome.callbacks[0];
+        let keys: Vec<_> = callback.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(keys, [swap.user, swap.receiver_dst_ata]);
+        assert!(callback.accounts.iter().all(|meta| !meta.is_signer));
+        let data = [&[7, 7], swap.dst_mint.as_ref(), &ROUTE_OUT.to_le_bytes()].concat();
+        assert_eq!(callback.data, data);
+    }
+
+    #[test]
+    fn rejects_a_callback_that_is_not_a_program() {
+        let mut swap = TokenSwap::new();
+        let callback = swap
+            .ledger
+            .add(TestAccount::wallet(Pubkey::new_unique(), 1_000_000));
+        let outcome = run_with_callback(&mut swap, callback);
+
+        outcome.assert_error(errors::ErrorCode::InvalidCallbackProgram);
+        assert!(outcome.callbacks.is_empty());
+    }
+
+    /// Moves the source side of `swap` to a Token-2022 mint, keeping the legacy destination.
+    fn use_token_2022_source(swap: &mut TokenSwap) {
+        let ledger = &mut swap.ledger;
+        let src_mint = ledger.add(TestAccount::mint_2022(Pubkey::new_unique(), 6));
+        swap.src_mint = src_mint;
+        swap.user_src_ata = ledger.add(TestAccount::ata_2022(src_mint, swap.user, USER_BALANCE));
+        swap.unizen_src_ata = ledger.add(TestAccount::ata_2022(src_mint, UNIZEN, 0));
+        swap.integrator_src_ata = ledger.add(TestAccount::ata_2022(src_mint, swap.integrator, 0));
+        swap.pool_src = ledger.add(TestAccount::ata_2022(src_mint, Pubkey::new_unique(), 0));
+    }
+
+    fn run_mixed(
+        swap: &mut TokenSwap,
+        token_program: Pubkey,
+        dst_token_program: Pubkey,
+    ) -> Outcome {
+        let route = swap.route();
+        let args = args(swap, ROUTE_OUT);
+        swap.ledger.run(
+            crate::accounts::SwapTokensForTokens {
+                token_program,
+                dst_token_program,
+                ..accounts(swap)
+            },
+            &route,
+            crate::instruction::SwapTokensForTokens {
+                args,
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn swaps_a_token_2022_source_for_a_legacy_destination() {
+        let mut swap = TokenSwap::new();
+        use_token_2022_source(&mut swap);
+        run_mixed(&mut swap, anchor_spl::token_2022::ID, token::ID).assert_ok();
+
+        let ledger = &swap.ledger;
+        assert_eq!(
+            ledger.token_amount(&swap.user_src_ata),
+            USER_BALANCE - AMOUNT_IN
+        );
+        assert_eq!(ledger.token_amount(&swap.unizen_src_ata), UNIZEN_FEE);
+        assert_eq!(ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+    }
+
+    #[test]
+    fn rejects_a_source_ata_under_another_token_program() {
+        let mut swap = TokenSwap::new();
+        use_token_2022_source(&mut swap);
+        let outcome = run_mixed(&mut swap, token::ID, token::ID);
+        assert_eq!(
+            outcome.result,
+            Err(ProgramError::Custom(
+                anchor_lang::error::ErrorCode::ConstraintAssociatedTokenTokenProgram.into()
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_a_receiver_under_another_token_program() {
+        let mut swap = TokenSwap::new();
+        use_token_2022_source(&mut swap);
+        let outcome = run_mixed(
+            &mut swap,
+            anchor_spl::token_2022::ID,
+            anchor_spl::token_2022::ID,
+        );
+        assert_eq!(
+            outcome.result,
+            Err(ProgramError::Custom(
+                anchor_lang::error::ErrorCode::ConstraintTokenTokenProgram.into()
+            ))
+        );
+    }
+
+    /// Routes `ROUTE_OUT` of a 1% transfer-fee mint into the program's shared account, so
+    /// forwarding it strands 5_000 on the way to the receiver, against a config allowing
+    /// `stranded_output_epsilon`. Both sides are Token-2022 as the swap has one token program.
+    fn run_shared_with_transfer_fee(
+        swap: &mut TokenSwap,
+        stranded_output_epsilon: u64,
+        amount_out_min: u64,
+    ) -> Outcome {
+        swap.ledger.add(TestAccount::config(&Config {
+            stranded_output_epsilon,
+            ..default_config(Pubkey::new_unique())
+        }));
+        let program_authority = swap.ledger.add(TestAccount::wallet(
+            crate::pda::find_program_authority().0,
+            0,
+        ));
+        let dst_mint = swap.ledger.add(TestAccount::mint_2022_with_transfer_fee(
+            Pubkey::new_unique(),
+            6,
+            100,
+        ));
+        let pool_dst = swap.ledger.add(TestAccount::ata_2022(
+            dst_mint,
+            Pubkey::new_unique(),
+            ROUTE_OUT,
+        ));
+        let shared_dst_ata = swap
+            .ledger
+            .add(TestAccount::ata_2022(dst_mint, program_authority, 0));
+        swap.receiver_dst_ata = swap
+            .ledger
+            .add(TestAccount::ata_2022(dst_mint, swap.user, 0));
+        swap.dst_mint = dst_mint;
+        let route = route(
+            swap.user_src_ata,
+            swap.pool_src,
+            pool_dst,
+            shared_dst_ata,
+            swap.user,
+        );
+        let args = args(swap, amount_out_min);
+        swap.ledger.run(
+            crate::accounts::SwapTokensForTokens {
+                program_authority: Some(program_authority),
+                shared_dst_ata: Some(shared_dst_ata),
+                shared_dst_mint: Some(dst_mint),
+                dst_token_program: anchor_spl::token_2022::ID,
+                ..accounts(swap)
+            },
+            &route,
+            crate::instruction::SwapTokensForTokens {
+                args,
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn rejects_shared_output_stranded_beyond_the_epsilon() {
+        let mut swap = TokenSwap::new();
+        run_shared_with_transfer_fee(&mut swap, 4_999, 0)
+            .assert_error(errors::ErrorCode::OutputStranded);
+    }
+
+    #[test]
+    fn forwards_shared_output_stranding_up_to_the_epsilon() {
+        let mut swap = TokenSwap::new();
+        run_shared_with_transfer_fee(&mut swap, 5_000, ROUTE_OUT - 5_000).assert_ok();
+
+        assert_eq!(
+            swap.ledger.token_amount(&swap.receiver_dst_ata),
+            ROUTE_OUT - 5_000
+        );
+    }
+
+    /// The epsilon only tolerates the loss; the minimum still holds against what the
+    /// receiver actually got.
+    #[test]
+    fn minimum_applies_to_the_forwarded_output() {
+        let mut swap = TokenSwap::new();
+        run_shared_with_transfer_fee(&mut swap, 5_000, ROUTE_OUT)
+            .assert_error(errors::ErrorCode::OutputBelowMinimum);
+    }
+
+    #[test]
+    fn warns_below_soft_min() {
+        let mut swap = TokenSwap::new();
+        let args = SwapTokensForTokensArgs {
+            soft_min: Some(ROUTE_OUT + 1),
+            ..args(&swap, ROUTE_OUT)
+        };
+        let outcome = run(&mut swap, args);
+        outcome.assert_ok();
+
+        let warnings = outcome.events::<SlippageWarning>();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].expected, ROUTE_OUT + 1);
+        assert_eq!(warnings[0].actual, ROUTE_OUT);
+    }
+
+    #[test]
+    fn emits_nothing_with_events_off() {
+        let mut swap = TokenSwap::new();
+        swap.ledger.add(TestAccount::config(&Config {
+            emit_events: false,
+            ..default_config(Pubkey::new_unique())
+        }));
+        let args = SwapTokensForTokensArgs {
+            soft_min: Some(ROUTE_OUT + 1),
+            ..args(&swap, ROUTE_OUT)
+        };
+        let outcome = run(&mut swap, args);
+        outcome.assert_ok();
+
+        assert!(outcome.events::<SlippageWarning>().is_empty());
+        assert_eq!(outcome.event_count(), 0);
+    }
+
+    #[test]
+    fn swap_releases_the_reentrancy_lock() {
+        let mut swap = TokenSwap::new();
+        let config = swap
+            .ledger
+            .add(TestAccount::config(&default_config(Pubkey::new_unique())));
+        let args = args(&swap, ROUTE_OUT);
+        run(&mut swap, args).assert_ok();
+
+        assert!(!swap.ledger.get(&config).read::<Config>().reentrancy_locked);
+    }
+
+    #[test]
+    fn rejects_a_swap_reentered_from_the_route() {
+        let mut swap = TokenSwap::new();
+        swap.ledger
+            .add(TestAccount::config(&default_config(Pubkey::new_unique())));
+        let mut reentry_accounts = accounts(&swap).to_account_metas(None);
+        reentry_accounts.extend(swap.route());
+        let reentry = Instruction {
+            program_id: crate::ID,
+            accounts: reentry_accounts,
+            data: crate::instruction::SwapTokensForTokens {
+                args: args(&swap, ROUTE_OUT),
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            }
+            .data(),
+        };
+        // The route carries the accounts the nested swap needs after its own.
+        let mut route = swap.route();
+        route.extend(reentry.accounts.iter().cloned());
+        set_router_reentry(reentry);
+        let args = args(&swap, ROUTE_OUT);
+        let outcome = swap.ledger.run(
+            accounts(&swap),
+            &route,
+            crate::instruction::SwapTokensForTokens {
+                args,
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        );
+
+        outcome.assert_error(errors::ErrorCode::Reentrancy);
+        assert_eq!(
+            outcome.reentry,
+            Some(Err(ProgramError::Custom(
+                errors::ErrorCode::Reentrancy.into()
+            )))
+        );
+    }
+
+    #[test]
+    fn rejects_a_receiver_for_another_mint() {
+        let mut swap = TokenSwap::new();
+        let args = SwapTokensForTokensArgs {
+            dst_mint: Pubkey::new_unique(),
+            ..args(&swap, ROUTE_OUT)
+        };
+        run(&mut swap, args).assert_error(errors::ErrorCode::WrongDestinationMint);
+    }
+
+    #[test]
+    fn wsol_output_must_use_the_sol_entrypoint() {
+        let mut swap = TokenSwap::new();
+        swap.receiver_dst_ata = swap.ledger.add(TestAccount::native_ata(swap.user, 0));
+        let args = SwapTokensForTokensArgs {
+            dst_mint: NATIVE_MINT,
+            ..args(&swap, ROUTE_OUT)
+        };
+        run(&mut swap, args).assert_error(errors::ErrorCode::UseSolEntrypoint);
+    }
+
+    /// Swaps under `idempotency_key`, recording it in the user's idempotency PDA.
+    fn run_keyed(swap: &mut TokenSwap, idempotency_key: [u8; 16]) -> Outcome {
+        let record = Pubkey::find_program_address(
+            &[IDEMPOTENCY_SEED, swap.user.as_ref(), &idempotency_key],
+            &crate::ID,
+        )
+        .0;
+        let route = swap.route();
+        swap.ledger.run(
+            crate::accounts::SwapTokensForTokens {
+                idempotency_record: Some(record),
+                ..accounts(swap)
+            },
+            &route,
+            crate::instruction::SwapTokensForTokens {
+                args: SwapTokensForTokensArgs {
+                    idempotency_key: Some(idempotency_key),
+                    ..args(swap, ROUTE_OUT)
+                },
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn rejects_a_repeated_idempotency_key() {
+        let mut swap = TokenSwap::new();
+        run_keyed(&mut swap, [1; 16]).assert_ok();
+
+        run_keyed(&mut swap, [1; 16]).assert_error(errors::ErrorCode::DuplicateRequest);
+        run_keyed(&mut swap, [2; 16]).assert_ok();
+        assert_eq!(
+            swap.ledger.token_amount(&swap.receiver_dst_ata),
+            2 * ROUTE_OUT
+        );
+    }
+
+    #[test]
+    fn accepts_an_idempotency_key_again_once_expired() {
+        let mut swap = TokenSwap::new();
+        run_keyed(&mut swap, [1; 16]).assert_ok();
+
+        set_clock(NOW + IDEMPOTENCY_TTL - 1);
+        run_keyed(&mut swap, [1; 16]).assert_error(errors::ErrorCode::DuplicateRequest);
+        set_clock(NOW + IDEMPOTENCY_TTL);
+        run_keyed(&mut swap, [1; 16]).assert_ok();
+    }
+
+    #[test]
+    fn reports_the_route_size() {
+        let mut swap = TokenSwap::new();
+        let mut route = swap.route();
+        route.push(AccountMeta::new_readonly(Pubkey::new_unique(), false));
+        let data = route_data(ROUTE_IN, ROUTE_OUT);
+        let args = args(&swap, ROUTE_OUT);
+        let outcome = swap.ledger.run(
+            accounts(&swap),
+            &route,
+            crate::instruction::SwapTokensForTokens {
+                args,
+                data: data.clone(),
+            },
+        );
+        outcome.assert_ok();
+
+        let routes = outcome.events::<RouteExecuted>();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].program_id, Jupiter::id());
+        assert_eq!(usize::from(routes[0].account_count), route.len());
+        assert_eq!(usize::from(routes[0].data_len), data.len());
+    }
+
+    /// The compute-unit lines the swap logged with `Config.profiling` set to `profiling`.
+    fn profile(profiling: bool) -> Vec<String> {
+        let mut swap = TokenSwap::new();
+        swap.ledger.add(TestAccount::config(&Config {
+            profiling,
+            ..default_config(Pubkey::new_unique())
+        }));
+        let args = args(&swap, ROUTE_OUT);
+        let outcome = run(&mut swap, args);
+        outcome.assert_ok();
+
+        outcome
+            .logs
+            .into_iter()
+            .filter(|line| {
+                line.starts_with("Profile: ") || line.starts_with("Program consumption: ")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn profiling_logs_compute_units_around_each_phase() {
+        let logs = profile(true);
+        let phases: Vec<_> = logs
+            .iter()
+            .filter_map(|line| line.strip_prefix("Profile: "))
+            .collect();
+        assert_eq!(phases, ["fee start", "fee end", "swap start", "swap end"]);
+        assert!(logs
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .all(|line| line == "Program consumption: 1400000 units remaining"));
+    }
+
+    #[test]
+    fn logs_no_compute_units_without_profiling() {
+        assert!(profile(false).is_empty());
+    }
+
+    /// Swaps with `expected_out_approx` set to `expected`, within 1% of which the output
+    /// doesn't count as deviating.
+    fn run_approx(swap: &mut TokenSwap, expected: u64) -> Outcome {
+        let args = SwapTokensForTokensArgs {
+            expected_out_approx: Some(expected),
+            deviation_tolerance_bps: 100,
+            ..args(swap, ROUTE_OUT)
+        };
+        run(swap, args)
+    }
+
+    #[test]
+    fn flags_output_deviating_from_the_approximate_quote() {
+        let mut swap = TokenSwap::new();
+        let outcome = run_approx(&mut swap, ROUTE_OUT * 2);
+        outcome.assert_ok();
+
+        let events = outcome.events::<OutputDeviated>();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].expected, ROUTE_OUT * 2);
+        assert_eq!(events[0].actual, ROUTE_OUT);
+        assert_eq!(events[0].tolerance_bps, 100);
+    }
+
+    #[test]
+    fn output_within_tolerance_is_not_flagged() {
+        let mut swap = TokenSwap::new();
+        let outcome = run_approx(&mut swap, ROUTE_OUT + ROUTE_OUT / 100);
+        outcome.assert_ok();
+
+        assert!(outcome.events::<OutputDeviated>().is_empty());
+    }
+
+    #[test]
+    fn deviation_is_not_emitted_with_events_off() {
+        let mut swap = TokenSwap::new();
+        swap.ledger.add(TestAccount::config(&Config {
+            emit_events: false,
+            ..default_config(Pubkey::new_unique())
+        }));
+        let outcome = run_approx(&mut swap, ROUTE_OUT * 2);
+        outcome.assert_ok();
+
+        assert!(outcome.events::<OutputDeviated>().is_empty());
+    }
+
+    #[test]
+    fn swaps_from_the_deepest_cpi_that_leaves_room_for_the_route() {
+        let mut swap = TokenSwap::new();
+        set_stack_height((MAX_STACK_HEIGHT - ROUTE_STACK_DEPTH) as u64);
+        let args = args(&swap, ROUTE_OUT);
+        run(&mut swap, args).assert_ok();
+    }
+
+    #[test]
+    fn rejects_a_cpi_too_deep_for_the_route() {
+        let mut swap = TokenSwap::new();
+        set_stack_height((MAX_STACK_HEIGHT - ROUTE_STACK_DEPTH + 1) as u64);
+        let args = args(&swap, ROUTE_OUT);
+        run(&mut swap, args).assert_error(errors::ErrorCode::CpiDepthExceeded);
+    }
+
+    fn require_slippage_protection(swap: &mut TokenSwap) {
+        swap.ledger.add(TestAccount::config(&Config {
+            require_slippage_protection: true,
+            ..default_config(Pubkey::new_unique())
+        }));
+    }
+
+    #[test]
+    fn rejects_no_minimum_when_slippage_protection_is_required() {
+        let mut swap = TokenSwap::new();
+        require_slippage_protection(&mut swap);
+        let args = args(&swap, 0);
+        run(&mut swap, args).assert_error(errors::ErrorCode::SlippageProtectionRequired);
+    }
+
+    #[test]
+    fn accepts_any_minimum_when_slippage_protection_is_required() {
+        let mut swap = TokenSwap::new();
+        require_slippage_protection(&mut swap);
+        let args = args(&swap, 1);
+        run(&mut swap, args).assert_ok();
+    }
+}
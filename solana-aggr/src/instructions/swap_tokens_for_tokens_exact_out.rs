@@ -0,0 +1,341 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors,
+    helpers::*,
+    state::{FeeArgs, FeeCounter, RouteBuffer},
+};
+
+/// Arguments of `swap_tokens_for_tokens_exact_out`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapTokensForTokensExactOutArgs {
+    pub amount_out: u64,
+    pub amount_in_max: u64,
+    pub fee: FeeArgs,
+    pub dst_owner: Pubkey,
+}
+
+/// Exact-out: the user escrows `amount_in_max` in a per-swap escrow held by the user's
+/// escrow authority, the route spends what it needs from there and the rest is refunded.
+/// The fee is then taken on what the route actually spent, and the route spend plus the fee
+/// together may not exceed `amount_in_max`.
+pub fn swap_tokens_for_tokens_exact_out(
+    ctx: Context<SwapTokensForTokensExactOut>,
+    args: SwapTokensForTokensExactOutArgs,
+    data: Vec<u8>,
+) -> Result<()> {
+    let SwapTokensForTokensExactOutArgs {
+        amount_out,
+        amount_in_max,
+        fee,
+        dst_owner,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    assert_amount_in(amount_out)?;
+    assert_amount_in(amount_in_max)?;
+    assert_receiver_ata_with_program(
+        &ctx.accounts.receiver_dst_ata,
+        dst_owner,
+        ctx.accounts.dst_token_program.key,
+    )?;
+
+    let user_key = ctx.accounts.user.key();
+    let authority_bump = bump_seed(ctx.bumps.escrow_authority);
+    let signer_seeds: &[&[&[u8]]] = &[&[ESCROW_AUTHORITY_SEED, user_key.as_ref(), &authority_bump]];
+
+    open_swap_escrow(
+        &ctx.accounts.escrow_authority,
+        &ctx.accounts.src_escrow,
+        &ctx.accounts.src_token,
+        &ctx.accounts.src_token_program,
+        &ctx.accounts.system_program,
+        &ctx.accounts.user,
+        ctx.bumps.src_escrow,
+    )?;
+
+    let src_bal_before = ctx.accounts.user_src_ata.amount;
+    msg!("Escrow maximum input");
+    transfer_interface(
+        &ctx.accounts.src_token_program,
+        &ctx.accounts.src_token,
+        ctx.accounts.user_src_ata.to_account_info(),
+        ctx.accounts.src_escrow.to_account_info(),
+        ctx.accounts.user.to_account_info(),
+        &[],
+        amount_in_max,
+    )?;
+
+    // A transfer-fee mint can deliver less than `amount_in_max` to the escrow.
+    let escrowed = read_token_amount(&ctx.accounts.src_escrow)?;
+    let prev_bal = ctx.accounts.receiver_dst_ata.amount;
+
+    assert_route_escrow_only(
+        ctx.remaining_accounts,
+        ctx.accounts.escrow_authority.key,
+        &[ctx.accounts.src_escrow.key()],
+    )?;
+    log_phase_compute(&config, "swap start");
+    swap_on_jupiter_signed(
+        &ctx.accounts.config,
+        ctx.remaining_accounts,
+        ctx.accounts.jupiter_program.clone(),
+        resolve_route_data(&ctx.accounts.route_buffer, data),
+        ctx.accounts.escrow_authority.key,
+        signer_seeds,
+    )?;
+    log_phase_compute(&config, "swap end");
+
+    let unspent = read_token_amount(&ctx.accounts.src_escrow)?;
+    let spent = escrowed
+        .checked_sub(unspent)
+        .ok_or_else(|| error!(errors::ErrorCode::Underflow))?;
+    if unspent > 0 {
+        msg!("Refund unspent input to user");
+        transfer_interface(
+            &ctx.accounts.src_token_program,
+            &ctx.accounts.src_token,
+            ctx.accounts.src_escrow.to_account_info(),
+            ctx.accounts.user_src_ata.to_account_info(),
+            ctx.accounts.escrow_authority.to_account_info(),
+            signer_seeds,
+            unspent,
+        )?;
+    }
+    close_swap_escrow(
+        &ctx.accounts.escrow_authority,
+        &ctx.accounts.src_escrow,
+        &ctx.accounts.user,
+        &ctx.accounts.src_token_program,
+        signer_seeds,
+    )?;
+
+    let nonce = next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    track_daily_volume(&ctx.accounts.volume_cap, spent)?;
+
+    ctx.accounts.user_src_ata.reload()?;
+    log_phase_compute(&config, "fee start");
+    take_integrator_fee(
+        AccountsForFee {
+            user: ctx.accounts.user.to_account_info(),
+            // The route has already run, so the fee is paid from the user's remaining
+            // balance like a sponsored fee instead of being checked against the route input.
+            fee_payer: Some(FeePayer::signer(&ctx.accounts.user)),
+            token_program: ctx.accounts.src_token_program.clone(),
+            mint: ctx.accounts.src_token.clone(),
+            user_token_account: ctx.accounts.user_src_ata.clone(),
+            unizen_token_account: ctx.accounts.unizen_src_ata.clone(),
+            integrator_token_account: ctx.accounts.integrator_src_ata.clone(),
+            referrer_token_account: ctx.accounts.referrer_src_ata.clone(),
+            discount_token_account: ctx.accounts.discount_ata.clone(),
+            config: config.clone(),
+            fee_receipt: ctx.accounts.fee_receipt.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+            sol_fee: None,
+        },
+        spent,
+        fee.mode(),
+        fee.referrer_percent,
+        nonce,
+        None,
+    )?;
+    log_phase_compute(&config, "fee end");
+
+    ctx.accounts.user_src_ata.reload()?;
+    assert_amount_spent(
+        src_bal_before,
+        ctx.accounts.user_src_ata.amount,
+        amount_in_max,
+    )?;
+
+    ctx.accounts.receiver_dst_ata.reload()?;
+    let post_bal = ctx.accounts.receiver_dst_ata.amount;
+    assert_amount_out(
+        prev_bal,
+        post_bal,
+        amount_out,
+        None,
+        events_enabled(&config),
+    )
+}
+
+#[derive(Accounts)]
+pub struct SwapTokensForTokensExactOut<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mint::token_program = src_token_program)]
+    pub src_token: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = src_token,
+        associated_token::authority = user,
+        associated_token::token_program = src_token_program
+    )]
+    pub user_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds = [ESCROW_AUTHORITY_SEED, user.key().as_ref()], bump)]
+    pub escrow_authority: SystemAccount<'info>,
+    /// CHECK: Created and closed within the swap by `open_swap_escrow`/`close_swap_escrow`.
+    #[account(mut, seeds = [ESCROW_SEED, user.key().as_ref(), src_token.key().as_ref()], bump)]
+    pub src_escrow: UncheckedAccount<'info>,
+    #[account(mut, token::token_program = dst_token_program)]
+    pub receiver_dst_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = src_token,
+        token::token_program = src_token_program
+    )]
+    pub unizen_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_src_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    pub route_buffer: Option<Account<'info, RouteBuffer>>,
+    pub jupiter_program: Program<'info, Jupiter>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub src_token_program: Interface<'info, TokenInterface>,
+    pub dst_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::test_harness::{route, route_data, Outcome, TokenSwap, USER_BALANCE};
+
+    const AMOUNT_IN_MAX: u64 = 1_000_000;
+    const FEE: FeeArgs = FeeArgs {
+        fee_percent: 100,
+        share_percent: 2_000,
+        referrer_percent: 0,
+    };
+    const ROUTE_IN: u64 = 900_000;
+    const ROUTE_OUT: u64 = 500_000;
+    // 1% of `ROUTE_IN`, a fifth of it to Unizen.
+    const UNIZEN_FEE: u64 = 1_800;
+    const INTEGRATOR_FEE: u64 = 7_200;
+
+    fn find_escrow(swap: &TokenSwap) -> Pubkey {
+        Pubkey::find_program_address(
+            &[ESCROW_SEED, swap.user.as_ref(), swap.src_mint.as_ref()],
+            &crate::ID,
+        )
+        .0
+    }
+
+    fn run(swap: &mut TokenSwap, amount_out: u64) -> Outcome {
+        run_spending(swap, ROUTE_IN, amount_out)
+    }
+
+    fn run_spending(swap: &mut TokenSwap, route_in: u64, amount_out: u64) -> Outcome {
+        let escrow_authority =
+            Pubkey::find_program_address(&[ESCROW_AUTHORITY_SEED, swap.user.as_ref()], &crate::ID)
+                .0;
+        let route = route(
+            find_escrow(swap),
+            swap.pool_src,
+            swap.pool_dst,
+            swap.receiver_dst_ata,
+            escrow_authority,
+        );
+        swap.ledger.run(
+            crate::accounts::SwapTokensForTokensExactOut {
+                user: swap.user,
+                src_token: swap.src_mint,
+                user_src_ata: swap.user_src_ata,
+                escrow_authority,
+                src_escrow: find_escrow(swap),
+                receiver_dst_ata: swap.receiver_dst_ata,
+                unizen_src_ata: swap.unizen_src_ata,
+                integrator_src_ata: swap.integrator_src_ata,
+                referrer_src_ata: None,
+                discount_ata: None,
+                config: crate::pda::find_config().0,
+                fee_counter: None,
+                volume_cap: Pubkey::find_program_address(
+                    &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                    &crate::ID,
+                )
+                .0,
+                route_buffer: None,
+                jupiter_program: Jupiter::id(),
+                fee_receipt: None,
+                src_token_program: token::ID,
+                dst_token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &route,
+            crate::instruction::SwapTokensForTokensExactOut {
+                args: SwapTokensForTokensExactOutArgs {
+                    amount_out,
+                    amount_in_max: AMOUNT_IN_MAX,
+                    fee: FEE,
+                    dst_owner: swap.user,
+                },
+                data: route_data(route_in, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn refunds_unspent_input_and_charges_fee_on_spent() {
+        let mut swap = TokenSwap::new();
+        run(&mut swap, ROUTE_OUT).assert_ok();
+
+        let ledger = &swap.ledger;
+        assert_eq!(
+            ledger.token_amount(&swap.user_src_ata),
+            USER_BALANCE - ROUTE_IN - UNIZEN_FEE - INTEGRATOR_FEE
+        );
+        assert_eq!(ledger.token_amount(&swap.unizen_src_ata), UNIZEN_FEE);
+        assert_eq!(
+            ledger.token_amount(&swap.integrator_src_ata),
+            INTEGRATOR_FEE
+        );
+        assert_eq!(ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+        assert_eq!(ledger.lamports(&find_escrow(&swap)), 0);
+    }
+
+    #[test]
+    fn fails_when_route_delivers_less_than_amount_out() {
+        let mut swap = TokenSwap::new();
+        run(&mut swap, ROUTE_OUT + 1).assert_error(errors::ErrorCode::OutputBelowMinimum);
+    }
+
+    #[test]
+    fn fails_when_route_spend_plus_fee_exceeds_amount_in_max() {
+        let mut swap = TokenSwap::new();
+        // The route spends the whole escrow, so the 1% fee on top overspends the cap.
+        run_spending(&mut swap, AMOUNT_IN_MAX, ROUTE_OUT)
+            .assert_error(errors::ErrorCode::InputOverspent);
+    }
+}
@@ -0,0 +1,164 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{constants::*, errors, helpers::*, state::RouteBuffer};
+
+/// Arguments of `swap_tokens_for_tokens_no_fee`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapTokensForTokensNoFeeArgs {
+    pub amount_in: u64,
+    pub max_amount_in: u64,
+    pub amount_out_min: u64,
+    pub soft_min: Option<u64>,
+}
+
+pub fn swap_tokens_for_tokens_no_fee(
+    ctx: Context<SwapTokensForTokensNoFee>,
+    args: SwapTokensForTokensNoFeeArgs,
+    data: Vec<u8>,
+) -> Result<()> {
+    let SwapTokensForTokensNoFeeArgs {
+        amount_in,
+        max_amount_in,
+        amount_out_min,
+        soft_min,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    assert_amount_in(amount_in)?;
+    assert_slippage_protection(&config, amount_out_min)?;
+
+    let Some(state) = config.as_ref() else {
+        return err!(errors::ErrorCode::ConfigMissing);
+    };
+    if !state.privileged.contains(ctx.accounts.user.key) {
+        return err!(errors::ErrorCode::Unauthorized);
+    }
+
+    let prev_src_bal = ctx.accounts.user_src_ata.amount;
+    let prev_bal = ctx.accounts.receiver_dst_ata.amount;
+
+    log_phase_compute(&config, "swap start");
+    swap_on_jupiter(
+        &ctx.accounts.config,
+        ctx.remaining_accounts,
+        ctx.accounts.jupiter_program.clone(),
+        resolve_route_data(&ctx.accounts.route_buffer, data),
+    )?;
+    log_phase_compute(&config, "swap end");
+
+    ctx.accounts.user_src_ata.reload()?;
+    let post_src_bal = ctx.accounts.user_src_ata.amount;
+    assert_amount_spent(prev_src_bal, post_src_bal, max_amount_in)?;
+
+    ctx.accounts.receiver_dst_ata.reload()?;
+    let post_bal = ctx.accounts.receiver_dst_ata.amount;
+    assert_amount_out(
+        prev_bal,
+        post_bal,
+        amount_out_min,
+        soft_min,
+        state.emit_events,
+    )
+}
+
+#[derive(Accounts)]
+pub struct SwapTokensForTokensNoFee<'info> {
+    pub user: Signer<'info>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(mint::token_program = src_token_program)]
+    pub src_token: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = src_token,
+        associated_token::authority = user,
+        associated_token::token_program = src_token_program
+    )]
+    pub user_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::token_program = dst_token_program)]
+    pub receiver_dst_ata: InterfaceAccount<'info, TokenAccount>,
+    pub route_buffer: Option<Account<'info, RouteBuffer>>,
+    pub jupiter_program: Program<'info, Jupiter>,
+    pub src_token_program: Interface<'info, TokenInterface>,
+    pub dst_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::test_harness::{
+        default_config, route_data, Outcome, TestAccount, TokenSwap, USER_BALANCE,
+    };
+
+    const AMOUNT_IN: u64 = 1_000_000;
+    const ROUTE_OUT: u64 = 500_000;
+
+    /// Swaps with a config listing `privileged`.
+    fn run(swap: &mut TokenSwap, privileged: Vec<Pubkey>) -> Outcome {
+        let mut config = default_config(Pubkey::new_unique());
+        config.privileged = privileged;
+        swap.ledger.add(TestAccount::config(&config));
+        run_swap(swap)
+    }
+
+    fn run_swap(swap: &mut TokenSwap) -> Outcome {
+        swap.ledger.run(
+            crate::accounts::SwapTokensForTokensNoFee {
+                user: swap.user,
+                config: crate::pda::find_config().0,
+                src_token: swap.src_mint,
+                user_src_ata: swap.user_src_ata,
+                receiver_dst_ata: swap.receiver_dst_ata,
+                route_buffer: None,
+                jupiter_program: Jupiter::id(),
+                src_token_program: token::ID,
+                dst_token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &swap.route(),
+            crate::instruction::SwapTokensForTokensNoFee {
+                args: SwapTokensForTokensNoFeeArgs {
+                    amount_in: AMOUNT_IN,
+                    max_amount_in: AMOUNT_IN,
+                    amount_out_min: ROUTE_OUT,
+                    soft_min: None,
+                },
+                data: route_data(AMOUNT_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn privileged_user_swaps_without_fee() {
+        let mut swap = TokenSwap::new();
+        let user = swap.user;
+        run(&mut swap, vec![user]).assert_ok();
+
+        assert_eq!(
+            swap.ledger.token_amount(&swap.user_src_ata),
+            USER_BALANCE - AMOUNT_IN
+        );
+        assert_eq!(swap.ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+        assert_eq!(swap.ledger.token_amount(&swap.unizen_src_ata), 0);
+        assert_eq!(swap.ledger.token_amount(&swap.integrator_src_ata), 0);
+    }
+
+    #[test]
+    fn rejects_unlisted_user() {
+        let mut swap = TokenSwap::new();
+        run(&mut swap, vec![Pubkey::new_unique()]).assert_error(errors::ErrorCode::Unauthorized);
+
+        assert_eq!(swap.ledger.token_amount(&swap.user_src_ata), USER_BALANCE);
+    }
+
+    #[test]
+    fn rejects_swap_without_config() {
+        let mut swap = TokenSwap::new();
+        run_swap(&mut swap).assert_error(errors::ErrorCode::ConfigMissing);
+    }
+}
@@ -0,0 +1,245 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors,
+    helpers::*,
+    state::{FeeArgs, FeeCounter},
+};
+
+/// Arguments of `swap_tokens_for_tokens_orca`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapTokensForTokensOrcaArgs {
+    pub amount_in: u64,
+    pub max_amount_in: u64,
+    pub amount_out_min: u64,
+    pub fee: FeeArgs,
+    pub dry_run: bool,
+    pub dst_owner: Pubkey,
+}
+
+pub fn swap_tokens_for_tokens_orca(
+    ctx: Context<SwapTokensForTokensOrca>,
+    args: SwapTokensForTokensOrcaArgs,
+    data: Vec<u8>,
+) -> Result<()> {
+    let SwapTokensForTokensOrcaArgs {
+        amount_in,
+        max_amount_in,
+        amount_out_min,
+        fee,
+        dry_run,
+        dst_owner,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    assert_amount_in(amount_in)?;
+    assert_slippage_protection(&config, amount_out_min)?;
+    assert_receiver_ata_with_program(
+        &ctx.accounts.receiver_dst_ata,
+        dst_owner,
+        ctx.accounts.dst_token_program.key,
+    )?;
+
+    let fee_accounts = AccountsForFee {
+        user: ctx.accounts.user.to_account_info(),
+        fee_payer: None,
+        token_program: ctx.accounts.token_program.clone(),
+        mint: ctx.accounts.src_token.clone(),
+        user_token_account: ctx.accounts.user_src_ata.clone(),
+        unizen_token_account: ctx.accounts.unizen_src_ata.clone(),
+        integrator_token_account: ctx.accounts.integrator_src_ata.clone(),
+        referrer_token_account: ctx.accounts.referrer_src_ata.clone(),
+        discount_token_account: ctx.accounts.discount_ata.clone(),
+        config: config.clone(),
+        fee_receipt: ctx.accounts.fee_receipt.clone(),
+        system_program: ctx.accounts.system_program.clone(),
+        sol_fee: None,
+    };
+
+    if dry_run {
+        return validate_dry_run(&fee_accounts, amount_in, fee.mode(), fee.referrer_percent);
+    }
+
+    let nonce = next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
+
+    log_phase_compute(&config, "fee start");
+    take_integrator_fee(
+        fee_accounts,
+        amount_in,
+        fee.mode(),
+        fee.referrer_percent,
+        nonce,
+        None,
+    )?;
+    log_phase_compute(&config, "fee end");
+
+    ctx.accounts.user_src_ata.reload()?;
+    let prev_src_bal = ctx.accounts.user_src_ata.amount;
+    let prev_bal = ctx.accounts.receiver_dst_ata.amount;
+
+    log_phase_compute(&config, "swap start");
+    swap_on_orca(
+        &ctx.accounts.config,
+        ctx.remaining_accounts,
+        ctx.accounts.whirlpool_program.clone(),
+        data,
+    )?;
+    log_phase_compute(&config, "swap end");
+
+    ctx.accounts.user_src_ata.reload()?;
+    let post_src_bal = ctx.accounts.user_src_ata.amount;
+    assert_amount_spent(prev_src_bal, post_src_bal, max_amount_in)?;
+
+    ctx.accounts.receiver_dst_ata.reload()?;
+    let post_bal = ctx.accounts.receiver_dst_ata.amount;
+    assert_amount_out(
+        prev_bal,
+        post_bal,
+        amount_out_min,
+        None,
+        events_enabled(&config),
+    )
+}
+
+#[derive(Accounts)]
+pub struct SwapTokensForTokensOrca<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub src_token: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = src_token,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::token_program = dst_token_program)]
+    pub receiver_dst_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = src_token,
+        token::token_program = token_program
+    )]
+    pub unizen_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_src_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    pub whirlpool_program: Program<'info, Whirlpool>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub dst_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::{
+        instructions::swap_tokens_for_tokens::tests::{INTEGRATOR_FEE, ROUTE_IN, ROUTE_OUT},
+        test_harness::{route_data, Outcome, TokenSwap, USER_BALANCE},
+    };
+
+    const AMOUNT_IN: u64 = 1_000_000;
+
+    fn run(swap: &mut TokenSwap, max_amount_in: u64) -> Outcome {
+        let route = swap.route();
+        let accounts = crate::accounts::SwapTokensForTokensOrca {
+            user: swap.user,
+            src_token: swap.src_mint,
+            user_src_ata: swap.user_src_ata,
+            receiver_dst_ata: swap.receiver_dst_ata,
+            unizen_src_ata: swap.unizen_src_ata,
+            integrator_src_ata: swap.integrator_src_ata,
+            referrer_src_ata: None,
+            discount_ata: None,
+            config: crate::pda::find_config().0,
+            fee_counter: None,
+            volume_cap: Pubkey::find_program_address(
+                &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                &crate::ID,
+            )
+            .0,
+            whirlpool_program: Whirlpool::id(),
+            fee_receipt: None,
+            token_program: token::ID,
+            dst_token_program: token::ID,
+            system_program: system_program::ID,
+        };
+        let args = SwapTokensForTokensOrcaArgs {
+            amount_in: AMOUNT_IN,
+            max_amount_in,
+            amount_out_min: ROUTE_OUT,
+            fee: FeeArgs {
+                fee_percent: 100,
+                share_percent: 2_000,
+                referrer_percent: 0,
+            },
+            dry_run: false,
+            dst_owner: swap.user,
+        };
+        swap.ledger.run(
+            accounts,
+            &route,
+            crate::instruction::SwapTokensForTokensOrca {
+                args,
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn swap_through_orca_pays_fees_and_output() {
+        let mut swap = TokenSwap::new();
+        run(&mut swap, ROUTE_IN).assert_ok();
+
+        let ledger = &swap.ledger;
+        assert_eq!(
+            ledger.token_amount(&swap.user_src_ata),
+            USER_BALANCE - AMOUNT_IN
+        );
+        assert_eq!(
+            ledger.token_amount(&swap.integrator_src_ata),
+            INTEGRATOR_FEE
+        );
+        assert_eq!(ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+    }
+
+    #[test]
+    fn rejects_a_route_spending_more_than_allowed() {
+        let mut swap = TokenSwap::new();
+        run(&mut swap, ROUTE_IN - 1).assert_error(errors::ErrorCode::InputOverspent);
+        assert_eq!(swap.ledger.token_amount(&swap.user_src_ata), USER_BALANCE);
+    }
+}
@@ -0,0 +1,373 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors,
+    helpers::*,
+    state::{FeeArgs, FeeCounter, RouteBuffer},
+};
+
+/// Arguments of `swap_tokens_for_tokens_private`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapTokensForTokensPrivateArgs {
+    pub amount_in: u64,
+    pub amount_out_min: u64,
+    pub fee: FeeArgs,
+}
+
+/// Same as `swap_tokens_for_tokens`, but the route runs from and into per-swap escrows so
+/// the router only ever sees a PDA; the output is then forwarded to the user and both
+/// escrows are closed. The route is signed by the user's escrow authority, which owns
+/// nothing but the escrows this call opens, not by the shared program authority.
+pub fn swap_tokens_for_tokens_private(
+    ctx: Context<SwapTokensForTokensPrivate>,
+    args: SwapTokensForTokensPrivateArgs,
+    data: Vec<u8>,
+) -> Result<()> {
+    let SwapTokensForTokensPrivateArgs {
+        amount_in,
+        amount_out_min,
+        fee,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    assert_amount_in(amount_in)?;
+    assert_slippage_protection(&config, amount_out_min)?;
+
+    let nonce = next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
+
+    let prev_user_bal = ctx.accounts.user_src_ata.amount;
+    log_phase_compute(&config, "fee start");
+    take_integrator_fee(
+        AccountsForFee {
+            user: ctx.accounts.user.to_account_info(),
+            fee_payer: None,
+            token_program: ctx.accounts.token_program.clone(),
+            mint: ctx.accounts.src_token.clone(),
+            user_token_account: ctx.accounts.user_src_ata.clone(),
+            unizen_token_account: ctx.accounts.unizen_src_ata.clone(),
+            integrator_token_account: ctx.accounts.integrator_src_ata.clone(),
+            referrer_token_account: ctx.accounts.referrer_src_ata.clone(),
+            discount_token_account: ctx.accounts.discount_ata.clone(),
+            config: config.clone(),
+            fee_receipt: ctx.accounts.fee_receipt.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+            sol_fee: None,
+        },
+        amount_in,
+        fee.mode(),
+        fee.referrer_percent,
+        nonce,
+        None,
+    )?;
+    log_phase_compute(&config, "fee end");
+
+    ctx.accounts.user_src_ata.reload()?;
+    let total_fee = prev_user_bal
+        .checked_sub(ctx.accounts.user_src_ata.amount)
+        .ok_or_else(|| error!(errors::ErrorCode::Underflow))?;
+    let route_amount = amount_in
+        .checked_sub(total_fee)
+        .ok_or_else(|| error!(errors::ErrorCode::Underflow))?;
+
+    let user_key = ctx.accounts.user.key();
+    let authority_bump = bump_seed(ctx.bumps.escrow_authority);
+    let authority_seeds: &[&[u8]] = &[ESCROW_AUTHORITY_SEED, user_key.as_ref(), &authority_bump];
+    let signer_seeds: &[&[&[u8]]] = &[authority_seeds];
+
+    open_swap_escrow(
+        &ctx.accounts.escrow_authority,
+        &ctx.accounts.src_escrow,
+        &ctx.accounts.src_token,
+        &ctx.accounts.token_program,
+        &ctx.accounts.system_program,
+        &ctx.accounts.user,
+        ctx.bumps.src_escrow,
+    )?;
+    open_swap_escrow(
+        &ctx.accounts.escrow_authority,
+        &ctx.accounts.dst_escrow,
+        &ctx.accounts.dst_token,
+        &ctx.accounts.token_program,
+        &ctx.accounts.system_program,
+        &ctx.accounts.user,
+        ctx.bumps.dst_escrow,
+    )?;
+
+    msg!("Move route input to swap escrow");
+    transfer_interface(
+        &ctx.accounts.token_program,
+        &ctx.accounts.src_token,
+        ctx.accounts.user_src_ata.to_account_info(),
+        ctx.accounts.src_escrow.to_account_info(),
+        ctx.accounts.user.to_account_info(),
+        &[],
+        route_amount,
+    )?;
+
+    let prev_bal = ctx.accounts.receiver_dst_ata.amount;
+
+    assert_route_escrow_only(
+        ctx.remaining_accounts,
+        ctx.accounts.escrow_authority.key,
+        &[ctx.accounts.src_escrow.key(), ctx.accounts.dst_escrow.key()],
+    )?;
+    log_phase_compute(&config, "swap start");
+    swap_on_jupiter_signed(
+        &ctx.accounts.config,
+        ctx.remaining_accounts,
+        ctx.accounts.jupiter_program.clone(),
+        resolve_route_data(&ctx.accounts.route_buffer, data),
+        ctx.accounts.escrow_authority.key,
+        signer_seeds,
+    )?;
+    log_phase_compute(&config, "swap end");
+
+    let unspent = read_token_amount(&ctx.accounts.src_escrow)?;
+    if unspent > 0 {
+        msg!("Return unspent input to user");
+        transfer_interface(
+            &ctx.accounts.token_program,
+            &ctx.accounts.src_token,
+            ctx.accounts.src_escrow.to_account_info(),
+            ctx.accounts.user_src_ata.to_account_info(),
+            ctx.accounts.escrow_authority.to_account_info(),
+            signer_seeds,
+            unspent,
+        )?;
+    }
+
+    // Strict: all of the output must reach the user.
+    forward_shared_output(
+        SharedOutputAccounts {
+            program_authority: ctx.accounts.escrow_authority.clone(),
+            shared_dst_ata: ctx.accounts.dst_escrow.to_account_info(),
+            receiver_dst_ata: ctx.accounts.receiver_dst_ata.clone(),
+            dst_mint: ctx.accounts.dst_token.clone(),
+            token_program: ctx.accounts.token_program.clone(),
+        },
+        authority_seeds,
+        0,
+        0,
+    )?;
+
+    for escrow in [&ctx.accounts.src_escrow, &ctx.accounts.dst_escrow] {
+        close_swap_escrow(
+            &ctx.accounts.escrow_authority,
+            escrow,
+            &ctx.accounts.user,
+            &ctx.accounts.token_program,
+            signer_seeds,
+        )?;
+    }
+
+    ctx.accounts.receiver_dst_ata.reload()?;
+    let post_bal = ctx.accounts.receiver_dst_ata.amount;
+    assert_amount_out(
+        prev_bal,
+        post_bal,
+        amount_out_min,
+        None,
+        events_enabled(&config),
+    )
+}
+
+#[derive(Accounts)]
+pub struct SwapTokensForTokensPrivate<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub src_token: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = src_token,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = receiver_dst_ata.owner == user.key() @ errors::ErrorCode::ReceiverMismatch
+    )]
+    pub receiver_dst_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        address = receiver_dst_ata.mint @ errors::ErrorCode::MintMismatch,
+        constraint = dst_token.key() != src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub dst_token: InterfaceAccount<'info, Mint>,
+    #[account(seeds = [ESCROW_AUTHORITY_SEED, user.key().as_ref()], bump)]
+    pub escrow_authority: SystemAccount<'info>,
+    /// CHECK: Created and closed within the swap by `open_swap_escrow`/`close_swap_escrow`.
+    #[account(mut, seeds = [ESCROW_SEED, user.key().as_ref(), src_token.key().as_ref()], bump)]
+    pub src_escrow: UncheckedAccount<'info>,
+    /// CHECK: Created and closed within the swap by `open_swap_escrow`/`close_swap_escrow`.
+    #[account(mut, seeds = [ESCROW_SEED, user.key().as_ref(), dst_token.key().as_ref()], bump)]
+    pub dst_escrow: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        token::mint = src_token,
+        token::token_program = token_program
+    )]
+    pub unizen_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_src_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    pub route_buffer: Option<Account<'info, RouteBuffer>>,
+    pub jupiter_program: Program<'info, Jupiter>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::test_harness::{route, route_data, Outcome, TestAccount, TokenSwap, USER_BALANCE};
+
+    const AMOUNT_IN: u64 = 1_000_000;
+    const FEE: FeeArgs = FeeArgs {
+        fee_percent: 100,
+        share_percent: 2_000,
+        referrer_percent: 0,
+    };
+    // 1% of `AMOUNT_IN`, a fifth of it to Unizen.
+    const UNIZEN_FEE: u64 = 2_000;
+    const INTEGRATOR_FEE: u64 = 8_000;
+    const ROUTE_IN: u64 = 900_000;
+    const ROUTE_OUT: u64 = 500_000;
+
+    fn find_escrow(swap: &TokenSwap, mint: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[ESCROW_SEED, swap.user.as_ref(), mint.as_ref()],
+            &crate::ID,
+        )
+        .0
+    }
+
+    fn find_escrow_authority(swap: &TokenSwap) -> Pubkey {
+        Pubkey::find_program_address(&[ESCROW_AUTHORITY_SEED, swap.user.as_ref()], &crate::ID).0
+    }
+
+    /// Swaps `AMOUNT_IN` through a route from the source escrow into the destination
+    /// escrow, with `extra` appended to the route accounts.
+    fn run(swap: &mut TokenSwap, extra: &[AccountMeta]) -> Outcome {
+        let src_escrow = find_escrow(swap, &swap.src_mint);
+        let dst_escrow = find_escrow(swap, &swap.dst_mint);
+        let mut route = route(
+            src_escrow,
+            swap.pool_src,
+            swap.pool_dst,
+            dst_escrow,
+            find_escrow_authority(swap),
+        );
+        route.extend_from_slice(extra);
+        swap.ledger.run(
+            crate::accounts::SwapTokensForTokensPrivate {
+                user: swap.user,
+                src_token: swap.src_mint,
+                user_src_ata: swap.user_src_ata,
+                receiver_dst_ata: swap.receiver_dst_ata,
+                dst_token: swap.dst_mint,
+                escrow_authority: find_escrow_authority(swap),
+                src_escrow,
+                dst_escrow,
+                unizen_src_ata: swap.unizen_src_ata,
+                integrator_src_ata: swap.integrator_src_ata,
+                referrer_src_ata: None,
+                discount_ata: None,
+                config: crate::pda::find_config().0,
+                fee_counter: None,
+                volume_cap: Pubkey::find_program_address(
+                    &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                    &crate::ID,
+                )
+                .0,
+                route_buffer: None,
+                jupiter_program: Jupiter::id(),
+                fee_receipt: None,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &route,
+            crate::instruction::SwapTokensForTokensPrivate {
+                args: SwapTokensForTokensPrivateArgs {
+                    amount_in: AMOUNT_IN,
+                    amount_out_min: ROUTE_OUT,
+                    fee: FEE,
+                },
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn swap_routes_through_escrows_and_closes_them() {
+        let mut swap = TokenSwap::new();
+        run(&mut swap, &[]).assert_ok();
+
+        let ledger = &swap.ledger;
+        assert_eq!(
+            ledger.token_amount(&swap.user_src_ata),
+            USER_BALANCE - UNIZEN_FEE - INTEGRATOR_FEE - ROUTE_IN
+        );
+        assert_eq!(ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+        for mint in [swap.src_mint, swap.dst_mint] {
+            assert_eq!(ledger.lamports(&find_escrow(&swap, &mint)), 0);
+        }
+    }
+
+    #[test]
+    fn route_never_sees_the_user() {
+        let mut swap = TokenSwap::new();
+        let outcome = run(&mut swap, &[]);
+        outcome.assert_ok();
+
+        assert!(outcome.routed.contains(&find_escrow_authority(&swap)));
+        for user_account in [swap.user, swap.user_src_ata, swap.receiver_dst_ata] {
+            assert!(!outcome.routed.contains(&user_account));
+        }
+    }
+
+    #[test]
+    fn route_cannot_reach_other_escrow_authority_accounts() {
+        let mut swap = TokenSwap::new();
+        let stray = swap.ledger.add(TestAccount::token(
+            Pubkey::new_unique(),
+            swap.src_mint,
+            find_escrow_authority(&swap),
+            AMOUNT_IN,
+        ));
+        run(&mut swap, &[AccountMeta::new(stray, false)])
+            .assert_error(errors::ErrorCode::UnexpectedAuthorityAccount);
+    }
+}
@@ -0,0 +1,355 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors,
+    helpers::*,
+    state::{FeeArgs, FeeCounter, SignedQuote},
+};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct WeightedLeg {
+    pub weight_bps: u64,
+    pub amount_out_min: u64,
+    pub dst_index: u8,
+    pub route_start: u8,
+    pub route_len: u8,
+    pub data: Vec<u8>,
+    /// Replaces `amount_out_min` with the quoted minimum; see `resolve_quoted_min_out`.
+    pub signed_quote: Option<SignedQuote>,
+}
+
+/// Arguments of `swap_tokens_for_tokens_weighted`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapTokensForTokensWeightedArgs {
+    pub amount_in: u64,
+    pub legs: Vec<WeightedLeg>,
+    pub fee: FeeArgs,
+    pub dry_run: bool,
+}
+
+pub fn swap_tokens_for_tokens_weighted(
+    ctx: Context<SwapTokensForTokensWeighted>,
+    args: SwapTokensForTokensWeightedArgs,
+) -> Result<()> {
+    let SwapTokensForTokensWeightedArgs {
+        amount_in,
+        mut legs,
+        fee,
+        dry_run,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    assert_amount_in(amount_in)?;
+    if legs.is_empty() || legs.len() > MAX_BATCH_SWAPS {
+        return err!(errors::ErrorCode::ListTooLong);
+    }
+    let quote_accounts = QuoteAccounts {
+        user: ctx.accounts.user.clone(),
+        instructions_sysvar: ctx.accounts.instructions_sysvar.clone(),
+        quote_nonce: ctx.accounts.quote_nonce.clone(),
+        quote_nonce_bump: ctx.bumps.quote_nonce,
+        system_program: ctx.accounts.system_program.clone(),
+    };
+    // Quoted legs take the Ed25519 signatures in order.
+    let mut signature_index = 0;
+    for leg in legs.iter_mut() {
+        let dst_account = ctx
+            .remaining_accounts
+            .get(leg.dst_index as usize)
+            .ok_or_else(|| error!(errors::ErrorCode::AccountCountMismatch))?;
+        let dst_mint = load_token_account(dst_account)?
+            .ok_or_else(|| error!(errors::ErrorCode::IncorrectOwner))?
+            .mint;
+        leg.amount_out_min = resolve_quoted_min_out(
+            &quote_accounts,
+            &config,
+            leg.signed_quote.as_ref(),
+            signature_index,
+            &dst_mint,
+            leg.amount_out_min,
+            false,
+        )?;
+        if leg.signed_quote.is_some() {
+            signature_index += 1;
+        }
+    }
+    let total_weight = legs.iter().try_fold(0u64, |total, leg| {
+        if leg.weight_bps == 0 {
+            return err!(errors::ErrorCode::InvalidWeights);
+        }
+        assert_slippage_protection(&config, leg.amount_out_min)?;
+        total
+            .checked_add(leg.weight_bps)
+            .ok_or_else(|| error!(errors::ErrorCode::Overflow))
+    })?;
+    if total_weight != FEE_DENOM {
+        return err!(errors::ErrorCode::InvalidWeights);
+    }
+
+    let fee_accounts = AccountsForFee {
+        user: ctx.accounts.user.to_account_info(),
+        fee_payer: None,
+        token_program: ctx.accounts.token_program.clone(),
+        mint: ctx.accounts.src_token.clone(),
+        user_token_account: ctx.accounts.user_src_ata.clone(),
+        unizen_token_account: ctx.accounts.unizen_src_ata.clone(),
+        integrator_token_account: ctx.accounts.integrator_src_ata.clone(),
+        referrer_token_account: ctx.accounts.referrer_src_ata.clone(),
+        discount_token_account: ctx.accounts.discount_ata.clone(),
+        config: config.clone(),
+        fee_receipt: ctx.accounts.fee_receipt.clone(),
+        system_program: ctx.accounts.system_program.clone(),
+        sol_fee: None,
+    };
+
+    if dry_run {
+        return validate_dry_run(&fee_accounts, amount_in, fee.mode(), fee.referrer_percent);
+    }
+
+    let nonce = next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
+
+    log_phase_compute(&config, "fee start");
+    take_integrator_fee(
+        fee_accounts,
+        amount_in,
+        fee.mode(),
+        fee.referrer_percent,
+        nonce,
+        None,
+    )?;
+    log_phase_compute(&config, "fee end");
+
+    let emit_events = events_enabled(&config);
+    let last_leg = legs.len() - 1;
+    let mut remaining_in = amount_in;
+    for (index, leg) in legs.into_iter().enumerate() {
+        // The last leg takes the rounding remainder so the legs sum to amount_in.
+        let leg_amount_in = if index == last_leg {
+            remaining_in
+        } else {
+            apply_bps(amount_in, leg.weight_bps, FEE_DENOM)?
+        };
+        assert_amount_in(leg_amount_in)?;
+        remaining_in = remaining_in
+            .checked_sub(leg_amount_in)
+            .ok_or_else(|| error!(errors::ErrorCode::Underflow))?;
+
+        let route_start = leg.route_start as usize;
+        let route_end = route_start
+            .checked_add(leg.route_len as usize)
+            .ok_or_else(|| error!(errors::ErrorCode::AccountCountMismatch))?;
+        let route_accounts = ctx
+            .remaining_accounts
+            .get(route_start..route_end)
+            .ok_or_else(|| error!(errors::ErrorCode::AccountCountMismatch))?;
+        let dst_account = ctx
+            .remaining_accounts
+            .get(leg.dst_index as usize)
+            .ok_or_else(|| error!(errors::ErrorCode::AccountCountMismatch))?;
+
+        ctx.accounts.user_src_ata.reload()?;
+        let prev_src_bal = ctx.accounts.user_src_ata.amount;
+        let prev_bal = read_token_amount(dst_account)?;
+
+        log_phase_compute(&config, "swap start");
+        swap_on_jupiter(
+            &ctx.accounts.config,
+            route_accounts,
+            ctx.accounts.jupiter_program.clone(),
+            leg.data,
+        )?;
+        log_phase_compute(&config, "swap end");
+
+        ctx.accounts.user_src_ata.reload()?;
+        let post_src_bal = ctx.accounts.user_src_ata.amount;
+        assert_amount_spent(prev_src_bal, post_src_bal, leg_amount_in)?;
+
+        let post_bal = read_token_amount(dst_account)?;
+        assert_amount_out(prev_bal, post_bal, leg.amount_out_min, None, emit_events)?;
+
+        if emit_events {
+            emit!(WeightedLegSwapped {
+                leg: index as u8,
+                dst: dst_account.key(),
+                weight_bps: leg.weight_bps,
+                amount_in: leg_amount_in,
+                amount_out: post_bal.saturating_sub(prev_bal),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SwapTokensForTokensWeighted<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub src_token: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = src_token,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = src_token,
+        token::token_program = token_program
+    )]
+    pub unizen_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_src_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    /// CHECK: Address-checked instructions sysvar, read by `verify_signed_quote`.
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+    /// CHECK: Created and validated by `resolve_quoted_min_out`.
+    #[account(mut, seeds = [QUOTE_NONCE_SEED, user.key().as_ref()], bump)]
+    pub quote_nonce: Option<UncheckedAccount<'info>>,
+    pub jupiter_program: Program<'info, Jupiter>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::test_harness::{route, route_data, Outcome, TestAccount, TokenSwap};
+
+    const AMOUNT_IN: u64 = 1_000_001;
+    const FEE: FeeArgs = FeeArgs {
+        fee_percent: 100,
+        share_percent: 2_000,
+        referrer_percent: 0,
+    };
+    const LEG_ROUTE_IN: u64 = 1_000;
+    const LEG_ROUTE_OUT: u64 = 100_000;
+
+    /// Splits `AMOUNT_IN` by `weights` into one leg per weight, each routed into its own
+    /// destination account.
+    fn run(swap: &mut TokenSwap, weights: &[u64]) -> Outcome {
+        let mut remaining = Vec::new();
+        let mut legs = Vec::new();
+        for &weight_bps in weights {
+            let dst = swap.ledger.add(TestAccount::token(
+                Pubkey::new_unique(),
+                swap.dst_mint,
+                swap.user,
+                0,
+            ));
+            let route_start = remaining.len() as u8;
+            remaining.extend(route(
+                swap.user_src_ata,
+                swap.pool_src,
+                swap.pool_dst,
+                dst,
+                swap.user,
+            ));
+            legs.push(WeightedLeg {
+                weight_bps,
+                amount_out_min: LEG_ROUTE_OUT,
+                dst_index: route_start + 3,
+                route_start,
+                route_len: 5,
+                data: route_data(LEG_ROUTE_IN, LEG_ROUTE_OUT),
+                signed_quote: None,
+            });
+        }
+        swap.ledger.run(
+            crate::accounts::SwapTokensForTokensWeighted {
+                user: swap.user,
+                src_token: swap.src_mint,
+                user_src_ata: swap.user_src_ata,
+                unizen_src_ata: swap.unizen_src_ata,
+                integrator_src_ata: swap.integrator_src_ata,
+                referrer_src_ata: None,
+                discount_ata: None,
+                config: crate::pda::find_config().0,
+                fee_counter: None,
+                volume_cap: Pubkey::find_program_address(
+                    &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                    &crate::ID,
+                )
+                .0,
+                instructions_sysvar: None,
+                quote_nonce: None,
+                jupiter_program: Jupiter::id(),
+                fee_receipt: None,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &remaining,
+            crate::instruction::SwapTokensForTokensWeighted {
+                args: SwapTokensForTokensWeightedArgs {
+                    amount_in: AMOUNT_IN,
+                    legs,
+                    fee: FEE,
+                    dry_run: false,
+                },
+            },
+        )
+    }
+
+    #[test]
+    fn last_leg_takes_the_rounding_remainder() {
+        let mut swap = TokenSwap::new();
+        let outcome = run(&mut swap, &[3_333, 6_667]);
+        outcome.assert_ok();
+
+        let legs = outcome.events::<WeightedLegSwapped>();
+        assert_eq!(legs.len(), 2);
+        // 33.33% of `AMOUNT_IN` rounds down, the last leg gets the rest.
+        assert_eq!(legs[0].amount_in, 333_300);
+        assert_eq!(legs[1].amount_in, 666_701);
+        for leg in &legs {
+            assert_eq!(swap.ledger.token_amount(&leg.dst), LEG_ROUTE_OUT);
+        }
+    }
+
+    #[test]
+    fn rejects_weights_not_adding_up_to_the_whole() {
+        let mut swap = TokenSwap::new();
+        run(&mut swap, &[3_333, 6_666]).assert_error(errors::ErrorCode::InvalidWeights);
+    }
+
+    #[test]
+    fn rejects_a_zero_weight_leg() {
+        let mut swap = TokenSwap::new();
+        run(&mut swap, &[10_000, 0]).assert_error(errors::ErrorCode::InvalidWeights);
+    }
+}
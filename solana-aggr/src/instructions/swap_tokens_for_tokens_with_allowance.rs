@@ -0,0 +1,396 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors,
+    helpers::*,
+    state::{FeeArgs, FeeCounter, RouteBuffer, SwapAllowance},
+};
+
+/// Arguments of `swap_tokens_for_tokens_with_allowance`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapTokensForTokensWithAllowanceArgs {
+    pub amount_in: u64,
+    pub amount_out_min: u64,
+    pub fee: FeeArgs,
+}
+
+/// Runs a swap for `user` on the integrator's signature, within the budget the user
+/// approved for this integrator. `amount_in` is pulled into the pair's delegate escrow and
+/// the fee and the route are both paid from there, so the route can only reach what this
+/// swap escrowed.
+pub fn swap_tokens_for_tokens_with_allowance(
+    ctx: Context<SwapTokensForTokensWithAllowance>,
+    args: SwapTokensForTokensWithAllowanceArgs,
+    data: Vec<u8>,
+) -> Result<()> {
+    let SwapTokensForTokensWithAllowanceArgs {
+        amount_in,
+        amount_out_min,
+        fee,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    assert_amount_in(amount_in)?;
+    assert_slippage_protection(&config, amount_out_min)?;
+
+    let allowance = &mut ctx.accounts.allowance;
+    if amount_in > allowance.per_swap_cap {
+        return err!(errors::ErrorCode::SwapCapExceeded);
+    }
+    allowance.remaining_budget = allowance
+        .remaining_budget
+        .checked_sub(amount_in)
+        .ok_or_else(|| error!(errors::ErrorCode::AllowanceExhausted))?;
+
+    let user_key = ctx.accounts.user.key();
+    let integrator_key = ctx.accounts.integrator.key();
+    let delegate_bump = bump_seed(ctx.bumps.allowance_delegate);
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        ALLOWANCE_DELEGATE_SEED,
+        user_key.as_ref(),
+        integrator_key.as_ref(),
+        &delegate_bump,
+    ]];
+
+    msg!("Pull allowance from user");
+    transfer_interface(
+        &ctx.accounts.token_program,
+        &ctx.accounts.src_token,
+        ctx.accounts.user_src_ata.to_account_info(),
+        ctx.accounts.delegate_src_ata.to_account_info(),
+        ctx.accounts.allowance_delegate.to_account_info(),
+        signer_seeds,
+        amount_in,
+    )?;
+
+    let nonce = next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
+
+    ctx.accounts.delegate_src_ata.reload()?;
+    log_phase_compute(&config, "fee start");
+    take_integrator_fee(
+        AccountsForFee {
+            user: ctx.accounts.user.to_account_info(),
+            fee_payer: Some(FeePayer {
+                authority: ctx.accounts.allowance_delegate.to_account_info(),
+                signer_seeds: signer_seeds[0].iter().map(|seed| seed.to_vec()).collect(),
+                rent_payer: ctx.accounts.integrator.to_account_info(),
+            }),
+            token_program: ctx.accounts.token_program.clone(),
+            mint: ctx.accounts.src_token.clone(),
+            user_token_account: ctx.accounts.delegate_src_ata.clone(),
+            unizen_token_account: ctx.accounts.unizen_src_ata.clone(),
+            integrator_token_account: ctx.accounts.integrator_src_ata.clone(),
+            referrer_token_account: ctx.accounts.referrer_src_ata.clone(),
+            discount_token_account: ctx.accounts.discount_ata.clone(),
+            config: config.clone(),
+            fee_receipt: ctx.accounts.fee_receipt.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+            sol_fee: None,
+        },
+        amount_in,
+        fee.mode(),
+        fee.referrer_percent,
+        nonce,
+        None,
+    )?;
+    log_phase_compute(&config, "fee end");
+
+    ctx.accounts.delegate_src_ata.reload()?;
+    let prev_src_bal = ctx.accounts.delegate_src_ata.amount;
+    let prev_bal = ctx.accounts.receiver_dst_ata.amount;
+
+    assert_route_escrow_only(
+        ctx.remaining_accounts,
+        ctx.accounts.allowance_delegate.key,
+        &[ctx.accounts.delegate_src_ata.key()],
+    )?;
+    log_phase_compute(&config, "swap start");
+    swap_on_jupiter_signed(
+        &ctx.accounts.config,
+        ctx.remaining_accounts,
+        ctx.accounts.jupiter_program.clone(),
+        resolve_route_data(&ctx.accounts.route_buffer, data),
+        ctx.accounts.allowance_delegate.key,
+        signer_seeds,
+    )?;
+    log_phase_compute(&config, "swap end");
+
+    ctx.accounts.delegate_src_ata.reload()?;
+    let post_src_bal = ctx.accounts.delegate_src_ata.amount;
+    assert_amount_spent(prev_src_bal, post_src_bal, amount_in)?;
+
+    if post_src_bal > 0 {
+        msg!("Return unspent allowance to user");
+        transfer_interface(
+            &ctx.accounts.token_program,
+            &ctx.accounts.src_token,
+            ctx.accounts.delegate_src_ata.to_account_info(),
+            ctx.accounts.user_src_ata.to_account_info(),
+            ctx.accounts.allowance_delegate.to_account_info(),
+            signer_seeds,
+            post_src_bal,
+        )?;
+    }
+
+    ctx.accounts.receiver_dst_ata.reload()?;
+    let post_bal = ctx.accounts.receiver_dst_ata.amount;
+    assert_amount_out(
+        prev_bal,
+        post_bal,
+        amount_out_min,
+        None,
+        events_enabled(&config),
+    )
+}
+
+#[derive(Accounts)]
+pub struct SwapTokensForTokensWithAllowance<'info> {
+    /// Executes the swap and pays the fee receipt rent.
+    #[account(mut)]
+    pub integrator: Signer<'info>,
+    pub user: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [ALLOWANCE_SEED, user.key().as_ref(), integrator.key().as_ref()],
+        bump,
+        has_one = user @ errors::ErrorCode::Unauthorized,
+        has_one = integrator @ errors::ErrorCode::Unauthorized
+    )]
+    pub allowance: Account<'info, SwapAllowance>,
+    #[account(address = allowance.mint @ errors::ErrorCode::MintMismatch)]
+    pub src_token: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = src_token,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        seeds = [ALLOWANCE_DELEGATE_SEED, user.key().as_ref(), integrator.key().as_ref()],
+        bump
+    )]
+    pub allowance_delegate: SystemAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = src_token,
+        associated_token::authority = allowance_delegate,
+        associated_token::token_program = token_program
+    )]
+    pub delegate_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = receiver_dst_ata.owner == user.key() @ errors::ErrorCode::ReceiverMismatch
+    )]
+    pub receiver_dst_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = src_token,
+        token::token_program = token_program
+    )]
+    pub unizen_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_src_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub route_buffer: Option<Account<'info, RouteBuffer>>,
+    pub jupiter_program: Program<'info, Jupiter>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::{associated_token::get_associated_token_address, token};
+
+    use super::*;
+    use crate::{
+        instructions::approve_allowance::tests::{approve, find_allowance, find_delegate},
+        test_harness::{route, route_data, Outcome, TokenSwap, USER_BALANCE},
+    };
+
+    const BUDGET: u64 = 3_000_000;
+    const AMOUNT_IN: u64 = 1_000_000;
+    const FEE: FeeArgs = FeeArgs {
+        fee_percent: 100,
+        share_percent: 2_000,
+        referrer_percent: 0,
+    };
+    // 1% of `AMOUNT_IN`, a fifth of it to Unizen.
+    const UNIZEN_FEE: u64 = 2_000;
+    const INTEGRATOR_FEE: u64 = 8_000;
+    const ROUTE_IN: u64 = 900_000;
+    const ROUTE_OUT: u64 = 500_000;
+
+    fn delegate_src_ata(swap: &TokenSwap) -> Pubkey {
+        get_associated_token_address(&find_delegate(&swap.user, &swap.integrator), &swap.src_mint)
+    }
+
+    /// Approves `BUDGET` for the fixture's integrator, then swaps `amount_in` through a
+    /// route that spends `ROUTE_IN` from `route_src`.
+    fn run(swap: &mut TokenSwap, amount_in: u64, route_src: Pubkey) -> Outcome {
+        let integrator = swap.integrator;
+        approve(swap, integrator, BUDGET, AMOUNT_IN).assert_ok();
+
+        let allowance_delegate = find_delegate(&swap.user, &swap.integrator);
+        run_approved(swap, amount_in, route_src, allowance_delegate)
+    }
+
+    /// Swaps against the allowance as it stands, signing with `allowance_delegate`.
+    fn run_approved(
+        swap: &mut TokenSwap,
+        amount_in: u64,
+        route_src: Pubkey,
+        allowance_delegate: Pubkey,
+    ) -> Outcome {
+        let route = route(
+            route_src,
+            swap.pool_src,
+            swap.pool_dst,
+            swap.receiver_dst_ata,
+            allowance_delegate,
+        );
+        swap.ledger.run(
+            crate::accounts::SwapTokensForTokensWithAllowance {
+                integrator: swap.integrator,
+                user: swap.user,
+                allowance: find_allowance(&swap.user, &swap.integrator),
+                src_token: swap.src_mint,
+                user_src_ata: swap.user_src_ata,
+                allowance_delegate,
+                delegate_src_ata: delegate_src_ata(swap),
+                receiver_dst_ata: swap.receiver_dst_ata,
+                unizen_src_ata: swap.unizen_src_ata,
+                integrator_src_ata: swap.integrator_src_ata,
+                referrer_src_ata: None,
+                discount_ata: None,
+                config: crate::pda::find_config().0,
+                fee_counter: None,
+                volume_cap: Pubkey::find_program_address(
+                    &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                    &crate::ID,
+                )
+                .0,
+                fee_receipt: None,
+                route_buffer: None,
+                jupiter_program: Jupiter::id(),
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &route,
+            crate::instruction::SwapTokensForTokensWithAllowance {
+                args: SwapTokensForTokensWithAllowanceArgs {
+                    amount_in,
+                    amount_out_min: ROUTE_OUT,
+                    fee: FEE,
+                },
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn swap_charges_fee_and_spends_budget() {
+        let mut swap = TokenSwap::new();
+        let escrow = delegate_src_ata(&swap);
+        run(&mut swap, AMOUNT_IN, escrow).assert_ok();
+
+        let ledger = &swap.ledger;
+        let unspent = AMOUNT_IN - UNIZEN_FEE - INTEGRATOR_FEE - ROUTE_IN;
+        assert_eq!(
+            ledger.token_amount(&swap.user_src_ata),
+            USER_BALANCE - AMOUNT_IN + unspent
+        );
+        assert_eq!(ledger.token_amount(&escrow), 0);
+        assert_eq!(ledger.token_amount(&swap.unizen_src_ata), UNIZEN_FEE);
+        assert_eq!(
+            ledger.token_amount(&swap.integrator_src_ata),
+            INTEGRATOR_FEE
+        );
+        assert_eq!(ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+        let allowance: SwapAllowance = ledger
+            .get(&find_allowance(&swap.user, &swap.integrator))
+            .read();
+        assert_eq!(allowance.remaining_budget, BUDGET - AMOUNT_IN);
+    }
+
+    #[test]
+    fn route_cannot_spend_from_delegated_user_account() {
+        let mut swap = TokenSwap::new();
+        let user_src_ata = swap.user_src_ata;
+        run(&mut swap, AMOUNT_IN, user_src_ata)
+            .assert_error(errors::ErrorCode::UnexpectedAuthorityAccount);
+    }
+
+    #[test]
+    fn swap_over_cap_is_rejected() {
+        let mut swap = TokenSwap::new();
+        let escrow = delegate_src_ata(&swap);
+        run(&mut swap, AMOUNT_IN + 1, escrow).assert_error(errors::ErrorCode::SwapCapExceeded);
+    }
+
+    #[test]
+    fn swap_beyond_the_remaining_budget_is_rejected() {
+        let mut swap = TokenSwap::new();
+        let integrator = swap.integrator;
+        approve(&mut swap, integrator, AMOUNT_IN + AMOUNT_IN / 2, AMOUNT_IN).assert_ok();
+        let escrow = delegate_src_ata(&swap);
+        let delegate = find_delegate(&swap.user, &swap.integrator);
+        run_approved(&mut swap, AMOUNT_IN, escrow, delegate).assert_ok();
+
+        run_approved(&mut swap, AMOUNT_IN, escrow, delegate)
+            .assert_error(errors::ErrorCode::AllowanceExhausted);
+        let allowance: SwapAllowance = swap
+            .ledger
+            .get(&find_allowance(&swap.user, &swap.integrator))
+            .read();
+        assert_eq!(allowance.remaining_budget, AMOUNT_IN / 2);
+    }
+
+    #[test]
+    fn rejects_the_delegate_of_another_pair() {
+        let mut swap = TokenSwap::new();
+        let integrator = swap.integrator;
+        approve(&mut swap, integrator, BUDGET, AMOUNT_IN).assert_ok();
+        let escrow = delegate_src_ata(&swap);
+        let other_delegate = find_delegate(&swap.user, &Pubkey::new_unique());
+
+        let outcome = run_approved(&mut swap, AMOUNT_IN, escrow, other_delegate);
+        assert_eq!(
+            outcome.result,
+            Err(ProgramError::Custom(
+                anchor_lang::error::ErrorCode::ConstraintSeeds.into()
+            ))
+        );
+    }
+}
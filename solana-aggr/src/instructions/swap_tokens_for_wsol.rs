@@ -0,0 +1,254 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors,
+    helpers::*,
+    state::{FeeArgs, FeeCounter, RouteBuffer},
+};
+
+/// Arguments of `swap_tokens_for_wsol`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapTokensForWsolArgs {
+    pub amount_in: u64,
+    pub max_amount_in: u64,
+    pub amount_out_min: u64,
+    pub soft_min: Option<u64>,
+    pub fee: FeeArgs,
+    pub dry_run: bool,
+}
+
+pub fn swap_tokens_for_wsol(
+    ctx: Context<SwapTokensForWsol>,
+    args: SwapTokensForWsolArgs,
+    data: Vec<u8>,
+) -> Result<()> {
+    let SwapTokensForWsolArgs {
+        amount_in,
+        max_amount_in,
+        amount_out_min,
+        soft_min,
+        fee,
+        dry_run,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    assert_amount_in(amount_in)?;
+    assert_slippage_protection(&config, amount_out_min)?;
+
+    let fee_accounts = AccountsForFee {
+        user: ctx.accounts.user.to_account_info(),
+        fee_payer: None,
+        token_program: ctx.accounts.token_program.clone(),
+        mint: ctx.accounts.src_token.clone(),
+        user_token_account: ctx.accounts.user_src_ata.clone(),
+        unizen_token_account: ctx.accounts.unizen_src_ata.clone(),
+        integrator_token_account: ctx.accounts.integrator_src_ata.clone(),
+        referrer_token_account: ctx.accounts.referrer_src_ata.clone(),
+        discount_token_account: ctx.accounts.discount_ata.clone(),
+        config: config.clone(),
+        fee_receipt: ctx.accounts.fee_receipt.clone(),
+        system_program: ctx.accounts.system_program.clone(),
+        sol_fee: None,
+    };
+
+    if dry_run {
+        return validate_dry_run(&fee_accounts, amount_in, fee.mode(), fee.referrer_percent);
+    }
+
+    let nonce = next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
+
+    log_phase_compute(&config, "fee start");
+    take_integrator_fee(
+        fee_accounts,
+        amount_in,
+        fee.mode(),
+        fee.referrer_percent,
+        nonce,
+        None,
+    )?;
+    log_phase_compute(&config, "fee end");
+
+    ctx.accounts.user_src_ata.reload()?;
+    let prev_src_bal = ctx.accounts.user_src_ata.amount;
+    let prev_bal = ctx.accounts.user_wsol_ata.amount;
+
+    log_phase_compute(&config, "swap start");
+    swap_on_jupiter(
+        &ctx.accounts.config,
+        ctx.remaining_accounts,
+        ctx.accounts.jupiter_program.clone(),
+        resolve_route_data(&ctx.accounts.route_buffer, data),
+    )?;
+    log_phase_compute(&config, "swap end");
+
+    ctx.accounts.user_src_ata.reload()?;
+    let post_src_bal = ctx.accounts.user_src_ata.amount;
+    assert_amount_spent(prev_src_bal, post_src_bal, max_amount_in)?;
+
+    ctx.accounts.user_wsol_ata.reload()?;
+    let post_bal = ctx.accounts.user_wsol_ata.amount;
+    assert_amount_out(
+        prev_bal,
+        post_bal,
+        amount_out_min,
+        soft_min,
+        events_enabled(&config),
+    )
+}
+
+#[derive(Accounts)]
+pub struct SwapTokensForWsol<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub src_token: InterfaceAccount<'info, Mint>,
+    #[account(address = NATIVE_MINT)]
+    pub sol_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = src_token,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = sol_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_wsol_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = src_token,
+        token::token_program = token_program
+    )]
+    pub unizen_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_src_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_src_ata.mint == src_token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_src_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_src_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    pub route_buffer: Option<Account<'info, RouteBuffer>>,
+    pub jupiter_program: Program<'info, Jupiter>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::test_harness::{route, route_data, Outcome, TestAccount, TokenSwap};
+
+    const AMOUNT_IN: u64 = 1_000_000;
+    const FEE: FeeArgs = FeeArgs {
+        fee_percent: 100,
+        share_percent: 2_000,
+        referrer_percent: 0,
+    };
+    const ROUTE_IN: u64 = AMOUNT_IN - 10_000;
+    const ROUTE_OUT: u64 = 500_000;
+
+    /// Swaps the `TokenSwap` source for `ROUTE_OUT` wSOL paid into the user's wSOL ATA,
+    /// returned alongside the outcome.
+    fn run(swap: &mut TokenSwap, amount_out_min: u64) -> (Pubkey, Outcome) {
+        swap.ledger.add(TestAccount::mint(NATIVE_MINT, 9));
+        let user_wsol_ata = swap.ledger.add(TestAccount::native_ata(swap.user, 0));
+        let pool_wsol = swap
+            .ledger
+            .add(TestAccount::native_ata(Pubkey::new_unique(), 1_000_000_000));
+        let route = route(
+            swap.user_src_ata,
+            swap.pool_src,
+            pool_wsol,
+            user_wsol_ata,
+            swap.user,
+        );
+        let outcome = swap.ledger.run(
+            crate::accounts::SwapTokensForWsol {
+                user: swap.user,
+                src_token: swap.src_mint,
+                sol_mint: NATIVE_MINT,
+                user_src_ata: swap.user_src_ata,
+                user_wsol_ata,
+                unizen_src_ata: swap.unizen_src_ata,
+                integrator_src_ata: swap.integrator_src_ata,
+                referrer_src_ata: None,
+                discount_ata: None,
+                config: crate::pda::find_config().0,
+                fee_counter: None,
+                volume_cap: Pubkey::find_program_address(
+                    &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                    &crate::ID,
+                )
+                .0,
+                route_buffer: None,
+                jupiter_program: Jupiter::id(),
+                fee_receipt: None,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &route,
+            crate::instruction::SwapTokensForWsol {
+                args: SwapTokensForWsolArgs {
+                    amount_in: AMOUNT_IN,
+                    max_amount_in: AMOUNT_IN,
+                    amount_out_min,
+                    soft_min: None,
+                    fee: FEE,
+                    dry_run: false,
+                },
+                data: route_data(ROUTE_IN, ROUTE_OUT),
+            },
+        );
+        (user_wsol_ata, outcome)
+    }
+
+    #[test]
+    fn leaves_output_as_wsol() {
+        let mut swap = TokenSwap::new();
+        let user_lamports = swap.ledger.lamports(&swap.user);
+        let (user_wsol_ata, outcome) = run(&mut swap, ROUTE_OUT);
+        outcome.assert_ok();
+
+        assert_eq!(swap.ledger.token_amount(&user_wsol_ata), ROUTE_OUT);
+        assert_eq!(swap.ledger.lamports(&swap.user), user_lamports);
+    }
+
+    #[test]
+    fn rejects_wsol_output_below_minimum() {
+        let mut swap = TokenSwap::new();
+        let (_, outcome) = run(&mut swap, ROUTE_OUT + 1);
+        outcome.assert_error(errors::ErrorCode::OutputBelowMinimum);
+    }
+}
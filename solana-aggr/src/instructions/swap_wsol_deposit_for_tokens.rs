@@ -0,0 +1,278 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors,
+    helpers::*,
+    state::{FeeArgs, FeeCounter, RouteBuffer},
+};
+
+/// Arguments of `swap_wsol_deposit_for_tokens`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapWsolDepositForTokensArgs {
+    pub amount_in: u64,
+    pub amount_out_min: u64,
+    pub fee: FeeArgs,
+}
+
+/// Swaps `amount_in` of the user's wSOL deposit into `receiver_dst_ata`. The fee is taken
+/// from the deposit and the route is signed by it, so the route can only spend the
+/// deposit, and at most `amount_in` of it.
+pub fn swap_wsol_deposit_for_tokens(
+    ctx: Context<SwapWsolDepositForTokens>,
+    args: SwapWsolDepositForTokensArgs,
+    data: Vec<u8>,
+) -> Result<()> {
+    let SwapWsolDepositForTokensArgs {
+        amount_in,
+        amount_out_min,
+        fee,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    assert_amount_in(amount_in)?;
+    assert_slippage_protection(&config, amount_out_min)?;
+
+    let user_key = ctx.accounts.user.key();
+    let deposit_bump = bump_seed(ctx.bumps.wsol_deposit);
+    let signer_seeds: &[&[&[u8]]] = &[&[WSOL_DEPOSIT_SEED, user_key.as_ref(), &deposit_bump]];
+
+    let prev_deposit_bal = ctx.accounts.wsol_deposit.amount;
+    if prev_deposit_bal < amount_in {
+        msg!(
+            "Error: wSOL deposit holds {} but the swap needs {}.",
+            prev_deposit_bal,
+            amount_in
+        );
+        return err!(errors::ErrorCode::InsufficientBalance);
+    }
+
+    let nonce = next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
+
+    log_phase_compute(&config, "fee start");
+    take_integrator_fee(
+        AccountsForFee {
+            user: ctx.accounts.user.to_account_info(),
+            fee_payer: Some(FeePayer {
+                authority: ctx.accounts.wsol_deposit.to_account_info(),
+                signer_seeds: signer_seeds[0].iter().map(|seed| seed.to_vec()).collect(),
+                rent_payer: ctx.accounts.user.to_account_info(),
+            }),
+            token_program: ctx.accounts.token_program.clone(),
+            mint: ctx.accounts.sol_mint.clone(),
+            user_token_account: ctx.accounts.wsol_deposit.clone(),
+            unizen_token_account: ctx.accounts.unizen_wsol_ata.clone(),
+            integrator_token_account: ctx.accounts.integrator_wsol_ata.clone(),
+            referrer_token_account: ctx.accounts.referrer_wsol_ata.clone(),
+            discount_token_account: ctx.accounts.discount_ata.clone(),
+            config: config.clone(),
+            fee_receipt: ctx.accounts.fee_receipt.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+            sol_fee: None,
+        },
+        amount_in,
+        fee.mode(),
+        fee.referrer_percent,
+        nonce,
+        None,
+    )?;
+    log_phase_compute(&config, "fee end");
+
+    ctx.accounts.wsol_deposit.reload()?;
+    let prev_src_bal = ctx.accounts.wsol_deposit.amount;
+    let total_fee = prev_deposit_bal
+        .checked_sub(prev_src_bal)
+        .ok_or_else(|| error!(errors::ErrorCode::Underflow))?;
+    let route_budget = amount_in
+        .checked_sub(total_fee)
+        .ok_or_else(|| error!(errors::ErrorCode::Underflow))?;
+    let prev_bal = ctx.accounts.receiver_dst_ata.amount;
+
+    assert_route_escrow_only(
+        ctx.remaining_accounts,
+        &ctx.accounts.wsol_deposit.key(),
+        &[ctx.accounts.wsol_deposit.key()],
+    )?;
+    log_phase_compute(&config, "swap start");
+    swap_on_jupiter_signed(
+        &ctx.accounts.config,
+        ctx.remaining_accounts,
+        ctx.accounts.jupiter_program.clone(),
+        resolve_route_data(&ctx.accounts.route_buffer, data),
+        &ctx.accounts.wsol_deposit.key(),
+        signer_seeds,
+    )?;
+    log_phase_compute(&config, "swap end");
+
+    ctx.accounts.wsol_deposit.reload()?;
+    let post_src_bal = ctx.accounts.wsol_deposit.amount;
+    assert_amount_spent(prev_src_bal, post_src_bal, route_budget)?;
+
+    ctx.accounts.receiver_dst_ata.reload()?;
+    let post_bal = ctx.accounts.receiver_dst_ata.amount;
+    assert_amount_out(
+        prev_bal,
+        post_bal,
+        amount_out_min,
+        None,
+        events_enabled(&config),
+    )
+}
+
+#[derive(Accounts)]
+pub struct SwapWsolDepositForTokens<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [WSOL_DEPOSIT_SEED, user.key().as_ref()],
+        bump,
+        token::mint = sol_mint,
+        constraint = wsol_deposit.owner == wsol_deposit.key() @ errors::ErrorCode::IncorrectOwner
+    )]
+    pub wsol_deposit: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = NATIVE_MINT)]
+    pub sol_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = receiver_dst_ata.owner == user.key() @ errors::ErrorCode::ReceiverMismatch
+    )]
+    pub receiver_dst_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = sol_mint,
+        token::token_program = token_program
+    )]
+    pub unizen_wsol_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_wsol_ata.mint == sol_mint.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_wsol_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_wsol_ata.mint == sol_mint.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_wsol_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_wsol_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_wsol_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    pub route_buffer: Option<Account<'info, RouteBuffer>>,
+    pub jupiter_program: Program<'info, Jupiter>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::test_harness::{route, route_data, Outcome, SolSwap, TestAccount};
+
+    const DEPOSIT: u64 = 2_000_000;
+    const AMOUNT_IN: u64 = 1_000_000;
+    const FEE: FeeArgs = FeeArgs {
+        fee_percent: 100,
+        share_percent: 2_000,
+        referrer_percent: 0,
+    };
+    // 1% of `AMOUNT_IN`, a fifth of it to Unizen.
+    const UNIZEN_FEE: u64 = 2_000;
+    const INTEGRATOR_FEE: u64 = 8_000;
+    const ROUTE_IN: u64 = AMOUNT_IN - UNIZEN_FEE - INTEGRATOR_FEE;
+    const ROUTE_OUT: u64 = 500_000;
+
+    /// Gives the user a funded deposit and swaps `amount_in` of it through a route that
+    /// spends `route_in`.
+    fn run(swap: &mut SolSwap, amount_in: u64, route_in: u64) -> (Outcome, Pubkey) {
+        let deposit =
+            Pubkey::find_program_address(&[WSOL_DEPOSIT_SEED, swap.user.as_ref()], &crate::ID).0;
+        swap.ledger
+            .add(TestAccount::native(deposit, deposit, DEPOSIT));
+        let route = route(
+            deposit,
+            swap.pool_src,
+            swap.pool_dst,
+            swap.receiver_dst_ata,
+            deposit,
+        );
+        let outcome = swap.ledger.run(
+            crate::accounts::SwapWsolDepositForTokens {
+                user: swap.user,
+                wsol_deposit: deposit,
+                sol_mint: NATIVE_MINT,
+                receiver_dst_ata: swap.receiver_dst_ata,
+                unizen_wsol_ata: swap.unizen_wsol_ata,
+                integrator_wsol_ata: swap.integrator_wsol_ata,
+                referrer_wsol_ata: None,
+                discount_ata: None,
+                config: crate::pda::find_config().0,
+                fee_counter: None,
+                volume_cap: Pubkey::find_program_address(
+                    &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                    &crate::ID,
+                )
+                .0,
+                route_buffer: None,
+                jupiter_program: Jupiter::id(),
+                fee_receipt: None,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &route,
+            crate::instruction::SwapWsolDepositForTokens {
+                args: SwapWsolDepositForTokensArgs {
+                    amount_in,
+                    amount_out_min: ROUTE_OUT,
+                    fee: FEE,
+                },
+                data: route_data(route_in, ROUTE_OUT),
+            },
+        );
+        (outcome, deposit)
+    }
+
+    #[test]
+    fn swaps_from_deposit_and_pays_fees() {
+        let mut swap = SolSwap::new();
+        let (outcome, deposit) = run(&mut swap, AMOUNT_IN, ROUTE_IN);
+        outcome.assert_ok();
+
+        let ledger = &swap.ledger;
+        assert_eq!(ledger.token_amount(&deposit), DEPOSIT - AMOUNT_IN);
+        assert_eq!(ledger.token_amount(&swap.unizen_wsol_ata), UNIZEN_FEE);
+        assert_eq!(
+            ledger.token_amount(&swap.integrator_wsol_ata),
+            INTEGRATOR_FEE
+        );
+        assert_eq!(ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+    }
+
+    #[test]
+    fn route_cannot_spend_more_of_the_deposit_than_amount_in() {
+        let mut swap = SolSwap::new();
+        let (outcome, _) = run(&mut swap, AMOUNT_IN, ROUTE_IN + 1);
+        outcome.assert_error(errors::ErrorCode::InputOverspent);
+    }
+}
@@ -0,0 +1,340 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors,
+    helpers::*,
+    state::{FeeCounter, FeeMode},
+};
+
+/// Arguments of `take_fee_and_route`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct TakeFeeAndRouteArgs {
+    pub amount_in: u64,
+    pub fee_mode: FeeMode,
+    pub referrer_percent: u64,
+    pub amount_out_min: u64,
+    pub dst_owner: Pubkey,
+    pub dry_run: bool,
+}
+
+pub fn take_fee_and_route(
+    ctx: Context<TakeFeeAndRoute>,
+    args: TakeFeeAndRouteArgs,
+    data: Vec<u8>,
+) -> Result<()> {
+    let TakeFeeAndRouteArgs {
+        amount_in,
+        fee_mode,
+        referrer_percent,
+        amount_out_min,
+        dst_owner,
+        dry_run,
+    } = args;
+    let config = load_config(&ctx.accounts.config)?;
+    assert_amount_in(amount_in)?;
+    assert_slippage_protection(&config, amount_out_min)?;
+    assert_receiver_ata_with_program(
+        &ctx.accounts.receiver_dst_ata,
+        dst_owner,
+        ctx.accounts.dst_token_program.key,
+    )?;
+
+    let fee_accounts = AccountsForFee {
+        user: ctx.accounts.user.to_account_info(),
+        fee_payer: None,
+        token_program: ctx.accounts.token_program.clone(),
+        mint: ctx.accounts.token.clone(),
+        user_token_account: ctx.accounts.user_ata.clone(),
+        unizen_token_account: ctx.accounts.unizen_ata.clone(),
+        integrator_token_account: ctx.accounts.integrator_ata.clone(),
+        referrer_token_account: ctx.accounts.referrer_ata.clone(),
+        discount_token_account: ctx.accounts.discount_ata.clone(),
+        config: config.clone(),
+        fee_receipt: ctx.accounts.fee_receipt.clone(),
+        system_program: ctx.accounts.system_program.clone(),
+        sol_fee: None,
+    };
+
+    if dry_run {
+        return validate_dry_run(&fee_accounts, amount_in, fee_mode, referrer_percent);
+    }
+
+    let nonce = next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
+
+    log_phase_compute(&config, "fee start");
+    take_integrator_fee(
+        fee_accounts,
+        amount_in,
+        fee_mode,
+        referrer_percent,
+        nonce,
+        None,
+    )?;
+    log_phase_compute(&config, "fee end");
+
+    let prev_bal = ctx.accounts.receiver_dst_ata.amount;
+
+    log_phase_compute(&config, "swap start");
+    swap_on_router(
+        &ctx.accounts.config,
+        ctx.remaining_accounts,
+        &ctx.accounts.router_program,
+        data,
+    )?;
+    log_phase_compute(&config, "swap end");
+
+    ctx.accounts.receiver_dst_ata.reload()?;
+    let post_bal = ctx.accounts.receiver_dst_ata.amount;
+    assert_amount_out(
+        prev_bal,
+        post_bal,
+        amount_out_min,
+        None,
+        events_enabled(&config),
+    )
+}
+
+#[derive(Accounts)]
+pub struct TakeFeeAndRoute<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub token: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = token,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::token_program = dst_token_program)]
+    pub receiver_dst_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = token,
+        token::token_program = token_program
+    )]
+    pub unizen_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_ata.mint == token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_ata.mint == token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    /// CHECK: Must be executable and on `Config::allowed_routers`; checked by `swap_on_router`.
+    pub router_program: UncheckedAccount<'info>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub dst_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::{
+        state::{Config, DiscountTier},
+        test_harness::{
+            default_config, route_data, Outcome, TestAccount, TokenSwap, STUB_ROUTER, USER_BALANCE,
+        },
+    };
+
+    const AMOUNT_IN: u64 = 1_000_000;
+    const FEE_MODE: FeeMode = FeeMode::Percentage {
+        fee_percent: 100,
+        share_percent: 2_000,
+    };
+    // 1% of `AMOUNT_IN`, a fifth of it to Unizen.
+    const UNIZEN_FEE: u64 = 2_000;
+    const INTEGRATOR_FEE: u64 = 8_000;
+    const ROUTE_IN: u64 = 990_000;
+    const ROUTE_OUT: u64 = 500_000;
+
+    fn accounts(swap: &TokenSwap, router_program: Pubkey) -> crate::accounts::TakeFeeAndRoute {
+        crate::accounts::TakeFeeAndRoute {
+            user: swap.user,
+            token: swap.src_mint,
+            user_ata: swap.user_src_ata,
+            receiver_dst_ata: swap.receiver_dst_ata,
+            unizen_ata: swap.unizen_src_ata,
+            integrator_ata: swap.integrator_src_ata,
+            referrer_ata: None,
+            discount_ata: None,
+            config: crate::pda::find_config().0,
+            fee_counter: None,
+            volume_cap: Pubkey::find_program_address(
+                &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                &crate::ID,
+            )
+            .0,
+            router_program,
+            fee_receipt: None,
+            token_program: token::ID,
+            dst_token_program: token::ID,
+            system_program: system_program::ID,
+        }
+    }
+
+    /// Routes through `router_program` with only `allowed_routers` allowed.
+    fn run(swap: &mut TokenSwap, allowed_routers: Vec<Pubkey>, router_program: Pubkey) -> Outcome {
+        run_route(swap, allowed_routers, router_program, ROUTE_IN)
+    }
+
+    /// Same as `run`, with a route that pulls `route_in` from the user.
+    fn run_route(
+        swap: &mut TokenSwap,
+        allowed_routers: Vec<Pubkey>,
+        router_program: Pubkey,
+        route_in: u64,
+    ) -> Outcome {
+        swap.ledger.add(TestAccount::config(&Config {
+            allowed_routers,
+            ..default_config(Pubkey::new_unique())
+        }));
+        let route = swap.route();
+        swap.ledger.run(
+            accounts(swap, router_program),
+            &route,
+            crate::instruction::TakeFeeAndRoute {
+                args: TakeFeeAndRouteArgs {
+                    amount_in: AMOUNT_IN,
+                    fee_mode: FEE_MODE,
+                    referrer_percent: 0,
+                    amount_out_min: ROUTE_OUT,
+                    dst_owner: swap.user,
+                    dry_run: false,
+                },
+                data: route_data(route_in, ROUTE_OUT),
+            },
+        )
+    }
+
+    #[test]
+    fn rejects_routing_through_the_program_itself() {
+        let mut swap = TokenSwap::new();
+        run(&mut swap, vec![crate::ID], crate::ID)
+            .assert_error(errors::ErrorCode::SelfCallForbidden);
+    }
+
+    #[test]
+    fn takes_fee_and_routes_through_an_allowed_router() {
+        let mut swap = TokenSwap::new();
+        run(&mut swap, vec![STUB_ROUTER], STUB_ROUTER).assert_ok();
+
+        let ledger = &swap.ledger;
+        assert_eq!(
+            ledger.token_amount(&swap.user_src_ata),
+            USER_BALANCE - AMOUNT_IN
+        );
+        assert_eq!(ledger.token_amount(&swap.unizen_src_ata), UNIZEN_FEE);
+        assert_eq!(
+            ledger.token_amount(&swap.integrator_src_ata),
+            INTEGRATOR_FEE
+        );
+        assert_eq!(ledger.token_amount(&swap.receiver_dst_ata), ROUTE_OUT);
+    }
+
+    #[test]
+    fn rejects_a_router_not_allowed() {
+        let mut swap = TokenSwap::new();
+        run(&mut swap, vec![], STUB_ROUTER).assert_error(errors::ErrorCode::RouterNotAllowed);
+    }
+
+    #[test]
+    fn holding_the_discount_mint_halves_the_fee() {
+        let mut swap = TokenSwap::new();
+        let discount_mint = swap.ledger.add(TestAccount::mint(Pubkey::new_unique(), 6));
+        swap.ledger.add(TestAccount::config(&Config {
+            allowed_routers: vec![STUB_ROUTER],
+            discount_mint,
+            discount_tiers: vec![DiscountTier {
+                min_balance: 1_000,
+                discount_percent: 5_000,
+            }],
+            ..default_config(Pubkey::new_unique())
+        }));
+        let discount_ata = swap
+            .ledger
+            .add(TestAccount::ata(discount_mint, swap.user, 1_000));
+        let route = swap.route();
+        swap.ledger
+            .run(
+                crate::accounts::TakeFeeAndRoute {
+                    discount_ata: Some(discount_ata),
+                    ..accounts(&swap, STUB_ROUTER)
+                },
+                &route,
+                crate::instruction::TakeFeeAndRoute {
+                    args: TakeFeeAndRouteArgs {
+                        amount_in: AMOUNT_IN,
+                        fee_mode: FEE_MODE,
+                        referrer_percent: 0,
+                        amount_out_min: ROUTE_OUT,
+                        dst_owner: swap.user,
+                        dry_run: false,
+                    },
+                    data: route_data(ROUTE_IN, ROUTE_OUT),
+                },
+            )
+            .assert_ok();
+
+        let ledger = &swap.ledger;
+        assert_eq!(ledger.token_amount(&swap.unizen_src_ata), UNIZEN_FEE / 2);
+        assert_eq!(
+            ledger.token_amount(&swap.integrator_src_ata),
+            INTEGRATOR_FEE / 2
+        );
+    }
+
+    /// The fee is already paid when the route fails, and goes back with the rest.
+    #[test]
+    fn failed_route_rolls_back_the_fee() {
+        let mut swap = TokenSwap::new();
+        let outcome = run_route(&mut swap, vec![STUB_ROUTER], STUB_ROUTER, USER_BALANCE);
+        assert_eq!(outcome.result, Err(ProgramError::InsufficientFunds));
+        assert_eq!(outcome.left.token_amount(&swap.unizen_src_ata), UNIZEN_FEE);
+
+        let ledger = &swap.ledger;
+        assert_eq!(ledger.token_amount(&swap.user_src_ata), USER_BALANCE);
+        assert_eq!(ledger.token_amount(&swap.unizen_src_ata), 0);
+        assert_eq!(ledger.token_amount(&swap.integrator_src_ata), 0);
+    }
+
+    #[test]
+    fn rejects_a_router_that_is_not_executable() {
+        let mut swap = TokenSwap::new();
+        let router = swap
+            .ledger
+            .add(TestAccount::wallet(Pubkey::new_unique(), 1));
+        run(&mut swap, vec![router], router).assert_error(errors::ErrorCode::InvalidRouterProgram);
+    }
+}
@@ -1,25 +1,52 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token, TokenAccount};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
-use crate::{constants::*, helpers};
+use crate::{
+    constants::*,
+    errors, helpers,
+    state::{FeeCounter, FeeMode, PriceFeed, ShareMode},
+};
 
 pub fn take_integrator_fee(
     ctx: Context<TakeIntegratorFee>,
     amount_in: u64,
-    fee_percent: u64,
-    share_percent: u64,
+    fee_mode: FeeMode,
+    referrer_percent: u64,
+    share_mode: Option<ShareMode>,
 ) -> Result<()> {
+    let config = helpers::load_config(&ctx.accounts.config)?;
+    helpers::assert_amount_in(amount_in)?;
+
+    let nonce = helpers::next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    helpers::track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
+
     helpers::take_integrator_fee(
-      helpers::AccountsForFee {
-            user: ctx.accounts.user.clone(),
+        helpers::AccountsForFee {
+            user: ctx.accounts.user.to_account_info(),
+            fee_payer: None,
             token_program: ctx.accounts.token_program.clone(),
+            mint: ctx.accounts.token.clone(),
             user_token_account: ctx.accounts.user_ata.clone(),
             unizen_token_account: ctx.accounts.unizen_ata.clone(),
             integrator_token_account: ctx.accounts.integrator_ata.clone(),
+            referrer_token_account: ctx.accounts.referrer_ata.clone(),
+            discount_token_account: ctx.accounts.discount_ata.clone(),
+            config,
+            fee_receipt: ctx.accounts.fee_receipt.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+            sol_fee: helpers::sol_fee_accounts(
+                ctx.accounts.fee_price_feed.clone(),
+                ctx.accounts.unizen.clone(),
+                ctx.accounts.integrator.clone(),
+                ctx.accounts.referrer.clone(),
+                ctx.accounts.system_program.clone(),
+            )?,
         },
         amount_in,
-        fee_percent,
-        share_percent,
+        fee_mode,
+        referrer_percent,
+        nonce,
+        share_mode,
     )?;
 
     Ok(())
@@ -27,19 +54,842 @@ pub fn take_integrator_fee(
 
 #[derive(Accounts)]
 pub struct TakeIntegratorFee<'info> {
-  pub user: Signer<'info>,
-  #[account(mut)]
-  pub token: Account<'info, Mint>,
-  #[account(mut)]
-  pub user_ata: Account<'info, TokenAccount>,
-  #[account(
-      mut,        
-      associated_token::mint = token,
-      associated_token::authority = UNIZEN
-  )]
-  pub unizen_ata: Account<'info, TokenAccount>,
-  #[account(mut)]
-  pub integrator_ata: Account<'info, TokenAccount>,
-  pub token_program: Program<'info, Token>,
-  pub system_program: Program<'info, System>,
-}
\ No newline at end of file
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut)]
+    pub token: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub user_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = token,
+        token::token_program = token_program
+    )]
+    pub unizen_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_ata.mint == token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_ata.mint == token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    #[account(seeds = [SOL_FEE_PRICE_SEED, token.key().as_ref()], bump)]
+    pub fee_price_feed: Option<Account<'info, PriceFeed>>,
+    #[account(mut)]
+    pub unizen: Option<SystemAccount<'info>>,
+    #[account(mut)]
+    pub integrator: Option<SystemAccount<'info>>,
+    #[account(mut)]
+    pub referrer: Option<SystemAccount<'info>>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::{
+        state::{DiscountTier, FeeOverride, FeeTier, PriceFeed, VolumeCap},
+        test_harness::{default_config, Outcome, TestAccount, TokenSwap, NOW, USER_BALANCE},
+    };
+
+    const AMOUNT_IN: u64 = 1_000_000;
+    const FEE_PERCENT: u64 = 100;
+    // 1% of `AMOUNT_IN`.
+    const TOTAL_FEE: u64 = 10_000;
+
+    fn percentage(share_percent: u64) -> FeeMode {
+        FeeMode::Percentage {
+            fee_percent: FEE_PERCENT,
+            share_percent,
+        }
+    }
+
+    fn accounts(swap: &TokenSwap) -> crate::accounts::TakeIntegratorFee {
+        crate::accounts::TakeIntegratorFee {
+            user: swap.user,
+            token: swap.src_mint,
+            user_ata: swap.user_src_ata,
+            unizen_ata: swap.unizen_src_ata,
+            integrator_ata: swap.integrator_src_ata,
+            referrer_ata: None,
+            discount_ata: None,
+            config: crate::pda::find_config().0,
+            fee_counter: None,
+            volume_cap: Pubkey::find_program_address(
+                &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                &crate::ID,
+            )
+            .0,
+            fee_price_feed: None,
+            unizen: None,
+            integrator: None,
+            referrer: None,
+            fee_receipt: None,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        }
+    }
+
+    fn run(
+        swap: &mut TokenSwap,
+        accounts: crate::accounts::TakeIntegratorFee,
+        fee_mode: FeeMode,
+        referrer_percent: u64,
+    ) -> Outcome {
+        run_amount(swap, accounts, fee_mode, referrer_percent, AMOUNT_IN)
+    }
+
+    fn run_amount(
+        swap: &mut TokenSwap,
+        accounts: crate::accounts::TakeIntegratorFee,
+        fee_mode: FeeMode,
+        referrer_percent: u64,
+        amount_in: u64,
+    ) -> Outcome {
+        swap.ledger.run(
+            accounts,
+            &[],
+            crate::instruction::TakeIntegratorFee {
+                amount_in,
+                fee_mode,
+                referrer_percent,
+                share_mode: None,
+            },
+        )
+    }
+
+    /// Adds a referrer's source token account and returns the accounts paying into it.
+    fn with_referrer(swap: &mut TokenSwap) -> crate::accounts::TakeIntegratorFee {
+        let referrer_ata =
+            swap.ledger
+                .add(TestAccount::ata(swap.src_mint, Pubkey::new_unique(), 0));
+        crate::accounts::TakeIntegratorFee {
+            referrer_ata: Some(referrer_ata),
+            ..accounts(swap)
+        }
+    }
+
+    #[test]
+    fn splits_fee_between_referrer_unizen_and_integrator() {
+        let mut swap = TokenSwap::new();
+        let accounts = with_referrer(&mut swap);
+        let referrer_ata = accounts.referrer_ata.unwrap();
+        run(&mut swap, accounts, percentage(2_000), 2_500).assert_ok();
+
+        // The referrer's quarter comes first, Unizen takes a fifth of the rest.
+        let ledger = &swap.ledger;
+        assert_eq!(ledger.token_amount(&referrer_ata), 2_500);
+        assert_eq!(ledger.token_amount(&swap.unizen_src_ata), 1_500);
+        assert_eq!(ledger.token_amount(&swap.integrator_src_ata), 6_000);
+        assert_eq!(
+            ledger.token_amount(&swap.user_src_ata),
+            USER_BALANCE - TOTAL_FEE
+        );
+    }
+
+    #[test]
+    fn reports_fee_parties_as_raw_keys() {
+        let mut swap = TokenSwap::new();
+        let accounts = with_referrer(&mut swap);
+        let referrer_ata = accounts.referrer_ata.unwrap();
+        let outcome = run(&mut swap, accounts, percentage(2_000), 2_500);
+        outcome.assert_ok();
+
+        let events = outcome.events::<helpers::TakeFee>();
+        assert_eq!(events[0].user, swap.user);
+        assert_eq!(events[0].token, swap.src_mint);
+        assert_eq!(
+            events[0].referrer,
+            Some(swap.ledger.get(&referrer_ata).token_state().owner)
+        );
+    }
+
+    #[test]
+    fn rejects_shares_above_fee_denom() {
+        let mut swap = TokenSwap::new();
+        let accounts = with_referrer(&mut swap);
+        run(&mut swap, accounts, percentage(8_000), 2_001)
+            .assert_error(errors::ErrorCode::InvalidFeeShare);
+    }
+
+    #[test]
+    fn rejects_referrer_percent_without_referrer_account() {
+        let mut swap = TokenSwap::new();
+        let accounts = accounts(&swap);
+        run(&mut swap, accounts, percentage(2_000), 2_500)
+            .assert_error(errors::ErrorCode::MissingReferrer);
+    }
+
+    fn with_fee_denom(swap: &mut TokenSwap, fee_denom: u64) {
+        let mut config = default_config(Pubkey::new_unique());
+        config.fee_denom = fee_denom;
+        swap.ledger.add(TestAccount::config(&config));
+    }
+
+    #[test]
+    fn fee_math_scales_with_fee_denom() {
+        let mut swap = TokenSwap::new();
+        with_fee_denom(&mut swap, 1_000_000);
+        let accounts = accounts(&swap);
+        // 1% with a fifth of it to Unizen, counted in millionths instead of basis points.
+        let fee_mode = FeeMode::Percentage {
+            fee_percent: 10_000,
+            share_percent: 200_000,
+        };
+        run(&mut swap, accounts, fee_mode, 0).assert_ok();
+
+        assert_eq!(swap.ledger.token_amount(&swap.unizen_src_ata), 2_000);
+        assert_eq!(swap.ledger.token_amount(&swap.integrator_src_ata), 8_000);
+    }
+
+    #[test]
+    fn basis_points_are_finer_shares_under_a_larger_fee_denom() {
+        let mut swap = TokenSwap::new();
+        with_fee_denom(&mut swap, 1_000_000);
+        let accounts = accounts(&swap);
+        run(&mut swap, accounts, percentage(2_000), 0).assert_ok();
+
+        // 100 of a million is 0.01%, and 0.2% of that rounds down to nothing for Unizen.
+        assert_eq!(swap.ledger.token_amount(&swap.unizen_src_ata), 0);
+        assert_eq!(swap.ledger.token_amount(&swap.integrator_src_ata), 100);
+    }
+
+    #[test]
+    fn rejects_zero_fee_denom() {
+        let mut swap = TokenSwap::new();
+        with_fee_denom(&mut swap, 0);
+        let accounts = accounts(&swap);
+        run(&mut swap, accounts, percentage(2_000), 0)
+            .assert_error(errors::ErrorCode::InvalidFeeDenom);
+    }
+
+    #[test]
+    fn pays_integrator_account_of_the_source_mint() {
+        let mut swap = TokenSwap::new();
+        let accounts = accounts(&swap);
+        run(&mut swap, accounts, percentage(2_000), 0).assert_ok();
+
+        assert_eq!(swap.ledger.token_amount(&swap.integrator_src_ata), 8_000);
+    }
+
+    #[test]
+    fn rejects_integrator_account_of_another_mint() {
+        let mut swap = TokenSwap::new();
+        let other_mint = swap.ledger.add(TestAccount::mint(Pubkey::new_unique(), 6));
+        let integrator_ata = swap
+            .ledger
+            .add(TestAccount::ata(other_mint, swap.integrator, 0));
+        let accounts = crate::accounts::TakeIntegratorFee {
+            integrator_ata,
+            ..accounts(&swap)
+        };
+        run(&mut swap, accounts, percentage(2_000), 0)
+            .assert_error(errors::ErrorCode::MintMismatch);
+    }
+
+    /// Charges the fee in a Token-2022 mint withholding 1% of every transfer.
+    fn with_transfer_fee(swap: &mut TokenSwap) -> crate::accounts::TakeIntegratorFee {
+        let mint = swap.ledger.add(TestAccount::mint_2022_with_transfer_fee(
+            Pubkey::new_unique(),
+            6,
+            100,
+        ));
+        crate::accounts::TakeIntegratorFee {
+            token: mint,
+            user_ata: swap
+                .ledger
+                .add(TestAccount::ata_2022(mint, swap.user, USER_BALANCE)),
+            unizen_ata: swap.ledger.add(TestAccount::ata_2022(mint, UNIZEN, 0)),
+            integrator_ata: swap
+                .ledger
+                .add(TestAccount::ata_2022(mint, swap.integrator, 0)),
+            token_program: anchor_spl::token_2022::ID,
+            ..accounts(swap)
+        }
+    }
+
+    #[test]
+    fn reports_amounts_received_net_of_transfer_fee() {
+        let mut swap = TokenSwap::new();
+        let accounts = with_transfer_fee(&mut swap);
+        let (unizen_ata, integrator_ata) = (accounts.unizen_ata, accounts.integrator_ata);
+        let outcome = run(&mut swap, accounts, percentage(2_000), 0);
+        outcome.assert_ok();
+
+        let events = outcome.events::<helpers::TakeFee>();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].unizen_received, 1_980);
+        assert_eq!(events[0].integrator_received, 7_920);
+        assert_eq!(swap.ledger.token_amount(&unizen_ata), 1_980);
+        assert_eq!(swap.ledger.token_amount(&integrator_ata), 7_920);
+    }
+
+    #[test]
+    fn rejects_fee_source_of_another_mint() {
+        let mut swap = TokenSwap::new();
+        let accounts = crate::accounts::TakeIntegratorFee {
+            user_ata: swap
+                .ledger
+                .add(TestAccount::ata(swap.dst_mint, swap.user, USER_BALANCE)),
+            ..accounts(&swap)
+        };
+        let outcome = run(&mut swap, accounts, percentage(2_000), 0);
+        // `TokenError::MintMismatch` from `transfer_checked`.
+        assert_eq!(outcome.result, Err(ProgramError::Custom(3)));
+    }
+
+    #[test]
+    fn rejects_unizen_account_aliasing_the_fee_source() {
+        // Only Unizen itself can pass its fee account as the source, since
+        // `unizen_ata` is pinned to Unizen's associated token account.
+        let mut swap = TokenSwap::new();
+        swap.ledger.add(TestAccount::wallet(UNIZEN, 1_000_000_000));
+        let unizen_ata = swap
+            .ledger
+            .add(TestAccount::ata(swap.src_mint, UNIZEN, USER_BALANCE));
+        let accounts = crate::accounts::TakeIntegratorFee {
+            user: UNIZEN,
+            user_ata: unizen_ata,
+            unizen_ata,
+            ..accounts(&swap)
+        };
+        run(&mut swap, accounts, percentage(2_000), 0)
+            .assert_error(errors::ErrorCode::InvalidFeeAccount);
+    }
+
+    #[test]
+    fn rejects_integrator_account_owned_by_the_user() {
+        let mut swap = TokenSwap::new();
+        let integrator_ata = swap.ledger.add(TestAccount::token(
+            Pubkey::new_unique(),
+            swap.src_mint,
+            swap.user,
+            0,
+        ));
+        let accounts = crate::accounts::TakeIntegratorFee {
+            integrator_ata,
+            volume_cap: Pubkey::find_program_address(
+                &[VOLUME_CAP_SEED, swap.user.as_ref()],
+                &crate::ID,
+            )
+            .0,
+            ..accounts(&swap)
+        };
+        run(&mut swap, accounts, percentage(2_000), 0)
+            .assert_error(errors::ErrorCode::InvalidFeeAccount);
+    }
+
+    /// The fee percentage applied under a 30 bps override for `override_mint`, or for the
+    /// source mint when `None`.
+    fn overridden_fee(override_mint: Option<Pubkey>) -> u64 {
+        let mut swap = TokenSwap::new();
+        let mut config = default_config(Pubkey::new_unique());
+        config.fee_overrides = vec![FeeOverride {
+            mint: override_mint.unwrap_or(swap.src_mint),
+            fee_percent: 30,
+        }];
+        swap.ledger.add(TestAccount::config(&config));
+        let accounts = accounts(&swap);
+        let outcome = run(&mut swap, accounts, percentage(2_000), 0);
+        outcome.assert_ok();
+
+        outcome.events::<helpers::TakeFee>()[0].fee_percent
+    }
+
+    #[test]
+    fn fee_override_replaces_the_passed_fee() {
+        assert_eq!(overridden_fee(None), 30);
+    }
+
+    #[test]
+    fn fee_override_for_another_mint_is_ignored() {
+        assert_eq!(overridden_fee(Some(Pubkey::new_unique())), FEE_PERCENT);
+    }
+
+    #[test]
+    fn rejects_zero_amount_in() {
+        let mut swap = TokenSwap::new();
+        let accounts = accounts(&swap);
+        run_amount(&mut swap, accounts, percentage(2_000), 0, 0)
+            .assert_error(errors::ErrorCode::ZeroAmount);
+    }
+
+    /// The fee would fit, but would leave the route short of `AMOUNT_IN`.
+    #[test]
+    fn rejects_a_balance_only_covering_amount_in_minus_the_fee() {
+        let mut swap = TokenSwap::new();
+        swap.ledger.add(TestAccount::ata(
+            swap.src_mint,
+            swap.user,
+            AMOUNT_IN - TOTAL_FEE,
+        ));
+        let accounts = accounts(&swap);
+        run(&mut swap, accounts, percentage(2_000), 0)
+            .assert_error(errors::ErrorCode::InsufficientBalance);
+    }
+
+    #[test]
+    fn flat_fee_is_split_regardless_of_amount_in() {
+        let mut swap = TokenSwap::new();
+        let accounts = accounts(&swap);
+        let fee_mode = FeeMode::Flat {
+            amount: 5_000,
+            share_percent: 2_000,
+        };
+        let outcome = run(&mut swap, accounts, fee_mode, 0);
+        outcome.assert_ok();
+
+        assert_eq!(swap.ledger.token_amount(&swap.unizen_src_ata), 1_000);
+        assert_eq!(swap.ledger.token_amount(&swap.integrator_src_ata), 4_000);
+        let events = outcome.events::<helpers::TakeFee>();
+        assert!(matches!(
+            events[..],
+            [helpers::TakeFee {
+                fee_mode: FeeMode::Flat { amount: 5_000, .. },
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn rejects_flat_fee_above_amount_in() {
+        let mut swap = TokenSwap::new();
+        let accounts = accounts(&swap);
+        let fee_mode = FeeMode::Flat {
+            amount: AMOUNT_IN + 1,
+            share_percent: 2_000,
+        };
+        run(&mut swap, accounts, fee_mode, 0).assert_error(errors::ErrorCode::FlatFeeExceedsAmount);
+    }
+
+    /// The tier index and fee percentage applied to `amount_in` under two size tiers.
+    fn applied_tier(amount_in: u64) -> (Option<u8>, u64) {
+        let mut swap = TokenSwap::new();
+        let mut config = default_config(Pubkey::new_unique());
+        config.fee_tiers = vec![
+            FeeTier {
+                threshold_amount: 500_000,
+                fee_percent: 50,
+            },
+            FeeTier {
+                threshold_amount: AMOUNT_IN,
+                fee_percent: 25,
+            },
+        ];
+        swap.ledger.add(TestAccount::config(&config));
+        let accounts = accounts(&swap);
+        let outcome = run_amount(&mut swap, accounts, percentage(2_000), 0, amount_in);
+        outcome.assert_ok();
+
+        let fee = &outcome.events::<helpers::TakeFee>()[0];
+        (fee.fee_tier, fee.fee_percent)
+    }
+
+    #[test]
+    fn applies_fee_tier_from_its_threshold() {
+        assert_eq!(applied_tier(AMOUNT_IN), (Some(1), 25));
+        assert_eq!(applied_tier(AMOUNT_IN - 1), (Some(0), 50));
+        assert_eq!(applied_tier(500_000), (Some(0), 50));
+    }
+
+    #[test]
+    fn falls_back_to_passed_fee_below_every_tier() {
+        assert_eq!(applied_tier(499_999), (None, 100));
+    }
+
+    /// Takes the fee with the integrator's counter and the receipt for `nonce`.
+    fn run_counted(swap: &mut TokenSwap, counter: Pubkey, nonce: u64) -> Outcome {
+        let fee_receipt = Pubkey::find_program_address(
+            &[
+                FEE_RECEIPT_SEED,
+                swap.integrator_src_ata.as_ref(),
+                &nonce.to_le_bytes(),
+            ],
+            &crate::ID,
+        )
+        .0;
+        let accounts = crate::accounts::TakeIntegratorFee {
+            fee_counter: Some(counter),
+            fee_receipt: Some(fee_receipt),
+            ..accounts(swap)
+        };
+        run(swap, accounts, percentage(2_000), 0)
+    }
+
+    #[test]
+    fn fee_nonce_counts_every_take_per_integrator() {
+        let mut swap = TokenSwap::new();
+        let counter =
+            Pubkey::find_program_address(&[FEE_COUNTER_SEED, swap.integrator.as_ref()], &crate::ID)
+                .0;
+        swap.ledger.add(TestAccount::state(
+            counter,
+            &FeeCounter { nonce: 0 },
+            8 + FeeCounter::INIT_SPACE,
+        ));
+
+        for nonce in 1..=3 {
+            let outcome = run_counted(&mut swap, counter, nonce);
+            outcome.assert_ok();
+
+            let events = outcome.events::<helpers::TakeFee>();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].nonce, Some(nonce));
+            assert_eq!(swap.ledger.get(&counter).read::<FeeCounter>().nonce, nonce);
+        }
+        assert_eq!(
+            swap.ledger.token_amount(&swap.integrator_src_ata),
+            3 * TOTAL_FEE * 4 / 5
+        );
+    }
+
+    #[test]
+    fn fee_nonce_needs_the_receipt_for_the_next_take() {
+        let mut swap = TokenSwap::new();
+        let counter =
+            Pubkey::find_program_address(&[FEE_COUNTER_SEED, swap.integrator.as_ref()], &crate::ID)
+                .0;
+        swap.ledger.add(TestAccount::state(
+            counter,
+            &FeeCounter { nonce: 4 },
+            8 + FeeCounter::INIT_SPACE,
+        ));
+
+        run_counted(&mut swap, counter, 4).assert_error(errors::ErrorCode::InvalidPda);
+        assert_eq!(swap.ledger.get(&counter).read::<FeeCounter>().nonce, 4);
+    }
+
+    fn with_min_fee(swap: &mut TokenSwap, min_fee: u64, min_fee_in_whole_tokens: bool) {
+        let mut config = default_config(Pubkey::new_unique());
+        config.min_fee = min_fee;
+        config.min_fee_in_whole_tokens = min_fee_in_whole_tokens;
+        swap.ledger.add(TestAccount::config(&config));
+    }
+
+    #[test]
+    fn min_fee_lifts_a_smaller_fee() {
+        let mut swap = TokenSwap::new();
+        with_min_fee(&mut swap, 5 * TOTAL_FEE, false);
+        let accounts = accounts(&swap);
+        run(&mut swap, accounts, percentage(2_000), 0).assert_ok();
+
+        assert_eq!(swap.ledger.token_amount(&swap.unizen_src_ata), TOTAL_FEE);
+        assert_eq!(
+            swap.ledger.token_amount(&swap.integrator_src_ata),
+            4 * TOTAL_FEE
+        );
+    }
+
+    #[test]
+    fn rejects_a_whole_token_min_fee_above_the_amount() {
+        let mut swap = TokenSwap::new();
+        // Two whole tokens of the 6 decimal mint, twice `AMOUNT_IN`.
+        with_min_fee(&mut swap, 2, true);
+        let accounts = accounts(&swap);
+        run(&mut swap, accounts, percentage(2_000), 0)
+            .assert_error(errors::ErrorCode::MinFeeExceedsAmount);
+    }
+
+    /// Takes the fee from a user holding `balance` of the discount mint, owned by `owner`,
+    /// under a 25% discount from 1_000 and a 50% discount from 10_000.
+    fn with_discount(balance: u64, owner: Option<Pubkey>) -> Outcome {
+        let mut swap = TokenSwap::new();
+        let discount_mint = swap.ledger.add(TestAccount::mint(Pubkey::new_unique(), 6));
+        let mut config = default_config(Pubkey::new_unique());
+        config.discount_mint = discount_mint;
+        config.discount_tiers = vec![
+            DiscountTier {
+                min_balance: 1_000,
+                discount_percent: 2_500,
+            },
+            DiscountTier {
+                min_balance: 10_000,
+                discount_percent: 5_000,
+            },
+        ];
+        swap.ledger.add(TestAccount::config(&config));
+        let discount_ata = swap.ledger.add(TestAccount::ata(
+            discount_mint,
+            owner.unwrap_or(swap.user),
+            balance,
+        ));
+        let accounts = crate::accounts::TakeIntegratorFee {
+            discount_ata: Some(discount_ata),
+            ..accounts(&swap)
+        };
+        run(&mut swap, accounts, percentage(2_000), 0)
+    }
+
+    /// The fee percentage applied and the fee paid when holding `balance`.
+    fn discounted_fee(balance: u64) -> (u64, u64) {
+        let outcome = with_discount(balance, None);
+        outcome.assert_ok();
+        let fee = &outcome.events::<helpers::TakeFee>()[0];
+        (
+            fee.fee_percent,
+            fee.unizen_received + fee.integrator_received,
+        )
+    }
+
+    #[test]
+    fn holding_the_discount_mint_reduces_the_fee() {
+        assert_eq!(discounted_fee(999), (100, TOTAL_FEE));
+        assert_eq!(discounted_fee(1_000), (75, 7_500));
+        assert_eq!(discounted_fee(9_999), (75, 7_500));
+        assert_eq!(discounted_fee(10_000), (50, 5_000));
+    }
+
+    #[test]
+    fn rejects_a_discount_account_of_another_owner() {
+        with_discount(10_000, Some(Pubkey::new_unique()))
+            .assert_error(errors::ErrorCode::InvalidDiscountAccount);
+    }
+
+    #[test]
+    fn rejects_a_unizen_account_of_another_fee_recipient() {
+        let mut swap = TokenSwap::new();
+        let mut config = default_config(Pubkey::new_unique());
+        config.unizen_fee_recipient = Pubkey::new_unique();
+        swap.ledger.add(TestAccount::config(&config));
+        let accounts = accounts(&swap);
+        run(&mut swap, accounts, percentage(2_000), 0)
+            .assert_error(errors::ErrorCode::WrongFeeRecipient);
+
+        assert_eq!(swap.ledger.token_amount(&swap.user_src_ata), USER_BALANCE);
+    }
+
+    /// Caps the integrator at `daily_cap`, of which `volume` is already used on `day`.
+    fn with_volume_cap(swap: &mut TokenSwap, daily_cap: u64, day: i64, volume: u64) -> Pubkey {
+        let key = accounts(swap).volume_cap;
+        let volume_cap = VolumeCap {
+            integrator: swap.integrator,
+            daily_cap,
+            day,
+            volume,
+        };
+        swap.ledger.add(TestAccount::state(
+            key,
+            &volume_cap,
+            8 + VolumeCap::INIT_SPACE,
+        ))
+    }
+
+    #[test]
+    fn volume_may_reach_the_daily_cap() {
+        let mut swap = TokenSwap::new();
+        let today = NOW / SECONDS_PER_DAY;
+        let volume_cap = with_volume_cap(&mut swap, 2 * AMOUNT_IN, today, AMOUNT_IN);
+        let accounts = accounts(&swap);
+        run(&mut swap, accounts, percentage(2_000), 0).assert_ok();
+
+        let state: VolumeCap = swap.ledger.get(&volume_cap).read();
+        assert_eq!(state.volume, 2 * AMOUNT_IN);
+    }
+
+    #[test]
+    fn rejects_volume_beyond_the_daily_cap() {
+        let mut swap = TokenSwap::new();
+        let today = NOW / SECONDS_PER_DAY;
+        with_volume_cap(&mut swap, 2 * AMOUNT_IN, today, AMOUNT_IN + 1);
+        let accounts = accounts(&swap);
+        run(&mut swap, accounts, percentage(2_000), 0)
+            .assert_error(errors::ErrorCode::VolumeCapExceeded);
+    }
+
+    #[test]
+    fn volume_starts_over_on_a_new_day() {
+        let mut swap = TokenSwap::new();
+        let today = NOW / SECONDS_PER_DAY;
+        let volume_cap = with_volume_cap(&mut swap, 2 * AMOUNT_IN, today - 1, 2 * AMOUNT_IN);
+        let accounts = accounts(&swap);
+        run(&mut swap, accounts, percentage(2_000), 0).assert_ok();
+
+        let state: VolumeCap = swap.ledger.get(&volume_cap).read();
+        assert_eq!(state.day, today);
+        assert_eq!(state.volume, AMOUNT_IN);
+    }
+
+    // Lamports per whole source token, so a token unit pays a thousand lamports.
+    const SOL_PRICE: u64 = 1_000_000_000;
+
+    /// Pays the fee in SOL, priced by a feed for `mint` published at `publish_time` and
+    /// stored at the source mint's SOL fee price PDA.
+    fn with_sol_fee(
+        swap: &mut TokenSwap,
+        mint: Pubkey,
+        publish_time: i64,
+    ) -> crate::accounts::TakeIntegratorFee {
+        let feed_address =
+            Pubkey::find_program_address(&[SOL_FEE_PRICE_SEED, swap.src_mint.as_ref()], &crate::ID)
+                .0;
+        with_sol_fee_at(swap, feed_address, mint, publish_time)
+    }
+
+    fn with_sol_fee_at(
+        swap: &mut TokenSwap,
+        feed_address: Pubkey,
+        mint: Pubkey,
+        publish_time: i64,
+    ) -> crate::accounts::TakeIntegratorFee {
+        let price_feed = swap.ledger.add(TestAccount::state(
+            feed_address,
+            &PriceFeed {
+                mint,
+                decimals: 6,
+                price: SOL_PRICE,
+                publish_time,
+            },
+            8 + PriceFeed::INIT_SPACE,
+        ));
+        let unizen = swap.ledger.add(TestAccount::wallet(UNIZEN, 1_000_000_000));
+        crate::accounts::TakeIntegratorFee {
+            fee_price_feed: Some(price_feed),
+            unizen: Some(unizen),
+            integrator: Some(swap.integrator),
+            ..accounts(swap)
+        }
+    }
+
+    #[test]
+    fn pays_fee_in_sol_at_the_feed_price() {
+        let mut swap = TokenSwap::new();
+        let src_mint = swap.src_mint;
+        let accounts = with_sol_fee(&mut swap, src_mint, NOW);
+        let before = swap.ledger.clone();
+        run(&mut swap, accounts, percentage(2_000), 0).assert_ok();
+
+        let ledger = &swap.ledger;
+        let lamports_gained = |key: &Pubkey| ledger.lamports(key) - before.lamports(key);
+        assert_eq!(lamports_gained(&UNIZEN), 2_000 * 1_000);
+        assert_eq!(lamports_gained(&swap.integrator), 8_000 * 1_000);
+        assert_eq!(
+            before.lamports(&swap.user) - ledger.lamports(&swap.user),
+            TOTAL_FEE * 1_000
+        );
+        assert_eq!(ledger.token_amount(&swap.user_src_ata), USER_BALANCE);
+        assert_eq!(ledger.token_amount(&swap.unizen_src_ata), 0);
+    }
+
+    #[test]
+    fn rejects_a_price_feed_for_another_mint() {
+        let mut swap = TokenSwap::new();
+        let dst_mint = swap.dst_mint;
+        let accounts = with_sol_fee(&mut swap, dst_mint, NOW);
+        run(&mut swap, accounts, percentage(2_000), 0)
+            .assert_error(errors::ErrorCode::MintMismatch);
+    }
+
+    #[test]
+    fn rejects_a_price_feed_outside_the_sol_fee_seeds() {
+        let mut swap = TokenSwap::new();
+        let src_mint = swap.src_mint;
+        let accounts = with_sol_fee_at(&mut swap, Pubkey::new_unique(), src_mint, NOW);
+        let outcome = run(&mut swap, accounts, percentage(2_000), 0);
+        assert_eq!(
+            outcome.result,
+            Err(ProgramError::Custom(
+                anchor_lang::error::ErrorCode::ConstraintSeeds.into()
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_a_stale_price_feed() {
+        let mut swap = TokenSwap::new();
+        let src_mint = swap.src_mint;
+        let accounts = with_sol_fee(&mut swap, src_mint, NOW - MAX_PRICE_AGE - 1);
+        run(&mut swap, accounts, percentage(2_000), 0).assert_error(errors::ErrorCode::StalePrice);
+    }
+
+    /// Takes the fee with the fixture's user exempt when `exempt`, another user otherwise.
+    fn with_fee_tolerance(swap: &mut TokenSwap, tolerance_bps: u64) {
+        let mut config = default_config(Pubkey::new_unique());
+        config.integrator_fee_tolerance_bps = Some(tolerance_bps);
+        swap.ledger.add(TestAccount::config(&config));
+    }
+
+    #[test]
+    fn rejects_integrator_fee_withheld_beyond_the_tolerance() {
+        let mut swap = TokenSwap::new();
+        with_fee_tolerance(&mut swap, 99);
+        let accounts = with_transfer_fee(&mut swap);
+        run(&mut swap, accounts, percentage(2_000), 0)
+            .assert_error(errors::ErrorCode::FeeNotReceived);
+    }
+
+    #[test]
+    fn accepts_integrator_fee_withheld_within_the_tolerance() {
+        let mut swap = TokenSwap::new();
+        with_fee_tolerance(&mut swap, 100);
+        let accounts = with_transfer_fee(&mut swap);
+        let integrator_ata = accounts.integrator_ata;
+        run(&mut swap, accounts, percentage(2_000), 0).assert_ok();
+
+        assert_eq!(swap.ledger.token_amount(&integrator_ata), 7_920);
+    }
+
+    fn with_exempt_user(exempt: bool) -> (TokenSwap, Outcome) {
+        let mut swap = TokenSwap::new();
+        let mut config = default_config(Pubkey::new_unique());
+        config.fee_exempt_users = vec![if exempt {
+            swap.user
+        } else {
+            Pubkey::new_unique()
+        }];
+        swap.ledger.add(TestAccount::config(&config));
+        let accounts = accounts(&swap);
+        let outcome = run(&mut swap, accounts, percentage(2_000), 0);
+        (swap, outcome)
+    }
+
+    #[test]
+    fn exempt_user_pays_no_fee() {
+        let (swap, outcome) = with_exempt_user(true);
+        outcome.assert_ok();
+
+        assert_eq!(outcome.events::<helpers::FeeWaived>().len(), 1);
+        assert_eq!(swap.ledger.token_amount(&swap.user_src_ata), USER_BALANCE);
+        assert_eq!(swap.ledger.token_amount(&swap.unizen_src_ata), 0);
+        assert_eq!(swap.ledger.token_amount(&swap.integrator_src_ata), 0);
+    }
+
+    #[test]
+    fn other_users_still_pay_the_fee() {
+        let (swap, outcome) = with_exempt_user(false);
+        outcome.assert_ok();
+
+        assert!(outcome.events::<helpers::FeeWaived>().is_empty());
+        assert_eq!(
+            swap.ledger.token_amount(&swap.user_src_ata),
+            USER_BALANCE - TOTAL_FEE
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    constants::*,
+    errors, helpers,
+    state::{FeeCounter, FeeMode},
+};
+
+pub fn take_integrator_fee_sponsored(
+    ctx: Context<TakeIntegratorFeeSponsored>,
+    amount_in: u64,
+    fee_mode: FeeMode,
+    referrer_percent: u64,
+) -> Result<()> {
+    let config = helpers::load_config(&ctx.accounts.config)?;
+    helpers::assert_amount_in(amount_in)?;
+
+    let nonce = helpers::next_fee_nonce(&mut ctx.accounts.fee_counter, &config)?;
+    helpers::track_daily_volume(&ctx.accounts.volume_cap, amount_in)?;
+
+    helpers::take_integrator_fee(
+        helpers::AccountsForFee {
+            user: ctx.accounts.user.to_account_info(),
+            fee_payer: Some(helpers::FeePayer::signer(&ctx.accounts.fee_payer)),
+            token_program: ctx.accounts.token_program.clone(),
+            mint: ctx.accounts.token.clone(),
+            user_token_account: ctx.accounts.fee_payer_ata.clone(),
+            unizen_token_account: ctx.accounts.unizen_ata.clone(),
+            integrator_token_account: ctx.accounts.integrator_ata.clone(),
+            referrer_token_account: ctx.accounts.referrer_ata.clone(),
+            discount_token_account: ctx.accounts.discount_ata.clone(),
+            config,
+            fee_receipt: ctx.accounts.fee_receipt.clone(),
+            system_program: ctx.accounts.system_program.clone(),
+            sol_fee: None,
+        },
+        amount_in,
+        fee_mode,
+        referrer_percent,
+        nonce,
+        None,
+    )?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct TakeIntegratorFeeSponsored<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+    pub token: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = fee_payer_ata.mint == token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub fee_payer_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = token,
+        token::token_program = token_program
+    )]
+    pub unizen_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = integrator_ata.mint == token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub integrator_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = referrer_ata.mint == token.key() @ errors::ErrorCode::MintMismatch
+    )]
+    pub referrer_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Checked against `Config.discount_mint` and the user by `take_integrator_fee`.
+    pub discount_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Read with `load_config`; may not be initialized.
+    #[account(seeds = [CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [FEE_COUNTER_SEED, integrator_ata.owner.as_ref()],
+        bump
+    )]
+    pub fee_counter: Option<Account<'info, FeeCounter>>,
+    /// CHECK: Read by `track_daily_volume`; may not be initialized.
+    #[account(
+        mut,
+        seeds = [VOLUME_CAP_SEED, integrator_ata.owner.as_ref()],
+        bump
+    )]
+    pub volume_cap: UncheckedAccount<'info>,
+    /// CHECK: Created and validated by `record_fee_receipt`.
+    #[account(mut)]
+    pub fee_receipt: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::test_harness::{Outcome, TestAccount, TokenSwap, USER_BALANCE};
+
+    const AMOUNT_IN: u64 = 1_000_000;
+    const SPONSOR_BALANCE: u64 = 50_000;
+
+    /// Takes a 1% fee, a fifth of it to Unizen, paid by `fee_payer` from `fee_payer_ata`.
+    fn run(swap: &mut TokenSwap, fee_payer: Pubkey, fee_payer_ata: Pubkey) -> Outcome {
+        swap.ledger.run(
+            crate::accounts::TakeIntegratorFeeSponsored {
+                user: swap.user,
+                fee_payer,
+                token: swap.src_mint,
+                fee_payer_ata,
+                unizen_ata: swap.unizen_src_ata,
+                integrator_ata: swap.integrator_src_ata,
+                referrer_ata: None,
+                discount_ata: None,
+                config: crate::pda::find_config().0,
+                fee_counter: None,
+                volume_cap: Pubkey::find_program_address(
+                    &[VOLUME_CAP_SEED, swap.integrator.as_ref()],
+                    &crate::ID,
+                )
+                .0,
+                fee_receipt: None,
+                token_program: token::ID,
+                system_program: system_program::ID,
+            },
+            &[],
+            crate::instruction::TakeIntegratorFeeSponsored {
+                amount_in: AMOUNT_IN,
+                fee_mode: FeeMode::Percentage {
+                    fee_percent: 100,
+                    share_percent: 2_000,
+                },
+                referrer_percent: 0,
+            },
+        )
+    }
+
+    /// Adds a sponsor holding `SPONSOR_BALANCE` of the source token.
+    fn sponsor(swap: &mut TokenSwap) -> (Pubkey, Pubkey) {
+        let sponsor = swap
+            .ledger
+            .add(TestAccount::wallet(Pubkey::new_unique(), 1_000_000_000));
+        let sponsor_ata =
+            swap.ledger
+                .add(TestAccount::ata(swap.src_mint, sponsor, SPONSOR_BALANCE));
+        (sponsor, sponsor_ata)
+    }
+
+    #[test]
+    fn sponsor_pays_fee_instead_of_user() {
+        let mut swap = TokenSwap::new();
+        let (sponsor, sponsor_ata) = sponsor(&mut swap);
+        run(&mut swap, sponsor, sponsor_ata).assert_ok();
+
+        let ledger = &swap.ledger;
+        assert_eq!(ledger.token_amount(&sponsor_ata), SPONSOR_BALANCE - 10_000);
+        assert_eq!(ledger.token_amount(&swap.user_src_ata), USER_BALANCE);
+        assert_eq!(ledger.token_amount(&swap.unizen_src_ata), 2_000);
+        assert_eq!(ledger.token_amount(&swap.integrator_src_ata), 8_000);
+    }
+
+    #[test]
+    fn rejects_fee_source_not_owned_by_fee_payer() {
+        let mut swap = TokenSwap::new();
+        let (sponsor, _) = sponsor(&mut swap);
+        let user_src_ata = swap.user_src_ata;
+        run(&mut swap, sponsor, user_src_ata).assert_error(errors::ErrorCode::InvalidFeePayer);
+
+        assert_eq!(swap.ledger.token_amount(&swap.user_src_ata), USER_BALANCE);
+    }
+}
@@ -0,0 +1,33 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    constants::*,
+    errors,
+    state::{Config, PriceFeed},
+};
+
+/// Posts the authority's reference price for the feed's mint, usable for `MAX_PRICE_AGE`.
+pub fn update_price_feed(ctx: Context<UpdatePriceFeed>, price: u64) -> Result<()> {
+    let price_feed = &mut ctx.accounts.price_feed;
+    price_feed.price = price;
+    price_feed.publish_time = Clock::get()?.unix_timestamp;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdatePriceFeed<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [SOL_FEE_PRICE_SEED, price_feed.mint.as_ref()],
+        bump
+    )]
+    pub price_feed: Account<'info, PriceFeed>,
+}
@@ -0,0 +1,92 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{self, TokenAccount, TokenInterface};
+
+use crate::{constants::*, errors, helpers};
+
+/// Closes the user's wSOL deposit account, returning the wrapped SOL and its rent to them.
+pub fn withdraw_wsol_deposit(ctx: Context<WithdrawWsolDeposit>) -> Result<()> {
+    let user_key = ctx.accounts.user.key();
+    let deposit_bump = helpers::bump_seed(ctx.bumps.wsol_deposit);
+
+    msg!(
+        "Withdraw {} wSOL from deposit",
+        ctx.accounts.wsol_deposit.amount
+    );
+    token_interface::close_account(CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        token_interface::CloseAccount {
+            account: ctx.accounts.wsol_deposit.to_account_info(),
+            destination: ctx.accounts.user.to_account_info(),
+            authority: ctx.accounts.wsol_deposit.to_account_info(),
+        },
+        &[&[WSOL_DEPOSIT_SEED, user_key.as_ref(), &deposit_bump]],
+    ))
+}
+
+#[derive(Accounts)]
+pub struct WithdrawWsolDeposit<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [WSOL_DEPOSIT_SEED, user.key().as_ref()],
+        bump,
+        token::mint = NATIVE_MINT,
+        constraint = wsol_deposit.owner == wsol_deposit.key() @ errors::ErrorCode::IncorrectOwner
+    )]
+    pub wsol_deposit: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_spl::token;
+
+    use super::*;
+    use crate::test_harness::{rent, Ledger, Outcome, TestAccount};
+
+    const AMOUNT: u64 = 1_000_000;
+
+    fn deposit(user: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[WSOL_DEPOSIT_SEED, user.as_ref()], &crate::ID).0
+    }
+
+    /// Withdraws `user`'s deposit, which holds `AMOUNT` and is held by `authority`.
+    fn run(user: Pubkey, authority: Pubkey) -> (Ledger, Outcome) {
+        let wsol_deposit = deposit(&user);
+        let mut ledger = Ledger::new([
+            TestAccount::wallet(user, 0),
+            TestAccount::native(wsol_deposit, authority, AMOUNT),
+        ]);
+        let outcome = ledger.run(
+            crate::accounts::WithdrawWsolDeposit {
+                user,
+                wsol_deposit,
+                token_program: token::ID,
+            },
+            &[],
+            crate::instruction::WithdrawWsolDeposit {},
+        );
+        (ledger, outcome)
+    }
+
+    #[test]
+    fn returns_the_deposit_to_its_user() {
+        let user = Pubkey::new_unique();
+        let wsol_deposit = deposit(&user);
+        let (ledger, outcome) = run(user, wsol_deposit);
+        outcome.assert_ok();
+
+        assert_eq!(ledger.lamports(&wsol_deposit), 0);
+        assert_eq!(
+            ledger.lamports(&user),
+            AMOUNT + rent(token::TokenAccount::LEN)
+        );
+    }
+
+    #[test]
+    fn rejects_deposit_held_by_another_authority() {
+        let (_, outcome) = run(Pubkey::new_unique(), Pubkey::new_unique());
+        outcome.assert_error(errors::ErrorCode::IncorrectOwner);
+    }
+}
@@ -0,0 +1,101 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, errors, state::RouteBuffer};
+
+pub fn write_route_buffer(
+    ctx: Context<WriteRouteBuffer>,
+    offset: u32,
+    chunk: Vec<u8>,
+) -> Result<()> {
+    let data = &mut ctx.accounts.route_buffer.data;
+    let start = offset as usize;
+    let end = start
+        .checked_add(chunk.len())
+        .ok_or_else(|| error!(errors::ErrorCode::RouteBufferOverflow))?;
+    if end > data.len() {
+        return err!(errors::ErrorCode::RouteBufferOverflow);
+    }
+
+    data[start..end].copy_from_slice(&chunk);
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct WriteRouteBuffer<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [ROUTE_BUFFER_SEED, authority.key().as_ref()],
+        bump,
+        has_one = authority @ errors::ErrorCode::Unauthorized
+    )]
+    pub route_buffer: Account<'info, RouteBuffer>,
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::system_program;
+
+    use super::*;
+    use crate::test_harness::{Ledger, Outcome, TestAccount};
+
+    const CHUNK: usize = 400;
+
+    fn route_buffer(authority: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[ROUTE_BUFFER_SEED, authority.as_ref()], &crate::ID).0
+    }
+
+    /// A ledger with `authority`'s route buffer initialized to `len` bytes.
+    fn init(authority: Pubkey, len: u32) -> Ledger {
+        let mut ledger = Ledger::new([TestAccount::wallet(authority, 1_000_000_000)]);
+        ledger
+            .run(
+                crate::accounts::InitRouteBuffer {
+                    authority,
+                    route_buffer: route_buffer(&authority),
+                    system_program: system_program::ID,
+                },
+                &[],
+                crate::instruction::InitRouteBuffer { len },
+            )
+            .assert_ok();
+        ledger
+    }
+
+    fn write(ledger: &mut Ledger, authority: Pubkey, offset: u32, chunk: &[u8]) -> Outcome {
+        ledger.run(
+            crate::accounts::WriteRouteBuffer {
+                authority,
+                route_buffer: route_buffer(&authority),
+            },
+            &[],
+            crate::instruction::WriteRouteBuffer {
+                offset,
+                chunk: chunk.to_vec(),
+            },
+        )
+    }
+
+    #[test]
+    fn round_trips_route_larger_than_a_kilobyte() {
+        let authority = Pubkey::new_unique();
+        let route: Vec<u8> = (0..1_200u32).map(|byte| byte as u8).collect();
+        let mut ledger = init(authority, route.len() as u32);
+        for (index, chunk) in route.chunks(CHUNK).enumerate() {
+            write(&mut ledger, authority, (index * CHUNK) as u32, chunk).assert_ok();
+        }
+
+        let buffer: RouteBuffer = ledger.get(&route_buffer(&authority)).read();
+        assert_eq!(buffer.authority, authority);
+        assert_eq!(buffer.data, route);
+    }
+
+    #[test]
+    fn rejects_chunk_past_the_end() {
+        let authority = Pubkey::new_unique();
+        let mut ledger = init(authority, CHUNK as u32);
+        write(&mut ledger, authority, 1, &[1; CHUNK])
+            .assert_error(errors::ErrorCode::RouteBufferOverflow);
+    }
+}
@@ -4,6 +4,13 @@ mod constants;
 mod errors;
 mod helpers;
 mod instructions;
+pub mod pda;
+mod state;
+#[cfg(test)]
+mod test_harness;
+mod version;
+
+pub use pda::*;
 
 declare_id!("BUCtBoPAL3YDq7sv5LXQeCF977862G4AmDqgf56qHSTM");
 
@@ -14,72 +21,379 @@ pub mod unizen_aggr {
 
     pub fn swap_tokens_for_sol(
         ctx: Context<SwapTokensForSol>,
-        amount_in: u64,
-        amount_out_min: u64,
-        fee_percent: u64,
-        share_percent: u64,
+        args: SwapTokensForSolArgs,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::swap_tokens_for_sol(ctx, args, data)
+    }
+
+    pub fn swap_tokens_for_program_wsol(
+        ctx: Context<SwapTokensForProgramWsol>,
+        args: SwapTokensForProgramWsolArgs,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::swap_tokens_for_program_wsol(ctx, args, data)
+    }
+
+    pub fn swap_tokens_for_wsol(
+        ctx: Context<SwapTokensForWsol>,
+        args: SwapTokensForWsolArgs,
         data: Vec<u8>,
     ) -> Result<()> {
-        instructions::swap_tokens_for_sol(
-            ctx,
-            amount_in,
-            amount_out_min,
-            fee_percent,
-            share_percent,
-            data,
-        )
+        instructions::swap_tokens_for_wsol(ctx, args, data)
     }
 
     pub fn swap_sol_for_tokens(
         ctx: Context<SwapSolForTokens>,
-        amount_in: u64,
-        amount_out_min: u64,
-        fee_percent: u64,
-        share_percent: u64,
+        args: SwapSolForTokensArgs,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::swap_sol_for_tokens(ctx, args, data)
+    }
+
+    pub fn swap_sol_for_tokens_percent(
+        ctx: Context<SwapSolForTokens>,
+        args: SwapSolForTokensPercentArgs,
         data: Vec<u8>,
     ) -> Result<()> {
-        instructions::swap_sol_for_tokens(
-            ctx,
-            amount_in,
-            amount_out_min,
-            fee_percent,
-            share_percent,
-            data,
-        )
+        instructions::swap_sol_for_tokens_percent(ctx, args, data)
     }
 
     pub fn swap_tokens_for_tokens(
         ctx: Context<SwapTokensForTokens>,
-        amount_in: u64,
-        amount_out_min: u64,
-        fee_percent: u64,
-        share_percent: u64,
+        args: SwapTokensForTokensArgs,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::swap_tokens_for_tokens(ctx, args, data)
+    }
+
+    pub fn swap_tokens_for_tokens_orca(
+        ctx: Context<SwapTokensForTokensOrca>,
+        args: SwapTokensForTokensOrcaArgs,
         data: Vec<u8>,
     ) -> Result<()> {
-        instructions::swap_tokens_for_tokens(
-            ctx,
-            amount_in,
-            amount_out_min,
-            fee_percent,
-            share_percent,
-            data,
-        )
+        instructions::swap_tokens_for_tokens_orca(ctx, args, data)
+    }
+
+    pub fn swap_tokens_for_multiple_tokens(
+        ctx: Context<SwapTokensForMultipleTokens>,
+        args: SwapTokensForMultipleTokensArgs,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::swap_tokens_for_multiple_tokens(ctx, args, data)
+    }
+
+    pub fn swap_batch(ctx: Context<SwapBatch>, args: SwapBatchArgs) -> Result<()> {
+        instructions::swap_batch(ctx, args)
+    }
+
+    pub fn swap_tokens_for_tokens_weighted(
+        ctx: Context<SwapTokensForTokensWeighted>,
+        args: SwapTokensForTokensWeightedArgs,
+    ) -> Result<()> {
+        instructions::swap_tokens_for_tokens_weighted(ctx, args)
+    }
+
+    pub fn swap_tokens_for_tokens_no_fee(
+        ctx: Context<SwapTokensForTokensNoFee>,
+        args: SwapTokensForTokensNoFeeArgs,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::swap_tokens_for_tokens_no_fee(ctx, args, data)
     }
 
     pub fn take_integrator_fee(
         ctx: Context<TakeIntegratorFee>,
         amount_in: u64,
-        fee_percent: u64,
-        share_percent: u64,
+        fee_mode: state::FeeMode,
+        referrer_percent: u64,
+        share_mode: Option<state::ShareMode>,
+    ) -> Result<()> {
+        instructions::take_integrator_fee(ctx, amount_in, fee_mode, referrer_percent, share_mode)
+    }
+
+    pub fn take_fee_and_route(
+        ctx: Context<TakeFeeAndRoute>,
+        args: TakeFeeAndRouteArgs,
+        data: Vec<u8>,
     ) -> Result<()> {
-        instructions::take_integrator_fee(ctx, amount_in, fee_percent, share_percent)
+        instructions::take_fee_and_route(ctx, args, data)
+    }
+
+    pub fn take_integrator_fee_sponsored(
+        ctx: Context<TakeIntegratorFeeSponsored>,
+        amount_in: u64,
+        fee_mode: state::FeeMode,
+        referrer_percent: u64,
+    ) -> Result<()> {
+        instructions::take_integrator_fee_sponsored(ctx, amount_in, fee_mode, referrer_percent)
     }
 
     pub fn create_program_wsol_idempotent(ctx: Context<CreateWsolTokenIdempotent>) -> Result<()> {
         instructions::create_program_wsol_idempotent(ctx)
     }
 
+    pub fn fund_program_wsol(ctx: Context<FundProgramWsol>, amount: u64) -> Result<()> {
+        instructions::fund_program_wsol(ctx, amount)
+    }
+
+    pub fn withdraw_wsol_deposit(ctx: Context<WithdrawWsolDeposit>) -> Result<()> {
+        instructions::withdraw_wsol_deposit(ctx)
+    }
+
+    pub fn swap_wsol_deposit_for_tokens(
+        ctx: Context<SwapWsolDepositForTokens>,
+        args: SwapWsolDepositForTokensArgs,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::swap_wsol_deposit_for_tokens(ctx, args, data)
+    }
+
+    pub fn create_unizen_ata_idempotent(ctx: Context<CreateUnizenAtaIdempotent>) -> Result<()> {
+        instructions::create_unizen_ata_idempotent(ctx)
+    }
+
+    pub fn create_fee_atas_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateFeeAtasBatch<'info>>,
+    ) -> Result<()> {
+        instructions::create_fee_atas_batch(ctx)
+    }
+
     pub fn close_program_wsol(ctx: Context<CloseProgramWsol>) -> Result<()> {
         instructions::close_program_wsol(ctx)
     }
+
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        instructions::initialize_config(ctx)
+    }
+
+    pub fn close_config(ctx: Context<CloseConfig>) -> Result<()> {
+        instructions::close_config(ctx)
+    }
+
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        instructions::migrate_config(ctx)
+    }
+
+    pub fn set_privileged(ctx: Context<SetPrivileged>, privileged: Vec<Pubkey>) -> Result<()> {
+        instructions::set_privileged(ctx, privileged)
+    }
+
+    pub fn set_wsol_closers(ctx: Context<SetWsolClosers>, wsol_closers: Vec<Pubkey>) -> Result<()> {
+        instructions::set_wsol_closers(ctx, wsol_closers)
+    }
+
+    pub fn set_fee_denom(ctx: Context<SetFeeDenom>, fee_denom: u64) -> Result<()> {
+        instructions::set_fee_denom(ctx, fee_denom)
+    }
+
+    pub fn set_emit_events(ctx: Context<SetEmitEvents>, emit_events: bool) -> Result<()> {
+        instructions::set_emit_events(ctx, emit_events)
+    }
+
+    pub fn set_profiling(ctx: Context<SetProfiling>, profiling: bool) -> Result<()> {
+        instructions::set_profiling(ctx, profiling)
+    }
+
+    pub fn set_strict_cleanup(ctx: Context<SetStrictCleanup>, strict_cleanup: bool) -> Result<()> {
+        instructions::set_strict_cleanup(ctx, strict_cleanup)
+    }
+
+    pub fn set_wsol_dust_sweep(
+        ctx: Context<SetWsolDustSweep>,
+        threshold: u64,
+        to_unizen: bool,
+    ) -> Result<()> {
+        instructions::set_wsol_dust_sweep(ctx, threshold, to_unizen)
+    }
+
+    pub fn add_fee_exempt_user(ctx: Context<AddFeeExemptUser>, user: Pubkey) -> Result<()> {
+        instructions::add_fee_exempt_user(ctx, user)
+    }
+
+    pub fn remove_fee_exempt_user(ctx: Context<RemoveFeeExemptUser>, user: Pubkey) -> Result<()> {
+        instructions::remove_fee_exempt_user(ctx, user)
+    }
+
+    pub fn add_allowed_router(ctx: Context<AddAllowedRouter>, router: Pubkey) -> Result<()> {
+        instructions::add_allowed_router(ctx, router)
+    }
+
+    pub fn remove_allowed_router(ctx: Context<RemoveAllowedRouter>, router: Pubkey) -> Result<()> {
+        instructions::remove_allowed_router(ctx, router)
+    }
+
+    pub fn set_min_fee(ctx: Context<SetMinFee>, min_fee: u64, in_whole_tokens: bool) -> Result<()> {
+        instructions::set_min_fee(ctx, min_fee, in_whole_tokens)
+    }
+
+    pub fn set_stranded_output_epsilon(
+        ctx: Context<SetStrandedOutputEpsilon>,
+        stranded_output_epsilon: u64,
+    ) -> Result<()> {
+        instructions::set_stranded_output_epsilon(ctx, stranded_output_epsilon)
+    }
+
+    pub fn init_volume_cap(ctx: Context<InitVolumeCap>, daily_cap: u64) -> Result<()> {
+        instructions::init_volume_cap(ctx, daily_cap)
+    }
+
+    pub fn set_volume_cap(ctx: Context<SetVolumeCap>, daily_cap: u64) -> Result<()> {
+        instructions::set_volume_cap(ctx, daily_cap)
+    }
+
+    pub fn init_route_buffer(ctx: Context<InitRouteBuffer>, len: u32) -> Result<()> {
+        instructions::init_route_buffer(ctx, len)
+    }
+
+    pub fn write_route_buffer(
+        ctx: Context<WriteRouteBuffer>,
+        offset: u32,
+        chunk: Vec<u8>,
+    ) -> Result<()> {
+        instructions::write_route_buffer(ctx, offset, chunk)
+    }
+
+    pub fn close_route_buffer(ctx: Context<CloseRouteBuffer>) -> Result<()> {
+        instructions::close_route_buffer(ctx)
+    }
+
+    pub fn init_price_feed(ctx: Context<InitPriceFeed>) -> Result<()> {
+        instructions::init_price_feed(ctx)
+    }
+
+    pub fn update_price_feed(ctx: Context<UpdatePriceFeed>, price: u64) -> Result<()> {
+        instructions::update_price_feed(ctx, price)
+    }
+
+    pub fn set_fee_override(
+        ctx: Context<SetFeeOverride>,
+        mint: Pubkey,
+        fee_percent: u64,
+    ) -> Result<()> {
+        instructions::set_fee_override(ctx, mint, fee_percent)
+    }
+
+    pub fn clear_fee_override(ctx: Context<ClearFeeOverride>, mint: Pubkey) -> Result<()> {
+        instructions::clear_fee_override(ctx, mint)
+    }
+
+    pub fn set_fee_tiers(ctx: Context<SetFeeTiers>, fee_tiers: Vec<state::FeeTier>) -> Result<()> {
+        instructions::set_fee_tiers(ctx, fee_tiers)
+    }
+
+    pub fn set_discount_tiers(
+        ctx: Context<SetDiscountTiers>,
+        discount_mint: Pubkey,
+        discount_tiers: Vec<state::DiscountTier>,
+    ) -> Result<()> {
+        instructions::set_discount_tiers(ctx, discount_mint, discount_tiers)
+    }
+
+    pub fn init_fee_counter(ctx: Context<InitFeeCounter>) -> Result<()> {
+        instructions::init_fee_counter(ctx)
+    }
+
+    pub fn migrate_wsol_authority(ctx: Context<MigrateWsolAuthority>) -> Result<()> {
+        instructions::migrate_wsol_authority(ctx)
+    }
+
+    pub fn close_program_atas<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CloseProgramAtas<'info>>,
+        force: bool,
+    ) -> Result<()> {
+        instructions::close_program_atas(ctx, force)
+    }
+
+    pub fn get_version(ctx: Context<GetVersion>) -> Result<version::ProgramVersion> {
+        instructions::get_version(ctx)
+    }
+
+    pub fn get_accrued_fees(ctx: Context<GetAccruedFees>) -> Result<u64> {
+        instructions::get_accrued_fees(ctx)
+    }
+
+    pub fn close_idempotency_record(ctx: Context<CloseIdempotencyRecord>) -> Result<()> {
+        instructions::close_idempotency_record(ctx)
+    }
+
+    pub fn approve_allowance(
+        ctx: Context<ApproveAllowance>,
+        remaining_budget: u64,
+        per_swap_cap: u64,
+    ) -> Result<()> {
+        instructions::approve_allowance(ctx, remaining_budget, per_swap_cap)
+    }
+
+    pub fn swap_tokens_for_tokens_with_allowance(
+        ctx: Context<SwapTokensForTokensWithAllowance>,
+        args: SwapTokensForTokensWithAllowanceArgs,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::swap_tokens_for_tokens_with_allowance(ctx, args, data)
+    }
+
+    pub fn approve_fee_clawback(ctx: Context<ApproveFeeClawback>, amount: u64) -> Result<()> {
+        instructions::approve_fee_clawback(ctx, amount)
+    }
+
+    pub fn clawback_fee(ctx: Context<ClawbackFee>, nonce: u64, amount: u64) -> Result<()> {
+        instructions::clawback_fee(ctx, nonce, amount)
+    }
+
+    pub fn close_fee_receipt(ctx: Context<CloseFeeReceipt>) -> Result<()> {
+        instructions::close_fee_receipt(ctx)
+    }
+
+    pub fn set_slippage_protection(
+        ctx: Context<SetSlippageProtection>,
+        require_slippage_protection: bool,
+    ) -> Result<()> {
+        instructions::set_slippage_protection(ctx, require_slippage_protection)
+    }
+
+    pub fn set_integrator_fee_tolerance(
+        ctx: Context<SetIntegratorFeeTolerance>,
+        integrator_fee_tolerance_bps: Option<u64>,
+    ) -> Result<()> {
+        instructions::set_integrator_fee_tolerance(ctx, integrator_fee_tolerance_bps)
+    }
+
+    pub fn set_quote_signer(
+        ctx: Context<SetQuoteSigner>,
+        quote_signer: Pubkey,
+        require_signed_quote: bool,
+    ) -> Result<()> {
+        instructions::set_quote_signer(ctx, quote_signer, require_signed_quote)
+    }
+
+    pub fn set_clawback_window(
+        ctx: Context<SetClawbackWindow>,
+        clawback_window: i64,
+    ) -> Result<()> {
+        instructions::set_clawback_window(ctx, clawback_window)
+    }
+
+    pub fn set_unizen_fee_recipient(
+        ctx: Context<SetUnizenFeeRecipient>,
+        unizen_fee_recipient: Pubkey,
+    ) -> Result<()> {
+        instructions::set_unizen_fee_recipient(ctx, unizen_fee_recipient)
+    }
+
+    pub fn swap_tokens_for_tokens_exact_out(
+        ctx: Context<SwapTokensForTokensExactOut>,
+        args: SwapTokensForTokensExactOutArgs,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::swap_tokens_for_tokens_exact_out(ctx, args, data)
+    }
+
+    pub fn swap_tokens_for_tokens_private(
+        ctx: Context<SwapTokensForTokensPrivate>,
+        args: SwapTokensForTokensPrivateArgs,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::swap_tokens_for_tokens_private(ctx, args, data)
+    }
 }
@@ -0,0 +1,45 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+
+pub fn find_program_authority() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[AUTHORITY_SEED], &crate::ID)
+}
+
+pub fn find_program_wsol() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[WSOL_SEED], &crate::ID)
+}
+
+pub fn find_config() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], &crate::ID)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finders_match_the_seeds() {
+        for (seed, (address, bump)) in [
+            (AUTHORITY_SEED, find_program_authority()),
+            (WSOL_SEED, find_program_wsol()),
+            (CONFIG_SEED, find_config()),
+        ] {
+            assert_eq!(
+                Pubkey::create_program_address(&[seed, &[bump]], &crate::ID).unwrap(),
+                address
+            );
+            assert!(!address.is_on_curve());
+        }
+    }
+
+    #[test]
+    fn finders_are_distinct() {
+        let authority = find_program_authority().0;
+        let wsol = find_program_wsol().0;
+        let config = find_config().0;
+        assert_ne!(authority, wsol);
+        assert_ne!(authority, config);
+        assert_ne!(wsol, config);
+    }
+}
@@ -0,0 +1,279 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    pub authority: Pubkey,
+    #[max_len(MAX_PRIVILEGED)]
+    pub privileged: Vec<Pubkey>,
+    pub fee_denom: u64,
+    #[max_len(MAX_FEE_OVERRIDES)]
+    pub fee_overrides: Vec<FeeOverride>,
+    #[max_len(MAX_FEE_TIERS)]
+    pub fee_tiers: Vec<FeeTier>,
+    #[max_len(MAX_WSOL_CLOSERS)]
+    pub wsol_closers: Vec<Pubkey>,
+    /// When false, instructions skip their events to save compute and log space.
+    pub emit_events: bool,
+    /// Set for the duration of the Jupiter CPI; a swap finding it set is a reentrant call.
+    pub reentrancy_locked: bool,
+    pub min_fee: u64,
+    /// When true, `min_fee` is in whole tokens and scaled by the source mint's decimals.
+    pub min_fee_in_whole_tokens: bool,
+    /// Route output that may stay behind in a shared account after forwarding.
+    pub stranded_output_epsilon: u64,
+    /// Owner of the accounts Unizen's fee share is paid to, see `set_unizen_fee_recipient`.
+    pub unizen_fee_recipient: Pubkey,
+    /// Logs compute units at the start and end of each swap phase.
+    pub profiling: bool,
+    #[max_len(MAX_FEE_EXEMPT_USERS)]
+    pub fee_exempt_users: Vec<Pubkey>,
+    /// Programs `take_fee_and_route` may CPI into.
+    #[max_len(MAX_ALLOWED_ROUTERS)]
+    pub allowed_routers: Vec<Pubkey>,
+    /// wSOL left in the user account after `swap_sol_for_tokens` up to this amount is swept.
+    pub wsol_dust_threshold: u64,
+    pub wsol_dust_to_unizen: bool,
+    /// Who fronted the program wSOL rent; `Pubkey::default()` when the authority PDA did.
+    pub wsol_rent_payer: Pubkey,
+    /// Seconds after a fee is taken during which `clawback_fee` may reclaim it; 0 disables.
+    pub clawback_window: i64,
+    /// Ed25519 key whose signed quotes swaps accept in place of `amount_out_min`.
+    pub quote_signer: Pubkey,
+    /// Rejects swaps with `amount_out_min == 0`.
+    pub require_slippage_protection: bool,
+    /// When set, the integrator must receive its fee share within this many bps.
+    pub integrator_fee_tolerance_bps: Option<u64>,
+    /// Holding this mint earns the fee discount of the highest `discount_tiers` entry reached.
+    pub discount_mint: Pubkey,
+    #[max_len(MAX_DISCOUNT_TIERS)]
+    pub discount_tiers: Vec<DiscountTier>,
+    /// Fails `close_program_wsol` if proceeds linger on the program authority. For staging.
+    pub strict_cleanup: bool,
+    /// Makes the signed quote mandatory on the swaps that accept one: `swap_tokens_for_tokens`,
+    /// `swap_sol_for_tokens` and its percent variant, `swap_batch` and the weighted swap.
+    pub require_signed_quote: bool,
+    /// Layout version, see `migrate_config`. New fields are only ever appended after it.
+    pub version: u8,
+}
+
+impl Config {
+    /// Decodes a config written by any earlier layout. Fields are only ever appended, so
+    /// fields an account runs out of bytes for get their defaults. Older accounts were
+    /// zero-padded to their full size though, so their predated fields mostly decode as zero
+    /// rather than running out; `migrate_config` restores the defaults that aren't zero.
+    pub fn deserialize_lenient(mut data: &[u8]) -> Result<Self> {
+        let buf = &mut data;
+        Ok(Self {
+            authority: read_or(buf, Pubkey::default())?,
+            privileged: read_or(buf, Vec::new())?,
+            fee_denom: read_or(buf, FEE_DENOM)?,
+            fee_overrides: read_or(buf, Vec::new())?,
+            fee_tiers: read_or(buf, Vec::new())?,
+            wsol_closers: read_or(buf, Vec::new())?,
+            emit_events: read_or(buf, true)?,
+            reentrancy_locked: read_or(buf, false)?,
+            min_fee: read_or(buf, 0)?,
+            min_fee_in_whole_tokens: read_or(buf, false)?,
+            stranded_output_epsilon: read_or(buf, 0)?,
+            unizen_fee_recipient: read_or(buf, UNIZEN)?,
+            profiling: read_or(buf, false)?,
+            fee_exempt_users: read_or(buf, Vec::new())?,
+            allowed_routers: read_or(buf, Vec::new())?,
+            wsol_dust_threshold: read_or(buf, 0)?,
+            wsol_dust_to_unizen: read_or(buf, false)?,
+            wsol_rent_payer: read_or(buf, Pubkey::default())?,
+            clawback_window: read_or(buf, 0)?,
+            quote_signer: read_or(buf, Pubkey::default())?,
+            require_slippage_protection: read_or(buf, false)?,
+            integrator_fee_tolerance_bps: read_or(buf, None)?,
+            discount_mint: read_or(buf, Pubkey::default())?,
+            discount_tiers: read_or(buf, Vec::new())?,
+            strict_cleanup: read_or(buf, false)?,
+            require_signed_quote: read_or(buf, false)?,
+            version: read_or(buf, 0)?,
+        })
+    }
+}
+
+fn read_or<T: AnchorDeserialize>(buf: &mut &[u8], default: T) -> Result<T> {
+    if buf.is_empty() {
+        return Ok(default);
+    }
+    Ok(T::deserialize(buf)?)
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct FeeOverride {
+    pub mint: Pubkey,
+    pub fee_percent: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct FeeTier {
+    pub threshold_amount: u64,
+    pub fee_percent: u64,
+}
+
+/// `discount_percent` is in `fee_denom` units of the fee, not of the amount.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct DiscountTier {
+    pub min_balance: u64,
+    pub discount_percent: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub enum FeeMode {
+    Percentage {
+        fee_percent: u64,
+        share_percent: u64,
+    },
+    Flat {
+        amount: u64,
+        share_percent: u64,
+    },
+}
+
+/// Percentage fee terms of the swap instructions.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct FeeArgs {
+    pub fee_percent: u64,
+    pub share_percent: u64,
+    pub referrer_percent: u64,
+}
+
+impl FeeArgs {
+    pub fn mode(&self) -> FeeMode {
+        FeeMode::Percentage {
+            fee_percent: self.fee_percent,
+            share_percent: self.share_percent,
+        }
+    }
+}
+
+/// Borsh-serialized, this is the exact message the quote signer signs.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct SignedQuote {
+    pub user: Pubkey,
+    /// Must exceed the user's last used quote nonce, so each quote is accepted once.
+    pub nonce: u64,
+    pub dst_mint: Pubkey,
+    pub min_out: u64,
+    pub expiry: i64,
+}
+
+/// Overrides how Unizen's cut of the fee is resolved; `Absolute` is clamped to the
+/// fee left after the referrer.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub enum ShareMode {
+    Percent(u64),
+    Absolute(u64),
+}
+
+/// Lamports per whole token of `mint` as posted by the config authority with
+/// `update_price_feed`, used to charge fees in SOL. This is not an oracle: nothing ties
+/// `price` to a market, and `publish_time` is when the authority last posted it.
+#[account]
+#[derive(InitSpace)]
+pub struct PriceFeed {
+    pub mint: Pubkey,
+    pub decimals: u8,
+    pub price: u64,
+    pub publish_time: i64,
+}
+
+/// Pyth receiver `PriceUpdateV2` account, owned by `PYTH_RECEIVER` and read with
+/// `load_oracle_price`.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct PriceUpdateV2 {
+    pub write_authority: Pubkey,
+    pub verification_level: VerificationLevel,
+    pub price_message: PriceFeedMessage,
+    pub posted_slot: u64,
+}
+
+impl PriceUpdateV2 {
+    /// Anchor discriminator of the receiver program's `PriceUpdateV2` account.
+    pub const DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, PartialEq, Eq)]
+pub enum VerificationLevel {
+    Partial { num_signatures: u8 },
+    Full,
+}
+
+/// The price is `price * 10^exponent` units of the feed's quote currency per whole token.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct PriceFeedMessage {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub prev_publish_time: i64,
+    pub ema_price: i64,
+    pub ema_conf: u64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct FeeCounter {
+    pub nonce: u64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct QuoteNonce {
+    pub last_nonce: u64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct IdempotencyRecord {
+    pub payer: Pubkey,
+    pub created_at: i64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct FeeReceipt {
+    pub integrator_token_account: Pubkey,
+    pub amount: u64,
+    pub taken_at: i64,
+    pub clawed_back: u64,
+    pub payer: Pubkey,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct VolumeCap {
+    pub integrator: Pubkey,
+    pub daily_cap: u64,
+    pub day: i64,
+    pub volume: u64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct SwapAllowance {
+    pub user: Pubkey,
+    pub integrator: Pubkey,
+    pub mint: Pubkey,
+    pub remaining_budget: u64,
+    pub per_swap_cap: u64,
+}
+
+#[account]
+pub struct RouteBuffer {
+    pub authority: Pubkey,
+    pub data: Vec<u8>,
+}
+
+impl RouteBuffer {
+    pub fn space(len: usize) -> usize {
+        8 + 32 + 4 + len
+    }
+}
@@ -0,0 +1,1276 @@
+//! In-process runner for instruction tests. The system, token and associated token
+//! program calls the instructions make are applied directly to the account data, and the
+//! router is replaced by a stub that moves fixed amounts, so whole instructions can run
+//! through `entry` without a validator.
+
+use std::{
+    cell::RefCell,
+    mem::size_of,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::Once,
+};
+
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE},
+        instruction::Instruction,
+        program_error::ProgramError,
+        program_option::COption,
+        program_pack::Pack,
+        program_stubs::{set_syscall_stubs, SyscallStubs},
+        program_utils::limited_deserialize,
+        system_instruction::SystemInstruction,
+        system_program,
+        sysvar::{
+            self,
+            instructions::{BorrowedAccountMeta, BorrowedInstruction},
+        },
+    },
+    Discriminator, InstructionData,
+};
+use anchor_spl::{
+    associated_token::{self, get_associated_token_address_with_program_id},
+    token,
+    token_2022::{
+        self,
+        spl_token_2022::{
+            extension::{
+                transfer_fee::{TransferFee, TransferFeeConfig},
+                BaseStateWithExtensions, BaseStateWithExtensionsMut, ExtensionType,
+                StateWithExtensions, StateWithExtensionsMut,
+            },
+            instruction::TokenInstruction,
+            state::{Account as TokenState, AccountState, Mint as MintState},
+        },
+    },
+};
+
+use crate::{
+    constants::NATIVE_MINT,
+    helpers::{Jupiter, Whirlpool},
+    state::{Config, SignedQuote},
+};
+
+/// Program the stub router also answers for, e.g. as an allowed router.
+pub const STUB_ROUTER: Pubkey = pubkey!("Router1111111111111111111111111111111111111");
+
+/// Program that accepts any call, recording it in `Outcome::callbacks`.
+pub const STUB_CALLBACK: Pubkey = pubkey!("Ca11back11111111111111111111111111111111111");
+
+/// Clock every test starts at.
+pub const NOW: i64 = 1_700_000_000;
+
+thread_local! {
+    static UNIX_TIMESTAMP: RefCell<i64> = const { RefCell::new(NOW) };
+    static COMPUTE_UNITS: RefCell<u64> = const { RefCell::new(1_400_000) };
+    static STACK_HEIGHT: RefCell<u64> = const { RefCell::new(1) };
+    static LOGGED: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+    static LOGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static RETURN_DATA: RefCell<Option<(Pubkey, Vec<u8>)>> = const { RefCell::new(None) };
+    static ROUTER_REENTRY: RefCell<Option<Instruction>> = const { RefCell::new(None) };
+    static REENTRY_RESULT: RefCell<Option<ProgramResult>> = const { RefCell::new(None) };
+    static ROUTED: RefCell<Vec<Pubkey>> = const { RefCell::new(Vec::new()) };
+    static ROUTER_FAILURE: RefCell<Option<ProgramError>> = const { RefCell::new(None) };
+    static CALLBACKS: RefCell<Vec<Instruction>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Makes the next route the stub router runs call back into this program with
+/// `instruction`, whose accounts all have to be among the route's.
+pub fn set_router_reentry(instruction: Instruction) {
+    ROUTER_REENTRY.with(|reentry| *reentry.borrow_mut() = Some(instruction));
+}
+
+/// Makes the next route the stub router runs fail with `error` before moving anything,
+/// which aborts the instruction with that error.
+pub fn set_router_failure(error: ProgramError) {
+    ROUTER_FAILURE.with(|failure| *failure.borrow_mut() = Some(error));
+}
+
+pub fn set_clock(unix_timestamp: i64) {
+    UNIX_TIMESTAMP.with(|clock| *clock.borrow_mut() = unix_timestamp);
+}
+
+/// Caps what `sol_remaining_compute_units` reports; the harness never consumes any.
+pub fn set_compute_units(units: u64) {
+    COMPUTE_UNITS.with(|compute_units| *compute_units.borrow_mut() = units);
+}
+
+/// Sets what `get_stack_height` reports, 1 being a top-level instruction.
+pub fn set_stack_height(height: u64) {
+    STACK_HEIGHT.with(|stack_height| *stack_height.borrow_mut() = height);
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestAccount {
+    pub key: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+impl TestAccount {
+    pub fn wallet(key: Pubkey, lamports: u64) -> Self {
+        Self {
+            key,
+            lamports,
+            data: Vec::new(),
+            owner: system_program::ID,
+            executable: false,
+        }
+    }
+
+    /// An account nothing has been written to yet, such as an uninitialized PDA.
+    pub fn empty(key: Pubkey) -> Self {
+        Self::wallet(key, 0)
+    }
+
+    pub fn program(key: Pubkey) -> Self {
+        Self {
+            executable: true,
+            owner: bpf_loader_upgradeable(),
+            ..Self::wallet(key, 1)
+        }
+    }
+
+    pub fn mint(key: Pubkey, decimals: u8) -> Self {
+        Self::mint_of(token::ID, key, decimals)
+    }
+
+    pub fn mint_2022(key: Pubkey, decimals: u8) -> Self {
+        Self::mint_of(token_2022::ID, key, decimals)
+    }
+
+    fn mint_of(token_program: Pubkey, key: Pubkey, decimals: u8) -> Self {
+        let mut data = vec![0; MintState::LEN];
+        MintState {
+            mint_authority: COption::None,
+            supply: u64::MAX,
+            decimals,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        }
+        .pack_into_slice(&mut data);
+        Self {
+            data,
+            owner: token_program,
+            ..Self::wallet(key, rent(MintState::LEN))
+        }
+    }
+
+    /// A Token-2022 mint withholding `fee_bps` of every transfer.
+    pub fn mint_2022_with_transfer_fee(key: Pubkey, decimals: u8, fee_bps: u16) -> Self {
+        let space = ExtensionType::try_calculate_account_len::<MintState>(&[
+            ExtensionType::TransferFeeConfig,
+        ])
+        .unwrap();
+        let mut data = vec![0; space];
+        let mut state =
+            StateWithExtensionsMut::<MintState>::unpack_uninitialized(&mut data).unwrap();
+        let fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: fee_bps.into(),
+        };
+        let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+        config.older_transfer_fee = fee;
+        config.newer_transfer_fee = fee;
+        state.base = MintState {
+            mint_authority: COption::None,
+            supply: u64::MAX,
+            decimals,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        Self {
+            data,
+            owner: token_2022::ID,
+            ..Self::wallet(key, rent(space))
+        }
+    }
+
+    pub fn token(key: Pubkey, mint: Pubkey, owner: Pubkey, amount: u64) -> Self {
+        Self::token_of(token::ID, key, mint, owner, amount)
+    }
+
+    fn token_of(
+        token_program: Pubkey,
+        key: Pubkey,
+        mint: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+    ) -> Self {
+        let mut account = Self {
+            data: vec![0; TokenState::LEN],
+            owner: token_program,
+            ..Self::wallet(key, rent(TokenState::LEN))
+        };
+        account.set_token_state(TokenState {
+            mint,
+            owner,
+            amount,
+            delegate: COption::None,
+            state: AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        });
+        account
+    }
+
+    pub fn ata(mint: Pubkey, owner: Pubkey, amount: u64) -> Self {
+        let key = get_associated_token_address_with_program_id(&owner, &mint, &token::ID);
+        Self::token(key, mint, owner, amount)
+    }
+
+    pub fn ata_2022(mint: Pubkey, owner: Pubkey, amount: u64) -> Self {
+        let key = get_associated_token_address_with_program_id(&owner, &mint, &token_2022::ID);
+        Self::token_of(token_2022::ID, key, mint, owner, amount)
+    }
+
+    /// A wSOL account at `key` holding `amount` above its rent reserve.
+    pub fn native(key: Pubkey, owner: Pubkey, amount: u64) -> Self {
+        let mut account = Self::token(key, NATIVE_MINT, owner, amount);
+        let reserve = account.lamports;
+        let mut state = account.token_state();
+        state.is_native = COption::Some(reserve);
+        account.set_token_state(state);
+        account.lamports = reserve + amount;
+        account
+    }
+
+    pub fn native_ata(owner: Pubkey, amount: u64) -> Self {
+        let key = get_associated_token_address_with_program_id(&owner, &NATIVE_MINT, &token::ID);
+        Self::native(key, owner, amount)
+    }
+
+    /// An account of this program holding `value`, sized to `space` like `init` would.
+    pub fn state<T: AccountSerialize>(key: Pubkey, value: &T, space: usize) -> Self {
+        let mut data = Vec::with_capacity(space);
+        value.try_serialize(&mut data).unwrap();
+        data.resize(space.max(data.len()), 0);
+        Self {
+            lamports: rent(data.len()),
+            data,
+            owner: crate::ID,
+            ..Self::wallet(key, 0)
+        }
+    }
+
+    /// The config PDA holding `config`.
+    pub fn config(config: &Config) -> Self {
+        Self::state(
+            crate::pda::find_config().0,
+            config,
+            8 + <Config as anchor_lang::Space>::INIT_SPACE,
+        )
+    }
+
+    pub fn token_state(&self) -> TokenState {
+        TokenState::unpack_from_slice(&self.data[..TokenState::LEN]).unwrap()
+    }
+
+    pub fn set_token_state(&mut self, state: TokenState) {
+        state.pack_into_slice(&mut self.data[..TokenState::LEN]);
+    }
+
+    pub fn token_amount(&self) -> u64 {
+        self.token_state().amount
+    }
+
+    pub fn read<T: AccountDeserialize>(&self) -> T {
+        T::try_deserialize(&mut self.data.as_slice()).unwrap()
+    }
+}
+
+/// A fresh config with every field at its default, as `initialize_config` writes it.
+pub fn default_config(authority: Pubkey) -> Config {
+    Config {
+        authority,
+        version: crate::constants::CONFIG_VERSION,
+        ..Config::deserialize_lenient(&[]).unwrap()
+    }
+}
+
+/// The instructions sysvar of a transaction made of `instructions`, executing `current`.
+pub fn instructions_sysvar(instructions: &[Instruction], current: u16) -> TestAccount {
+    let borrowed: Vec<BorrowedInstruction> = instructions
+        .iter()
+        .map(|instruction| BorrowedInstruction {
+            program_id: &instruction.program_id,
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|meta| BorrowedAccountMeta {
+                    pubkey: &meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: &instruction.data,
+        })
+        .collect();
+    let mut data = sysvar::instructions::construct_instructions_data(&borrowed);
+    sysvar::instructions::store_current_index(&mut data, current);
+    TestAccount {
+        data,
+        owner: sysvar::ID,
+        ..TestAccount::wallet(sysvar::instructions::ID, 1)
+    }
+}
+
+/// Ed25519 program instruction carrying `quotes` signed by `signer`. The signatures are
+/// zeroed: the runtime checks them before the program runs, so tests never get that far
+/// with a bad one.
+pub fn quote_instruction(signer: &Pubkey, quotes: &[SignedQuote]) -> Instruction {
+    let header = 2 + 14 * quotes.len();
+    let mut offsets = vec![quotes.len() as u8, 0];
+    let mut payload = Vec::new();
+    for quote in quotes {
+        let message = quote.try_to_vec().unwrap();
+        let public_key_offset = header + payload.len();
+        let signature_offset = public_key_offset + 32;
+        let message_offset = signature_offset + 64;
+        for value in [
+            signature_offset,
+            u16::MAX as usize,
+            public_key_offset,
+            u16::MAX as usize,
+            message_offset,
+            message.len(),
+            u16::MAX as usize,
+        ] {
+            offsets.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+        payload.extend_from_slice(signer.as_ref());
+        payload.extend_from_slice(&[0; 64]);
+        payload.extend_from_slice(&message);
+    }
+    offsets.extend_from_slice(&payload);
+    Instruction {
+        program_id: anchor_lang::solana_program::ed25519_program::ID,
+        accounts: Vec::new(),
+        data: offsets,
+    }
+}
+
+fn bpf_loader_upgradeable() -> Pubkey {
+    anchor_lang::solana_program::bpf_loader_upgradeable::ID
+}
+
+pub fn rent(space: usize) -> u64 {
+    Rent::default().minimum_balance(space)
+}
+
+/// Route data for the stub router: it pulls `amount_in` from its first account into the
+/// second and pays `amount_out` from the third into the fourth. Amounts appended to it
+/// are paid out of each further pair of accounts, after the authority.
+pub fn route_data(amount_in: u64, amount_out: u64) -> Vec<u8> {
+    [amount_in.to_le_bytes(), amount_out.to_le_bytes()].concat()
+}
+
+/// Route accounts for the stub router. `authority` has to sign for `src`, as its owner
+/// or delegate.
+pub fn route(
+    src: Pubkey,
+    pool_src: Pubkey,
+    pool_dst: Pubkey,
+    dst: Pubkey,
+    authority: Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(src, false),
+        AccountMeta::new(pool_src, false),
+        AccountMeta::new(pool_dst, false),
+        AccountMeta::new(dst, false),
+        AccountMeta::new_readonly(authority, false),
+    ]
+}
+
+/// Source tokens the `TokenSwap` user starts with.
+pub const USER_BALANCE: u64 = 10_000_000;
+
+/// Destination tokens the `TokenSwap` pool starts with.
+pub const POOL_BALANCE: u64 = 1_000_000_000;
+
+/// A user holding `src_mint`, Unizen and integrator fee accounts in it, and a pool the
+/// stub router swaps into `dst_mint` through.
+pub struct TokenSwap {
+    pub ledger: Ledger,
+    pub user: Pubkey,
+    pub integrator: Pubkey,
+    pub src_mint: Pubkey,
+    pub dst_mint: Pubkey,
+    pub user_src_ata: Pubkey,
+    pub unizen_src_ata: Pubkey,
+    pub integrator_src_ata: Pubkey,
+    pub receiver_dst_ata: Pubkey,
+    pub pool_src: Pubkey,
+    pub pool_dst: Pubkey,
+}
+
+impl TokenSwap {
+    pub fn new() -> Self {
+        let user = Pubkey::new_unique();
+        let integrator = Pubkey::new_unique();
+        let pool = Pubkey::new_unique();
+        let src_mint = TestAccount::mint(Pubkey::new_unique(), 6);
+        let dst_mint = TestAccount::mint(Pubkey::new_unique(), 6);
+        let user_src_ata = TestAccount::ata(src_mint.key, user, USER_BALANCE);
+        let unizen_src_ata = TestAccount::ata(src_mint.key, crate::constants::UNIZEN, 0);
+        let integrator_src_ata = TestAccount::ata(src_mint.key, integrator, 0);
+        let receiver_dst_ata = TestAccount::ata(dst_mint.key, user, 0);
+        let pool_src = TestAccount::ata(src_mint.key, pool, 0);
+        let pool_dst = TestAccount::ata(dst_mint.key, pool, POOL_BALANCE);
+        Self {
+            user,
+            integrator,
+            src_mint: src_mint.key,
+            dst_mint: dst_mint.key,
+            user_src_ata: user_src_ata.key,
+            unizen_src_ata: unizen_src_ata.key,
+            integrator_src_ata: integrator_src_ata.key,
+            receiver_dst_ata: receiver_dst_ata.key,
+            pool_src: pool_src.key,
+            pool_dst: pool_dst.key,
+            ledger: Ledger::new([
+                TestAccount::wallet(user, 10_000_000_000),
+                TestAccount::wallet(integrator, 10_000_000_000),
+                src_mint,
+                dst_mint,
+                user_src_ata,
+                unizen_src_ata,
+                integrator_src_ata,
+                receiver_dst_ata,
+                pool_src,
+                pool_dst,
+            ]),
+        }
+    }
+
+    /// Route from the user's source account into the receiver, signed by the user.
+    pub fn route(&self) -> Vec<AccountMeta> {
+        route(
+            self.user_src_ata,
+            self.pool_src,
+            self.pool_dst,
+            self.receiver_dst_ata,
+            self.user,
+        )
+    }
+}
+
+/// Lamports the `SolSwap` user starts with.
+pub const USER_LAMPORTS: u64 = 10_000_000_000;
+
+/// A user with `USER_LAMPORTS` and an empty wSOL account, Unizen and integrator wSOL fee
+/// accounts, and a pool the stub router swaps wSOL into `dst_mint` through.
+pub struct SolSwap {
+    pub ledger: Ledger,
+    pub user: Pubkey,
+    pub integrator: Pubkey,
+    pub dst_mint: Pubkey,
+    pub user_wsol_ata: Pubkey,
+    pub unizen_wsol_ata: Pubkey,
+    pub integrator_wsol_ata: Pubkey,
+    pub receiver_dst_ata: Pubkey,
+    pub pool_src: Pubkey,
+    pub pool_dst: Pubkey,
+}
+
+impl SolSwap {
+    pub fn new() -> Self {
+        let user = Pubkey::new_unique();
+        let integrator = Pubkey::new_unique();
+        let pool = Pubkey::new_unique();
+        let dst_mint = TestAccount::mint(Pubkey::new_unique(), 6);
+        let user_wsol_ata = TestAccount::native_ata(user, 0);
+        let unizen_wsol_ata = TestAccount::native_ata(crate::constants::UNIZEN, 0);
+        let integrator_wsol_ata = TestAccount::native_ata(integrator, 0);
+        let receiver_dst_ata = TestAccount::ata(dst_mint.key, user, 0);
+        let pool_src = TestAccount::native_ata(pool, 0);
+        let pool_dst = TestAccount::ata(dst_mint.key, pool, POOL_BALANCE);
+        Self {
+            user,
+            integrator,
+            dst_mint: dst_mint.key,
+            user_wsol_ata: user_wsol_ata.key,
+            unizen_wsol_ata: unizen_wsol_ata.key,
+            integrator_wsol_ata: integrator_wsol_ata.key,
+            receiver_dst_ata: receiver_dst_ata.key,
+            pool_src: pool_src.key,
+            pool_dst: pool_dst.key,
+            ledger: Ledger::new([
+                TestAccount::wallet(user, USER_LAMPORTS),
+                TestAccount::wallet(integrator, 10_000_000_000),
+                TestAccount::mint(NATIVE_MINT, 9),
+                dst_mint,
+                user_wsol_ata,
+                unizen_wsol_ata,
+                integrator_wsol_ata,
+                receiver_dst_ata,
+                pool_src,
+                pool_dst,
+            ]),
+        }
+    }
+
+    /// Route from the user's wSOL account into the receiver, signed by the user.
+    pub fn route(&self) -> Vec<AccountMeta> {
+        route(
+            self.user_wsol_ata,
+            self.pool_src,
+            self.pool_dst,
+            self.receiver_dst_ata,
+            self.user,
+        )
+    }
+}
+
+/// Accounts as instructions see them, kept across instructions.
+#[derive(Clone, Debug, Default)]
+pub struct Ledger {
+    pub accounts: Vec<TestAccount>,
+}
+
+impl Ledger {
+    pub fn new(accounts: impl IntoIterator<Item = TestAccount>) -> Self {
+        Self {
+            accounts: accounts.into_iter().collect(),
+        }
+    }
+
+    pub fn add(&mut self, account: TestAccount) -> Pubkey {
+        let key = account.key;
+        match self.accounts.iter_mut().find(|known| known.key == key) {
+            Some(known) => *known = account,
+            None => self.accounts.push(account),
+        }
+        key
+    }
+
+    pub fn get(&self, key: &Pubkey) -> &TestAccount {
+        find(&self.accounts, key)
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.get(key).lamports
+    }
+
+    pub fn token_amount(&self, key: &Pubkey) -> u64 {
+        self.get(key).token_amount()
+    }
+
+    /// Runs an instruction of this program with `remaining` after its own accounts.
+    pub fn run(
+        &mut self,
+        accounts: impl ToAccountMetas,
+        remaining: &[AccountMeta],
+        data: impl InstructionData,
+    ) -> Outcome {
+        let mut metas = accounts.to_account_metas(None);
+        metas.extend_from_slice(remaining);
+        self.process(&metas, &data.data())
+    }
+
+    /// Runs one instruction the way the runtime does: a key listed twice is the same
+    /// account, accounts the ledger doesn't know start out empty, a failed cross-program
+    /// call fails the instruction with the callee's error, and a failed instruction leaves
+    /// every account as it was.
+    pub fn process(&mut self, metas: &[AccountMeta], data: &[u8]) -> Outcome {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            set_syscall_stubs(Box::new(Stubs));
+        });
+        LOGGED.with(|logged| logged.borrow_mut().clear());
+        LOGS.with(|logs| logs.borrow_mut().clear());
+        RETURN_DATA.with(|return_data| *return_data.borrow_mut() = None);
+        REENTRY_RESULT.with(|result| *result.borrow_mut() = None);
+        ROUTED.with(|routed| routed.borrow_mut().clear());
+        CALLBACKS.with(|callbacks| callbacks.borrow_mut().clear());
+
+        for meta in metas {
+            if !self
+                .accounts
+                .iter()
+                .any(|account| account.key == meta.pubkey)
+            {
+                self.accounts.push(known_program(meta.pubkey));
+            }
+        }
+
+        let mut buffers: Vec<Serialized> = self.accounts.iter().map(Serialized::new).collect();
+        let result = {
+            let infos: Vec<AccountInfo> = buffers
+                .iter_mut()
+                .map(|buffer| {
+                    let key = buffer.key();
+                    let flags = metas.iter().filter(|meta| meta.pubkey == key);
+                    let is_signer = flags.clone().any(|meta| meta.is_signer);
+                    let is_writable = flags.clone().any(|meta| meta.is_writable);
+                    buffer.info(is_signer, is_writable)
+                })
+                .collect();
+            let metas: Vec<AccountInfo> = metas
+                .iter()
+                .map(|meta| {
+                    infos
+                        .iter()
+                        .find(|info| *info.key == meta.pubkey)
+                        .unwrap()
+                        .clone()
+                })
+                .collect();
+            panic::catch_unwind(AssertUnwindSafe(|| crate::entry(&crate::ID, &metas, data)))
+                .unwrap_or_else(|payload| match payload.downcast::<CpiAborted>() {
+                    Ok(aborted) => Err(aborted.0),
+                    Err(payload) => panic::resume_unwind(payload),
+                })
+        };
+
+        let left: Vec<TestAccount> = buffers.iter().map(Serialized::account).collect();
+        if result.is_ok() {
+            self.accounts.clone_from(&left);
+        }
+        Outcome {
+            result,
+            left: Ledger { accounts: left },
+            logged: LOGGED.with(|logged| logged.take()),
+            logs: LOGS.with(|logs| logs.take()),
+            return_data: RETURN_DATA.with(|return_data| return_data.take()),
+            reentry: REENTRY_RESULT.with(|result| result.take()),
+            routed: ROUTED.with(|routed| routed.take()),
+            callbacks: CALLBACKS.with(|callbacks| callbacks.take()),
+        }
+    }
+}
+
+fn find<'a>(accounts: &'a [TestAccount], key: &Pubkey) -> &'a TestAccount {
+    accounts
+        .iter()
+        .find(|account| account.key == *key)
+        .unwrap_or_else(|| panic!("no test account {key}"))
+}
+
+fn known_program(key: Pubkey) -> TestAccount {
+    let programs = [
+        crate::ID,
+        system_program::ID,
+        token::ID,
+        token_2022::ID,
+        associated_token::ID,
+        Jupiter::id(),
+        Whirlpool::id(),
+        STUB_ROUTER,
+        STUB_CALLBACK,
+    ];
+    if programs.contains(&key) {
+        TestAccount::program(key)
+    } else {
+        TestAccount::empty(key)
+    }
+}
+
+/// What an instruction did. `left` holds the accounts as the program left them, before
+/// a failed instruction is rolled back.
+pub struct Outcome {
+    pub result: ProgramResult,
+    pub left: Ledger,
+    logged: Vec<Vec<u8>>,
+    /// Lines the instruction logged, with `sol_log_compute_units` lines as the runtime
+    /// writes them.
+    pub logs: Vec<String>,
+    pub return_data: Option<(Pubkey, Vec<u8>)>,
+    /// What the call back into the program from `set_router_reentry` returned, if it ran.
+    pub reentry: Option<ProgramResult>,
+    /// The accounts of every route the stub router was handed, in order.
+    pub routed: Vec<Pubkey>,
+    /// Every call `STUB_CALLBACK` received, in order.
+    pub callbacks: Vec<Instruction>,
+}
+
+impl Outcome {
+    /// Fails the test unless the instruction failed with `error`.
+    #[track_caller]
+    pub fn assert_error(&self, error: crate::errors::ErrorCode) {
+        assert_eq!(self.result, Err(ProgramError::Custom(error.into())));
+    }
+
+    #[track_caller]
+    pub fn assert_ok(&self) {
+        assert_eq!(self.result, Ok(()));
+    }
+
+    /// Events of type `T` the instruction emitted, in order.
+    pub fn events<T: anchor_lang::Event + Discriminator + AnchorDeserialize>(&self) -> Vec<T> {
+        self.logged
+            .iter()
+            .filter_map(|data| data.strip_prefix(T::DISCRIMINATOR.as_slice()))
+            .map(|mut data| T::deserialize(&mut data).unwrap())
+            .collect()
+    }
+
+    /// How many events of any type the instruction emitted.
+    pub fn event_count(&self) -> usize {
+        self.logged.len()
+    }
+}
+
+// An account serialized the way the runtime hands it to a program: the original data
+// length right before the key, then owner, lamports, data length and the data, with room
+// for the data to grow. `AccountInfo::realloc` and `assign` write through to it.
+const ORIGINAL_LEN: usize = 4;
+const KEY: usize = 8;
+const OWNER: usize = KEY + 32;
+const LAMPORTS: usize = OWNER + 32;
+const DATA_LEN: usize = LAMPORTS + 8;
+const DATA: usize = DATA_LEN + 8;
+
+struct Serialized {
+    buf: Vec<u64>,
+    executable: bool,
+}
+
+impl Serialized {
+    fn new(account: &TestAccount) -> Self {
+        let len = DATA + account.data.len() + MAX_PERMITTED_DATA_INCREASE;
+        let mut serialized = Self {
+            buf: vec![0; len.div_ceil(size_of::<u64>())],
+            executable: account.executable,
+        };
+        let bytes = serialized.bytes_mut();
+        bytes[ORIGINAL_LEN..KEY].copy_from_slice(&(account.data.len() as u32).to_le_bytes());
+        bytes[KEY..OWNER].copy_from_slice(account.key.as_ref());
+        bytes[OWNER..LAMPORTS].copy_from_slice(account.owner.as_ref());
+        bytes[LAMPORTS..DATA_LEN].copy_from_slice(&account.lamports.to_le_bytes());
+        bytes[DATA_LEN..DATA].copy_from_slice(&(account.data.len() as u64).to_le_bytes());
+        bytes[DATA..DATA + account.data.len()].copy_from_slice(&account.data);
+        serialized
+    }
+
+    fn bytes(&self) -> &[u8] {
+        let len = self.buf.len() * size_of::<u64>();
+        unsafe { std::slice::from_raw_parts(self.buf.as_ptr().cast(), len) }
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        let len = self.buf.len() * size_of::<u64>();
+        unsafe { std::slice::from_raw_parts_mut(self.buf.as_mut_ptr().cast(), len) }
+    }
+
+    fn read_u64(&self, at: usize) -> u64 {
+        u64::from_le_bytes(self.bytes()[at..at + 8].try_into().unwrap())
+    }
+
+    fn key(&self) -> Pubkey {
+        Pubkey::try_from(&self.bytes()[KEY..OWNER]).unwrap()
+    }
+
+    fn info(&mut self, is_signer: bool, is_writable: bool) -> AccountInfo<'_> {
+        let data_len = self.read_u64(DATA_LEN) as usize;
+        let executable = self.executable;
+        let base: *mut u8 = self.buf.as_mut_ptr().cast();
+        // The references all point into `buf`, which outlives the returned info, and the
+        // regions don't overlap.
+        unsafe {
+            AccountInfo::new(
+                &*base.add(KEY).cast::<Pubkey>(),
+                is_signer,
+                is_writable,
+                &mut *base.add(LAMPORTS).cast::<u64>(),
+                std::slice::from_raw_parts_mut(base.add(DATA), data_len),
+                &*base.add(OWNER).cast::<Pubkey>(),
+                executable,
+                0,
+            )
+        }
+    }
+
+    fn account(&self) -> TestAccount {
+        let bytes = self.bytes();
+        let data_len = self.read_u64(DATA_LEN) as usize;
+        TestAccount {
+            key: self.key(),
+            lamports: self.read_u64(LAMPORTS),
+            data: bytes[DATA..DATA + data_len].to_vec(),
+            owner: Pubkey::try_from(&bytes[OWNER..LAMPORTS]).unwrap(),
+            executable: self.executable,
+        }
+    }
+}
+
+struct Stubs;
+
+/// Unwinds out of a failed cross-program call, which the runtime never returns to the
+/// caller: the whole instruction fails with the callee's error.
+struct CpiAborted(ProgramError);
+
+impl SyscallStubs for Stubs {
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if let Err(error) = invoke(instruction, account_infos, signers_seeds) {
+            panic::resume_unwind(Box::new(CpiAborted(error)));
+        }
+        Ok(())
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { ptr::write(var_addr.cast(), Rent::default()) };
+        0
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = Clock {
+            unix_timestamp: UNIX_TIMESTAMP.with(|clock| *clock.borrow()),
+            ..Clock::default()
+        };
+        unsafe { ptr::write(var_addr.cast(), clock) };
+        0
+    }
+
+    fn sol_remaining_compute_units(&self) -> u64 {
+        COMPUTE_UNITS.with(|units| *units.borrow())
+    }
+
+    fn sol_get_stack_height(&self) -> u64 {
+        STACK_HEIGHT.with(|height| *height.borrow())
+    }
+
+    fn sol_log(&self, message: &str) {
+        LOGS.with(|logs| logs.borrow_mut().push(message.to_string()));
+    }
+
+    fn sol_log_compute_units(&self) {
+        let units = self.sol_remaining_compute_units();
+        let line = format!("Program consumption: {units} units remaining");
+        LOGS.with(|logs| logs.borrow_mut().push(line));
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        LOGGED.with(|logged| logged.borrow_mut().push(fields.concat()));
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        let return_data = (!data.is_empty()).then(|| (crate::ID, data.to_vec()));
+        RETURN_DATA.with(|current| *current.borrow_mut() = return_data);
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        RETURN_DATA.with(|current| current.borrow().clone())
+    }
+}
+
+struct Cpi<'a, 'info> {
+    info: &'a AccountInfo<'info>,
+    is_signer: bool,
+}
+
+struct CpiAccounts<'a, 'info>(Vec<Cpi<'a, 'info>>);
+
+impl<'a, 'info> CpiAccounts<'a, 'info> {
+    fn get(&self, index: usize) -> std::result::Result<&'a AccountInfo<'info>, ProgramError> {
+        self.0
+            .get(index)
+            .map(|account| account.info)
+            .ok_or(ProgramError::NotEnoughAccountKeys)
+    }
+
+    fn signer(&self, index: usize) -> std::result::Result<&'a AccountInfo<'info>, ProgramError> {
+        match self.0.get(index) {
+            Some(account) if account.is_signer => Ok(account.info),
+            Some(_) => Err(ProgramError::MissingRequiredSignature),
+            None => Err(ProgramError::NotEnoughAccountKeys),
+        }
+    }
+}
+
+fn process_system(accounts: &CpiAccounts, data: &[u8]) -> ProgramResult {
+    let instruction: SystemInstruction =
+        limited_deserialize(data, 1_024).map_err(|_| ProgramError::InvalidInstructionData)?;
+    match instruction {
+        SystemInstruction::CreateAccount {
+            lamports,
+            space,
+            owner,
+        } => {
+            let (from, to) = (accounts.signer(0)?, accounts.signer(1)?);
+            if to.lamports() > 0 || !to.data_is_empty() {
+                // `SystemError::AccountAlreadyInUse`
+                return Err(ProgramError::Custom(0));
+            }
+            move_lamports(from, to, lamports)?;
+            to.realloc(space as usize, true)?;
+            to.assign(&owner);
+            Ok(())
+        }
+        SystemInstruction::Transfer { lamports } => {
+            let (from, to) = (accounts.signer(0)?, accounts.get(1)?);
+            if from.owner != &system_program::ID || !from.data_is_empty() {
+                return Err(ProgramError::InvalidArgument);
+            }
+            move_lamports(from, to, lamports)
+        }
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+fn move_lamports(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+    let remaining = from
+        .lamports()
+        .checked_sub(lamports)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    **from.try_borrow_mut_lamports()? = remaining;
+    **to.try_borrow_mut_lamports()? += lamports;
+    Ok(())
+}
+
+fn process_token(token_program: &Pubkey, accounts: &CpiAccounts, data: &[u8]) -> ProgramResult {
+    for index in 0..accounts.0.len() {
+        let account = accounts.get(index)?;
+        let is_token_account = account.data_len() >= TokenState::LEN
+            && account.owner != &crate::ID
+            && !account.executable;
+        if is_token_account && account.owner != token_program && account.owner != &sysvar::ID {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+    }
+
+    match TokenInstruction::unpack(data)? {
+        TokenInstruction::TransferChecked { amount, decimals } => {
+            let (from, mint, to) = (accounts.get(0)?, accounts.get(1)?, accounts.get(2)?);
+            let mint_state = MintState::unpack_from_slice(&mint.try_borrow_data()?)?;
+            if load_token(from)?.mint != *mint.key {
+                // `TokenError::MintMismatch`
+                return Err(ProgramError::Custom(3));
+            }
+            if mint_state.decimals != decimals {
+                // `TokenError::MintDecimalsMismatch`
+                return Err(ProgramError::Custom(18));
+            }
+            let withheld = transfer_fee(mint, amount)?;
+            transfer(from, to, Some(accounts.signer(3)?), amount, withheld)
+        }
+        TokenInstruction::SyncNative => {
+            let native = accounts.get(0)?;
+            let mut state = load_token(native)?;
+            let COption::Some(reserve) = state.is_native else {
+                return Err(ProgramError::InvalidAccountData);
+            };
+            state.amount = native
+                .lamports()
+                .checked_sub(reserve)
+                .ok_or(ProgramError::InsufficientFunds)?;
+            store_token(native, &state)
+        }
+        TokenInstruction::InitializeAccount3 { owner } => {
+            let (account, mint) = (accounts.get(0)?, accounts.get(1)?);
+            if account.data_len() < TokenState::LEN || account.try_borrow_data()?[108] != 0 {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let is_native = *mint.key == NATIVE_MINT;
+            let reserve = rent(account.data_len());
+            let amount = if is_native {
+                account
+                    .lamports()
+                    .checked_sub(reserve)
+                    .ok_or(ProgramError::InsufficientFunds)?
+            } else {
+                0
+            };
+            store_token(
+                account,
+                &TokenState {
+                    mint: *mint.key,
+                    owner,
+                    amount,
+                    delegate: COption::None,
+                    state: AccountState::Initialized,
+                    is_native: if is_native {
+                        COption::Some(reserve)
+                    } else {
+                        COption::None
+                    },
+                    delegated_amount: 0,
+                    close_authority: COption::None,
+                },
+            )
+        }
+        TokenInstruction::CloseAccount => {
+            let (account, destination) = (accounts.get(0)?, accounts.get(1)?);
+            let authority = accounts.signer(2)?;
+            let state = load_token(account)?;
+            if state.is_native.is_none() && state.amount != 0 {
+                // `TokenError::NonNativeHasBalance`
+                return Err(ProgramError::Custom(11));
+            }
+            if *authority.key != state.close_authority.unwrap_or(state.owner) {
+                // `TokenError::OwnerMismatch`
+                return Err(ProgramError::Custom(4));
+            }
+            move_lamports(account, destination, account.lamports())?;
+            account.realloc(0, false)?;
+            account.assign(&system_program::ID);
+            Ok(())
+        }
+        TokenInstruction::Approve { amount } => approve(accounts, 0, 1, 2, amount),
+        TokenInstruction::ApproveChecked { amount, .. } => approve(accounts, 0, 2, 3, amount),
+        TokenInstruction::Revoke => {
+            let account = accounts.get(0)?;
+            let mut state = load_token(account)?;
+            if *accounts.signer(1)?.key != state.owner {
+                return Err(ProgramError::Custom(4));
+            }
+            state.delegate = COption::None;
+            state.delegated_amount = 0;
+            store_token(account, &state)
+        }
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+fn approve(
+    accounts: &CpiAccounts,
+    account: usize,
+    delegate: usize,
+    owner: usize,
+    amount: u64,
+) -> ProgramResult {
+    let (account, delegate) = (accounts.get(account)?, accounts.get(delegate)?);
+    let mut state = load_token(account)?;
+    if *accounts.signer(owner)?.key != state.owner {
+        return Err(ProgramError::Custom(4));
+    }
+    state.delegate = COption::Some(*delegate.key);
+    state.delegated_amount = amount;
+    store_token(account, &state)
+}
+
+fn process_associated_token(accounts: &CpiAccounts, data: &[u8]) -> ProgramResult {
+    let idempotent = data.first() == Some(&1);
+    let (payer, account) = (accounts.signer(0)?, accounts.get(1)?);
+    let (wallet, mint, token_program) = (accounts.get(2)?, accounts.get(3)?, accounts.get(5)?);
+    if *account.key
+        != get_associated_token_address_with_program_id(wallet.key, mint.key, token_program.key)
+    {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !account.data_is_empty() {
+        let state = load_token(account)?;
+        if idempotent && state.owner == *wallet.key && state.mint == *mint.key {
+            return Ok(());
+        }
+        return Err(ProgramError::Custom(0));
+    }
+
+    let shortfall = rent(TokenState::LEN).saturating_sub(account.lamports());
+    move_lamports(payer, account, shortfall)?;
+    account.realloc(TokenState::LEN, true)?;
+    account.assign(token_program.key);
+    let is_native = *mint.key == NATIVE_MINT;
+    store_token(
+        account,
+        &TokenState {
+            mint: *mint.key,
+            owner: *wallet.key,
+            amount: 0,
+            delegate: COption::None,
+            state: AccountState::Initialized,
+            is_native: if is_native {
+                COption::Some(account.lamports())
+            } else {
+                COption::None
+            },
+            delegated_amount: 0,
+            close_authority: COption::None,
+        },
+    )
+}
+
+/// The stub router: pulls the first amount of its data from account 0 into account 1,
+/// signed for by account 4, then pays the second amount from account 2 into account 3.
+fn process_route(accounts: &CpiAccounts, data: &[u8]) -> ProgramResult {
+    let amount = |range: std::ops::Range<usize>| {
+        data.get(range)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u64::from_le_bytes)
+            .ok_or(ProgramError::InvalidInstructionData)
+    };
+    transfer(
+        accounts.get(0)?,
+        accounts.get(1)?,
+        Some(accounts.signer(4)?),
+        amount(0..8)?,
+        0,
+    )?;
+    transfer(accounts.get(2)?, accounts.get(3)?, None, amount(8..16)?, 0)?;
+    // Further outputs, each paid from a pool account into the one after it.
+    for (output, start) in (16..data.len()).step_by(8).enumerate() {
+        transfer(
+            accounts.get(5 + 2 * output)?,
+            accounts.get(6 + 2 * output)?,
+            None,
+            amount(start..start + 8)?,
+            0,
+        )?;
+    }
+    Ok(())
+}
+
+/// Applies a cross-program call the way the callee program would.
+fn invoke(
+    instruction: &Instruction,
+    account_infos: &[AccountInfo],
+    signers_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let signers: Vec<Pubkey> = signers_seeds
+        .iter()
+        .map(|seeds| Pubkey::create_program_address(seeds, &crate::ID))
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+    let mut accounts = Vec::with_capacity(instruction.accounts.len());
+    for meta in &instruction.accounts {
+        let info = account_infos
+            .iter()
+            .find(|info| *info.key == meta.pubkey)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if meta.is_signer && !info.is_signer && !signers.contains(info.key) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        accounts.push(Cpi {
+            info,
+            is_signer: meta.is_signer,
+        });
+    }
+    let accounts = CpiAccounts(accounts);
+
+    let program_id = instruction.program_id;
+    if program_id == system_program::ID {
+        process_system(&accounts, &instruction.data)
+    } else if program_id == token::ID || program_id == token_2022::ID {
+        process_token(&program_id, &accounts, &instruction.data)
+    } else if program_id == associated_token::ID {
+        process_associated_token(&accounts, &instruction.data)
+    } else if [Jupiter::id(), Whirlpool::id(), STUB_ROUTER].contains(&program_id) {
+        ROUTED.with(|routed| {
+            let keys = instruction.accounts.iter().map(|meta| meta.pubkey);
+            routed.borrow_mut().extend(keys)
+        });
+        if let Some(error) = ROUTER_FAILURE.with(|failure| failure.borrow_mut().take()) {
+            return Err(error);
+        }
+        process_route(&accounts, &instruction.data)?;
+        reenter(account_infos)
+    } else if program_id == STUB_CALLBACK {
+        CALLBACKS.with(|callbacks| callbacks.borrow_mut().push(instruction.clone()));
+        Ok(())
+    } else {
+        Err(ProgramError::IncorrectProgramId)
+    }
+}
+
+/// Runs the instruction queued by `set_router_reentry`, if any, over the route's accounts.
+fn reenter(account_infos: &[AccountInfo]) -> ProgramResult {
+    let Some(instruction) = ROUTER_REENTRY.with(|reentry| reentry.borrow_mut().take()) else {
+        return Ok(());
+    };
+    let accounts = instruction
+        .accounts
+        .iter()
+        .map(|meta| {
+            account_infos
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .cloned()
+                .ok_or(ProgramError::NotEnoughAccountKeys)
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    // `entry` wants the infos for as long as the accounts they point into live.
+    let result = crate::entry(&crate::ID, Vec::leak(accounts), &instruction.data);
+    REENTRY_RESULT.with(|reentry| *reentry.borrow_mut() = Some(result.clone()));
+    result
+}
+
+fn load_token(account: &AccountInfo) -> std::result::Result<TokenState, ProgramError> {
+    let data = account.try_borrow_data()?;
+    if data.len() < TokenState::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    TokenState::unpack_from_slice(&data[..TokenState::LEN])
+}
+
+fn store_token(account: &AccountInfo, state: &TokenState) -> ProgramResult {
+    state.pack_into_slice(&mut account.try_borrow_mut_data()?[..TokenState::LEN]);
+    Ok(())
+}
+
+/// What a transfer of `amount` withholds under the mint's transfer fee, if it has one.
+fn transfer_fee(mint: &AccountInfo, amount: u64) -> std::result::Result<u64, ProgramError> {
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<MintState>::unpack(&data)?;
+    let Ok(config) = state.get_extension::<TransferFeeConfig>() else {
+        return Ok(0);
+    };
+    config
+        .calculate_epoch_fee(0, amount)
+        .ok_or(ProgramError::InvalidArgument)
+}
+
+/// Moves `amount` between two token accounts of one mint, of which `to` receives all but
+/// `withheld`. With `authority` set it has to be the source owner or a delegate allowed
+/// at least `amount`.
+fn transfer(
+    from: &AccountInfo,
+    to: &AccountInfo,
+    authority: Option<&AccountInfo>,
+    amount: u64,
+    withheld: u64,
+) -> ProgramResult {
+    let mut source = load_token(from)?;
+    let mut destination = load_token(to)?;
+    if source.mint != destination.mint {
+        // `TokenError::MintMismatch`
+        return Err(ProgramError::Custom(3));
+    }
+    if let Some(authority) = authority {
+        if *authority.key != source.owner {
+            if source.delegate != COption::Some(*authority.key) {
+                // `TokenError::OwnerMismatch`
+                return Err(ProgramError::Custom(4));
+            }
+            source.delegated_amount = source
+                .delegated_amount
+                .checked_sub(amount)
+                .ok_or(ProgramError::InsufficientFunds)?;
+            if source.delegated_amount == 0 {
+                source.delegate = COption::None;
+            }
+        }
+    }
+    if from.key == to.key {
+        return store_token(from, &source);
+    }
+
+    source.amount = source
+        .amount
+        .checked_sub(amount)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    destination.amount = destination
+        .amount
+        .checked_add(amount - withheld)
+        .ok_or(ProgramError::InvalidArgument)?;
+    store_token(from, &source)?;
+    store_token(to, &destination)?;
+    // Native balances are the account's lamports above its reserve.
+    if source.is_native.is_some() {
+        move_lamports(from, to, amount)?;
+    }
+    Ok(())
+}
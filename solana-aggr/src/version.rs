@@ -0,0 +1,112 @@
+use anchor_lang::{prelude::*, solana_program::hash::hashv};
+
+use crate::constants::*;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ProgramVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+    pub build_seeds_hash: [u8; 32],
+}
+
+pub fn program_version() -> ProgramVersion {
+    ProgramVersion {
+        major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or_default(),
+        minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or_default(),
+        patch: env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or_default(),
+        build_seeds_hash: build_seeds_hash(),
+    }
+}
+
+/// Hash over every PDA seed, so clients can tell whether a deployment derives the expected addresses.
+pub fn build_seeds_hash() -> [u8; 32] {
+    seeds_hash(&[
+        AUTHORITY_SEED,
+        WSOL_SEED,
+        CONFIG_SEED,
+        ROUTE_BUFFER_SEED,
+        SOL_FEE_PRICE_SEED,
+        FEE_COUNTER_SEED,
+        ALLOWANCE_SEED,
+        ALLOWANCE_DELEGATE_SEED,
+        VOLUME_CAP_SEED,
+        IDEMPOTENCY_SEED,
+        FEE_RECEIPT_SEED,
+        CLAWBACK_SEED,
+        WSOL_DEPOSIT_SEED,
+        ESCROW_SEED,
+        ESCROW_AUTHORITY_SEED,
+        QUOTE_NONCE_SEED,
+        PREVIOUS_AUTHORITY_SEED,
+        PREVIOUS_WSOL_SEED,
+    ])
+}
+
+/// Hashes `seeds` each prefixed with its little-endian `u32` length, so moving bytes from
+/// one seed to its neighbour changes the hash.
+fn seeds_hash(seeds: &[&[u8]]) -> [u8; 32] {
+    let lengths: Vec<[u8; 4]> = seeds
+        .iter()
+        .map(|seed| (seed.len() as u32).to_le_bytes())
+        .collect();
+    let parts: Vec<&[u8]> = seeds
+        .iter()
+        .zip(&lengths)
+        .flat_map(|(seed, length)| [&length[..], seed])
+        .collect();
+    hashv(&parts).to_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::solana_program::hash::hash;
+
+    use super::*;
+
+    #[test]
+    fn version_matches_the_package() {
+        let version = program_version();
+        assert_eq!(
+            format!("{}.{}.{}", version.major, version.minor, version.patch),
+            env!("CARGO_PKG_VERSION")
+        );
+        assert_eq!(version.build_seeds_hash, build_seeds_hash());
+    }
+
+    /// Changing, adding or dropping a seed must change the hash, and this list with it.
+    #[test]
+    fn seeds_hash_covers_every_seed() {
+        let seeds: [&[u8]; 18] = [
+            b"authority",
+            b"wsol",
+            b"config",
+            b"route_buffer",
+            b"sol_fee_price",
+            b"fee_counter",
+            b"allowance",
+            b"allowance_delegate",
+            b"volume_cap",
+            b"idem",
+            b"fee_receipt",
+            b"clawback",
+            b"wsol_deposit",
+            b"escrow",
+            b"escrow_authority",
+            b"quote_nonce",
+            b"authority",
+            b"wsol",
+        ];
+        let mut bytes = Vec::new();
+        for seed in seeds {
+            bytes.extend_from_slice(&(seed.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(seed);
+        }
+        assert_eq!(build_seeds_hash(), hash(&bytes).to_bytes());
+    }
+
+    #[test]
+    fn seeds_hash_separates_neighbouring_seeds() {
+        assert_ne!(seeds_hash(&[b"ab", b"c"]), seeds_hash(&[b"a", b"bc"]));
+    }
+}